@@ -0,0 +1,64 @@
+//! Parsing benchmarks on a generated config the size of a large, machine-written sway config.
+//! The binary has no library target, so the modules under test are included by path.
+#![allow(dead_code)]
+
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/expr.rs"]
+mod expr;
+#[path = "../src/options.rs"]
+mod options;
+#[path = "../src/text.rs"]
+mod text;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const EPSILON: f32 = 0.0005;
+
+/// Function to build a config of roughly `lines` lines with the scale options near the end
+fn large_config(lines: usize) -> String {
+    let mut config = String::from("# Generated config\nset $mod Mod4\n");
+    let mut i = 0;
+    while config.lines().count() < lines {
+        config.push_str(&format!(
+            "bindsym $mod+Shift+{i} move container to workspace number {i}\n\
+             # binding {i}\n\
+             for_window [app_id=\"app-{i}\"] floating enable\n\
+             exec_always --no-startup-id ~/bin/helper-{i} --flag value\n"
+        ));
+        i += 1;
+    }
+    config.push_str(
+        "### Scale Options Start\n\
+         # Target Display = eDP-1\n\
+         # Target Display = HDMI-A-1\n\
+         # Scale Options = 1.0, 1.5, 2.0@3840x2160\n\
+         ### Scale Options End\n\
+         output \"eDP-1\" scale 1.5 pos 0 0\n\
+         output HDMI-A-1 {\n    mode 3840x2160@60Hz\n    scale 2\n}\n",
+    );
+    config
+}
+
+fn parse(c: &mut Criterion) {
+    let contents = large_config(8000);
+    let lines = text::decode_lines(contents.as_bytes());
+    let targets = vec!["eDP-1".to_string(), "HDMI-A-1".to_string()];
+    let next: options::ScaleOption = "1.0".parse().unwrap();
+
+    c.bench_function("decode_lines 8k", |b| {
+        b.iter(|| text::decode_lines(black_box(contents.as_bytes())))
+    });
+    c.bench_function("find_scale_options 8k", |b| {
+        b.iter(|| config::find_scale_options(black_box(&lines)).unwrap())
+    });
+    c.bench_function("get_current_scale 8k", |b| {
+        b.iter(|| config::get_current_scale(black_box(&lines), &targets, EPSILON))
+    });
+    c.bench_function("update_scale_in_outputs 8k", |b| {
+        b.iter(|| config::update_scale_in_outputs(black_box(&lines), &targets, &next))
+    });
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
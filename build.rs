@@ -0,0 +1,23 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Function to record the commit the binary is built from for `--version --json`, left empty
+/// when building outside a git checkout
+fn main() {
+    let hash = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=GIT_HASH={}", hash);
+
+    // Rebuild when the checked-out commit changes, but not on every build without a checkout
+    for path in [".git/HEAD", ".git/refs/heads"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,61 @@
+//! Rewrites the scales in arbitrary input and checks the rewrite only touched scale values.
+//! Run with `cargo +nightly fuzz run update_scale` from the repository root.
+#![no_main]
+#![allow(dead_code)]
+
+#[path = "../../src/config.rs"]
+mod config;
+#[path = "../../src/expr.rs"]
+mod expr;
+#[path = "../../src/options.rs"]
+mod options;
+#[path = "../../src/text.rs"]
+mod text;
+
+use libfuzzer_sys::fuzz_target;
+use options::ScaleOption;
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte picks the new scale; the rest is the config
+    let Some((&selector, contents)) = data.split_first() else {
+        return;
+    };
+    let new = ScaleOption::scale(f32::from(selector % 16 + 1) * 0.25);
+
+    let lines = text::decode_lines(contents);
+    assert_eq!(text::decode_lines(&text::encode_lines(contents, &lines)), lines);
+
+    // Target every other display so both sides of the filter are exercised
+    let names = config::find_output_names(&lines);
+    let targets: Vec<String> = names.iter().step_by(2).cloned().collect();
+    let updated = config::update_scale_in_outputs(&lines, &targets, &new);
+    assert_eq!(updated.len(), lines.len());
+
+    let before = config::find_output_entries(&lines);
+    let touched: Vec<usize> = before
+        .iter()
+        .filter(|entry| targets.contains(&entry.name))
+        .map(|entry| entry.scale_line)
+        .collect();
+    for (i, (before, after)) in lines.iter().zip(&updated).enumerate() {
+        if !touched.contains(&i) {
+            assert_eq!(before, after, "line {} changed", i);
+        }
+    }
+
+    let after = config::find_output_entries(&updated);
+    assert_eq!(before.len(), after.len());
+    for (before, after) in before.iter().zip(&after) {
+        assert_eq!(before.name, after.name);
+        if targets.contains(&before.name) {
+            assert_eq!(after.scale, new.scale);
+        } else {
+            assert_eq!(after.scale, before.scale);
+        }
+    }
+
+    assert_eq!(
+        config::update_scale_in_outputs(&updated, &targets, &new),
+        updated
+    );
+});
@@ -0,0 +1,135 @@
+use crate::settings;
+use crate::state::{self, State};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Format version written to new archives; importing refuses anything newer
+const VERSION: u32 = 1;
+
+/// Everything the tool keeps about a display setup, bundled into one JSON file
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    /// The settings file verbatim, so comments and formatting survive the round trip
+    pub settings: Option<String>,
+    pub state: Option<State>,
+    /// The output lines owned by the tool in managed mode
+    pub snippet: Option<String>,
+}
+
+/// Function to read a file that may not exist yet
+fn read_optional(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Function to bundle the settings file, the state file and the managed snippet
+pub fn collect(snippet_path: &Path) -> io::Result<Archive> {
+    let settings = match settings::settings_path() {
+        Some(path) => read_optional(&path)?,
+        None => None,
+    };
+    let state = match state::state_path() {
+        Some(path) if path.exists() => Some(state::load_state()?),
+        _ => None,
+    };
+    Ok(Archive {
+        version: VERSION,
+        settings,
+        state,
+        snippet: read_optional(snippet_path)?,
+    })
+}
+
+/// Function to parse an archive, checking its version and that its settings would load
+pub fn parse(contents: &str) -> io::Result<Archive> {
+    let archive: Archive = serde_json::from_str(contents)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if archive.version > VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "The archive has version {}, but this build only reads up to version {}.",
+                archive.version, VERSION
+            ),
+        ));
+    }
+    if let Some(settings) = &archive.settings {
+        settings::parse_settings(settings).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("The archived settings are invalid: {}", e),
+            )
+        })?;
+    }
+    Ok(archive)
+}
+
+/// Function to write the archived files into place. Existing files are only replaced with
+/// `force`, so an import never silently discards a setup. Returns the files written.
+pub fn restore(archive: &Archive, snippet_path: &Path, force: bool) -> io::Result<Vec<PathBuf>> {
+    let settings_path = settings::settings_path()
+        .ok_or_else(|| io::Error::other("Could not determine config directory"))?;
+    let state_path = state::state_path()
+        .ok_or_else(|| io::Error::other("Could not determine state directory"))?;
+
+    let mut files: Vec<(PathBuf, String)> = Vec::new();
+    if let Some(settings) = &archive.settings {
+        files.push((settings_path, settings.clone()));
+    }
+    if let Some(state) = &archive.state {
+        let json = serde_json::to_string_pretty(state).map_err(io::Error::other)?;
+        files.push((state_path, json + "\n"));
+    }
+    if let Some(snippet) = &archive.snippet {
+        files.push((snippet_path.to_path_buf(), snippet.clone()));
+    }
+
+    // Check every file before writing any, so a refused import changes nothing
+    if !force {
+        let existing: Vec<String> = files
+            .iter()
+            .filter(|(path, contents)| {
+                read_optional(path)
+                    .ok()
+                    .flatten()
+                    .is_some_and(|current| &current != contents)
+            })
+            .map(|(path, _)| path.display().to_string())
+            .collect();
+        if !existing.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "Importing would replace {}; pass --force to overwrite.",
+                    existing.join(", ")
+                ),
+            ));
+        }
+    }
+
+    for (path, contents) in &files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)?;
+    }
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_newer_versions_and_broken_settings() {
+        assert!(parse(r#"{"version": 1, "settings": "managed = true\n"}"#).is_ok());
+        assert!(parse(r#"{"version": 2}"#).is_err());
+        assert!(parse(r#"{"version": 1, "settings": "no_such_setting = 1\n"}"#).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+use crate::options::ScaleOption;
+
+/// One operation of a script run by `sway-scale-switcher run`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// `scale OUTPUT SCALE`, where SCALE is an option such as `1.5`, `2@3840x2160` or `off`
+    Scale { output: String, option: ScaleOption },
+    /// `mode OUTPUT WIDTHxHEIGHT[@RATEHz]`
+    Mode { output: String, mode: String },
+    /// `workspace NAME OUTPUT`, moving the workspace to the output
+    Workspace { workspace: String, output: String },
+    /// `hook COMMAND...`, run with `sh -c`
+    Hook(String),
+}
+
+/// Function to read a script, one step per line. Blank lines and lines starting with `#` are
+/// skipped, and names with spaces are quoted as in the sway config. Errors name the line.
+pub fn parse(text: &str) -> Result<Vec<Step>, String> {
+    let mut steps = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fail = |message: String| format!("line {}: {}", i + 1, message);
+
+        // A hook takes the rest of the line as it is, for the shell to split
+        if let Some(command) = line.strip_prefix("hook") {
+            if command.starts_with(char::is_whitespace) && !command.trim().is_empty() {
+                steps.push(Step::Hook(command.trim().to_string()));
+                continue;
+            }
+        }
+
+        let words = crate::mock::words(line);
+        let arguments = |count: usize| {
+            if words.len() == count + 1 {
+                Ok(())
+            } else {
+                Err(fail(format!(
+                    "'{}' takes {} arguments, got {}",
+                    words[0],
+                    count,
+                    words.len() - 1
+                )))
+            }
+        };
+        let step = match words[0].as_str() {
+            "scale" => {
+                arguments(2)?;
+                let option: ScaleOption = words[2].parse().map_err(fail)?;
+                if option.expr.is_some() {
+                    return Err(fail(format!(
+                        "scale '{}' depends on the output; give a number",
+                        words[2]
+                    )));
+                }
+                Step::Scale {
+                    output: words[1].clone(),
+                    option,
+                }
+            }
+            "mode" => {
+                arguments(2)?;
+                Step::Mode {
+                    output: words[1].clone(),
+                    mode: words[2].clone(),
+                }
+            }
+            "workspace" => {
+                arguments(2)?;
+                Step::Workspace {
+                    workspace: words[1].clone(),
+                    output: words[2].clone(),
+                }
+            }
+            "hook" => return Err(fail("'hook' needs a command".to_string())),
+            other => {
+                return Err(fail(format!(
+                    "unknown step '{}'; use scale, mode, workspace or hook",
+                    other
+                )))
+            }
+        };
+        steps.push(step);
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_script() {
+        let steps = parse(
+            "# Docked at the desk\n\
+             scale eDP-1 2\n\
+             mode \"Dell Inc. U2720Q\" 3840x2160@60Hz\n\
+             \n\
+             workspace 9:media HDMI-A-1\n\
+             hook   pkill -USR1 waybar\n",
+        )
+        .unwrap();
+        assert_eq!(
+            steps,
+            [
+                Step::Scale {
+                    output: "eDP-1".to_string(),
+                    option: ScaleOption::scale(2.0),
+                },
+                Step::Mode {
+                    output: "Dell Inc. U2720Q".to_string(),
+                    mode: "3840x2160@60Hz".to_string(),
+                },
+                Step::Workspace {
+                    workspace: "9:media".to_string(),
+                    output: "HDMI-A-1".to_string(),
+                },
+                Step::Hook("pkill -USR1 waybar".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_the_line_of_a_mistake() {
+        assert_eq!(
+            parse("scale eDP-1 2\nscale eDP-1\n").unwrap_err(),
+            "line 2: 'scale' takes 2 arguments, got 1"
+        );
+        assert!(parse("scale eDP-1 auto\n")
+            .unwrap_err()
+            .starts_with("line 1: scale 'auto' depends on the output"));
+        assert!(parse("hook\n").unwrap_err().contains("needs a command"));
+        assert!(parse("reload\n")
+            .unwrap_err()
+            .contains("unknown step 'reload'"));
+    }
+}
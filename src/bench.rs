@@ -0,0 +1,137 @@
+use serde::Serialize;
+use std::time::Duration;
+
+/// The time one stage of applying a scale took in each iteration
+#[derive(Debug, Clone)]
+pub struct Stage {
+    pub name: &'static str,
+    pub samples: Vec<Duration>,
+}
+
+/// The spread of a stage's samples, in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Summary {
+    pub stage: String,
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Stage {
+    pub fn new(name: &'static str) -> Stage {
+        Stage {
+            name,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Function to summarize the samples, or None if the stage never ran
+    pub fn summary(&self) -> Option<Summary> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut millis: Vec<f64> = self
+            .samples
+            .iter()
+            .map(|sample| sample.as_secs_f64() * 1000.0)
+            .collect();
+        millis.sort_by(f64::total_cmp);
+        let middle = millis.len() / 2;
+        let median = if millis.len().is_multiple_of(2) {
+            (millis[middle - 1] + millis[middle]) / 2.0
+        } else {
+            millis[middle]
+        };
+        Some(Summary {
+            stage: self.name.to_string(),
+            min_ms: millis[0],
+            median_ms: median,
+            mean_ms: millis.iter().sum::<f64>() / millis.len() as f64,
+            max_ms: millis[millis.len() - 1],
+        })
+    }
+}
+
+/// Function to add up the stages of each iteration, for the end-to-end latency
+pub fn total(stages: &[Stage]) -> Stage {
+    let iterations = stages.iter().map(|stage| stage.samples.len()).max();
+    Stage {
+        name: "total",
+        samples: (0..iterations.unwrap_or(0))
+            .map(|i| stages.iter().filter_map(|stage| stage.samples.get(i)).sum())
+            .collect(),
+    }
+}
+
+/// Function to print the breakdown of each stage and the total, as a table or as JSON
+pub fn print_report(backend: &str, stages: &[Stage], json: bool) -> std::io::Result<()> {
+    let total = total(stages);
+    let summaries: Vec<Summary> = stages
+        .iter()
+        .chain(std::iter::once(&total))
+        .filter_map(Stage::summary)
+        .collect();
+
+    if json {
+        let report = serde_json::json!({
+            "backend": backend,
+            "iterations": total.samples.len(),
+            "stages": summaries,
+        });
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&report).map_err(std::io::Error::other)?
+        );
+        return Ok(());
+    }
+
+    println!(
+        "Backend: {}, {} iterations (times in ms)",
+        backend,
+        total.samples.len()
+    );
+    println!(
+        "{:<8} {:>9} {:>9} {:>9} {:>9}",
+        "stage", "min", "median", "mean", "max"
+    );
+    for summary in &summaries {
+        println!(
+            "{:<8} {:>9.3} {:>9.3} {:>9.3} {:>9.3}",
+            summary.stage, summary.min_ms, summary.median_ms, summary.mean_ms, summary.max_ms
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_each_stage_and_the_total() {
+        let mut parse = Stage::new("parse");
+        parse.samples = [4, 1, 3, 2]
+            .into_iter()
+            .map(Duration::from_millis)
+            .collect();
+        let mut apply = Stage::new("apply");
+        apply.samples = [10, 10, 10, 10]
+            .into_iter()
+            .map(Duration::from_millis)
+            .collect();
+
+        let summary = parse.summary().unwrap();
+        assert_eq!(summary.min_ms, 1.0);
+        assert_eq!(summary.median_ms, 2.5);
+        assert_eq!(summary.mean_ms, 2.5);
+        assert_eq!(summary.max_ms, 4.0);
+
+        let total = total(&[parse, apply]).summary().unwrap();
+        assert_eq!(total.stage, "total");
+        assert_eq!(total.min_ms, 11.0);
+        assert_eq!(total.max_ms, 14.0);
+
+        assert_eq!(Stage::new("reload").summary(), None);
+    }
+}
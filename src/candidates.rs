@@ -0,0 +1,64 @@
+/// Scales are tried in steps of an eighth, which the fractional-scale protocol represents exactly
+const STEPS_PER_UNIT: u32 = 8;
+
+/// A scale at which the mode maps onto a whole number of logical pixels in both directions, so
+/// nothing is blurred by rounding at the edges
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Candidate {
+    pub scale: f32,
+    pub logical_width: u32,
+    pub logical_height: u32,
+}
+
+/// Function to list the scales from 1 up to `max_scale` that divide a mode evenly
+pub fn candidates(width: u32, height: u32, max_scale: f32) -> Vec<Candidate> {
+    let last = (max_scale * STEPS_PER_UNIT as f32).floor() as u32;
+    (STEPS_PER_UNIT..=last)
+        .filter(|&steps| {
+            (width * STEPS_PER_UNIT).is_multiple_of(steps)
+                && (height * STEPS_PER_UNIT).is_multiple_of(steps)
+        })
+        .map(|steps| Candidate {
+            scale: steps as f32 / STEPS_PER_UNIT as f32,
+            logical_width: width * STEPS_PER_UNIT / steps,
+            logical_height: height * STEPS_PER_UNIT / steps,
+        })
+        .collect()
+}
+
+/// Function to pick a short list of options from the candidates: everything from 1 up to the
+/// scale that shows 96 logical pixels per inch, rounded up, and at least up to 2
+pub fn suggest(candidates: &[Candidate], native: Option<f32>) -> Vec<f32> {
+    let upper = native.map_or(2.0, |native| native.ceil().max(2.0));
+    candidates
+        .iter()
+        .map(|candidate| candidate.scale)
+        .filter(|&scale| scale <= upper)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_scales_with_whole_logical_sizes() {
+        let scales: Vec<f32> = candidates(3840, 2160, 3.0)
+            .iter()
+            .map(|candidate| candidate.scale)
+            .collect();
+        assert_eq!(scales, [1.0, 1.25, 1.5, 1.875, 2.0, 2.5, 3.0]);
+        assert_eq!(
+            candidates(2560, 1440, 2.0)[1],
+            Candidate {
+                scale: 1.25,
+                logical_width: 2048,
+                logical_height: 1152,
+            }
+        );
+
+        let options = candidates(3840, 2160, 4.0);
+        assert_eq!(suggest(&options, None), [1.0, 1.25, 1.5, 1.875, 2.0]);
+        assert_eq!(suggest(&options, Some(2.9)).last(), Some(&3.0));
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The clock timers read and wait on, the system one unless `--simulate-time` replaced it
+static CLOCK: OnceLock<Box<dyn Clock>> = OnceLock::new();
+
+/// A source of time for schedules, debouncing and other timers, so they can be tested without
+/// waiting for them
+pub trait Clock: Send + Sync {
+    /// Function to get the current Unix time in milliseconds
+    fn now_ms(&self) -> u64;
+    /// Function to let the given time pass
+    fn sleep(&self, duration: Duration);
+    /// Whether waiting returns at once, with the clock moved on instead
+    fn is_simulated(&self) -> bool;
+}
+
+/// The clock on the wall
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64)
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+
+    fn is_simulated(&self) -> bool {
+        false
+    }
+}
+
+/// A clock that starts at a given time and only moves when something sleeps on it, which it
+/// then does at once
+pub struct SimulatedClock {
+    now_ms: AtomicU64,
+}
+
+impl SimulatedClock {
+    /// Function to start a simulated clock at the given Unix time in milliseconds
+    pub fn new(start_ms: u64) -> Self {
+        SimulatedClock {
+            now_ms: AtomicU64::new(start_ms),
+        }
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now_ms(&self) -> u64 {
+        self.now_ms.load(Ordering::SeqCst)
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.now_ms
+            .fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
+    }
+
+    fn is_simulated(&self) -> bool {
+        true
+    }
+}
+
+/// Function to fast-forward every timer from here on, starting at the given Unix time in seconds
+pub fn simulate(start: u64) {
+    let _ = CLOCK.set(Box::new(SimulatedClock::new(start * 1000)));
+}
+
+/// Function to get the clock in use
+pub fn clock() -> &'static dyn Clock {
+    CLOCK.get_or_init(|| Box::new(SystemClock)).as_ref()
+}
+
+/// Function to get the current Unix time in seconds
+pub fn now() -> u64 {
+    clock().now_ms() / 1000
+}
+
+/// Function to get the current Unix time in milliseconds
+pub fn now_ms() -> u64 {
+    clock().now_ms()
+}
+
+/// Function to sleep on the clock in use
+pub fn sleep(duration: Duration) {
+    clock().sleep(duration);
+}
+
+/// Function to wait for a message for at most `timeout`. A simulated clock does not wait: it
+/// takes a message that is already there, or else moves on by the timeout.
+pub fn recv_timeout<T>(receiver: &Receiver<T>, timeout: Duration) -> Result<T, RecvTimeoutError> {
+    recv_timeout_on(clock(), receiver, timeout)
+}
+
+/// Function to wait for a message on the given clock, see `recv_timeout`
+fn recv_timeout_on<T>(
+    clock: &dyn Clock,
+    receiver: &Receiver<T>,
+    timeout: Duration,
+) -> Result<T, RecvTimeoutError> {
+    if !clock.is_simulated() {
+        return receiver.recv_timeout(timeout);
+    }
+    match receiver.try_recv() {
+        Ok(message) => Ok(message),
+        Err(TryRecvError::Empty) => {
+            clock.sleep(timeout);
+            Err(RecvTimeoutError::Timeout)
+        }
+        Err(TryRecvError::Disconnected) => Err(RecvTimeoutError::Disconnected),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    #[test]
+    fn simulated_time_moves_only_when_slept_on() {
+        let clock = SimulatedClock::new(1_704_139_200_000);
+        assert_eq!(clock.now_ms(), 1_704_139_200_000);
+        clock.sleep(Duration::from_secs(90));
+        assert_eq!(clock.now_ms(), 1_704_139_290_000);
+
+        // Waiting takes what is there, and otherwise runs the clock forward by the timeout
+        let (sender, receiver) = mpsc::channel();
+        sender.send(7).unwrap();
+        let timeout = Duration::from_millis(250);
+        assert_eq!(recv_timeout_on(&clock, &receiver, timeout), Ok(7));
+        assert_eq!(clock.now_ms(), 1_704_139_290_000);
+        assert_eq!(
+            recv_timeout_on(&clock, &receiver, timeout),
+            Err(RecvTimeoutError::Timeout)
+        );
+        assert_eq!(clock.now_ms(), 1_704_139_290_250);
+        drop(sender);
+        assert_eq!(
+            recv_timeout_on(&clock, &receiver, timeout),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+}
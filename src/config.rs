@@ -0,0 +1,1034 @@
+use crate::options::{CycleOrder, ScaleOption};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+// Regular expressions to extract target displays and scale options
+static TARGET_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"# Target Display = (.+)").unwrap());
+static OPTIONS_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"# Scale Options = (.+)").unwrap());
+
+// Regular expression to match an output line and split off the display name, quoted or not
+static OUTPUT_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^\s*output\s+("(?:[^"\\]|\\.)*"|\S+)(.*)$"#).unwrap());
+// Regular expression to match a variable definition, e.g. `set $laptop "eDP-1"`
+static SET_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*set\s+(\$\S+)\s+(.*?)\s*$").unwrap());
+// Regular expressions to find the scale, mode and scale_filter subcommands
+static SCALE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|\s)scale\s+([0-9.]+)").unwrap());
+static MODE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|\s)(?:mode|resolution|res)\s+(?:--custom\s+)?(\S+)").unwrap()
+});
+static SCALE_FILTER_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|\s)scale_filter\s+(\S+)").unwrap());
+// Regular expression to find `#` tokens, which start a trailing comment unless they are colors
+static HASH_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?:^|\s)(#\S*)").unwrap());
+
+/// Struct to hold scale options and target displays
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScaleOptions {
+    pub target_displays: Vec<String>,
+    pub scale_values: Vec<ScaleOption>,
+}
+
+/// An `output` definition that sets a scale, either on one line or as a `{ ... }` block
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputEntry {
+    pub name: String,
+    /// Index of the line starting the definition
+    pub header_line: usize,
+    /// Index of the line holding the scale value
+    pub scale_line: usize,
+    /// Byte range of the scale value within its line
+    pub scale_range: Range<usize>,
+    pub scale: f32,
+    /// Line index, byte range and value of the mode, if one is set
+    pub mode: Option<(usize, Range<usize>, String)>,
+    /// Line index and byte range of the scale filter, if one is set
+    pub scale_filter: Option<(usize, Range<usize>)>,
+    /// Whether the definition is a `{ ... }` block
+    pub is_block: bool,
+}
+
+/// Several output definitions that each set the scale of one target display
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub display: String,
+    /// Indices of the lines starting the definitions that name the display
+    pub named: Vec<usize>,
+    /// Indices of the lines starting `output *` definitions that set a scale
+    pub wildcard: Vec<usize>,
+}
+
+impl Conflict {
+    /// Function to describe the conflict with line numbers, and which line a change edits
+    pub fn describe(&self, file: &str) -> String {
+        let mut lines: Vec<(usize, &str)> = self
+            .named
+            .iter()
+            .map(|&line| (line, ""))
+            .chain(self.wildcard.iter().map(|&line| (line, " (output *)")))
+            .collect();
+        lines.sort();
+        let listed: Vec<String> = lines
+            .iter()
+            .map(|(line, label)| format!("{}{}", line + 1, label))
+            .collect();
+        let reason = if self.named.len() > 1 {
+            "sway applies the last one"
+        } else {
+            "sway prefers it over output *"
+        };
+        format!(
+            "Lines {} of {} all set the scale of {}; line {} is the one changed, since {}.",
+            listed.join(", "),
+            file,
+            self.display,
+            self.named[self.named.len() - 1] + 1,
+            reason
+        )
+    }
+}
+
+/// How the current scale of the target displays was determined
+#[derive(Debug, Clone, PartialEq)]
+pub enum CurrentScale {
+    /// All target displays share this scale
+    Uniform(ScaleOption),
+    /// The target displays disagree; this is the scale of the first one
+    Mixed(ScaleOption),
+    /// None of the target displays has an output line with a scale
+    Missing,
+}
+
+/// Function to find the 'Scale Options Start' and 'Scale Options End' markers and parse the section between them
+pub fn find_scale_options(lines: &[String]) -> Result<ScaleOptions, String> {
+    let scale_start = lines
+        .iter()
+        .position(|line| line.contains("Scale Options Start"))
+        .ok_or("'Scale Options Start' marker not found in the config file.")?;
+    let scale_end = lines
+        .iter()
+        .position(|line| line.contains("Scale Options End"))
+        .ok_or("'Scale Options End' marker not found in the config file.")?;
+
+    // Targets may be given as variables, e.g. `# Target Display = $laptop`
+    let mut scale_options = parse_scale_options(&lines[scale_start..=scale_end])?;
+    let mut variables = Variables::default();
+    for line in lines {
+        variables.read(line);
+    }
+    for target in &mut scale_options.target_displays {
+        *target = variables.resolve(target);
+    }
+    Ok(scale_options)
+}
+
+/// Function to parse the Scale Options section
+pub fn parse_scale_options(lines: &[String]) -> Result<ScaleOptions, String> {
+    let mut target_displays = Vec::new();
+    let mut scale_values = Vec::new();
+
+    for line in lines {
+        if let Some(captures) = TARGET_REGEX.captures(line) {
+            let display = captures.get(1).unwrap().as_str().trim().to_string();
+            target_displays.push(display);
+        } else if let Some(captures) = OPTIONS_REGEX.captures(line) {
+            let scales_str = captures.get(1).unwrap().as_str();
+            scale_values = scales_str
+                .split(',')
+                .map(|s| {
+                    s.parse::<ScaleOption>()
+                        .map_err(|e| format!("Invalid scale option '{}': {}", s.trim(), e))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+    }
+
+    // Error handling if no target displays or scale options are found
+    if target_displays.is_empty() {
+        return Err("No target displays found in Scale Options section.".to_string());
+    }
+
+    if scale_values.is_empty() {
+        return Err("No scale options found in Scale Options section.".to_string());
+    }
+
+    Ok(ScaleOptions {
+        target_displays,
+        scale_values,
+    })
+}
+
+/// Function to append an option to the `# Scale Options` line of the marker section, keeping the
+/// rest of the line as written. Returns None if the config has no such line.
+pub fn add_scale_option(lines: &[String], option: &ScaleOption) -> Option<Vec<String>> {
+    let start = lines
+        .iter()
+        .position(|line| line.contains("Scale Options Start"))?;
+    let index = lines[start..]
+        .iter()
+        .take_while(|line| !line.contains("Scale Options End"))
+        .position(|line| OPTIONS_REGEX.is_match(line))?
+        + start;
+    let mut updated = lines.to_vec();
+    let line = &mut updated[index];
+    let kept = line.trim_end().len();
+    line.replace_range(kept.., &format!(", {}", option));
+    Some(updated)
+}
+
+/// The `set $name value` variables defined so far, as sway reads the config from the top
+#[derive(Debug, Default)]
+struct Variables(BTreeMap<String, String>);
+
+impl Variables {
+    /// Function to take note of a variable if the line defines one
+    fn read(&mut self, line: &str) {
+        if !line.trim_start().starts_with("set") {
+            return;
+        }
+        if let Some(captures) = SET_REGEX.captures(code(line)) {
+            let value = self.resolve(&unquote(captures.get(2).unwrap().as_str()));
+            self.0
+                .insert(captures.get(1).unwrap().as_str().to_string(), value);
+        }
+    }
+
+    /// Function to replace a name that is a known variable by its value
+    fn resolve(&self, name: &str) -> String {
+        match self.0.get(name) {
+            Some(value) => value.clone(),
+            None => name.to_string(),
+        }
+    }
+}
+
+/// Function to find every uncommented output definition that sets a scale. Names given as
+/// variables, e.g. `output $laptop`, are resolved through the `set` lines above them.
+pub fn find_output_entries(lines: &[String]) -> Vec<OutputEntry> {
+    let mut entries = Vec::new();
+    let mut variables = Variables::default();
+    let mut index = 0;
+
+    while index < lines.len() {
+        let line = &lines[index];
+        variables.read(line);
+        // Most lines are not output lines; skip them without running the regex
+        if !line.trim_start().starts_with("output") {
+            index += 1;
+            continue;
+        }
+        let Some(captures) = OUTPUT_REGEX.captures(line) else {
+            index += 1;
+            continue;
+        };
+
+        let name = variables.resolve(&unquote(captures.get(1).unwrap().as_str()));
+        let rest = captures.get(2).unwrap();
+        let header_line = index;
+
+        if code(rest.as_str()).trim_end().ends_with('{') {
+            // Block form: subcommands follow on their own lines until the closing brace
+            let mut scale = None;
+            let mut mode = None;
+            let mut scale_filter = None;
+            index += 1;
+            while index < lines.len() && !lines[index].trim_start().starts_with('}') {
+                let inner = code(&lines[index]);
+                if !inner.trim_start().starts_with('#') {
+                    if let Some(c) = SCALE_REGEX.captures(inner) {
+                        scale.get_or_insert((index, c.get(1).unwrap().range()));
+                    }
+                    if let Some(c) = MODE_REGEX.captures(inner) {
+                        let m = c.get(1).unwrap();
+                        mode.get_or_insert((index, m.range(), m.as_str().to_string()));
+                    }
+                    if let Some(c) = SCALE_FILTER_REGEX.captures(inner) {
+                        scale_filter.get_or_insert((index, c.get(1).unwrap().range()));
+                    }
+                }
+                index += 1;
+            }
+            if let Some((scale_line, scale_range)) = scale {
+                if let Ok(value) = lines[scale_line][scale_range.clone()].parse() {
+                    entries.push(OutputEntry {
+                        name,
+                        header_line,
+                        scale_line,
+                        scale_range,
+                        scale: value,
+                        mode,
+                        scale_filter,
+                        is_block: true,
+                    });
+                }
+            }
+        } else {
+            // Single-line form: subcommands follow the display name
+            let offset = rest.start();
+            let rest = code(rest.as_str());
+            if let Some(c) = SCALE_REGEX.captures(rest) {
+                let m = c.get(1).unwrap();
+                let mode = MODE_REGEX.captures(rest).map(|c| {
+                    let m = c.get(1).unwrap();
+                    (
+                        index,
+                        m.start() + offset..m.end() + offset,
+                        m.as_str().to_string(),
+                    )
+                });
+                let scale_filter = SCALE_FILTER_REGEX.captures(rest).map(|c| {
+                    let m = c.get(1).unwrap();
+                    (index, m.start() + offset..m.end() + offset)
+                });
+                if let Ok(value) = m.as_str().parse() {
+                    entries.push(OutputEntry {
+                        name,
+                        header_line,
+                        scale_line: index,
+                        scale_range: m.start() + offset..m.end() + offset,
+                        scale: value,
+                        mode,
+                        scale_filter,
+                        is_block: false,
+                    });
+                }
+            }
+        }
+        index += 1;
+    }
+
+    entries
+}
+
+/// Function to list the displays named by uncommented output lines, whether or not they set a
+/// scale. The `*` wildcard is left out since it does not name a display.
+pub fn find_output_names(lines: &[String]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut variables = Variables::default();
+    for line in lines {
+        variables.read(line);
+        if !line.trim_start().starts_with("output") {
+            continue;
+        }
+        if let Some(captures) = OUTPUT_REGEX.captures(line) {
+            let name = variables.resolve(&unquote(captures.get(1).unwrap().as_str()));
+            if name != "*" && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Function to cut a trailing comment off a line, e.g. `scale 1.5 # laptop panel`.
+/// Colors such as `bg #000000 solid_color` are arguments, not comments.
+fn code(line: &str) -> &str {
+    for captures in HASH_REGEX.captures_iter(line) {
+        let token = captures.get(1).unwrap();
+        if !is_color(token.as_str()) {
+            return &line[..token.start()];
+        }
+    }
+    line
+}
+
+/// Function to check whether a token is a `#RRGGBB` or `#RRGGBBAA` color
+fn is_color(token: &str) -> bool {
+    let digits = &token[1..];
+    matches!(digits.len(), 6 | 8) && digits.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Function to strip the quotes (and escapes) from a display name
+fn unquote(name: &str) -> String {
+    match name.strip_prefix('"').and_then(|n| n.strip_suffix('"')) {
+        Some(inner) => inner.replace("\\\"", "\"").replace("\\\\", "\\"),
+        None => name.to_string(),
+    }
+}
+
+/// Function to find the target displays whose scale is set by more than one output definition,
+/// counting `output *` lines that set a scale
+pub fn find_conflicts(lines: &[String], target_displays: &[String]) -> Vec<Conflict> {
+    let entries = find_output_entries(lines);
+    let wildcard: Vec<usize> = entries
+        .iter()
+        .filter(|entry| entry.name == "*")
+        .map(|entry| entry.header_line)
+        .collect();
+    target_displays
+        .iter()
+        .filter_map(|display| {
+            let named: Vec<usize> = entries
+                .iter()
+                .filter(|entry| &entry.name == display)
+                .map(|entry| entry.header_line)
+                .collect();
+            (!named.is_empty() && named.len() + wildcard.len() > 1).then(|| Conflict {
+                display: display.clone(),
+                named,
+                wildcard: wildcard.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Function to collect the scale (and mode, if set) of each target display from its output line.
+/// When several lines name a display, the last one counts, as it does in sway.
+pub fn get_output_scales(
+    lines: &[String],
+    target_displays: &[String],
+) -> BTreeMap<String, ScaleOption> {
+    let mut scales = BTreeMap::new();
+
+    for entry in find_output_entries(lines) {
+        if target_displays.contains(&entry.name) {
+            scales.insert(
+                entry.name,
+                ScaleOption::new(entry.scale, entry.mode.map(|(_, _, mode)| mode)),
+            );
+        }
+    }
+
+    scales
+}
+
+/// Function to determine the current scale by inspecting the output lines for target displays.
+pub fn get_current_scale(
+    lines: &[String],
+    target_displays: &[String],
+    epsilon: f32,
+) -> CurrentScale {
+    let scales: Vec<ScaleOption> = get_output_scales(lines, target_displays)
+        .into_values()
+        .collect();
+
+    match scales.first() {
+        None => CurrentScale::Missing,
+        Some(first)
+            if scales
+                .iter()
+                .all(|s| s.mode == first.mode && (s.scale - first.scale).abs() <= epsilon) =>
+        {
+            CurrentScale::Uniform(first.clone())
+        }
+        Some(first) => CurrentScale::Mixed(first.clone()),
+    }
+}
+
+/// Function to list the indices of the options in the order cycling visits them.
+/// Options sharing a scale keep the order in which they were written.
+fn cycle_indices(scale_values: &[ScaleOption], order: CycleOrder) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..scale_values.len()).collect();
+    let by_scale = |&a: &usize, &b: &usize| scale_values[a].scale.total_cmp(&scale_values[b].scale);
+    match order {
+        CycleOrder::Ascending => indices.sort_by(by_scale),
+        CycleOrder::Descending => indices.sort_by(|a, b| by_scale(b, a)),
+        CycleOrder::AsWritten => {}
+    }
+    indices
+}
+
+/// Function to find the position of the current scale among the options in cycling order,
+/// preferring an option with a matching mode
+fn position_of(
+    sorted_scales: &[ScaleOption],
+    current_scale: &ScaleOption,
+    epsilon: f32,
+) -> Option<usize> {
+    if current_scale.off {
+        return sorted_scales.iter().position(|option| option.off);
+    }
+    let current_mode = current_scale.mode.as_deref();
+    sorted_scales
+        .iter()
+        .position(|option| {
+            option.mode.is_some() && option.matches(current_scale.scale, current_mode, epsilon)
+        })
+        .or_else(|| {
+            sorted_scales
+                .iter()
+                .position(|option| option.matches(current_scale.scale, current_mode, epsilon))
+        })
+}
+
+/// Function to check whether the current scale is one of the options
+pub fn contains_scale(
+    scale_values: &[ScaleOption],
+    current_scale: &ScaleOption,
+    epsilon: f32,
+) -> bool {
+    position_of(scale_values, current_scale, epsilon).is_some()
+}
+
+/// Function to get the index of the next scale in cycling order, cycling back to the first if at the end.
+/// If the current scale is not among the options, the first option other than `off` is chosen.
+pub fn get_next_index(
+    scale_values: &[ScaleOption],
+    current_scale: &ScaleOption,
+    epsilon: f32,
+    cycle_order: CycleOrder,
+) -> usize {
+    let order = cycle_indices(scale_values, cycle_order);
+    let sorted_scales: Vec<ScaleOption> = order.iter().map(|&i| scale_values[i].clone()).collect();
+
+    match position_of(&sorted_scales, current_scale, epsilon) {
+        // Move to the next index, wrapping around if necessary
+        Some(i) => order[(i + 1) % order.len()],
+        // Never turn a display off just because its scale is unknown
+        None => order
+            .iter()
+            .copied()
+            .find(|&i| !scale_values[i].off)
+            .unwrap_or(order[0]),
+    }
+}
+
+/// Function to get the index of the previous scale in cycling order, cycling back to the last if at the start.
+/// If the current scale is not among the options, the last option is chosen.
+pub fn get_previous_index(
+    scale_values: &[ScaleOption],
+    current_scale: &ScaleOption,
+    epsilon: f32,
+    cycle_order: CycleOrder,
+) -> usize {
+    let order = cycle_indices(scale_values, cycle_order);
+    let sorted_scales: Vec<ScaleOption> = order.iter().map(|&i| scale_values[i].clone()).collect();
+
+    match position_of(&sorted_scales, current_scale, epsilon) {
+        // Move to the previous index, wrapping around if necessary
+        Some(i) => order[(i + order.len() - 1) % order.len()],
+        None => order[order.len() - 1],
+    }
+}
+
+/// Function to update the scale (and mode, if the option carries one) in the output lines for all target displays.
+/// Only the values themselves are replaced, so spacing, quoting and other subcommands are preserved.
+/// Of several lines naming a display only the last is changed, since that is the one sway applies.
+pub fn update_scale_in_outputs(
+    lines: &[String],
+    target_displays: &[String],
+    new_scale: &ScaleOption,
+) -> Vec<String> {
+    let mut updated = lines.to_vec();
+    // Edits are (line, byte range, replacement); a later insertion of a whole line is kept apart
+    let mut edits: Vec<(usize, Range<usize>, String)> = Vec::new();
+    let mut inserted_lines: Vec<(usize, String)> = Vec::new();
+
+    let entries = find_output_entries(lines);
+    let effective = target_displays
+        .iter()
+        .filter_map(|display| entries.iter().rev().find(|entry| &entry.name == display));
+    for entry in effective {
+        edits.push((
+            entry.scale_line,
+            entry.scale_range.clone(),
+            new_scale.scale.to_string(),
+        ));
+
+        if let Some(mode) = &new_scale.mode {
+            match &entry.mode {
+                Some((line, range, _)) => edits.push((*line, range.clone(), mode.clone())),
+                None if entry.is_block => {
+                    // Add the mode on its own line, indented like the scale line
+                    let scale_line = &lines[entry.scale_line];
+                    let indent_len = scale_line.len() - scale_line.trim_start().len();
+                    let indent = &scale_line[..indent_len];
+                    inserted_lines.push((entry.scale_line + 1, format!("{}mode {}", indent, mode)));
+                }
+                None => {
+                    let end = entry.scale_range.end;
+                    edits.push((entry.scale_line, end..end, format!(" mode {}", mode)));
+                }
+            }
+        }
+    }
+
+    // Apply edits from the end of each line backwards so earlier byte ranges stay valid
+    edits.sort_by(|a, b| (a.0, a.1.start).cmp(&(b.0, b.1.start)).reverse());
+    for (line, range, replacement) in edits {
+        updated[line].replace_range(range, &replacement);
+    }
+
+    // Insert whole lines from the bottom up so earlier indices stay valid
+    inserted_lines.sort_by_key(|&(index, _)| std::cmp::Reverse(index));
+    for (index, line) in inserted_lines {
+        updated.insert(index, line);
+    }
+
+    updated
+}
+
+/// Function to set the scale filter in the output lines for the given displays, next to the
+/// scale. As with the scale, only the last line naming a display is changed.
+pub fn update_scale_filter_in_outputs(
+    lines: &[String],
+    target_displays: &[String],
+    filter: &str,
+) -> Vec<String> {
+    let mut updated = lines.to_vec();
+    let entries = find_output_entries(lines);
+    let effective = target_displays
+        .iter()
+        .filter_map(|display| entries.iter().rev().find(|entry| &entry.name == display));
+    let mut inserted_lines: Vec<(usize, String)> = Vec::new();
+    for entry in effective {
+        match &entry.scale_filter {
+            Some((line, range)) => updated[*line].replace_range(range.clone(), filter),
+            None if entry.is_block => {
+                let scale_line = &lines[entry.scale_line];
+                let indent_len = scale_line.len() - scale_line.trim_start().len();
+                let indent = &scale_line[..indent_len];
+                inserted_lines.push((
+                    entry.scale_line + 1,
+                    format!("{}scale_filter {}", indent, filter),
+                ));
+            }
+            None => {
+                let end = entry.scale_range.end;
+                updated[entry.scale_line].insert_str(end, &format!(" scale_filter {}", filter));
+            }
+        }
+    }
+
+    inserted_lines.sort_by_key(|&(index, _)| std::cmp::Reverse(index));
+    for (index, line) in inserted_lines {
+        updated.insert(index, line);
+    }
+    updated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(contents: &str) -> Vec<String> {
+        contents.lines().map(String::from).collect()
+    }
+
+    fn targets(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    const EPSILON: f32 = 0.0005;
+
+    fn option(s: &str) -> ScaleOption {
+        s.parse().unwrap()
+    }
+
+    fn get_next_scale(values: &[ScaleOption], current: &ScaleOption) -> ScaleOption {
+        values[get_next_index(values, current, EPSILON, CycleOrder::Ascending)].clone()
+    }
+
+    #[test]
+    fn parses_scale_options_section() {
+        let config = lines(include_str!("../tests/fixtures/basic.conf"));
+        let options = find_scale_options(&config).unwrap();
+        assert_eq!(options.target_displays, targets(&["eDP-1", "HDMI-A-1"]));
+        assert_eq!(
+            options.scale_values,
+            vec![option("1.0"), option("1.5"), option("2.0")]
+        );
+    }
+
+    #[test]
+    fn missing_markers_are_reported() {
+        let config = lines("output eDP-1 scale 2\n");
+        let err = find_scale_options(&config).unwrap_err();
+        assert!(err.contains("Scale Options Start"));
+    }
+
+    #[test]
+    fn invalid_scale_option_is_an_error() {
+        let config = lines(
+            "# Scale Options Start\n# Target Display = eDP-1\n# Scale Options = 1.0, big\n# Scale Options End\n",
+        );
+        assert!(find_scale_options(&config).unwrap_err().contains("big"));
+    }
+
+    #[test]
+    fn scales_must_be_positive_numbers() {
+        for value in ["nan", "inf", "0", "-3", "1-2"] {
+            assert!(value.parse::<ScaleOption>().is_err(), "{}", value);
+        }
+        let config = lines(
+            "# Scale Options Start\n# Target Display = eDP-1\n# Scale Options = 1.0, nan, 2.0\n# Scale Options End\n",
+        );
+        assert!(find_scale_options(&config).unwrap_err().contains("nan"));
+    }
+
+    #[test]
+    fn detects_current_scale_with_quoted_names() {
+        let config = lines(include_str!("../tests/fixtures/basic.conf"));
+        assert_eq!(
+            get_current_scale(&config, &targets(&["eDP-1", "HDMI-A-1"]), EPSILON),
+            CurrentScale::Uniform(option("1.5"))
+        );
+    }
+
+    #[test]
+    fn detects_unquoted_names_and_scale_after_other_subcommands() {
+        let config = lines(include_str!("../tests/fixtures/unquoted.conf"));
+        let scales = get_output_scales(&config, &targets(&["eDP-1", "DP-2"]));
+        assert_eq!(scales["eDP-1"], option("2"));
+        assert_eq!(scales["DP-2"], option("1.25@2560x1440@144Hz"));
+    }
+
+    #[test]
+    fn detects_scales_in_blocks() {
+        let config = lines(include_str!("../tests/fixtures/blocks.conf"));
+        let scales = get_output_scales(&config, &targets(&["eDP-1", "Dell Inc. U2720Q"]));
+        assert_eq!(scales["eDP-1"], option("1.5@2560x1600"));
+        assert_eq!(scales["Dell Inc. U2720Q"], option("2"));
+    }
+
+    #[test]
+    fn ignores_commented_and_unrelated_lines() {
+        let config = lines(include_str!("../tests/fixtures/whitespace.conf"));
+        let entries = find_output_entries(&config);
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["eDP-1", "HDMI-A-1"]);
+    }
+
+    #[test]
+    fn lists_output_names_without_wildcards() {
+        let config = lines(include_str!("../tests/fixtures/basic.conf"));
+        assert_eq!(find_output_names(&config), targets(&["eDP-1", "HDMI-A-1"]));
+        let config =
+            lines("output DP-1 pos 0 0\n# output DP-2 scale 2\noutput \"Dell Inc. U2720Q\" {\n}\n");
+        assert_eq!(
+            find_output_names(&config),
+            targets(&["DP-1", "Dell Inc. U2720Q"])
+        );
+    }
+
+    #[test]
+    fn mixed_and_missing_scales() {
+        let config = lines("output A scale 1\noutput B scale 2\n");
+        assert_eq!(
+            get_current_scale(&config, &targets(&["A", "B"]), EPSILON),
+            CurrentScale::Mixed(option("1"))
+        );
+        assert_eq!(
+            get_current_scale(&config, &targets(&["C"]), EPSILON),
+            CurrentScale::Missing
+        );
+    }
+
+    #[test]
+    fn cycles_in_ascending_order_and_wraps() {
+        let values = vec![option("2.0"), option("1.0"), option("1.5")];
+        assert_eq!(get_next_scale(&values, &option("1.0")), option("1.5"));
+        assert_eq!(get_next_scale(&values, &option("1.5")), option("2.0"));
+        assert_eq!(get_next_scale(&values, &option("2.0")), option("1.0"));
+    }
+
+    #[test]
+    fn appends_options_to_the_marker_section() {
+        let config = lines(
+            "# Scale Options = 9\n### Scale Options Start\n# Target Display = eDP-1\n# Scale Options = 1.0, 2.0  \n### Scale Options End\n",
+        );
+        let updated = add_scale_option(&config, &option("1.37")).unwrap();
+        assert_eq!(updated[0], "# Scale Options = 9");
+        assert_eq!(updated[3], "# Scale Options = 1.0, 2.0, 1.37");
+        assert_eq!(
+            find_scale_options(&updated).unwrap().scale_values,
+            vec![option("1.0"), option("2.0"), option("1.37")]
+        );
+        assert!(add_scale_option(&lines("output A scale 1\n"), &option("1.37")).is_none());
+    }
+
+    #[test]
+    fn parses_expression_options() {
+        let values = lines(
+            "# Target Display = eDP-1\n# Scale Options = 1, auto, auto+0.25@2880x1800, 3/2\n",
+        );
+        let options = parse_scale_options(&values).unwrap();
+        let strings: Vec<String> = options.scale_values.iter().map(|o| o.to_string()).collect();
+        assert_eq!(strings, vec!["1", "auto", "auto+0.25@2880x1800", "1.5"]);
+        assert!(options.scale_values[1].expr.is_some());
+        assert!(options.scale_values[3].expr.is_none());
+
+        let vars = crate::expr::Vars::from_dpi(216.0);
+        let resolved = options.scale_values[2].resolve(&vars).unwrap();
+        assert_eq!(resolved, option("2.5@2880x1800"));
+    }
+
+    #[test]
+    fn apparent_sizes_resolve_against_the_native_scale() {
+        let vars = crate::expr::Vars::from_dpi(192.0);
+        let apparent = |value: &str| option(value).resolve_apparent(&vars).unwrap();
+        assert_eq!(apparent("1.25"), option("2.5"));
+        assert_eq!(apparent("1.5@2880x1800"), option("3@2880x1800"));
+        assert_eq!(apparent("auto"), option("2"));
+    }
+
+    #[test]
+    fn float_artifacts_compare_and_write_as_the_intended_scale() {
+        let values = vec![option("1.0"), option("1.2"), option("1.5")];
+        assert_eq!(get_next_scale(&values, &option("1.2001")), option("1.5"));
+
+        let precision = crate::options::Precision::default();
+        assert_eq!(precision.round(1.2 + 1e-5).to_string(), "1.2");
+        assert_eq!(option("2.0").rounded(&precision).to_string(), "2");
+        assert_eq!(
+            crate::options::Precision {
+                decimals: 2,
+                ..precision
+            }
+            .round(1.125)
+            .to_string(),
+            "1.13"
+        );
+    }
+
+    #[test]
+    fn cycles_backwards_and_wraps() {
+        let values = vec![option("2.0"), option("1.0"), option("1.5")];
+        let previous = |current: &str| {
+            values[get_previous_index(&values, &option(current), EPSILON, CycleOrder::Ascending)]
+                .clone()
+        };
+        assert_eq!(previous("1.5"), option("1.0"));
+        assert_eq!(previous("1.0"), option("2.0"));
+        assert_eq!(previous("3"), option("2.0"));
+    }
+
+    #[test]
+    fn cycles_in_the_configured_order() {
+        let values = vec![option("1.0"), option("2.0"), option("1.5")];
+        let next = |current: &str, order| {
+            values[get_next_index(&values, &option(current), EPSILON, order)].clone()
+        };
+        let previous = |current: &str, order| {
+            values[get_previous_index(&values, &option(current), EPSILON, order)].clone()
+        };
+        assert_eq!(next("1.0", CycleOrder::AsWritten), option("2.0"));
+        assert_eq!(next("2.0", CycleOrder::AsWritten), option("1.5"));
+        assert_eq!(next("1.5", CycleOrder::AsWritten), option("1.0"));
+        assert_eq!(previous("1.0", CycleOrder::AsWritten), option("1.5"));
+        assert_eq!(next("2.0", CycleOrder::Descending), option("1.5"));
+        assert_eq!(next("1.0", CycleOrder::Descending), option("2.0"));
+        assert_eq!(previous("2.0", CycleOrder::Descending), option("1.0"));
+    }
+
+    #[test]
+    fn off_comes_before_the_smallest_scale() {
+        let values = vec![option("1.0"), option("off"), option("2.0")];
+        assert_eq!(get_next_scale(&values, &option("2.0")), ScaleOption::off());
+        assert_eq!(get_next_scale(&values, &ScaleOption::off()), option("1.0"));
+        assert_eq!(get_next_scale(&values, &option("3")), option("1.0"));
+        assert!(!contains_scale(&values, &ScaleOption::scale(0.0), EPSILON));
+    }
+
+    #[test]
+    fn cycling_past_a_nan_scale_does_not_panic() {
+        let values = vec![option("1.0"), ScaleOption::scale(f32::NAN), option("2.0")];
+        for order in [CycleOrder::Ascending, CycleOrder::Descending] {
+            let index = get_next_index(&values, &option("1.0"), EPSILON, order);
+            assert!(index < values.len());
+        }
+    }
+
+    #[test]
+    fn unknown_current_scale_falls_back_to_smallest() {
+        let values = vec![option("2.0"), option("1.25")];
+        assert!(!contains_scale(&values, &option("3"), EPSILON));
+        assert_eq!(get_next_scale(&values, &option("3")), option("1.25"));
+    }
+
+    #[test]
+    fn cycles_scale_and_mode_pairs_as_a_unit() {
+        let values = vec![
+            option("1.0@3840x2160"),
+            option("2.0@3840x2160"),
+            option("1.0@1920x1080"),
+        ];
+        assert_eq!(
+            get_next_scale(&values, &option("1.0@3840x2160@60Hz")),
+            option("1.0@1920x1080")
+        );
+        assert_eq!(
+            get_next_scale(&values, &option("1.0@1920x1080")),
+            option("2.0@3840x2160")
+        );
+    }
+
+    #[test]
+    fn rewrites_only_the_scale_value() {
+        let config = lines(include_str!("../tests/fixtures/whitespace.conf"));
+        let updated = update_scale_in_outputs(&config, &targets(&["eDP-1"]), &option("2"));
+        let changed: Vec<usize> = (0..config.len())
+            .filter(|&i| config[i] != updated[i])
+            .collect();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(
+            updated[changed[0]],
+            "\t  output   eDP-1\tscale  2   pos 0 0"
+        );
+    }
+
+    #[test]
+    fn ignores_settings_inside_trailing_comments() {
+        let config = lines(include_str!("../tests/fixtures/comments.conf"));
+        let scales = get_output_scales(&config, &targets(&["eDP-1", "DP-2", "HDMI-A-1"]));
+        assert_eq!(scales["eDP-1"], option("1.5"));
+        assert_eq!(scales["DP-2"], option("1"));
+        assert_eq!(scales["HDMI-A-1"], option("1"));
+        assert!(find_output_entries(&config)
+            .iter()
+            .all(|e| e.mode.is_none()));
+    }
+
+    #[test]
+    fn preserves_comments_subcommands_and_indentation() {
+        let config = lines(include_str!("../tests/fixtures/comments.conf"));
+        let updated = update_scale_in_outputs(
+            &config,
+            &targets(&["eDP-1", "DP-2", "HDMI-A-1"]),
+            &option("2@3840x2160"),
+        );
+        let expected = vec![
+            "  output \"eDP-1\" scale 2 mode 3840x2160 pos 0 0 adaptive_sync on # laptop panel, mode 1920x1080 is wrong",
+            "output DP-2 bg #1d2021 solid_color scale 2 mode 3840x2160 #scale 3",
+            "\toutput HDMI-A-1 { # the TV",
+            "\t    scale 2   # scale 2 looks blurry",
+            "\t    mode 3840x2160",
+            "\t    # mode 1280x720",
+            "\t    pos 1920 0",
+            "\t}",
+        ];
+        assert_eq!(updated[7..], expected);
+    }
+
+    #[test]
+    fn rewrites_blocks_and_inserts_missing_mode() {
+        let config = lines(include_str!("../tests/fixtures/blocks.conf"));
+        let updated = update_scale_in_outputs(
+            &config,
+            &targets(&["Dell Inc. U2720Q"]),
+            &option("1.5@3840x2160"),
+        );
+        assert_eq!(updated.len(), config.len() + 1);
+        assert_eq!(
+            get_output_scales(&updated, &targets(&["Dell Inc. U2720Q"]))["Dell Inc. U2720Q"],
+            option("1.5@3840x2160")
+        );
+        assert!(updated.contains(&"    mode 3840x2160".to_string()));
+    }
+
+    #[test]
+    fn replaces_existing_mode_on_single_line() {
+        let config = lines(include_str!("../tests/fixtures/unquoted.conf"));
+        let updated = update_scale_in_outputs(&config, &targets(&["DP-2"]), &option("1@1920x1080"));
+        assert!(updated.contains(&"output DP-2 mode 1920x1080 scale 1 pos 1280 0".to_string()));
+    }
+
+    #[test]
+    fn sets_the_scale_filter_next_to_the_scale() {
+        let config = lines(include_str!("../tests/fixtures/basic.conf"));
+        let updated = update_scale_filter_in_outputs(&config, &targets(&["eDP-1"]), "linear");
+        assert!(
+            updated.contains(&"output \"eDP-1\" scale 1.5 scale_filter linear pos 0 0".to_string())
+        );
+        let updated = update_scale_filter_in_outputs(&updated, &targets(&["eDP-1"]), "nearest");
+        assert!(updated
+            .contains(&"output \"eDP-1\" scale 1.5 scale_filter nearest pos 0 0".to_string()));
+
+        let config = lines(include_str!("../tests/fixtures/blocks.conf"));
+        let updated =
+            update_scale_filter_in_outputs(&config, &targets(&["Dell Inc. U2720Q"]), "smart");
+        assert_eq!(updated.len(), config.len() + 1);
+        assert!(updated.contains(&"    scale_filter smart".to_string()));
+    }
+
+    #[test]
+    fn resolves_variables_in_output_lines() {
+        let config = lines(
+            "set $laptop eDP-1\n\
+             set $desk \"Dell Inc. U2720Q\"\n\
+             set $big $desk\n\
+             ### Scale Options Start\n\
+             # Target Display = $laptop\n\
+             # Target Display = Dell Inc. U2720Q\n\
+             # Scale Options = 1, 2\n\
+             ### Scale Options End\n\
+             output $laptop scale 1.5\n\
+             output $big {\n\
+             \x20   scale 2\n\
+             }\n\
+             output $unset scale 3\n",
+        );
+        let options = find_scale_options(&config).unwrap();
+        assert_eq!(
+            options.target_displays,
+            targets(&["eDP-1", "Dell Inc. U2720Q"])
+        );
+        assert_eq!(
+            find_output_names(&config),
+            targets(&["eDP-1", "Dell Inc. U2720Q", "$unset"])
+        );
+
+        let updated = update_scale_in_outputs(&config, &options.target_displays, &option("1"));
+        assert_eq!(updated[8], "output $laptop scale 1");
+        assert_eq!(updated[10], "    scale 1");
+        let scales = get_output_scales(&updated, &options.target_displays);
+        assert_eq!(scales["eDP-1"], option("1"));
+    }
+
+    #[test]
+    fn works_out_the_logical_size_of_an_option() {
+        assert_eq!(option("1.5").logical_size((3840, 2160)), Some((2560, 1440)));
+        assert_eq!(
+            option("2@2880x1800@120Hz").logical_size((3840, 2160)),
+            Some((1440, 900))
+        );
+        assert_eq!(ScaleOption::off().logical_size((3840, 2160)), None);
+    }
+
+    #[test]
+    fn reports_conflicts_and_edits_the_effective_line() {
+        let config = lines(
+            "output * scale 2 bg ~/wall.png fill\n\
+             output eDP-1 scale 1.5\n\
+             output HDMI-A-1 scale 1\n\
+             output eDP-1 scale 1.25 pos 0 0\n",
+        );
+        let conflicts = find_conflicts(&config, &targets(&["eDP-1", "HDMI-A-1", "DP-1"]));
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(
+            conflicts[0].describe("config"),
+            "Lines 1 (output *), 2, 4 of config all set the scale of eDP-1; line 4 is the one \
+             changed, since sway applies the last one."
+        );
+        assert_eq!(conflicts[1].named, [2]);
+        assert!(find_conflicts(&lines("output eDP-1 scale 1\n"), &targets(&["eDP-1"])).is_empty());
+
+        assert_eq!(
+            get_output_scales(&config, &targets(&["eDP-1"]))["eDP-1"],
+            option("1.25")
+        );
+        let updated = update_scale_in_outputs(&config, &targets(&["eDP-1"]), &option("2"));
+        assert_eq!(updated[1], "output eDP-1 scale 1.5");
+        assert_eq!(updated[3], "output eDP-1 scale 2 pos 0 0");
+    }
+
+    #[test]
+    fn leaves_include_lines_untouched() {
+        let config = lines(include_str!("../tests/fixtures/includes.conf"));
+        let updated = update_scale_in_outputs(&config, &targets(&["eDP-1"]), &option("1"));
+        for (before, after) in config.iter().zip(&updated) {
+            if before.starts_with("include") {
+                assert_eq!(before, after);
+            }
+        }
+        assert_eq!(
+            get_current_scale(&updated, &targets(&["eDP-1"]), EPSILON),
+            CurrentScale::Uniform(option("1"))
+        );
+    }
+}
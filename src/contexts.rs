@@ -0,0 +1,137 @@
+use crate::ipc::{self, Output};
+use crate::settings::{OptionGroup, Settings};
+use std::fs;
+use std::io;
+
+/// Function to read the machine's hostname from the kernel
+pub fn hostname() -> Option<String> {
+    let name = fs::read_to_string("/proc/sys/kernel/hostname").ok()?;
+    Some(name.trim().to_string())
+}
+
+/// Function to check whether a group's automatic criteria all hold. A group without any
+/// criteria is only ever chosen by name.
+fn group_matches(group: &OptionGroup, hostname: Option<&str>, outputs: &[Output]) -> bool {
+    if group.hostname.is_none() && group.outputs.is_empty() {
+        return false;
+    }
+    let host_matches = group
+        .hostname
+        .as_ref()
+        .is_none_or(|expected| hostname == Some(expected.as_str()));
+    let outputs_match = group.outputs.iter().all(|criteria| {
+        outputs
+            .iter()
+            .any(|output| criteria == &output.name || criteria == &output.description())
+    });
+    host_matches && outputs_match
+}
+
+/// Function to pick the first group, by name, whose hostname and connected outputs match
+pub fn find_group<'a>(
+    settings: &'a Settings,
+    hostname: Option<&str>,
+    outputs: &[Output],
+) -> Option<(&'a str, &'a OptionGroup)> {
+    settings
+        .options
+        .iter()
+        .find(|(_, group)| group_matches(group, hostname, outputs))
+        .map(|(name, group)| (name.as_str(), group))
+}
+
+/// Function to choose the option group to cycle through: the one named by `--context` or the
+/// `context` setting, or else the first that matches this machine. None means the options in
+/// the config's marker section apply.
+pub fn select(settings: &Settings) -> io::Result<Option<&OptionGroup>> {
+    if let Some(name) = &settings.context {
+        return match settings.options.get(name) {
+            Some(group) => Ok(Some(group)),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "No option group named '{}'. Defined groups: {}",
+                    name,
+                    if settings.options.is_empty() {
+                        "none".to_string()
+                    } else {
+                        settings
+                            .options
+                            .keys()
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    }
+                ),
+            )),
+        };
+    }
+
+    // Only ask sway for the outputs if some group is chosen by the monitors it sees
+    let outputs = if settings
+        .options
+        .values()
+        .any(|group| !group.outputs.is_empty())
+    {
+        ipc::get_outputs().unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    Ok(find_group(settings, hostname().as_deref(), &outputs).map(|(_, group)| group))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn group(hostname: Option<&str>, outputs: &[&str]) -> OptionGroup {
+        OptionGroup {
+            scales: vec!["1".parse().unwrap()],
+            hostname: hostname.map(str::to_string),
+            outputs: outputs.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    fn output(name: &str, make: &str, model: &str, serial: &str) -> Output {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "make": make,
+            "model": model,
+            "serial": serial,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn picks_groups_by_hostname_and_monitors() {
+        let settings = Settings {
+            options: BTreeMap::from([
+                ("fallback".to_string(), group(None, &[])),
+                (
+                    "home".to_string(),
+                    group(Some("laptop"), &["LG Electronics LG TV 0x01"]),
+                ),
+                ("work".to_string(), group(Some("laptop"), &["DP-3"])),
+            ]),
+            ..Default::default()
+        };
+        let laptop = [output("eDP-1", "BOE", "0x095F", "Unknown")];
+        let at_work = [
+            laptop[0].clone(),
+            output("DP-3", "Dell Inc.", "U2720Q", "1"),
+        ];
+        let at_home = [
+            laptop[0].clone(),
+            output("HDMI-A-1", "LG Electronics", "LG TV", "0x01"),
+        ];
+
+        let name = |host: &str, outputs: &[Output]| {
+            find_group(&settings, Some(host), outputs).map(|(name, _)| name.to_string())
+        };
+        assert_eq!(name("laptop", &at_work), Some("work".to_string()));
+        assert_eq!(name("laptop", &at_home), Some("home".to_string()));
+        assert_eq!(name("desktop", &at_work), None);
+        assert_eq!(name("laptop", &laptop), None);
+    }
+}
@@ -0,0 +1,748 @@
+use crate::clock;
+use crate::contexts;
+use crate::dbus;
+use crate::events::{self, EventSocket, Peer, Reply, ScaleEvent};
+use crate::history;
+use crate::ipc::{self, Output};
+use crate::matcher;
+use crate::metrics;
+use crate::pidfile::PidFile;
+use crate::settings::{OutputSettings, Power, ScaleFilter, Settings, Subpixel};
+use crate::state;
+use crate::workspaces::Overrides;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The daemon's current settings. Each event and each D-Bus call works on the snapshot it took
+/// when it started, so a reload never changes the settings under a command halfway through.
+pub type SharedSettings = Arc<RwLock<Arc<Settings>>>;
+
+/// What the daemon knows about the outputs, shared with the metrics endpoint
+#[derive(Debug, Default)]
+pub struct DaemonState {
+    pub scales: BTreeMap<String, f32>,
+    pub changes: u64,
+    pub last_change: Option<u64>,
+}
+
+/// Events handled by the daemon's main loop
+enum Event {
+    OutputsChanged,
+    WorkspacesChanged,
+    SubscriptionEnded,
+    /// SIGUSR1 asks for the next scale, SIGUSR2 for the previous one
+    Cycle {
+        reverse: bool,
+        /// The signal and the process that sent it, for the history
+        trigger: String,
+    },
+    /// swayidle saw no input for the configured time
+    Idle,
+    /// Input resumed after an idle period
+    Resumed,
+    /// The sway config or the settings file changed on disk
+    ConfigChanged,
+    /// A command a client sent over the event socket, answered on `reply`
+    Command {
+        line: String,
+        peer: Option<Peer>,
+        reply: Sender<Reply>,
+    },
+    /// SIGINT or SIGTERM
+    Terminate,
+}
+
+/// Cycle steps waiting to be applied as one change once no further request has come in time
+#[derive(Debug, Default)]
+struct Debounce {
+    steps: i64,
+    /// Unix time in milliseconds the steps are due at, None while none are waiting
+    due_ms: Option<u64>,
+}
+
+impl Debounce {
+    /// Function to add a step and put the change off until the given time
+    fn push(&mut self, step: i64, due_ms: u64) {
+        self.steps += step;
+        self.due_ms = Some(due_ms);
+    }
+
+    /// Function to get the time left until the waiting steps are due, if any are waiting
+    fn remaining(&self, now_ms: u64) -> Option<Duration> {
+        self.due_ms
+            .map(|due_ms| Duration::from_millis(due_ms.saturating_sub(now_ms)))
+    }
+
+    /// Function to take the net number of steps once they are due
+    fn take_due(&mut self, now_ms: u64) -> Option<i64> {
+        if self.due_ms? > now_ms {
+            return None;
+        }
+        self.due_ms = None;
+        Some(std::mem::take(&mut self.steps))
+    }
+}
+
+/// Function to run the daemon until the sway event subscription ends or it is told to stop.
+/// `load` reads the settings again when the files change or a client sends `reload-config`.
+pub fn run(
+    settings: Settings,
+    load: &dyn Fn() -> io::Result<Settings>,
+    metrics_addr: Option<SocketAddr>,
+    dbus: bool,
+    pidfile: &Path,
+    events_socket: Option<&Path>,
+) -> io::Result<()> {
+    let pidfile = PidFile::create(pidfile)?;
+    let shared: SharedSettings = Arc::new(RwLock::new(Arc::new(settings)));
+    let settings = Arc::clone(&shared.read().unwrap());
+    let state = Arc::new(Mutex::new(DaemonState::default()));
+    let outputs = ipc::get_outputs()?;
+    refresh_outputs(&state, &settings, &outputs, false);
+
+    // Apply the matching automatic profile at startup, then again on every hotplug
+    let mut connected = connected_names(&outputs);
+    apply_matching_profile(&settings, &outputs, "startup");
+
+    if let Some(addr) = metrics_addr {
+        metrics::serve(addr, Arc::clone(&state))?;
+        println!("Serving metrics on http://{}/metrics", addr);
+    }
+
+    let bus = if dbus {
+        let service = dbus::Service::start(Arc::clone(&shared), Arc::clone(&state))?;
+        println!("Registered {} on the session bus", dbus::BUS_NAME);
+        Some(service)
+    } else {
+        None
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    let events_socket = match events_socket {
+        Some(path) => {
+            let commands = sender.clone();
+            let socket = EventSocket::listen(path, move |line, peer| {
+                // The main loop answers, so the command sees the same settings as everything else
+                let (reply, answer) = mpsc::channel();
+                let line = line.to_string();
+                let _ = commands.send(Event::Command {
+                    line: line.clone(),
+                    peer,
+                    reply,
+                });
+                answer.recv().unwrap_or(Reply {
+                    command: line,
+                    ok: false,
+                    error: Some("The daemon is shutting down".to_string()),
+                })
+            })?;
+            println!("Streaming scale changes on {}", path.display());
+            Some(socket)
+        }
+        None => None,
+    };
+
+    watch_events("output", || Event::OutputsChanged, sender.clone())?;
+    let mut overrides = Overrides::default();
+    let mut watching_workspaces = !settings.workspaces.is_empty();
+    if watching_workspaces {
+        watch_events("workspace", || Event::WorkspacesChanged, sender.clone())?;
+        apply_workspace_overrides(&settings, &mut overrides);
+    }
+    check_idle(&settings).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut swayidle = settings
+        .idle
+        .as_ref()
+        .and_then(|idle| watch_idle(idle.after_minutes, sender.clone()));
+    let mut idle_restore: Option<Vec<OutputSettings>> = None;
+    watch_files(sender.clone());
+    watch_signals(sender.clone())?;
+    println!(
+        "Send SIGUSR1 or SIGUSR2 to PID {} (see {}) to cycle the scale.",
+        std::process::id(),
+        pidfile.path().display()
+    );
+
+    // Cycle requests are held back until none has come for a while, then applied as one change,
+    // which the history puts down to the last of them
+    let mut pending = Debounce::default();
+    let mut pending_trigger = String::new();
+
+    println!("Watching for output changes.");
+    loop {
+        let settings = Arc::clone(&shared.read().unwrap());
+        let debounce_ms = settings.debounce_ms.unwrap_or(250);
+        if let Some(steps) = pending.take_due(clock::now_ms()) {
+            if steps != 0 {
+                triggered_by(std::mem::take(&mut pending_trigger));
+                if let Err(e) = crate::cycle_scale_by(&settings, steps, false) {
+                    warning!("Failed to cycle the scale: {}", e);
+                }
+            }
+            continue;
+        }
+        let event = match pending.remaining(clock::now_ms()) {
+            Some(wait) => match clock::recv_timeout(&receiver, wait) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            },
+            None => match receiver.recv() {
+                Ok(event) => event,
+                Err(_) => break,
+            },
+        };
+        match event {
+            Event::OutputsChanged => {
+                let outputs = match ipc::get_outputs() {
+                    Ok(outputs) => outputs,
+                    Err(e) => {
+                        warning!("Failed to query outputs: {}", e);
+                        continue;
+                    }
+                };
+
+                let changed = refresh_outputs(&state, &settings, &outputs, true);
+                if let Some(bus) = &bus {
+                    for event in &changed {
+                        if let Err(e) = bus.scale_changed(&event.output, event.scale) {
+                            warning!("Failed to emit ScaleChanged: {}", e);
+                        }
+                    }
+                }
+                if let Some(socket) = &events_socket {
+                    socket.broadcast(&changed);
+                }
+
+                // Scale and mode changes also raise output events; only hotplugs pick a profile
+                let names = connected_names(&outputs);
+                if names != connected {
+                    connected = names;
+                    apply_matching_profile(&settings, &outputs, "hotplug");
+                }
+            }
+            Event::WorkspacesChanged => apply_workspace_overrides(&settings, &mut overrides),
+            Event::Cycle { reverse, trigger } if debounce_ms > 0 => {
+                let step = if reverse { -1 } else { 1 };
+                pending.push(step, clock::now_ms() + debounce_ms);
+                pending_trigger = trigger;
+            }
+            Event::Cycle { reverse, trigger } => {
+                triggered_by(trigger);
+                if let Err(e) = crate::cycle_scale(&settings, reverse, false) {
+                    warning!("Failed to cycle the scale: {}", e);
+                }
+            }
+            Event::Idle => {
+                if let Some(idle) = settings.idle.as_ref().filter(|_| idle_restore.is_none()) {
+                    idle_restore = enter_idle(&settings, &idle.profile);
+                }
+            }
+            Event::Resumed => {
+                if let Some(restore) = idle_restore.take() {
+                    println!("Input resumed; restoring the outputs");
+                    triggered_by("idle rule, input resumed".to_string());
+                    if let Err(e) = crate::apply_recorded_settings(&restore) {
+                        warning!("Failed to restore the outputs after idle: {}", e);
+                    }
+                }
+            }
+            Event::ConfigChanged | Event::Command { .. } => {
+                let (result, reply) = match event {
+                    Event::Command { line, peer, reply } if line == "reload-config" => {
+                        let client = match peer {
+                            Some(peer) => history::describe_process(peer.pid, Some(peer.uid)),
+                            None => "an unknown process".to_string(),
+                        };
+                        triggered_by(format!("socket client {}: {}", client, line));
+                        (reload_settings(load, &shared), Some((line, reply)))
+                    }
+                    Event::Command { line, reply, .. } => (
+                        Err(format!("Unknown command '{}'; use reload-config", line)),
+                        Some((line, reply)),
+                    ),
+                    _ => {
+                        triggered_by("configuration saved".to_string());
+                        (reload_settings(load, &shared), None)
+                    }
+                };
+                match &result {
+                    Ok(reloaded) => follow_reload(
+                        &settings,
+                        reloaded,
+                        &mut watching_workspaces,
+                        &mut overrides,
+                        &mut swayidle,
+                        &sender,
+                    ),
+                    Err(e) if reply.is_none() => {
+                        warning!("Keeping the previous configuration: {}", e)
+                    }
+                    Err(_) => {}
+                }
+                if let Some((command, reply)) = reply {
+                    let _ = reply.send(Reply {
+                        command,
+                        ok: result.is_ok(),
+                        error: result.err(),
+                    });
+                }
+            }
+            Event::Terminate => {
+                if let Some(child) = &mut swayidle {
+                    let _ = child.kill();
+                }
+                pidfile.remove();
+                return Ok(());
+            }
+            Event::SubscriptionEnded => {
+                error!("Lost the connection to sway.");
+                if let Some(child) = &mut swayidle {
+                    let _ = child.kill();
+                }
+                pidfile.remove();
+                process::exit(1);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Function to forward one kind of sway event to the main loop from a background thread
+fn watch_events(kind: &str, event: fn() -> Event, sender: Sender<Event>) -> io::Result<()> {
+    let mut child = ipc::subscribe(&[kind])?;
+    let stdout = child.stdout.take().expect("Subscription stdout is piped");
+
+    thread::spawn(move || {
+        for _ in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if sender.send(event()).is_err() {
+                break;
+            }
+        }
+        let _ = child.wait();
+        let _ = sender.send(Event::SubscriptionEnded);
+    });
+    Ok(())
+}
+
+/// Function to start swayidle with commands that report idle and resume on its output, and
+/// forward those reports to the main loop. Without swayidle the daemon runs on with a warning.
+fn watch_idle(after_minutes: f32, sender: Sender<Event>) -> Option<process::Child> {
+    let seconds = ((after_minutes * 60.0).round() as u64).max(1);
+    let spawned = process::Command::new("swayidle")
+        .args(["-w", "timeout", &seconds.to_string(), "echo idle"])
+        .args(["resume", "echo resumed"])
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::piped())
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => {
+            warning!("Failed to start swayidle, so [idle] has no effect: {}", e);
+            return None;
+        }
+    };
+    let stdout = child.stdout.take().expect("swayidle stdout is piped");
+
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            let event = match line.trim() {
+                "idle" => Event::Idle,
+                "resumed" => Event::Resumed,
+                _ => continue,
+            };
+            if sender.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    Some(child)
+}
+
+/// Function to apply the idle profile to the connected outputs it names. Returns the settings
+/// that undo it, covering only what the profile changes.
+fn enter_idle(settings: &Settings, name: &str) -> Option<Vec<OutputSettings>> {
+    let profile = settings.profiles.get(name)?;
+    let outputs = match ipc::get_outputs() {
+        Ok(outputs) => outputs,
+        Err(e) => {
+            warning!("Failed to query outputs: {}", e);
+            return None;
+        }
+    };
+    let entries = matcher::connected_entries(profile, &outputs);
+    let entries = match state::load_state() {
+        Ok(state) => state.skip_pinned(entries),
+        Err(e) => {
+            warning!("Failed to read the pinned displays: {}", e);
+            entries
+        }
+    };
+
+    let restore = entries
+        .iter()
+        .filter_map(|entry| {
+            let output = outputs.iter().find(|output| output.name == entry.name)?;
+            Some(OutputSettings {
+                name: output.name.clone(),
+                scale: entry.scale.and(output.scale),
+                mode: entry
+                    .mode
+                    .as_ref()
+                    .and(output.current_mode.as_ref())
+                    .map(|mode| mode.to_arg()),
+                power: entry.power.map(|_| {
+                    if output.is_powered() {
+                        Power::On
+                    } else {
+                        Power::Off
+                    }
+                }),
+                position: entry.position.map(|_| [output.rect.x, output.rect.y]),
+                render_bit_depth: entry.render_bit_depth.and(output.render_bit_depth),
+                hdr: entry.hdr.and(output.hdr),
+                scale_filter: entry
+                    .scale_filter
+                    .and(output.scale_filter.as_deref())
+                    .and_then(ScaleFilter::from_name),
+                subpixel: entry
+                    .subpixel
+                    .and(output.subpixel_hinting.as_deref())
+                    .and_then(Subpixel::from_name),
+                max_render_time: entry.max_render_time.and(output.max_render_time),
+            })
+        })
+        .collect();
+
+    println!("Session is idle; applying profile '{}'", name);
+    triggered_by(format!("idle rule, profile '{}'", name));
+    if let Err(e) = crate::apply_recorded_settings(&entries) {
+        warning!("Failed to apply profile '{}': {}", name, e);
+    }
+    Some(restore)
+}
+
+/// Function to forward SIGUSR1, SIGUSR2 and termination signals to the main loop, naming the
+/// process that sent a cycle request
+fn watch_signals(sender: Sender<Event>) -> io::Result<()> {
+    use signal_hook::consts::{SIGINT, SIGTERM, SIGUSR1, SIGUSR2};
+    use signal_hook::iterator::exfiltrator::WithOrigin;
+
+    let mut signals =
+        signal_hook::iterator::SignalsInfo::<WithOrigin>::new([SIGUSR1, SIGUSR2, SIGINT, SIGTERM])?;
+    thread::spawn(move || {
+        for origin in signals.forever() {
+            let name = if origin.signal == SIGUSR1 {
+                "SIGUSR1"
+            } else {
+                "SIGUSR2"
+            };
+            let trigger = match origin.process {
+                Some(process) => format!(
+                    "signal {} from {}",
+                    name,
+                    history::describe_process(process.pid as u32, Some(process.uid))
+                ),
+                None => format!("signal {}", name),
+            };
+            let event = match origin.signal {
+                SIGUSR1 | SIGUSR2 => Event::Cycle {
+                    reverse: origin.signal == SIGUSR2,
+                    trigger,
+                },
+                _ => Event::Terminate,
+            };
+            if sender.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Function to name what sets off the changes that follow, in the log and in the history
+fn triggered_by(trigger: String) {
+    println!("Triggered by {}", trigger);
+    history::set_trigger(trigger);
+}
+
+/// Function to list the names of the connected outputs, sorted so they can be compared
+fn connected_names(outputs: &[Output]) -> Vec<String> {
+    let mut names: Vec<String> = outputs.iter().map(|output| output.name.clone()).collect();
+    names.sort();
+    names
+}
+
+/// Function to apply the first automatic profile that matches the connected outputs, if any.
+/// `cause` says what made the daemon look, `startup` or `hotplug`.
+fn apply_matching_profile(settings: &Settings, outputs: &[Output], cause: &str) {
+    let Some((name, profile)) =
+        matcher::find_profile(&settings.profiles, contexts::hostname().as_deref(), outputs)
+    else {
+        return;
+    };
+    println!("Applying profile '{}'", name);
+    triggered_by(format!(
+        "{} rule, profile '{}' for {}",
+        cause,
+        name,
+        connected_names(outputs).join(", ")
+    ));
+    let profile = match state::load_state() {
+        Ok(state) => state.skip_pinned(profile),
+        Err(e) => {
+            warning!("Failed to read the pinned displays: {}", e);
+            profile
+        }
+    };
+    if let Err(e) = crate::apply_recorded_settings(&profile) {
+        warning!("Failed to apply profile '{}': {}", name, e);
+    }
+}
+
+/// Function to apply the workspace overrides for the visible workspaces over IPC, restoring the
+/// scale of outputs whose overridden workspace is no longer visible
+fn apply_workspace_overrides(settings: &Settings, overrides: &mut Overrides) {
+    let (workspaces, outputs) = match (ipc::get_workspaces(), ipc::get_outputs()) {
+        (Ok(workspaces), Ok(outputs)) => (workspaces, outputs),
+        (Err(e), _) | (_, Err(e)) => {
+            warning!("Failed to query workspaces: {}", e);
+            return;
+        }
+    };
+    let changes = overrides.update(
+        &settings.workspaces,
+        &workspaces,
+        &outputs,
+        settings.precision.epsilon,
+    );
+    if changes.is_empty() {
+        return;
+    }
+    let visible: Vec<String> = changes
+        .iter()
+        .map(|change| {
+            let shown = workspaces
+                .iter()
+                .find(|workspace| workspace.visible && workspace.output == change.name);
+            match shown {
+                Some(workspace) => format!("{} on {}", workspace.name, change.name),
+                None => change.name.clone(),
+            }
+        })
+        .collect();
+    triggered_by(format!("workspace rule, {}", visible.join(", ")));
+    if let Err(e) = crate::apply_recorded_settings(&changes) {
+        warning!("Failed to apply workspace overrides: {}", e);
+    }
+}
+
+/// Function to update the output scales, counting a change if any of them differ.
+/// Returns the outputs whose scale changed.
+fn refresh_outputs(
+    state: &Mutex<DaemonState>,
+    settings: &Settings,
+    outputs: &[Output],
+    count_changes: bool,
+) -> Vec<ScaleEvent> {
+    let scales = events::output_scales(outputs);
+
+    let mut state = state.lock().unwrap();
+    let changed = events::scale_changes(&state.scales, &scales, &settings.precision);
+
+    if count_changes && (!changed.is_empty() || !scales.keys().eq(state.scales.keys())) {
+        state.changes += 1;
+        state.last_change = Some(clock::now());
+        for event in &changed {
+            println!("{}: scale {}", event.output, event.scale);
+        }
+    }
+    state.scales = scales;
+    changed
+}
+
+/// Function to check that the [idle] section names a profile that exists
+fn check_idle(settings: &Settings) -> Result<(), String> {
+    match &settings.idle {
+        Some(idle) if !settings.profiles.contains_key(&idle.profile) => Err(format!(
+            "[idle] names profile '{}', which is not defined.",
+            idle.profile
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Function to load the settings again and, if they are usable, make them the daemon's current
+/// snapshot. Broken settings are refused and the previous ones stay in place.
+fn reload_settings(
+    load: &dyn Fn() -> io::Result<Settings>,
+    shared: &SharedSettings,
+) -> Result<Arc<Settings>, String> {
+    let settings = load().map_err(|e| e.to_string())?;
+    check_idle(&settings)?;
+
+    // The options are read from the sway config on every change; report what they are now
+    match crate::config_path().and_then(|path| crate::read_config(&path)) {
+        Ok(lines) => match crate::find_scale_options(&settings, &lines) {
+            Ok(options) => println!(
+                "Reloaded the configuration: {} target displays, {} scale options",
+                options.target_displays.len(),
+                options.scale_values.len()
+            ),
+            Err(e) => {
+                println!("Reloaded the settings");
+                warning!("The sway config has no usable scale options yet: {}", e);
+            }
+        },
+        Err(e) => {
+            println!("Reloaded the settings");
+            warning!("Failed to read the sway config: {}", e);
+        }
+    }
+    let settings = Arc::new(settings);
+    *shared.write().unwrap() = Arc::clone(&settings);
+    Ok(settings)
+}
+
+/// Function to bring the watchers in line with reloaded settings: follow workspaces once any
+/// have scales, and restart swayidle when [idle] changed
+fn follow_reload(
+    previous: &Settings,
+    reloaded: &Settings,
+    watching_workspaces: &mut bool,
+    overrides: &mut Overrides,
+    swayidle: &mut Option<process::Child>,
+    sender: &Sender<Event>,
+) {
+    if !*watching_workspaces && !reloaded.workspaces.is_empty() {
+        *watching_workspaces = true;
+        if let Err(e) = watch_events("workspace", || Event::WorkspacesChanged, sender.clone()) {
+            warning!("Failed to follow workspace changes: {}", e);
+        }
+    }
+    if *watching_workspaces {
+        apply_workspace_overrides(reloaded, overrides);
+    }
+    if reloaded.idle != previous.idle {
+        if let Some(child) = swayidle {
+            let _ = child.kill();
+        }
+        *swayidle = reloaded
+            .idle
+            .as_ref()
+            .and_then(|idle| watch_idle(idle.after_minutes, sender.clone()));
+    }
+}
+
+/// Function to send `ConfigChanged` to the main loop whenever the sway config or the settings
+/// file is written. inotifywait reports the writes; without it the files are polled.
+fn watch_files(sender: Sender<Event>) {
+    // Editors replace files rather than write them, so the directories are watched
+    let files: Vec<PathBuf> = crate::config_path()
+        .ok()
+        .map(|path| fs::canonicalize(&path).unwrap_or_else(|_| PathBuf::from(path)))
+        .into_iter()
+        .chain(crate::settings::settings_path())
+        .collect();
+    let dirs: Vec<&Path> = files
+        .iter()
+        .filter_map(|file| file.parent())
+        .filter(|dir| dir.is_dir())
+        .collect();
+    let spawned = process::Command::new("inotifywait")
+        .args([
+            "-m",
+            "-q",
+            "-e",
+            "close_write,moved_to,create",
+            "--format",
+            "%w%f",
+        ])
+        .args(&dirs)
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::piped())
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(_) => {
+            poll_files(files, sender);
+            return;
+        }
+    };
+    let stdout = child.stdout.take().expect("inotifywait stdout is piped");
+
+    thread::spawn(move || {
+        // One save often raises several events; they are reported once
+        let mut last: Option<Instant> = None;
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            // The daemon's own changes need no reload
+            if !files.iter().any(|file| file == Path::new(&line))
+                || crate::transaction::written_here(Path::new(&line))
+            {
+                continue;
+            }
+            if last.is_some_and(|last| last.elapsed() < Duration::from_millis(200)) {
+                continue;
+            }
+            last = Some(Instant::now());
+            if sender.send(Event::ConfigChanged).is_err() {
+                break;
+            }
+        }
+        let _ = child.kill();
+    });
+}
+
+/// Function to check the files' modification times every second, for systems without inotifywait
+fn poll_files(files: Vec<PathBuf>, sender: Sender<Event>) {
+    let modified = |file: &PathBuf| fs::metadata(file).and_then(|m| m.modified()).ok();
+    thread::spawn(move || {
+        let mut seen: Vec<_> = files.iter().map(modified).collect();
+        loop {
+            thread::sleep(Duration::from_secs(1));
+            let now: Vec<_> = files.iter().map(modified).collect();
+            let changed_elsewhere = files
+                .iter()
+                .zip(seen.iter().zip(&now))
+                .any(|(file, (seen, now))| seen != now && !crate::transaction::written_here(file));
+            seen = now;
+            if changed_elsewhere && sender.send(Event::ConfigChanged).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debounce_applies_quick_requests_as_one_change() {
+        let mut pending = Debounce::default();
+        assert_eq!(pending.remaining(1000), None);
+        assert_eq!(pending.take_due(1000), None);
+
+        // Each request puts the change off again, so two quick ones go out together
+        pending.push(1, 1250);
+        pending.push(1, 1400);
+        assert_eq!(pending.remaining(1300), Some(Duration::from_millis(100)));
+        assert_eq!(pending.take_due(1300), None);
+        assert_eq!(pending.take_due(1400), Some(2));
+        assert_eq!(pending.remaining(1400), None);
+
+        // Requests that cancel out are still taken, as zero steps
+        pending.push(1, 2250);
+        pending.push(-1, 2300);
+        assert_eq!(pending.take_due(5000), Some(0));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::daemon::{DaemonState, SharedSettings};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+/// Well-known name the daemon owns on the session bus
+pub const BUS_NAME: &str = "org.swayscale.Switcher1";
+/// Path of the single object exported by the daemon
+#[cfg(feature = "dbus")]
+pub const OBJECT_PATH: &str = "/org/swayscale/Switcher1";
+
+#[cfg(feature = "dbus")]
+mod interface {
+    use crate::daemon::{DaemonState, SharedSettings};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use zbus::fdo;
+    use zbus::interface;
+    use zbus::message::Header;
+    use zbus::object_server::SignalEmitter;
+
+    /// Function to put the changes a method call makes down to the bus client that called it
+    fn triggered_by(header: &Header<'_>, method: &str) {
+        let trigger = match header.sender() {
+            Some(sender) => format!("D-Bus call {} from {}", method, sender),
+            None => format!("D-Bus call {}", method),
+        };
+        println!("Triggered by {}", trigger);
+        crate::history::set_trigger(trigger);
+    }
+
+    /// The object exported on the bus; method calls reuse the same code paths as the CLI
+    pub struct Switcher {
+        pub settings: SharedSettings,
+        pub state: Arc<Mutex<DaemonState>>,
+    }
+
+    #[interface(name = "org.swayscale.Switcher1")]
+    impl Switcher {
+        /// Cycle the target displays to the next scale option and return it
+        fn cycle(&self, #[zbus(header)] header: Header<'_>) -> fdo::Result<String> {
+            let settings = Arc::clone(&self.settings.read().unwrap());
+            triggered_by(&header, "Cycle");
+            crate::cycle_scale(&settings, false, false)
+                .map(|scale| scale.to_string())
+                .map_err(|e| fdo::Error::Failed(e.to_string()))
+        }
+
+        /// Set the scale of a single output, named by connector, description or alias
+        fn set_scale(
+            &self,
+            #[zbus(header)] header: Header<'_>,
+            output: String,
+            value: f64,
+        ) -> fdo::Result<()> {
+            let settings = Arc::clone(&self.settings.read().unwrap());
+            triggered_by(&header, "SetScale");
+            crate::set_output_scale(&settings, &output, value).map_err(|e| match e.kind() {
+                std::io::ErrorKind::InvalidInput => fdo::Error::InvalidArgs(e.to_string()),
+                _ => fdo::Error::Failed(e.to_string()),
+            })
+        }
+
+        /// Return the current scale of each output
+        fn get_status(&self) -> HashMap<String, f64> {
+            self.state
+                .lock()
+                .unwrap()
+                .scales
+                .iter()
+                .map(|(output, &scale)| (output.clone(), scale as f64))
+                .collect()
+        }
+
+        /// Emitted whenever the daemon observes an output's scale change
+        #[zbus(signal)]
+        async fn scale_changed(
+            emitter: &SignalEmitter<'_>,
+            output: &str,
+            scale: f64,
+        ) -> zbus::Result<()>;
+    }
+}
+
+/// A connection to the session bus that owns [`BUS_NAME`]
+pub struct Service {
+    #[cfg(feature = "dbus")]
+    connection: zbus::blocking::Connection,
+}
+
+impl Service {
+    /// Function to export the switcher object and claim the bus name
+    #[cfg(feature = "dbus")]
+    pub fn start(settings: SharedSettings, state: Arc<Mutex<DaemonState>>) -> io::Result<Service> {
+        let connection = (|| {
+            zbus::blocking::connection::Builder::session()?
+                .name(BUS_NAME)?
+                .serve_at(OBJECT_PATH, interface::Switcher { settings, state })?
+                .build()
+        })()
+        .map_err(io::Error::other)?;
+        Ok(Service { connection })
+    }
+
+    /// Function to report that D-Bus support was not compiled in
+    #[cfg(not(feature = "dbus"))]
+    pub fn start(
+        _settings: SharedSettings,
+        _state: Arc<Mutex<DaemonState>>,
+    ) -> io::Result<Service> {
+        Err(io::Error::other(
+            "This build does not include D-Bus support (the `dbus` feature is disabled)",
+        ))
+    }
+
+    /// Function to broadcast the `ScaleChanged` signal
+    #[cfg(feature = "dbus")]
+    pub fn scale_changed(&self, output: &str, scale: f32) -> io::Result<()> {
+        self.connection
+            .emit_signal(
+                None::<&str>,
+                OBJECT_PATH,
+                BUS_NAME,
+                "ScaleChanged",
+                &(output, scale as f64),
+            )
+            .map_err(io::Error::other)
+    }
+
+    /// Function to broadcast the `ScaleChanged` signal; a no-op without D-Bus support
+    #[cfg(not(feature = "dbus"))]
+    pub fn scale_changed(&self, _output: &str, _scale: f32) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+use crate::platform::{self, on_path};
+use crate::settings::{ReloadStrategy, Settings};
+use crate::ui::{self, Style};
+use crate::validate::Validation;
+use crate::{config, contexts, includes, ipc, snippet};
+use std::io;
+use std::path::Path;
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Function to print one check's outcome, returning whether it failed
+fn report(name: &str, status: Status, detail: impl AsRef<str>) -> bool {
+    let label = match status {
+        Status::Ok => ui::paint(" ok ", Style::Green),
+        Status::Warn => ui::paint("warn", Style::Yellow),
+        Status::Fail => ui::paint("FAIL", Style::Red),
+    };
+    println!("[{}] {}: {}", label, name, detail.as_ref());
+    status == Status::Fail
+}
+
+/// Function to check the settings, the sway config and the session for the problems that
+/// commonly keep a scale change from applying. Fails if any check does.
+pub fn run(settings: io::Result<Settings>) -> io::Result<()> {
+    let mut failures = 0;
+    let mut check = |name: &str, status: Status, detail: String| {
+        if report(name, status, detail) {
+            failures += 1;
+        }
+    };
+
+    let settings = match settings {
+        Ok(settings) => {
+            let path = crate::settings::settings_path()
+                .filter(|path| path.exists())
+                .map_or("defaults, no settings file".to_string(), |path| {
+                    path.display().to_string()
+                });
+            check("settings", Status::Ok, path);
+
+            // Overrides from the environment are easy to forget in a shell's profile
+            let mut overrides: Vec<String> = std::env::vars()
+                .map(|(name, _)| name)
+                .filter(|name| name.starts_with(crate::settings::ENV_PREFIX))
+                .collect();
+            overrides.sort();
+            if !overrides.is_empty() {
+                check("environment", Status::Ok, overrides.join(", "));
+            }
+            settings
+        }
+        Err(e) => {
+            check("settings", Status::Fail, e.to_string());
+            Settings::default()
+        }
+    };
+
+    // The sway config and its scale options section
+    let config_path = crate::config_path()?;
+    let lines = match crate::read_config(&config_path) {
+        Ok(lines) => {
+            check("sway config", Status::Ok, config_path.clone());
+            Some(lines)
+        }
+        Err(e) => {
+            check("sway config", Status::Fail, e.to_string());
+            None
+        }
+    };
+    if let Some(lines) = &lines {
+        match crate::scale_options_section(&settings, lines) {
+            Ok(options) => {
+                check(
+                    "scale options",
+                    Status::Ok,
+                    format!(
+                        "{} for {}",
+                        options
+                            .scale_values
+                            .iter()
+                            .map(|option| option.to_string())
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        options.target_displays.join(", ")
+                    ),
+                );
+                match crate::validate_targets(&settings, lines, &options.target_displays) {
+                    Ok(()) => check("target displays", Status::Ok, "all found".to_string()),
+                    Err(e) => check("target displays", Status::Fail, e.to_string()),
+                }
+
+                // Lines that fight over a display's scale make the edited one easy to misjudge
+                let mut files = vec![(config_path.clone(), lines.clone())];
+                for (path, included) in includes::included_files(Path::new(&config_path), lines) {
+                    files.push((path.display().to_string(), included));
+                }
+                for (path, file_lines) in &files {
+                    for conflict in config::find_conflicts(file_lines, &options.target_displays) {
+                        check("output lines", Status::Warn, conflict.describe(path));
+                    }
+                }
+            }
+            Err(e) => check("scale options", Status::Fail, e),
+        }
+
+        if settings.managed {
+            let snippet_path = crate::expanduser(snippet::SNIPPET_PATH)
+                .expect("Failed to expand managed snippet path");
+            if snippet::is_included(lines, &snippet_path) {
+                check("managed snippet", Status::Ok, "included".to_string());
+            } else {
+                check(
+                    "managed snippet",
+                    Status::Fail,
+                    format!(
+                        "the config does not include it; add 'include {}'",
+                        snippet::SNIPPET_PATH
+                    ),
+                );
+            }
+        }
+    }
+
+    if !settings.options.is_empty() || settings.context.is_some() {
+        match contexts::select(&settings) {
+            Ok(Some(group)) if group.scales.is_empty() => check(
+                "option group",
+                Status::Fail,
+                "the group has no scales".to_string(),
+            ),
+            Ok(Some(group)) => check(
+                "option group",
+                Status::Ok,
+                format!("{} scales", group.scales.len()),
+            ),
+            Ok(None) => check(
+                "option group",
+                Status::Warn,
+                "no group matches; the config's options apply".to_string(),
+            ),
+            Err(e) => check("option group", Status::Fail, e.to_string()),
+        }
+    }
+
+    // The running session and the programs the settings rely on
+    if !on_path("swaymsg") {
+        check("sway", Status::Fail, "swaymsg is not on PATH".to_string());
+    } else if ipc::is_running() {
+        check("sway", Status::Ok, "running".to_string());
+    } else {
+        check(
+            "sway",
+            Status::Warn,
+            format!(
+                "not running, as {}; changes apply when sway starts",
+                platform::diagnose(&platform::Environment::detect())
+            ),
+        );
+    }
+    if settings.reload_strategy == ReloadStrategy::Exec && settings.reload_command.is_none() {
+        check(
+            "reload",
+            Status::Fail,
+            "the 'exec' strategy needs reload_command".to_string(),
+        );
+    }
+    if settings.validate != Validation::None && !on_path("sway") {
+        check(
+            "validation",
+            Status::Fail,
+            "validating the config needs sway on PATH".to_string(),
+        );
+    }
+    if settings.announce_offline_changes && !on_path("notify-send") {
+        check(
+            "notifications",
+            Status::Warn,
+            "notify-send is not on PATH; announcements are printed instead".to_string(),
+        );
+    }
+    if let Some(sync) = &settings.sync {
+        if !on_path("git") {
+            check(
+                "sync",
+                Status::Fail,
+                "'profiles sync' needs git on PATH".to_string(),
+            );
+        }
+        if sync.encrypt_to.is_some() && !on_path("gpg") {
+            check(
+                "sync",
+                Status::Fail,
+                "encrypt_to needs gpg on PATH".to_string(),
+            );
+        }
+    }
+    for template in &settings.templates {
+        let source = crate::expanduser(&template.source).unwrap_or(template.source.clone());
+        if !Path::new(&source).is_file() {
+            check(
+                "templates",
+                Status::Fail,
+                format!("{} does not exist", source),
+            );
+        }
+    }
+
+    match failures {
+        0 => Ok(()),
+        1 => Err(io::Error::other("1 problem found.")),
+        n => Err(io::Error::other(format!("{} problems found.", n))),
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// Function to look up an output's physical size in millimetres from the EDID the kernel exposes
+/// under `/sys/class/drm`; sway's IPC does not report it
+pub fn physical_size(output: &str) -> Option<(u32, u32)> {
+    let edid = fs::read(connector_path(output)?.join("edid")).ok()?;
+    parse_physical_size(&edid)
+}
+
+/// Function to find the DRM connector directory, e.g. `card1-eDP-1`, for a sway output name
+fn connector_path(output: &str) -> Option<PathBuf> {
+    let suffix = format!("-{}", output);
+    fs::read_dir("/sys/class/drm")
+        .ok()?
+        .filter_map(Result::ok)
+        .find(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.starts_with("card") && name.ends_with(&suffix)
+        })
+        .map(|entry| entry.path())
+}
+
+/// Function to read the image size from an EDID block, preferring the millimetre precision of
+/// the first detailed timing descriptor over the centimetre fields of the basic parameters
+fn parse_physical_size(edid: &[u8]) -> Option<(u32, u32)> {
+    const HEADER: [u8; 8] = [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00];
+    if edid.len() < 128 || edid[..8] != HEADER {
+        return None;
+    }
+
+    // A non-zero pixel clock marks the descriptor as a timing rather than a text block
+    let descriptor = &edid[54..72];
+    if descriptor[0] != 0 || descriptor[1] != 0 {
+        let width = descriptor[12] as u32 | ((descriptor[14] as u32 & 0xf0) << 4);
+        let height = descriptor[13] as u32 | ((descriptor[14] as u32 & 0x0f) << 8);
+        if width > 0 && height > 0 {
+            return Some((width, height));
+        }
+    }
+
+    // Projectors and some TVs report zero, meaning the size is undefined
+    let (width, height) = (edid[21] as u32 * 10, edid[22] as u32 * 10);
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edid() -> Vec<u8> {
+        let mut edid = vec![0; 128];
+        edid[..8].copy_from_slice(&[0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00]);
+        // 30 x 19 cm in the basic parameters
+        edid[21] = 30;
+        edid[22] = 19;
+        edid
+    }
+
+    #[test]
+    fn prefers_the_detailed_timing_size() {
+        let mut edid = edid();
+        // Pixel clock, then 302 x 188 mm split across the low bytes and a shared high nibble byte
+        edid[54] = 0x01;
+        edid[66] = 0x2e;
+        edid[67] = 188;
+        edid[68] = 0x10;
+        assert_eq!(parse_physical_size(&edid), Some((302, 188)));
+    }
+
+    #[test]
+    fn falls_back_to_the_basic_parameters() {
+        assert_eq!(parse_physical_size(&edid()), Some((300, 190)));
+    }
+
+    #[test]
+    fn rejects_missing_or_undefined_sizes() {
+        let mut edid = edid();
+        edid[21] = 0;
+        assert_eq!(parse_physical_size(&edid), None);
+        assert_eq!(parse_physical_size(&[0; 16]), None);
+    }
+}
@@ -0,0 +1,279 @@
+use crate::ipc::{self, Output};
+use crate::options::Precision;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// A change of one output's scale, sent as a line of JSON to subscribers
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScaleEvent {
+    pub output: String,
+    pub scale: f32,
+    /// None when the output was not known before, e.g. right after it was connected
+    pub previous: Option<f32>,
+    /// Unix time in seconds
+    pub timestamp: u64,
+}
+
+impl ScaleEvent {
+    /// Function to serialize the event as a single line of JSON, including the newline
+    pub fn to_line(&self) -> String {
+        let mut line = serde_json::to_string(self).expect("Scale events always serialize");
+        line.push('\n');
+        line
+    }
+}
+
+/// Function to collect the scale of every output that reports one
+pub fn output_scales(outputs: &[Output]) -> BTreeMap<String, f32> {
+    outputs
+        .iter()
+        .filter_map(|output| Some((output.name.clone(), output.scale?)))
+        .collect()
+}
+
+/// Function to list the outputs whose scale differs between two snapshots
+pub fn scale_changes(
+    old: &BTreeMap<String, f32>,
+    new: &BTreeMap<String, f32>,
+    precision: &Precision,
+) -> Vec<ScaleEvent> {
+    let timestamp = crate::clock::now();
+
+    new.iter()
+        .filter(|(output, &scale)| {
+            old.get(*output)
+                .is_none_or(|&previous| !precision.same(previous, scale))
+        })
+        .map(|(output, &scale)| ScaleEvent {
+            output: output.clone(),
+            scale,
+            previous: old.get(output).copied(),
+            timestamp,
+        })
+        .collect()
+}
+
+/// Function to print a line of JSON for the current scale of every output, then one for each
+/// change sway reports, until the connection to sway ends
+pub fn subscribe(precision: &Precision) -> io::Result<()> {
+    let mut child = ipc::subscribe(&["output"])?;
+    let stdout = child.stdout.take().expect("Subscription stdout is piped");
+
+    let mut scales = BTreeMap::new();
+    let print_changes = |scales: &mut BTreeMap<String, f32>| -> io::Result<()> {
+        let current = output_scales(&ipc::get_outputs()?);
+        let mut out = io::stdout().lock();
+        for event in scale_changes(scales, &current, precision) {
+            out.write_all(event.to_line().as_bytes())?;
+        }
+        out.flush()?;
+        *scales = current;
+        Ok(())
+    };
+
+    print_changes(&mut scales)?;
+    for _ in BufReader::new(stdout).lines().map_while(Result::ok) {
+        print_changes(&mut scales)?;
+    }
+
+    let _ = child.wait();
+    Err(io::Error::other("Lost the connection to sway."))
+}
+
+/// The answer to a command a client sent over the event socket, sent back as a line of JSON
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reply {
+    pub command: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Reply {
+    /// Function to serialize the reply as a single line of JSON, including the newline
+    pub fn to_line(&self) -> String {
+        let mut line = serde_json::to_string(self).expect("Replies always serialize");
+        line.push('\n');
+        line
+    }
+}
+
+/// The process at the other end of a client connection, as the kernel reports it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Peer {
+    pub pid: u32,
+    pub uid: u32,
+}
+
+/// Function to ask the kernel which process connected a stream, through SO_PEERCRED
+fn peer(stream: &UnixStream) -> Option<Peer> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: cred and len describe a buffer of the size SO_PEERCRED writes, alive for the call
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            (&mut cred as *mut libc::ucred).cast(),
+            &mut len,
+        )
+    };
+    (result == 0).then_some(Peer {
+        pid: cred.pid as u32,
+        uid: cred.uid,
+    })
+}
+
+/// A Unix socket that streams scale events to every connected client, removed again when dropped.
+/// Clients may also send commands, one per line, which are answered on the same connection.
+pub struct EventSocket {
+    path: PathBuf,
+    clients: Arc<Mutex<Vec<UnixStream>>>,
+}
+
+impl EventSocket {
+    /// Function to bind the socket and accept clients in the background, passing each command
+    /// line a client sends to `on_command` along with the process that sent it
+    pub fn listen(
+        path: &Path,
+        on_command: impl Fn(&str, Option<Peer>) -> Reply + Send + Sync + 'static,
+    ) -> io::Result<EventSocket> {
+        // A socket left behind by a daemon that did not shut down cleanly would block the bind
+        if UnixStream::connect(path).is_err() {
+            let _ = fs::remove_file(path);
+        }
+        let listener = UnixListener::bind(path)?;
+        let clients = Arc::new(Mutex::new(Vec::new()));
+
+        let accepted = Arc::clone(&clients);
+        let on_command = Arc::new(on_command);
+        thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                // A client that stops reading must not stall the daemon
+                let _ = stream.set_write_timeout(Some(Duration::from_secs(1)));
+                if let Ok(mut reader) = stream.try_clone() {
+                    let on_command = Arc::clone(&on_command);
+                    thread::spawn(move || {
+                        let sender = peer(&reader);
+                        let lines = BufReader::new(reader.try_clone()?).lines();
+                        for line in lines.map_while(Result::ok) {
+                            let command = line.trim();
+                            if !command.is_empty() {
+                                let reply = on_command(command, sender);
+                                reader.write_all(reply.to_line().as_bytes())?;
+                            }
+                        }
+                        io::Result::Ok(())
+                    });
+                }
+                accepted.lock().unwrap().push(stream);
+            }
+        });
+
+        Ok(EventSocket {
+            path: path.to_path_buf(),
+            clients,
+        })
+    }
+
+    /// Function to send events to every client, dropping the ones that have disconnected
+    pub fn broadcast(&self, events: &[ScaleEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        let lines: String = events.iter().map(ScaleEvent::to_line).collect();
+        self.clients
+            .lock()
+            .unwrap()
+            .retain_mut(|client| client.write_all(lines.as_bytes()).is_ok());
+    }
+}
+
+impl Drop for EventSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_new_and_changed_outputs_only() {
+        let old = BTreeMap::from([("eDP-1".to_string(), 1.5), ("DP-1".to_string(), 1.0)]);
+        let new = BTreeMap::from([
+            ("eDP-1".to_string(), 2.0),
+            ("DP-1".to_string(), 1.0001),
+            ("HDMI-A-1".to_string(), 1.0),
+        ]);
+        let events = scale_changes(&old, &new, &Precision::default());
+        let changes: Vec<(&str, f32, Option<f32>)> = events
+            .iter()
+            .map(|e| (e.output.as_str(), e.scale, e.previous))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![("HDMI-A-1", 1.0, None), ("eDP-1", 2.0, Some(1.5))]
+        );
+    }
+
+    #[test]
+    fn serializes_one_json_object_per_line() {
+        let event = ScaleEvent {
+            output: "eDP-1".to_string(),
+            scale: 2.0,
+            previous: None,
+            timestamp: 7,
+        };
+        assert_eq!(
+            event.to_line(),
+            "{\"output\":\"eDP-1\",\"scale\":2.0,\"previous\":null,\"timestamp\":7}\n"
+        );
+    }
+
+    #[test]
+    fn answers_commands_on_the_event_socket() {
+        let path = std::env::temp_dir().join(format!("sss-events-{}.sock", std::process::id()));
+        let socket = EventSocket::listen(&path, |command, _| Reply {
+            command: command.to_string(),
+            ok: command == "reload-config",
+            error: (command != "reload-config").then(|| "unknown command".to_string()),
+        })
+        .unwrap();
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(b"reload-config\nfrobnicate\n").unwrap();
+        let mut lines = BufReader::new(client.try_clone().unwrap()).lines();
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            r#"{"command":"reload-config","ok":true}"#
+        );
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            r#"{"command":"frobnicate","ok":false,"error":"unknown command"}"#
+        );
+        drop(socket);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn names_the_process_at_the_other_end() {
+        let (ours, _theirs) = UnixStream::pair().unwrap();
+        let peer = peer(&ours).unwrap();
+        assert_eq!(peer.pid, std::process::id());
+    }
+}
@@ -0,0 +1,242 @@
+use std::fmt;
+
+/// A scale option written as an arithmetic expression over per-output variables,
+/// e.g. `auto`, `auto+0.25` or `native/2`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScaleExpr {
+    source: String,
+    root: Node,
+}
+
+/// Values of the variables an expression may refer to, for one output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vars {
+    /// The scale at which the output shows 96 logical pixels per inch
+    pub native: f32,
+    /// `native` rounded to a quarter step, and never below 1
+    pub auto: f32,
+}
+
+impl Vars {
+    /// Function to derive the variables from an output's DPI
+    pub fn from_dpi(dpi: f32) -> Vars {
+        let native = dpi / 96.0;
+        let auto = ((native * 4.0).round() / 4.0).max(1.0);
+        Vars { native, auto }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f32),
+    Auto,
+    Native,
+    Neg(Box<Node>),
+    Binary(Box<Node>, char, Box<Node>),
+}
+
+impl ScaleExpr {
+    /// Function to parse an expression made of numbers, `auto`, `native`, `+ - * /` and parentheses
+    pub fn parse(source: &str) -> Result<ScaleExpr, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let root = parser.expr()?;
+        if parser.pos != parser.tokens.len() {
+            return Err(format!("unexpected '{}'", parser.tokens[parser.pos]));
+        }
+        Ok(ScaleExpr {
+            source: source.trim().to_string(),
+            root,
+        })
+    }
+
+    /// Function to check whether the expression refers to any output variables
+    pub fn has_variables(&self) -> bool {
+        self.root.has_variables()
+    }
+
+    /// Function to evaluate the expression, rounded to three decimals
+    pub fn eval(&self, vars: &Vars) -> Result<f32, String> {
+        let value = self.root.eval(vars)?;
+        if !value.is_finite() || value <= 0.0 {
+            return Err(format!("'{}' evaluates to {}", self.source, value));
+        }
+        Ok((value * 1000.0).round() / 1000.0)
+    }
+}
+
+impl fmt::Display for ScaleExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Node {
+    fn has_variables(&self) -> bool {
+        match self {
+            Node::Number(_) => false,
+            Node::Auto | Node::Native => true,
+            Node::Neg(inner) => inner.has_variables(),
+            Node::Binary(left, _, right) => left.has_variables() || right.has_variables(),
+        }
+    }
+
+    fn eval(&self, vars: &Vars) -> Result<f32, String> {
+        Ok(match self {
+            Node::Number(n) => *n,
+            Node::Auto => vars.auto,
+            Node::Native => vars.native,
+            Node::Neg(inner) => -inner.eval(vars)?,
+            Node::Binary(left, op, right) => {
+                let (l, r) = (left.eval(vars)?, right.eval(vars)?);
+                match op {
+                    '+' => l + r,
+                    '-' => l - r,
+                    '*' => l * r,
+                    '/' if r == 0.0 => return Err("division by zero".to_string()),
+                    _ => l / r,
+                }
+            }
+        })
+    }
+}
+
+/// Function to split an expression into numbers, identifiers and single-character operators
+fn tokenize(source: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit() || **d == '.') {
+                number.push(d);
+                chars.next();
+            }
+            tokens.push(number);
+        } else if c.is_ascii_alphabetic() {
+            let mut ident = String::new();
+            while let Some(&a) = chars.peek().filter(|a| a.is_ascii_alphabetic()) {
+                ident.push(a);
+                chars.next();
+            }
+            tokens.push(ident);
+        } else if "+-*/()".contains(c) {
+            tokens.push(c.to_string());
+            chars.next();
+        } else {
+            return Err(format!("unexpected character '{}'", c));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive-descent parser over the token list
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Node, String> {
+        let mut node = self.term()?;
+        while let Some(op) = self.peek().filter(|t| *t == "+" || *t == "-") {
+            let op = op.chars().next().unwrap();
+            self.pos += 1;
+            node = Node::Binary(Box::new(node), op, Box::new(self.term()?));
+        }
+        Ok(node)
+    }
+
+    // term := factor (('*' | '/') factor)*
+    fn term(&mut self) -> Result<Node, String> {
+        let mut node = self.factor()?;
+        while let Some(op) = self.peek().filter(|t| *t == "*" || *t == "/") {
+            let op = op.chars().next().unwrap();
+            self.pos += 1;
+            node = Node::Binary(Box::new(node), op, Box::new(self.factor()?));
+        }
+        Ok(node)
+    }
+
+    // factor := number | 'auto' | 'native' | '-' factor | '(' expr ')'
+    fn factor(&mut self) -> Result<Node, String> {
+        match self.next().as_deref() {
+            Some("auto") => Ok(Node::Auto),
+            Some("native") => Ok(Node::Native),
+            Some("-") => Ok(Node::Neg(Box::new(self.factor()?))),
+            Some("(") => {
+                let node = self.expr()?;
+                match self.next().as_deref() {
+                    Some(")") => Ok(node),
+                    _ => Err("missing ')'".to_string()),
+                }
+            }
+            Some(token) => token
+                .parse::<f32>()
+                .map(Node::Number)
+                .map_err(|_| format!("unknown value '{}'", token)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VARS: Vars = Vars {
+        native: 2.25,
+        auto: 2.25,
+    };
+
+    #[test]
+    fn evaluates_with_precedence_and_parentheses() {
+        assert_eq!(ScaleExpr::parse("auto+0.25").unwrap().eval(&VARS), Ok(2.5));
+        assert_eq!(ScaleExpr::parse("native/2").unwrap().eval(&VARS), Ok(1.125));
+        assert_eq!(
+            ScaleExpr::parse("1 + 2 * 0.5").unwrap().eval(&VARS),
+            Ok(2.0)
+        );
+        assert_eq!(
+            ScaleExpr::parse("(1 + 2) * 0.5").unwrap().eval(&VARS),
+            Ok(1.5)
+        );
+        assert_eq!(
+            ScaleExpr::parse("auto - -0.5").unwrap().eval(&VARS),
+            Ok(2.75)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        assert!(ScaleExpr::parse("auto +").is_err());
+        assert!(ScaleExpr::parse("(native").is_err());
+        assert!(ScaleExpr::parse("dpi/96").is_err());
+        assert!(ScaleExpr::parse("auto % 2").is_err());
+        assert!(ScaleExpr::parse("auto - 3").unwrap().eval(&VARS).is_err());
+    }
+
+    #[test]
+    fn auto_rounds_native_to_quarter_steps() {
+        let vars = Vars::from_dpi(216.0);
+        assert_eq!(vars.native, 2.25);
+        assert_eq!(vars.auto, 2.25);
+        assert_eq!(Vars::from_dpi(140.0).auto, 1.5);
+        assert_eq!(Vars::from_dpi(80.0).auto, 1.0);
+    }
+}
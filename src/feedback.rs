@@ -0,0 +1,94 @@
+use crate::ipc;
+use crate::settings;
+use std::time::Duration;
+
+/// Colors sway uses for the focused window when the config sets none
+const DEFAULT_FOCUSED: &str = "client.focused #4c7899 #285577 #ffffff #2e9ef4 #285577";
+
+/// Function to show a failure where a keybinding's output is not seen: switch sway to the mode
+/// `error_feedback` names, flash the focused window's border, or both, then put them back.
+/// Settings that fail to load show nothing; the error already says why.
+pub fn show() {
+    let Some(feedback) = settings::load_settings()
+        .ok()
+        .and_then(|settings| settings.error_feedback)
+    else {
+        return;
+    };
+    if ipc::is_wlr_randr() || !ipc::is_running() {
+        return;
+    }
+
+    let mut restore = Vec::new();
+    if let Some(mode) = &feedback.mode {
+        match ipc::run_command(&format!("mode {}", ipc::quote(mode))) {
+            Ok(()) => restore.push("mode default".to_string()),
+            Err(e) => warning!("Failed to switch to mode '{}': {}", mode, e),
+        }
+    }
+    if let Some(color) = &feedback.border_color {
+        let focused = focused_colors();
+        match ipc::run_command(&flash_colors(&focused, color)) {
+            Ok(()) => restore.push(focused),
+            Err(e) => warning!("Failed to flash the border: {}", e),
+        }
+    }
+    if restore.is_empty() {
+        return;
+    }
+
+    crate::clock::sleep(Duration::from_secs_f32(feedback.seconds.max(0.0)));
+    for command in restore {
+        if let Err(e) = ipc::run_command(&command) {
+            warning!("Failed to undo the error feedback: {}", e);
+        }
+    }
+}
+
+/// Function to find the `client.focused` line of the sway config, or sway's default colors
+fn focused_colors() -> String {
+    let lines = crate::config_path()
+        .and_then(|path| crate::read_config(&path))
+        .unwrap_or_default();
+    lines
+        .iter()
+        .rev()
+        .map(|line| line.trim())
+        .find(|line| line.split_whitespace().next() == Some("client.focused"))
+        .map_or(DEFAULT_FOCUSED.to_string(), |line| {
+            line.split_whitespace().collect::<Vec<_>>().join(" ")
+        })
+}
+
+/// Function to build the `client.focused` command that paints the border, indicator and child
+/// border in the given color, keeping the title bar's background and text
+fn flash_colors(focused: &str, color: &str) -> String {
+    let defaults: Vec<&str> = DEFAULT_FOCUSED.split_whitespace().collect();
+    let words: Vec<&str> = focused.split_whitespace().collect();
+    let word = |i: usize| words.get(i).copied().unwrap_or(defaults[i]);
+    format!(
+        "client.focused {} {} {} {} {}",
+        color,
+        word(2),
+        word(3),
+        color,
+        color
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flashes_the_border_and_keeps_the_title_bar() {
+        assert_eq!(
+            flash_colors("client.focused #4c7899 #285577 #ffffff", "#ff0000"),
+            "client.focused #ff0000 #285577 #ffffff #ff0000 #ff0000"
+        );
+        assert_eq!(
+            flash_colors(DEFAULT_FOCUSED, "#cc241d"),
+            "client.focused #cc241d #285577 #ffffff #cc241d #cc241d"
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use crate::options::ScaleOption;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+thread_local! {
+    /// What set off the changes made on this thread, see `set_trigger`
+    static TRIGGER: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// A single applied change, stored as one JSON object per line in the history file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    pub timestamp: u64,
+    pub outputs: BTreeMap<String, ScaleOption>,
+    #[serde(default)]
+    pub previous: BTreeMap<String, ScaleOption>,
+    /// Backend that applied the change, e.g. `ipc`, `wlr-randr` or `config`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+    /// What set the change off, e.g. `signal SIGUSR1 from pid 4242 (swaymsg), uid 1000`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger: Option<String>,
+}
+
+/// Function to name what sets off the changes this thread makes from now on. The daemon names
+/// each event before handling it; a one-shot command keeps the default, its parent process.
+pub fn set_trigger(trigger: String) {
+    TRIGGER.with(|current| *current.borrow_mut() = Some(trigger));
+}
+
+/// Function to get what set off the change being made
+pub fn trigger() -> String {
+    TRIGGER
+        .with(|current| current.borrow().clone())
+        .unwrap_or_else(|| {
+            format!(
+                "command line, run by {}",
+                describe_process(std::os::unix::process::parent_id(), None)
+            )
+        })
+}
+
+/// Function to describe a process by its id, name and, if known, user id
+pub fn describe_process(pid: u32, uid: Option<u32>) -> String {
+    let name = fs::read_to_string(format!("/proc/{}/comm", pid))
+        .map(|comm| format!(" ({})", comm.trim()))
+        .unwrap_or_default();
+    match uid {
+        Some(uid) => format!("pid {}{}, uid {}", pid, name, uid),
+        None => format!("pid {}{}", pid, name),
+    }
+}
+
+/// Function to locate the history file under the user's state directory
+pub fn history_path() -> Option<PathBuf> {
+    let base = dirs::state_dir().or_else(dirs::data_local_dir)?;
+    Some(base.join("sway-scale-switcher").join("history.jsonl"))
+}
+
+/// Function to read all history entries, skipping any lines that fail to parse
+pub fn read_history() -> io::Result<Vec<HistoryEntry>> {
+    let path = match history_path() {
+        Some(path) => path,
+        None => return Ok(Vec::new()),
+    };
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let entries = BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect();
+    Ok(entries)
+}
+
+/// Function to append a new entry to the history file, assigning it the next id
+pub fn record_change(
+    previous: BTreeMap<String, ScaleOption>,
+    outputs: BTreeMap<String, ScaleOption>,
+    backend: &str,
+) -> io::Result<HistoryEntry> {
+    let path = history_path().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not determine state directory",
+        )
+    })?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let id = read_history()?.last().map_or(1, |entry| entry.id + 1);
+    let timestamp = crate::clock::now();
+    let entry = HistoryEntry {
+        id,
+        timestamp,
+        outputs,
+        previous,
+        backend: Some(backend.to_string()),
+        trigger: Some(trigger()),
+    };
+
+    // Append only; earlier entries are never rewritten
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    let json = serde_json::to_string(&entry).map_err(io::Error::other)?;
+    writeln!(file, "{}", json)?;
+    Ok(entry)
+}
+
+/// Function to print the most recent history entries, newest last
+pub fn print_history(entries: &[HistoryEntry], limit: usize) {
+    if entries.is_empty() {
+        println!("No scale changes recorded yet.");
+        return;
+    }
+
+    let start = entries.len().saturating_sub(limit);
+    for entry in &entries[start..] {
+        let changes: Vec<String> = entry
+            .outputs
+            .iter()
+            .map(|(output, scale)| match entry.previous.get(output) {
+                Some(previous) => format!("{}: {}", output, crate::ui::change(previous, scale)),
+                None => format!("{}: {}", output, scale),
+            })
+            .collect();
+        match &entry.trigger {
+            Some(trigger) => println!(
+                "{:>4}  {}  {}  [{}]",
+                entry.id,
+                entry.timestamp,
+                changes.join(", "),
+                trigger
+            ),
+            None => println!(
+                "{:>4}  {}  {}",
+                entry.id,
+                entry.timestamp,
+                changes.join(", ")
+            ),
+        }
+    }
+}
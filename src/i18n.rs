@@ -0,0 +1,340 @@
+use std::fmt::Display;
+use std::sync::LazyLock;
+
+/// Look up a message in the user's language and fill its `{}` placeholders in order
+macro_rules! tr {
+    ($msg:ident $(, $arg:expr)* $(,)?) => {
+        $crate::i18n::format($crate::i18n::Msg::$msg, &[$(&$arg as &dyn std::fmt::Display),*])
+    };
+}
+
+/// Languages with a message catalog
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lang {
+    English,
+    Spanish,
+    German,
+}
+
+/// Identifiers of the translated messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Msg {
+    Warning,
+    Error,
+    TargetDisplays,
+    DisplayWithScale,
+    DisplayWithoutScale,
+    AllDisplays,
+    QuitOption,
+    EnterDisplays,
+    SelectedDisplays,
+    InvalidDisplays,
+    CurrentScale,
+    AvailableOptions,
+    CurrentOption,
+    EnterScale,
+    Quitting,
+    SelectedScale,
+    InvalidScale,
+    NoChanges,
+    Swapping,
+    ScaleNotFound,
+    ApplyingDefault,
+    SkippingPinned,
+    Pinned,
+    Unpinned,
+    AlreadyPinned,
+    NoPinned,
+    Reloaded,
+    ReloadSkipped,
+    SwayNotRunning,
+    OfflineChanges,
+    PreviewScale,
+    PreviewReverted,
+    ScaleOutOfRange,
+    ScaleRounded,
+    SaveCustomScale,
+    SavedCustomScale,
+}
+
+/// The language picked from the environment, following the gettext order of precedence
+static LANG: LazyLock<Lang> = LazyLock::new(|| {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    parse_locale(&locale)
+});
+
+/// Function to map a locale such as `es_MX.UTF-8` to a language with a catalog
+pub fn parse_locale(locale: &str) -> Lang {
+    let language = locale
+        .split(['_', '.', '@'])
+        .next()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    match language.as_str() {
+        "es" => Lang::Spanish,
+        "de" => Lang::German,
+        _ => Lang::English,
+    }
+}
+
+/// Function to fetch the template of a message in the user's language, placeholders unfilled
+pub fn text(msg: Msg) -> &'static str {
+    catalog(*LANG, msg)
+}
+
+/// Function to fill a message's `{}` placeholders with the given arguments, in order
+pub fn format(msg: Msg, args: &[&dyn Display]) -> String {
+    let mut parts = text(msg).split("{}");
+    let mut result = parts.next().unwrap_or("").to_string();
+    for (i, part) in parts.enumerate() {
+        if let Some(arg) = args.get(i) {
+            result.push_str(&arg.to_string());
+        }
+        result.push_str(part);
+    }
+    result
+}
+
+/// Function to look a message up in one language's catalog
+fn catalog(lang: Lang, msg: Msg) -> &'static str {
+    match lang {
+        Lang::English => english(msg),
+        Lang::Spanish => spanish(msg),
+        Lang::German => german(msg),
+    }
+}
+
+fn english(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Warning => "Warning:",
+        Msg::Error => "Error:",
+        Msg::TargetDisplays => "Target displays:",
+        Msg::DisplayWithScale => "{}. {} (scale {})",
+        Msg::DisplayWithoutScale => "{}. {} (no scale set)",
+        Msg::AllDisplays => "A. All displays (default)",
+        Msg::QuitOption => "Q. Quit without making changes",
+        Msg::EnterDisplays => {
+            "Enter the numbers of the displays to change, separated by spaces or commas:"
+        }
+        Msg::SelectedDisplays => "Selected displays: {}",
+        Msg::InvalidDisplays => {
+            "Invalid selection. Please enter numbers between 1 and {}, 'A' for all, or 'Q' to quit."
+        }
+        Msg::CurrentScale => "Current active scale: {}",
+        Msg::AvailableOptions => "Available scale options:",
+        Msg::CurrentOption => "{}. {} (current)",
+        Msg::EnterScale => {
+            "Enter the number of the scale you want to apply, a scale of your own such as 1.37, or 'Q' to quit:"
+        }
+        Msg::Quitting => "Quitting without making changes.",
+        Msg::SelectedScale => "Selected scale: {}",
+        Msg::InvalidScale => {
+            "Invalid selection. Please enter a number between 1 and {}, or 'Q' to quit."
+        }
+        Msg::NoChanges => "No changes made. Exiting.",
+        Msg::Swapping => "Swapping scale: {}",
+        Msg::ScaleNotFound => "Current scale {} not found in scale options. Using first scale {}",
+        Msg::ApplyingDefault => "Applying scale {} to {}",
+        Msg::SkippingPinned => "Skipping pinned display {}",
+        Msg::Pinned => "Pinned {}",
+        Msg::Unpinned => "Unpinned {}",
+        Msg::AlreadyPinned => "{} is already pinned.",
+        Msg::NoPinned => "No displays are pinned.",
+        Msg::Reloaded => "Successfully reloaded Sway configuration.",
+        Msg::ReloadSkipped => "Config updated; skipping reload.",
+        Msg::SwayNotRunning => {
+            "sway is not running, so nothing was reloaded; the new config applies when sway starts."
+        }
+        Msg::OfflineChanges => "Display scales changed while sway was not running:",
+        Msg::PreviewScale => {
+            "Previewing scale {}. Press Enter to keep it, enter another number to try it or 'Q' to go back:"
+        }
+        Msg::PreviewReverted => "Restored the previous scale.",
+        Msg::ScaleOutOfRange => "Scale {} is outside the allowed range of {} to {}.",
+        Msg::ScaleRounded => "Scale {} is rounded to {}.",
+        Msg::SaveCustomScale => "Add {} to the scale options for cycling? [y/N]",
+        Msg::SavedCustomScale => "Added {} to the scale options.",
+    }
+}
+
+fn spanish(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Warning => "Aviso:",
+        Msg::Error => "Error:",
+        Msg::TargetDisplays => "Pantallas de destino:",
+        Msg::DisplayWithScale => "{}. {} (escala {})",
+        Msg::DisplayWithoutScale => "{}. {} (sin escala)",
+        Msg::AllDisplays => "A. Todas las pantallas (predeterminado)",
+        Msg::QuitOption => "Q. Salir sin hacer cambios",
+        Msg::EnterDisplays => {
+            "Introduce los números de las pantallas que quieres cambiar, separados por espacios o comas:"
+        }
+        Msg::SelectedDisplays => "Pantallas seleccionadas: {}",
+        Msg::InvalidDisplays => {
+            "Selección no válida. Introduce números entre 1 y {}, 'A' para todas o 'Q' para salir."
+        }
+        Msg::CurrentScale => "Escala activa: {}",
+        Msg::AvailableOptions => "Opciones de escala disponibles:",
+        Msg::CurrentOption => "{}. {} (actual)",
+        Msg::EnterScale => {
+            "Introduce el número de la escala que quieres aplicar, una escala propia como 1.37 o 'Q' para salir:"
+        }
+        Msg::Quitting => "Saliendo sin hacer cambios.",
+        Msg::SelectedScale => "Escala seleccionada: {}",
+        Msg::InvalidScale => {
+            "Selección no válida. Introduce un número entre 1 y {} o 'Q' para salir."
+        }
+        Msg::NoChanges => "No se hicieron cambios. Saliendo.",
+        Msg::Swapping => "Cambiando la escala: {}",
+        Msg::ScaleNotFound => {
+            "La escala actual {} no está entre las opciones. Se usa la primera escala {}"
+        }
+        Msg::ApplyingDefault => "Aplicando la escala {} a {}",
+        Msg::SkippingPinned => "Se omite la pantalla fijada {}",
+        Msg::Pinned => "Pantalla {} fijada",
+        Msg::Unpinned => "Pantalla {} liberada",
+        Msg::AlreadyPinned => "{} ya está fijada.",
+        Msg::NoPinned => "No hay pantallas fijadas.",
+        Msg::Reloaded => "Configuración de Sway recargada.",
+        Msg::ReloadSkipped => "Configuración actualizada; no se recarga.",
+        Msg::SwayNotRunning => {
+            "sway no se está ejecutando, así que no se recargó nada; la nueva configuración se aplicará al iniciar sway."
+        }
+        Msg::OfflineChanges => "Escalas cambiadas mientras sway no se ejecutaba:",
+        Msg::PreviewScale => {
+            "Probando la escala {}. Pulsa Intro para conservarla, introduce otro número para probarlo o 'Q' para volver:"
+        }
+        Msg::PreviewReverted => "Se restauró la escala anterior.",
+        Msg::ScaleOutOfRange => "La escala {} está fuera del rango permitido de {} a {}.",
+        Msg::ScaleRounded => "La escala {} se redondea a {}.",
+        Msg::SaveCustomScale => "¿Añadir {} a las opciones de escala para alternar? [s/N]",
+        Msg::SavedCustomScale => "Se añadió {} a las opciones de escala.",
+    }
+}
+
+fn german(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Warning => "Warnung:",
+        Msg::Error => "Fehler:",
+        Msg::TargetDisplays => "Zielbildschirme:",
+        Msg::DisplayWithScale => "{}. {} (Skalierung {})",
+        Msg::DisplayWithoutScale => "{}. {} (keine Skalierung gesetzt)",
+        Msg::AllDisplays => "A. Alle Bildschirme (Standard)",
+        Msg::QuitOption => "Q. Beenden ohne Änderungen",
+        Msg::EnterDisplays => {
+            "Nummern der zu ändernden Bildschirme eingeben, getrennt durch Leerzeichen oder Kommas:"
+        }
+        Msg::SelectedDisplays => "Ausgewählte Bildschirme: {}",
+        Msg::InvalidDisplays => {
+            "Ungültige Auswahl. Bitte Zahlen zwischen 1 und {} eingeben, 'A' für alle oder 'Q' zum Beenden."
+        }
+        Msg::CurrentScale => "Aktuelle Skalierung: {}",
+        Msg::AvailableOptions => "Verfügbare Skalierungen:",
+        Msg::CurrentOption => "{}. {} (aktuell)",
+        Msg::EnterScale => {
+            "Nummer der gewünschten Skalierung, eine eigene Skalierung wie 1.37 oder 'Q' zum Beenden eingeben:"
+        }
+        Msg::Quitting => "Beenden ohne Änderungen.",
+        Msg::SelectedScale => "Ausgewählte Skalierung: {}",
+        Msg::InvalidScale => {
+            "Ungültige Auswahl. Bitte eine Zahl zwischen 1 und {} eingeben oder 'Q' zum Beenden."
+        }
+        Msg::NoChanges => "Keine Änderungen vorgenommen. Beenden.",
+        Msg::Swapping => "Skalierung wechseln: {}",
+        Msg::ScaleNotFound => {
+            "Aktuelle Skalierung {} ist keine der Optionen. Erste Skalierung {} wird verwendet"
+        }
+        Msg::ApplyingDefault => "Skalierung {} wird auf {} angewendet",
+        Msg::SkippingPinned => "Fixierter Bildschirm {} wird übersprungen",
+        Msg::Pinned => "{} fixiert",
+        Msg::Unpinned => "{} freigegeben",
+        Msg::AlreadyPinned => "{} ist bereits fixiert.",
+        Msg::NoPinned => "Keine Bildschirme fixiert.",
+        Msg::Reloaded => "Sway-Konfiguration neu geladen.",
+        Msg::ReloadSkipped => "Konfiguration aktualisiert; kein Neuladen.",
+        Msg::SwayNotRunning => {
+            "sway läuft nicht, daher wurde nichts neu geladen; die neue Konfiguration gilt beim nächsten Start von sway."
+        }
+        Msg::OfflineChanges => "Skalierungen, die geändert wurden, während sway nicht lief:",
+        Msg::PreviewScale => {
+            "Vorschau der Skalierung {}. Eingabe behält sie, eine andere Nummer probiert diese aus, 'Q' kehrt zurück:"
+        }
+        Msg::PreviewReverted => "Vorherige Skalierung wiederhergestellt.",
+        Msg::ScaleOutOfRange => "Skalierung {} liegt außerhalb des erlaubten Bereichs von {} bis {}.",
+        Msg::ScaleRounded => "Skalierung {} wird auf {} gerundet.",
+        Msg::SaveCustomScale => "{} zu den Skalierungsoptionen zum Durchschalten hinzufügen? [j/N]",
+        Msg::SavedCustomScale => "{} zu den Skalierungsoptionen hinzugefügt.",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Msg; 36] = [
+        Msg::Warning,
+        Msg::Error,
+        Msg::TargetDisplays,
+        Msg::DisplayWithScale,
+        Msg::DisplayWithoutScale,
+        Msg::AllDisplays,
+        Msg::QuitOption,
+        Msg::EnterDisplays,
+        Msg::SelectedDisplays,
+        Msg::InvalidDisplays,
+        Msg::CurrentScale,
+        Msg::AvailableOptions,
+        Msg::CurrentOption,
+        Msg::EnterScale,
+        Msg::Quitting,
+        Msg::SelectedScale,
+        Msg::InvalidScale,
+        Msg::NoChanges,
+        Msg::Swapping,
+        Msg::ScaleNotFound,
+        Msg::ApplyingDefault,
+        Msg::SkippingPinned,
+        Msg::Pinned,
+        Msg::Unpinned,
+        Msg::AlreadyPinned,
+        Msg::NoPinned,
+        Msg::Reloaded,
+        Msg::ReloadSkipped,
+        Msg::SwayNotRunning,
+        Msg::OfflineChanges,
+        Msg::PreviewScale,
+        Msg::PreviewReverted,
+        Msg::ScaleOutOfRange,
+        Msg::ScaleRounded,
+        Msg::SaveCustomScale,
+        Msg::SavedCustomScale,
+    ];
+
+    #[test]
+    fn picks_the_language_from_the_locale() {
+        assert_eq!(parse_locale("es_MX.UTF-8"), Lang::Spanish);
+        assert_eq!(parse_locale("de"), Lang::German);
+        assert_eq!(parse_locale("C.UTF-8"), Lang::English);
+        assert_eq!(parse_locale(""), Lang::English);
+    }
+
+    #[test]
+    fn translations_keep_every_placeholder() {
+        for msg in ALL {
+            let expected = english(msg).matches("{}").count();
+            for lang in [Lang::Spanish, Lang::German] {
+                assert_eq!(
+                    catalog(lang, msg).matches("{}").count(),
+                    expected,
+                    "{:?} in {:?}",
+                    msg,
+                    lang
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// How deep `include` lines are followed, guarding against include cycles sway would reject
+const MAX_DEPTH: usize = 8;
+
+/// Function to find the path named by an `include` line, if the line is one
+fn include_target(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix("include")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let target = rest.trim();
+    let target = target
+        .strip_prefix('"')
+        .and_then(|target| target.strip_suffix('"'))
+        .unwrap_or(target);
+    (!target.is_empty()).then_some(target)
+}
+
+/// Function to expand `$(command)`, `~`, `$VAR` and `${VAR}` the way sway's wordexp does
+fn expand_words(path: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = path;
+    while let Some(start) = rest.find("$(") {
+        expanded.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find(')') else {
+            break;
+        };
+        let command = &rest[start + 2..start + end];
+        let output = Command::new("sh").arg("-c").arg(command).output();
+        if let Ok(output) = output {
+            expanded.push_str(String::from_utf8_lossy(&output.stdout).trim());
+        }
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+
+    if let Some(after) = expanded.strip_prefix('~') {
+        if let Some(home) = dirs::home_dir() {
+            expanded = format!("{}{}", home.display(), after);
+        }
+    }
+
+    let mut result = String::new();
+    let mut chars = expanded.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+        let braced = chars.peek() == Some(&'{');
+        if braced {
+            chars.next();
+        }
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                name.push(c);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        if braced && chars.peek() == Some(&'}') {
+            chars.next();
+        }
+        if name.is_empty() {
+            result.push('$');
+        } else {
+            result.push_str(&std::env::var(&name).unwrap_or_default());
+        }
+    }
+    result
+}
+
+/// Function to match a file name against a pattern with `*` and `?` wildcards
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Function to turn an `include` target into the files it names. Relative paths are resolved
+/// against the including file's directory, and wildcards are supported in the file name.
+pub fn resolve(target: &str, base_dir: &Path) -> Vec<PathBuf> {
+    let path = PathBuf::from(expand_words(target));
+    let path = if path.is_relative() {
+        base_dir.join(path)
+    } else {
+        path
+    };
+
+    let Some(pattern) = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+    else {
+        return Vec::new();
+    };
+    if !pattern.contains(['*', '?']) {
+        return vec![path];
+    }
+    let Some(dir) = path.parent() else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut matches: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            !name.starts_with('.') && wildcard_match(&pattern, &name)
+        })
+        .map(|entry| entry.path())
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Function to read every file the config includes, directly or through other includes, in
+/// the order sway reads them. Files that cannot be read are skipped, as sway skips them too.
+pub fn included_files(config_path: &Path, lines: &[String]) -> Vec<(PathBuf, Vec<String>)> {
+    let mut files = Vec::new();
+    let mut seen = BTreeSet::new();
+    seen.insert(fs::canonicalize(config_path).unwrap_or(config_path.to_path_buf()));
+    follow(config_path, lines, 0, &mut seen, &mut files);
+    files
+}
+
+fn follow(
+    path: &Path,
+    lines: &[String],
+    depth: usize,
+    seen: &mut BTreeSet<PathBuf>,
+    files: &mut Vec<(PathBuf, Vec<String>)>,
+) {
+    if depth >= MAX_DEPTH {
+        return;
+    }
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    for target in lines.iter().filter_map(|line| include_target(line)) {
+        for included in resolve(target, base_dir) {
+            let canonical = fs::canonicalize(&included).unwrap_or(included.clone());
+            if !seen.insert(canonical) {
+                continue;
+            }
+            let Ok(contents) = fs::read(&included) else {
+                continue;
+            };
+            let included_lines = crate::text::decode_lines(&contents);
+            files.push((included.clone(), included_lines.clone()));
+            follow(&included, &included_lines, depth + 1, seen, files);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_relative_and_wildcard_includes() {
+        let dir = std::env::temp_dir().join(format!(
+            "sway-scale-switcher-includes-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("config.d")).unwrap();
+        fs::write(dir.join("config.d/10-outputs"), "include ../nested\n").unwrap();
+        fs::write(dir.join("config.d/20-input"), "input * xkb_layout us\n").unwrap();
+        fs::write(dir.join("config.d/.hidden"), "").unwrap();
+        fs::write(dir.join("nested"), "output eDP-1 scale 2\ninclude config\n").unwrap();
+        let config = dir.join("config");
+        let lines = vec![
+            "include config.d/*".to_string(),
+            "include \"missing file\"".to_string(),
+            "# include nested".to_string(),
+        ];
+        fs::write(&config, lines.join("\n")).unwrap();
+
+        let names: Vec<PathBuf> = included_files(&config, &lines)
+            .into_iter()
+            .map(|(path, _)| path.strip_prefix(&dir).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                PathBuf::from("config.d/10-outputs"),
+                PathBuf::from("config.d/../nested"),
+                PathBuf::from("config.d/20-input"),
+            ]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(wildcard_match("*", "outputs"));
+        assert!(wildcard_match("*.conf", "laptop.conf"));
+        assert!(wildcard_match("1?-*", "10-outputs"));
+        assert!(!wildcard_match("*.conf", "laptop.confx"));
+    }
+}
@@ -0,0 +1,576 @@
+use crate::mock::Compositor;
+use crate::settings::ApplyBackend;
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// The compositor to talk to, picked with `--backend` in place of the backend chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// A running sway, over its IPC socket
+    Sway,
+    /// An in-memory compositor that prints the commands it receives
+    Mock,
+    /// Any wlroots compositor, through wlr-randr and the output management protocol
+    WlrRandr,
+}
+
+/// The IPC socket picked with `--socket` or by discovery; unset means swaymsg follows `SWAYSOCK`
+static SOCKET: OnceLock<PathBuf> = OnceLock::new();
+
+/// The mock compositor picked with `--backend mock`, which replaces sway for every call
+static MOCK: OnceLock<Mutex<Compositor>> = OnceLock::new();
+
+/// The machine picked with `--host`, whose sway every swaymsg call reaches over SSH
+static HOST: OnceLock<String> = OnceLock::new();
+
+/// Whether the sway behind `--host` answered, asked once since every question is an SSH round trip
+static REMOTE_RUNNING: OnceLock<bool> = OnceLock::new();
+
+/// Set by `--backend wlr-randr`, which changes outputs through wlr-randr instead of sway IPC
+static WLR_RANDR: OnceLock<()> = OnceLock::new();
+
+/// The entry of the backend chain picked at startup, None when none of them could be used. Unset
+/// when `--backend` or `--host` picked the backend instead.
+static CHAIN: OnceLock<Option<ApplyBackend>> = OnceLock::new();
+
+/// Set when the backend chain leaves out `ipc`, so sway is never sent a command
+static IPC_DISABLED: OnceLock<()> = OnceLock::new();
+
+/// An output as reported by `swaymsg -t get_outputs`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Output {
+    pub name: String,
+    #[serde(default)]
+    pub make: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub serial: String,
+    #[serde(default)]
+    pub active: bool,
+    /// Reported by sway 1.8 and later
+    #[serde(default)]
+    pub power: Option<bool>,
+    /// Reported by older sway releases
+    #[serde(default)]
+    pub dpms: Option<bool>,
+    #[serde(default)]
+    pub scale: Option<f32>,
+    #[serde(default)]
+    pub current_mode: Option<Mode>,
+    /// Every mode the output supports
+    #[serde(default)]
+    pub modes: Vec<Mode>,
+    /// Bits per color channel sway renders with, when sway reports it
+    #[serde(default)]
+    pub render_bit_depth: Option<u8>,
+    /// Whether HDR is on, when sway reports it
+    #[serde(default)]
+    pub hdr: Option<bool>,
+    /// `enabled` or `disabled`, when sway reports it
+    #[serde(default)]
+    pub adaptive_sync_status: Option<String>,
+    /// `linear`, `nearest` or `smart`, when sway reports it
+    #[serde(default)]
+    pub scale_filter: Option<String>,
+    /// `rgb`, `bgr`, `vrgb`, `vbgr`, `none` or `unknown`, when sway reports it
+    #[serde(default)]
+    pub subpixel_hinting: Option<String>,
+    /// Milliseconds sway leaves itself to render a frame, 0 when that is off
+    #[serde(default)]
+    pub max_render_time: Option<u32>,
+    /// Position and logical size in the layout
+    #[serde(default)]
+    pub rect: Rect,
+}
+
+/// A workspace as reported by `swaymsg -t get_workspaces`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Workspace {
+    pub name: String,
+    pub output: String,
+    #[serde(default)]
+    pub visible: bool,
+    #[serde(default)]
+    pub focused: bool,
+}
+
+/// A node of the layout tree as reported by `swaymsg -t get_tree`, with only the fields needed
+/// to find fullscreen windows
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Node {
+    pub name: Option<String>,
+    pub app_id: Option<String>,
+    /// 0 when not fullscreen, 1 for fullscreen on its output, 2 for global fullscreen
+    pub fullscreen_mode: u8,
+    pub focused: bool,
+    /// Position and size in layout coordinates
+    pub rect: Rect,
+    pub nodes: Vec<Node>,
+    pub floating_nodes: Vec<Node>,
+}
+
+/// A mode as reported by `swaymsg -t get_outputs`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Mode {
+    pub width: i32,
+    pub height: i32,
+    /// Refresh rate in millihertz, 0 if unknown
+    #[serde(default)]
+    pub refresh: i32,
+}
+
+impl Mode {
+    /// Function to write the mode the way an `output ... mode` command takes it
+    pub fn to_arg(&self) -> String {
+        if self.refresh > 0 {
+            format!(
+                "{}x{}@{:.3}Hz",
+                self.width,
+                self.height,
+                self.refresh as f32 / 1000.0
+            )
+        } else {
+            format!("{}x{}", self.width, self.height)
+        }
+    }
+}
+
+/// A rectangle in layout coordinates
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Output {
+    /// Function to determine whether the output is currently powered on
+    pub fn is_powered(&self) -> bool {
+        self.power.or(self.dpms).unwrap_or(self.active)
+    }
+
+    /// Function to build the description sway uses to identify a display across ports,
+    /// e.g. `Dell Inc. DELL U2720Q ABC123`
+    pub fn description(&self) -> String {
+        format!("{} {} {}", self.make, self.model, self.serial)
+    }
+}
+
+/// Function to direct every later swaymsg call at the given socket
+pub fn set_socket(path: PathBuf) {
+    let _ = SOCKET.set(path);
+}
+
+/// Function to send every later call to the mock compositor instead of sway
+pub fn use_mock(compositor: Compositor) {
+    let _ = MOCK.set(Mutex::new(compositor));
+}
+
+/// Function to send every later call to wlr-randr, for wlroots compositors other than sway
+pub fn use_wlr_randr() {
+    let _ = WLR_RANDR.set(());
+}
+
+/// Function to apply changes with the entry of the backend chain that was found usable: sway IPC
+/// as usual, wlr-randr in its place, or neither, leaving the session alone
+pub fn follow_chain(chain: &[ApplyBackend], selected: Option<ApplyBackend>) {
+    if selected == Some(ApplyBackend::WlrRandr) {
+        use_wlr_randr();
+    }
+    if !chain.contains(&ApplyBackend::Ipc) {
+        let _ = IPC_DISABLED.set(());
+    }
+    let _ = CHAIN.set(selected);
+}
+
+/// Function to fail when the backend chain leaves the session alone, naming the reason
+fn check_chain() -> io::Result<()> {
+    match CHAIN.get() {
+        Some(Some(ApplyBackend::Config)) if IPC_DISABLED.get().is_some() => Err(io::Error::other(
+            "The backend chain leaves out ipc, so the running session is left alone",
+        )),
+        Some(None) => Err(no_backend()),
+        _ => Ok(()),
+    }
+}
+
+/// Function to describe a backend chain none of whose entries could be used
+pub fn no_backend() -> io::Error {
+    io::Error::other(
+        "None of the backends in the chain can reach the session, and 'config' is not among \
+         them; nothing was changed. Add \"config\" to backends in the settings to edit the \
+         config anyway.",
+    )
+}
+
+/// Function to check whether the backend chain found nothing to apply changes with
+pub fn chain_is_empty() -> bool {
+    CHAIN.get() == Some(&None)
+}
+
+/// Function to name the backend that applies changes to the session, for reports: `mock`,
+/// `wlr-randr`, `ssh`, `ipc`, or `config` when only the config is edited
+pub fn backend_name() -> &'static str {
+    if MOCK.get().is_some() {
+        "mock"
+    } else if is_wlr_randr() {
+        "wlr-randr"
+    } else if is_remote() {
+        "ssh"
+    } else if is_running() {
+        "ipc"
+    } else {
+        "config"
+    }
+}
+
+/// Function to check whether calls go to wlr-randr, which has no config to reload
+pub fn is_wlr_randr() -> bool {
+    WLR_RANDR.get().is_some()
+}
+
+/// Function to get the mock compositor, if one is in use
+fn mock() -> Option<MutexGuard<'static, Compositor>> {
+    MOCK.get()
+        .map(|mock| mock.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+/// Function to check whether a sway instance is listening on the selected socket, e.g. to tell
+/// an edit over SSH or from a TTY apart from one inside the session
+pub fn is_running() -> bool {
+    if MOCK.get().is_some() {
+        return true;
+    }
+    if check_chain().is_err() {
+        return false;
+    }
+    if is_wlr_randr() {
+        return crate::wlr::is_running();
+    }
+    if is_remote() {
+        return *REMOTE_RUNNING.get_or_init(|| {
+            swaymsg(&["-t", "get_version"])
+                .stdout(process::Stdio::null())
+                .stderr(process::Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+        });
+    }
+    let socket = SOCKET
+        .get()
+        .cloned()
+        .or_else(|| std::env::var_os("SWAYSOCK").map(PathBuf::from));
+    socket.is_some_and(|socket| UnixStream::connect(socket).is_ok())
+}
+
+/// Function to send every later swaymsg call to the sway on another machine, over SSH
+pub fn set_host(host: String) {
+    let _ = HOST.set(host);
+}
+
+/// Function to check whether swaymsg calls go to another machine
+pub fn is_remote() -> bool {
+    HOST.get().is_some()
+}
+
+/// Function to quote a word for a POSIX shell, so it reaches the program unchanged
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+/// Function to build the shell command that runs swaymsg on another machine. SSH sessions do
+/// not set SWAYSOCK, so the newest sway socket in the remote runtime directory is used, unless
+/// `--socket` names one.
+fn remote_command(socket: Option<&Path>, args: &[&str]) -> String {
+    let mut words = vec!["swaymsg".to_string()];
+    if let Some(socket) = socket {
+        words.push("-s".to_string());
+        words.push(shell_quote(&socket.to_string_lossy()));
+    }
+    words.extend(args.iter().map(|arg| shell_quote(arg)));
+    format!(
+        "SWAYSOCK=\"${{SWAYSOCK:-$(ls -t \"${{XDG_RUNTIME_DIR:-/run/user/$(id -u)}}\"/sway-ipc.*.sock \
+         2>/dev/null | head -n 1)}}\" {}",
+        words.join(" ")
+    )
+}
+
+/// Function to build a swaymsg call with the given arguments that talks to the selected sway
+/// instance, on this machine or over SSH
+pub fn swaymsg(args: &[&str]) -> process::Command {
+    if let Some(host) = HOST.get() {
+        // BatchMode fails instead of asking for a password nobody sees
+        let mut command = process::Command::new("ssh");
+        command
+            .args(["-o", "BatchMode=yes", host, "--"])
+            .arg(remote_command(SOCKET.get().map(PathBuf::as_path), args));
+        return command;
+    }
+    let mut command = process::Command::new("swaymsg");
+    if let Some(socket) = SOCKET.get() {
+        command.arg("-s").arg(socket);
+    }
+    command.args(args);
+    command
+}
+
+/// Function to start reloading the config, without waiting for sway to finish
+pub fn reload() -> io::Result<()> {
+    if let Some(mut mock) = mock() {
+        mock.reload();
+        return Ok(());
+    }
+    check_chain()?;
+    if is_wlr_randr() {
+        return Err(io::Error::other(
+            "Reloading the config needs sway; the wlr-randr backend only changes outputs",
+        ));
+    }
+    swaymsg(&["reload"]).spawn().map(|_| ())
+}
+
+/// Function to find the sway IPC sockets in a directory that a compositor still listens on,
+/// skipping the ones left behind by sessions that have ended
+pub fn discover_sockets(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut sockets: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("sway-ipc.") && name.ends_with(".sock"))
+        })
+        .filter(|path| UnixStream::connect(path).is_ok())
+        .collect();
+    sockets.sort();
+    sockets
+}
+
+/// Function to run a sway command over IPC, failing if sway reports an error
+pub fn run_command(command: &str) -> io::Result<()> {
+    if let Some(mut mock) = mock() {
+        return mock.run_command(command);
+    }
+    check_chain()?;
+    if is_wlr_randr() {
+        return crate::wlr::run_command(command);
+    }
+    let output = swaymsg(&[command]).output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Err(io::Error::other(format!(
+            "swaymsg '{}' failed: {}",
+            command,
+            if message.is_empty() { stdout } else { message }
+        )))
+    }
+}
+
+/// Function to query the connected outputs over IPC
+pub fn get_outputs() -> io::Result<Vec<Output>> {
+    if let Some(mock) = mock() {
+        return Ok(mock.outputs());
+    }
+    if is_wlr_randr() {
+        return crate::wlr::get_outputs();
+    }
+    let output = swaymsg(&["-t", "get_outputs", "-r"]).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "swaymsg -t get_outputs failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Function to query the workspaces over IPC
+pub fn get_workspaces() -> io::Result<Vec<Workspace>> {
+    if let Some(mock) = mock() {
+        return Ok(mock.workspaces());
+    }
+    if is_wlr_randr() {
+        return Err(io::Error::other(
+            "Workspaces are only known over sway IPC, not to the wlr-randr backend",
+        ));
+    }
+    let output = swaymsg(&["-t", "get_workspaces", "-r"]).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "swaymsg -t get_workspaces failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Function to query the layout tree over IPC
+pub fn get_tree() -> io::Result<Node> {
+    if MOCK.get().is_some() || is_wlr_randr() {
+        return Ok(Node::default());
+    }
+    let output = swaymsg(&["-t", "get_tree", "-r"]).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "swaymsg -t get_tree failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Function to list the fullscreen windows in a layout tree, by app id or else by title
+pub fn fullscreen_windows(node: &Node) -> Vec<String> {
+    let mut windows = Vec::new();
+    if node.fullscreen_mode != 0 {
+        let name = node.app_id.clone().or(node.name.clone());
+        windows.push(name.unwrap_or_else(|| "a window".to_string()));
+    }
+    for child in node.nodes.iter().chain(&node.floating_nodes) {
+        windows.extend(fullscreen_windows(child));
+    }
+    windows
+}
+
+/// Function to find the focused node in a layout tree
+pub fn focused_node(node: &Node) -> Option<&Node> {
+    if node.focused {
+        return Some(node);
+    }
+    node.nodes
+        .iter()
+        .chain(&node.floating_nodes)
+        .find_map(focused_node)
+}
+
+/// Function to find the output showing the focused workspace
+pub fn focused_output() -> io::Result<Output> {
+    let workspaces = get_workspaces()?;
+    let name = workspaces
+        .iter()
+        .find(|workspace| workspace.focused)
+        .map(|workspace| workspace.output.clone())
+        .ok_or_else(|| io::Error::other("sway reports no focused workspace"))?;
+    get_outputs()?
+        .into_iter()
+        .find(|output| output.name == name)
+        .ok_or_else(|| io::Error::other(format!("The focused output '{}' is not connected.", name)))
+}
+
+/// Function to quote an output name for use in a sway command
+pub fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Function to start `swaymsg` in monitor mode, printing one raw JSON event per line
+pub fn subscribe(events: &[&str]) -> io::Result<process::Child> {
+    // The mock and wlr-randr raise no events, so subscribers just wait, e.g. for the daemon's
+    // signals. The stand-in ends when its stdin closes, i.e. when this process exits.
+    if MOCK.get().is_some() || is_wlr_randr() {
+        return process::Command::new("sh")
+            .args(["-c", "read -r _"])
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::piped())
+            .spawn();
+    }
+    let events = format!(
+        "[{}]",
+        events
+            .iter()
+            .map(|event| format!("\"{}\"", event))
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+    swaymsg(&["-t", "subscribe", "-m", "-r", &events])
+        .stdout(process::Stdio::piped())
+        .spawn()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn quotes_remote_swaymsg_calls() {
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        let command = remote_command(None, &["output \"Dell Inc. U2720Q\" scale 2"]);
+        assert!(command.ends_with(r#" swaymsg 'output "Dell Inc. U2720Q" scale 2'"#));
+        let command = remote_command(Some(Path::new("/run/sway.sock")), &["-t", "get_outputs"]);
+        assert!(command.ends_with(" swaymsg -s '/run/sway.sock' '-t' 'get_outputs'"));
+
+        // The remote shell hands swaymsg the arguments as they were
+        let output = process::Command::new("sh")
+            .arg("-c")
+            .arg(format!(
+                "swaymsg() {{ printf '%s|' \"$@\"; }}; {}",
+                remote_command(None, &["a 'b'", "$HOME", "c;d"])
+            ))
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "a 'b'|$HOME|c;d|");
+    }
+
+    #[test]
+    fn discovers_only_live_sway_sockets() {
+        let dir = std::env::temp_dir().join(format!("sway-scale-switcher-ipc-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let live = dir.join("sway-ipc.1000.42.sock");
+        let _listener = UnixListener::bind(&live).unwrap();
+        // A socket file without a listener, as left behind by a crashed session
+        drop(UnixListener::bind(dir.join("sway-ipc.1000.7.sock")).unwrap());
+        fs::write(dir.join("sway-ipc.1000.9.sock"), "").unwrap();
+        let _other = UnixListener::bind(dir.join("wayland-1.sock")).unwrap();
+
+        assert_eq!(discover_sockets(&dir), vec![live]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn finds_fullscreen_windows_anywhere_in_the_tree() {
+        let tree: Node = serde_json::from_value(serde_json::json!({
+            "name": "root",
+            "nodes": [{
+                "name": "eDP-1",
+                "nodes": [{
+                    "name": "1",
+                    "nodes": [
+                        { "name": "Terminal", "app_id": "foot", "fullscreen_mode": 0 },
+                        { "name": "Meeting", "app_id": null, "fullscreen_mode": 1 },
+                    ],
+                    "floating_nodes": [
+                        { "name": "Game", "app_id": "steam_app_1", "fullscreen_mode": 2 },
+                    ],
+                }],
+            }],
+        }))
+        .unwrap();
+        assert_eq!(fullscreen_windows(&tree), vec!["Meeting", "steam_app_1"]);
+        assert!(focused_node(&tree).is_none());
+        let mut tree = tree;
+        tree.nodes[0].nodes[0].floating_nodes[0].focused = true;
+        assert_eq!(
+            focused_node(&tree).and_then(|node| node.app_id.as_deref()),
+            Some("steam_app_1")
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use crate::ipc::Output;
+use crate::settings::OutputSettings;
+use std::str::FromStr;
+
+/// The arrangements the `layout` subcommand can compute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Arrangement {
+    /// Place the outputs left to right, aligned at the top
+    SideBySide,
+    /// Place the outputs top to bottom, aligned at the left
+    Stacked,
+    /// Overlay every output on the first, scaled so the first one's whole area is visible
+    Mirror,
+}
+
+/// Function to compute positions (and, for mirroring, scales) for the given outputs, in order
+pub fn arrange(arrangement: Arrangement, outputs: &[&Output]) -> Vec<OutputSettings> {
+    let mut settings = Vec::new();
+    let (mut x, mut y) = (0, 0);
+
+    for (i, output) in outputs.iter().enumerate() {
+        let mut setting = OutputSettings {
+            name: output.name.clone(),
+            ..Default::default()
+        };
+
+        match arrangement {
+            Arrangement::SideBySide => {
+                setting.position = Some([x, 0]);
+                x += output.rect.width;
+            }
+            Arrangement::Stacked => {
+                setting.position = Some([0, y]);
+                y += output.rect.height;
+            }
+            Arrangement::Mirror => {
+                let source = outputs[0];
+                setting.position = Some([source.rect.x, source.rect.y]);
+                if i > 0 {
+                    setting.scale = mirror_scale(source, output);
+                }
+            }
+        }
+        settings.push(setting);
+    }
+
+    settings
+}
+
+/// Function to find the largest scale at which `target` still covers the whole logical area of
+/// `source`, so the same region of the layout is visible on both
+fn mirror_scale(source: &Output, target: &Output) -> Option<f32> {
+    let mode = target.current_mode.as_ref()?;
+    if source.rect.width <= 0 || source.rect.height <= 0 {
+        return None;
+    }
+    let scale = f32::min(
+        mode.width as f32 / source.rect.width as f32,
+        mode.height as f32 / source.rect.height as f32,
+    );
+    Some((scale * 1000.0).floor() / 1000.0)
+}
+
+/// Sides of another output an output can be placed on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    LeftOf,
+    RightOf,
+    Above,
+    Below,
+}
+
+impl FromStr for Side {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left-of" => Ok(Side::LeftOf),
+            "right-of" => Ok(Side::RightOf),
+            "above" => Ok(Side::Above),
+            "below" => Ok(Side::Below),
+            _ => Err(format!(
+                "'{}' is not a side; use left-of, right-of, above or below",
+                s
+            )),
+        }
+    }
+}
+
+/// An output on the arrangement map: where it is, its mode and its scale
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placement {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    /// Size of the current mode in pixels
+    pub mode: (i32, i32),
+    pub scale: f32,
+}
+
+impl Placement {
+    /// Function to place an active output where it is now, if its mode is known
+    pub fn from_output(output: &Output) -> Option<Placement> {
+        let mode = output.current_mode.as_ref().filter(|_| output.active)?;
+        Some(Placement {
+            name: output.name.clone(),
+            x: output.rect.x,
+            y: output.rect.y,
+            mode: (mode.width, mode.height),
+            scale: output.scale.unwrap_or(1.0),
+        })
+    }
+
+    /// Function to compute the size the output takes up in the layout at its scale
+    pub fn logical_size(&self) -> (i32, i32) {
+        (
+            (self.mode.0 as f32 / self.scale).round() as i32,
+            (self.mode.1 as f32 / self.scale).round() as i32,
+        )
+    }
+
+    /// Function to describe the placement as the settings that apply it
+    pub fn to_settings(&self) -> OutputSettings {
+        OutputSettings {
+            name: self.name.clone(),
+            scale: Some(self.scale),
+            position: Some([self.x, self.y]),
+            ..Default::default()
+        }
+    }
+}
+
+/// Function to move an output next to another one, lined up with its top or left edge
+pub fn place(
+    placements: &mut [Placement],
+    name: &str,
+    side: Side,
+    other: &str,
+) -> Result<(), String> {
+    let find = |name: &str| {
+        placements
+            .iter()
+            .position(|placement| placement.name == name)
+            .ok_or_else(|| format!("'{}' is not on the map", name))
+    };
+    let (moved, anchor) = (find(name)?, find(other)?);
+    if moved == anchor {
+        return Err("An output cannot be placed next to itself".to_string());
+    }
+    let (width, height) = placements[moved].logical_size();
+    let (anchor_width, anchor_height) = placements[anchor].logical_size();
+    let (x, y) = (placements[anchor].x, placements[anchor].y);
+    let (x, y) = match side {
+        Side::LeftOf => (x - width, y),
+        Side::RightOf => (x + anchor_width, y),
+        Side::Above => (x, y - height),
+        Side::Below => (x, y + anchor_height),
+    };
+    placements[moved].x = x;
+    placements[moved].y = y;
+    Ok(())
+}
+
+/// Function to shift every output so the layout starts at 0,0, as sway would
+pub fn normalize(placements: &mut [Placement]) {
+    let min_x = placements
+        .iter()
+        .map(|placement| placement.x)
+        .min()
+        .unwrap_or(0);
+    let min_y = placements
+        .iter()
+        .map(|placement| placement.y)
+        .min()
+        .unwrap_or(0);
+    for placement in placements {
+        placement.x -= min_x;
+        placement.y -= min_y;
+    }
+}
+
+/// Function to draw the outputs as boxes to scale, at most `columns` characters wide. A
+/// character cell counts as twice as tall as it is wide.
+pub fn render(placements: &[Placement], columns: usize) -> Vec<String> {
+    let rects: Vec<(i32, i32, i32, i32)> = placements
+        .iter()
+        .map(|placement| {
+            let (width, height) = placement.logical_size();
+            (
+                placement.x,
+                placement.y,
+                placement.x + width,
+                placement.y + height,
+            )
+        })
+        .collect();
+    let (Some(left), Some(top)) = (
+        rects.iter().map(|rect| rect.0).min(),
+        rects.iter().map(|rect| rect.1).min(),
+    ) else {
+        return Vec::new();
+    };
+    let right = rects.iter().map(|rect| rect.2).max().unwrap_or(left);
+    let bottom = rects.iter().map(|rect| rect.3).max().unwrap_or(top);
+
+    let cell_width = ((right - left) as f32 / columns.max(8) as f32).max(1.0);
+    let cell_height = cell_width * 2.0;
+    let column = |x: i32| ((x - left) as f32 / cell_width).round() as usize;
+    let row = |y: i32| ((y - top) as f32 / cell_height).round() as usize;
+    let mut grid = vec![vec![' '; column(right) + 1]; row(bottom) + 1];
+
+    for (placement, &(x0, y0, x1, y1)) in placements.iter().zip(&rects) {
+        let (c0, r0) = (column(x0), row(y0));
+        let (c1, r1) = (column(x1).max(c0 + 1), row(y1).max(r0 + 1));
+        for r in r0..=r1 {
+            for c in c0..=c1 {
+                if r >= grid.len() || c >= grid[r].len() {
+                    continue;
+                }
+                let edge_row = r == r0 || r == r1;
+                let edge_column = c == c0 || c == c1;
+                grid[r][c] = match (edge_row, edge_column) {
+                    (true, true) => '+',
+                    (true, false) => '-',
+                    (false, true) => '|',
+                    (false, false) => ' ',
+                };
+            }
+        }
+
+        // Label the box with the name and scale as far as they fit
+        let inner = c1 - c0 - 1;
+        let labels = [placement.name.clone(), format!("@{}", placement.scale)];
+        for (i, label) in labels.iter().enumerate() {
+            let r = r0 + 1 + i;
+            if r >= r1 || r >= grid.len() {
+                break;
+            }
+            for (j, ch) in label.chars().take(inner).enumerate() {
+                grid[r][c0 + 1 + j] = ch;
+            }
+        }
+    }
+
+    grid.into_iter()
+        .map(|line| line.into_iter().collect::<String>().trim_end().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placement(name: &str, x: i32, mode: (i32, i32), scale: f32) -> Placement {
+        Placement {
+            name: name.to_string(),
+            x,
+            y: 0,
+            mode,
+            scale,
+        }
+    }
+
+    #[test]
+    fn places_outputs_against_each_other() {
+        let mut placements = vec![
+            placement("eDP-1", 0, (2880, 1800), 2.0),
+            placement("DP-1", 0, (3840, 2160), 1.5),
+        ];
+        place(&mut placements, "DP-1", Side::RightOf, "eDP-1").unwrap();
+        assert_eq!((placements[1].x, placements[1].y), (1440, 0));
+        place(&mut placements, "eDP-1", Side::Below, "DP-1").unwrap();
+        assert_eq!((placements[0].x, placements[0].y), (1440, 1440));
+        place(&mut placements, "DP-1", Side::LeftOf, "eDP-1").unwrap();
+        normalize(&mut placements);
+        assert_eq!((placements[1].x, placements[1].y), (0, 0));
+        assert_eq!((placements[0].x, placements[0].y), (2560, 0));
+        assert!(place(&mut placements, "DP-1", Side::Above, "DP-1").is_err());
+        assert!(place(&mut placements, "HDMI-A-1", Side::Above, "DP-1").is_err());
+        assert_eq!("below".parse(), Ok(Side::Below));
+    }
+
+    #[test]
+    fn draws_outputs_to_scale() {
+        let placements = vec![
+            placement("eDP-1", 0, (1920, 1080), 1.0),
+            placement("HDMI-A-1", 1920, (1920, 1080), 2.0),
+        ];
+        assert_eq!(
+            render(&placements, 30),
+            [
+                "+-------------------+---------+",
+                "|eDP-1              |HDMI-A-1 |",
+                "|@1                 |@2       |",
+                "|                   +---------+",
+                "|                   |",
+                "|                   |",
+                "+-------------------+",
+            ]
+        );
+    }
+}
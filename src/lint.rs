@@ -0,0 +1,321 @@
+use crate::options::{CycleOrder, ScaleOption};
+use regex::Regex;
+use std::sync::LazyLock;
+
+// Regular expression to match a marker line as the tool writes it, e.g. `### Scale Options Start`
+static MARKER_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*#+\s*Scale Options (?:Start|End)\s*$").unwrap());
+
+const TARGET_PREFIX: &str = "# Target Display = ";
+const OPTIONS_PREFIX: &str = "# Scale Options = ";
+
+/// How a finding is fixed by `lint --fix`
+#[derive(Debug, Clone, PartialEq)]
+enum Fix {
+    ReplaceLine(String),
+    RemoveLine,
+}
+
+/// A problem in the Scale Options section
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    /// Index of the line the problem is on
+    pub line: usize,
+    pub message: String,
+    pub suggestion: String,
+    fix: Option<Fix>,
+}
+
+impl Finding {
+    pub fn is_fixable(&self) -> bool {
+        self.fix.is_some()
+    }
+}
+
+/// Function to check the Scale Options section for mistakes that parse but do not do what was
+/// meant. `outputs` are the displays some output line names, `range` the sane scales, and
+/// `order` the order cycling follows.
+pub fn lint(
+    lines: &[String],
+    outputs: &[String],
+    range: (f32, f32),
+    epsilon: f32,
+    order: CycleOrder,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    // The first mention of each marker is the one that counts, even inside another comment
+    for marker in ["Scale Options Start", "Scale Options End"] {
+        let mentions: Vec<usize> = (0..lines.len())
+            .filter(|&i| lines[i].contains(marker))
+            .collect();
+        let mut marked = false;
+        for (n, &i) in mentions.iter().enumerate() {
+            if !MARKER_REGEX.is_match(&lines[i]) {
+                findings.push(Finding {
+                    line: i,
+                    message: format!("'{}' appears inside another comment", marker),
+                    suggestion: if n == 0 {
+                        format!("reword the comment; it is taken as the {} marker", marker)
+                    } else {
+                        "reword the comment so it cannot be taken for a marker".to_string()
+                    },
+                    fix: None,
+                });
+            } else if std::mem::replace(&mut marked, true) {
+                findings.push(Finding {
+                    line: i,
+                    message: format!("'{}' is marked a second time", marker),
+                    suggestion: "remove it; only the first marker counts".to_string(),
+                    fix: Some(Fix::RemoveLine),
+                });
+            }
+        }
+    }
+    let start = lines
+        .iter()
+        .position(|line| line.contains("Scale Options Start"));
+    let end = lines
+        .iter()
+        .position(|line| line.contains("Scale Options End"));
+    let (Some(start), Some(end)) = (start, end) else {
+        return findings;
+    };
+    if end < start {
+        findings.push(Finding {
+            line: end,
+            message: "'Scale Options End' comes before 'Scale Options Start'".to_string(),
+            suggestion: "move the end marker below the section".to_string(),
+            fix: None,
+        });
+        return findings;
+    }
+
+    // Targets listed twice, or with no output line left to edit
+    let targets: Vec<(usize, String)> = (start..=end)
+        .filter_map(|i| {
+            let (_, target) = lines[i].split_once(TARGET_PREFIX)?;
+            Some((i, target.trim().to_string()))
+        })
+        .collect();
+    let mut kept = targets.len();
+    for (n, (i, target)) in targets.iter().enumerate() {
+        if targets[..n].iter().any(|(_, earlier)| earlier == target) {
+            kept -= 1;
+            findings.push(Finding {
+                line: *i,
+                message: format!("target display {} is listed twice", target),
+                suggestion: "remove the repeated line".to_string(),
+                fix: Some(Fix::RemoveLine),
+            });
+        } else if !outputs.contains(target) {
+            // The last target is kept, since the section needs one
+            let fix = (kept > 1).then(|| {
+                kept -= 1;
+                Fix::RemoveLine
+            });
+            findings.push(Finding {
+                line: *i,
+                message: format!("target display {} has no output line", target),
+                suggestion: format!(
+                    "remove the target, or add an output line for {} that sets its scale",
+                    target
+                ),
+                fix,
+            });
+        }
+    }
+
+    if let Some(i) = (start..=end)
+        .rev()
+        .find(|&i| lines[i].contains(OPTIONS_PREFIX))
+    {
+        findings.extend(lint_options(&lines[i], i, range, epsilon, order));
+    }
+    findings
+}
+
+/// Function to check the list of scale options: repeated scales, scales outside the sane range
+/// and an order cycling does not follow
+fn lint_options(
+    line: &str,
+    i: usize,
+    (min, max): (f32, f32),
+    epsilon: f32,
+    cycle_order: CycleOrder,
+) -> Vec<Finding> {
+    let (prefix, list) = line.split_once(OPTIONS_PREFIX).unwrap();
+    let tokens: Vec<&str> = list.split(',').map(str::trim).collect();
+    let Ok(options) = tokens
+        .iter()
+        .map(|token| token.parse::<ScaleOption>())
+        .collect::<Result<Vec<_>, _>>()
+    else {
+        // Options that do not parse are reported by every other command already
+        return Vec::new();
+    };
+
+    let mut findings = Vec::new();
+    let mut keep = vec![true; options.len()];
+    for (n, option) in options.iter().enumerate() {
+        let plain = !option.off && option.expr.is_none();
+        let repeated = options[..n].iter().zip(&keep).any(|(earlier, &kept)| {
+            kept && earlier.expr == option.expr
+                && earlier.off == option.off
+                && earlier.mode == option.mode
+                && (earlier.scale - option.scale).abs() <= epsilon
+        });
+        if repeated {
+            keep[n] = false;
+            findings.push((
+                format!("scale option {} is listed twice", tokens[n]),
+                "remove the repeated option".to_string(),
+            ));
+        } else if plain && !(min..=max).contains(&option.scale) {
+            keep[n] = false;
+            findings.push((
+                format!("scale option {} is outside {} to {}", tokens[n], min, max),
+                "remove it, or change min_scale and max_scale in the settings".to_string(),
+            ));
+        }
+    }
+
+    // Unless cycling follows the written order, it goes through the plain scales sorted
+    // whatever the order, so sort them to match
+    let mut order: Vec<usize> = (0..options.len()).filter(|&n| keep[n]).collect();
+    let sortable = order
+        .iter()
+        .all(|&n| !options[n].off && options[n].expr.is_none());
+    let compare = |a: usize, b: usize| match cycle_order {
+        CycleOrder::Descending => options[b].scale.total_cmp(&options[a].scale),
+        _ => options[a].scale.total_cmp(&options[b].scale),
+    };
+    let sorted = order
+        .windows(2)
+        .all(|pair| compare(pair[0], pair[1]).is_le());
+    if sortable && !sorted && cycle_order != CycleOrder::AsWritten {
+        order.sort_by(|&a, &b| compare(a, b));
+        let (direction, steps) = match cycle_order {
+            CycleOrder::Descending => ("descending", "from the largest scale down"),
+            _ => ("ascending", "from the smallest scale up"),
+        };
+        findings.push((
+            format!("scale options are not in {} order", direction),
+            format!("sort them; cycling steps through them {}", steps),
+        ));
+    }
+
+    // Every finding on the line shares one fix: the list without the dropped options, sorted
+    let fixed: Vec<&str> = order.iter().map(|&n| tokens[n]).collect();
+    let fix = (!fixed.is_empty())
+        .then(|| Fix::ReplaceLine(format!("{}{}{}", prefix, OPTIONS_PREFIX, fixed.join(", "))));
+    findings
+        .into_iter()
+        .map(|(message, suggestion)| Finding {
+            line: i,
+            message,
+            suggestion,
+            fix: fix.clone(),
+        })
+        .collect()
+}
+
+/// Function to apply the fixes of the given findings, returning the fixed lines
+pub fn fix<'a>(lines: &[String], findings: impl IntoIterator<Item = &'a Finding>) -> Vec<String> {
+    let mut fixed: Vec<Option<String>> = lines.iter().cloned().map(Some).collect();
+    for finding in findings {
+        match &finding.fix {
+            Some(Fix::ReplaceLine(line)) => fixed[finding.line] = Some(line.clone()),
+            Some(Fix::RemoveLine) => fixed[finding.line] = None,
+            None => {}
+        }
+    }
+    fixed.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn reports_and_fixes_the_options_section() {
+        let config = lines(
+            "# Scale Options Start is below\n\
+             ### Scale Options Start\n\
+             # Target Display = eDP-1\n\
+             # Target Display = eDP-1\n\
+             # Target Display = DP-9\n\
+             # Scale Options = 2, 1.0, 1.5, 1, 12\n\
+             ### Scale Options End\n",
+        );
+        let outputs = ["eDP-1".to_string()];
+        let findings = lint(&config, &outputs, (0.5, 4.0), 0.0005, CycleOrder::Ascending);
+        let messages: Vec<(usize, &str)> = findings
+            .iter()
+            .map(|finding| (finding.line, finding.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (0, "'Scale Options Start' appears inside another comment"),
+                (3, "target display eDP-1 is listed twice"),
+                (4, "target display DP-9 has no output line"),
+                (5, "scale option 1 is listed twice"),
+                (5, "scale option 12 is outside 0.5 to 4"),
+                (5, "scale options are not in ascending order"),
+            ]
+        );
+        assert!(!findings[0].is_fixable());
+
+        let fixed = fix(&config, &findings);
+        assert_eq!(
+            fixed[1..],
+            lines(
+                "### Scale Options Start\n\
+                 # Target Display = eDP-1\n\
+                 # Scale Options = 1.0, 1.5, 2\n\
+                 ### Scale Options End\n"
+            )
+        );
+        assert_eq!(
+            lint(&fixed, &outputs, (0.5, 4.0), 0.0005, CycleOrder::Ascending).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn keeps_the_last_target_and_expression_order() {
+        let config = lines(
+            "### Scale Options Start\n\
+             # Target Display = DP-9\n\
+             # Scale Options = auto, 1\n\
+             ### Scale Options End\n",
+        );
+        let findings = lint(&config, &[], (0.5, 4.0), 0.0005, CycleOrder::Ascending);
+        assert_eq!(findings.len(), 1);
+        assert!(!findings[0].is_fixable());
+    }
+
+    #[test]
+    fn follows_the_cycle_order() {
+        let config = lines(
+            "### Scale Options Start\n\
+             # Target Display = eDP-1\n\
+             # Scale Options = 1, 2, 1.5\n\
+             ### Scale Options End\n",
+        );
+        let outputs = ["eDP-1".to_string()];
+        let lint = |order| lint(&config, &outputs, (0.5, 4.0), 0.0005, order);
+        assert!(lint(CycleOrder::AsWritten).is_empty());
+        let findings = lint(CycleOrder::Descending);
+        assert_eq!(
+            findings[0].message,
+            "scale options are not in descending order"
+        );
+        assert_eq!(fix(&config, &findings)[2], "# Scale Options = 2, 1.5, 1");
+    }
+}
@@ -1,121 +1,3617 @@
-use clap::{Arg, Command};
-use regex::Regex;
-use std::fs::{self, File, OpenOptions};
-use std::io::{self, BufRead, BufReader, BufWriter, Write};
+#[macro_use]
+mod i18n;
+#[macro_use]
+mod ui;
+
+mod archive;
+mod batch;
+mod bench;
+mod candidates;
+mod clock;
+mod config;
+mod contexts;
+mod daemon;
+mod dbus;
+mod doctor;
+mod edid;
+mod events;
+mod expr;
+mod feedback;
+mod history;
+mod includes;
+mod ipc;
+mod layout;
+mod lint;
+mod matcher;
+mod metrics;
+mod migrate;
+mod mock;
+mod options;
+mod pidfile;
+mod platform;
+mod power;
+mod profile_sync;
+mod schedule;
+mod screencast;
+mod settings;
+mod snippet;
+mod state;
+mod templates;
+mod text;
+mod toolkits;
+mod transaction;
+mod validate;
+mod wlr;
+mod workspaces;
+
+use clap::{Arg, Command, ValueEnum};
+use config::{CurrentScale, ScaleOptions};
+use options::ScaleOption;
+use settings::{OutputSettings, Power, ReloadStrategy};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
 use std::path::Path;
 use std::process;
-use text_io::read;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn main() {
+    if let Err(e) = run() {
+        error!("{}", e);
+        feedback::show();
+        process::exit(1);
+    }
+}
+
+fn run() -> io::Result<()> {
+    // Parse command-line arguments using Clap
+    let cli = build_cli();
+    let matches = cli.clone().get_matches();
+
+    ui::init(matches.get_flag("no-color"));
+    if let Some(&start) = matches.get_one::<u64>("simulate-time") {
+        clock::simulate(start);
+    }
+    if let Some(trigger) = matches.get_one::<String>("trigger") {
+        history::set_trigger(trigger.clone());
+    }
+    if matches.get_flag("version") {
+        return print_version(&cli, matches.get_flag("json"));
+    }
+    select_session(&matches)?;
+
+    // Scheduled changes are kept in the state directory and run by a detached timer
+    if let Some(schedule_matches) = matches.subcommand_matches("schedule") {
+        return run_schedule(schedule_matches);
+    }
+    let set_applies = matches
+        .subcommand_matches("set")
+        .is_some_and(|set_matches| {
+            set_matches.contains_id("option") || set_matches.get_flag("default")
+        });
+    if let Some(due) = scheduled_time(&matches)? {
+        return defer_change(&matches, due, set_applies);
+    }
+
+    // Power management only talks to sway over IPC and never touches the config file
+    if let Some(power_matches) = matches.subcommand_matches("power") {
+        let output = unalias(power_matches.get_one::<String>("output").unwrap());
+        return match power_matches.get_one::<String>("state").unwrap().as_str() {
+            "on" => set_power(&output, Some(Power::On)),
+            "off" => set_power(&output, Some(Power::Off)),
+            _ => set_power(&output, None),
+        };
+    }
+    if let Some(output) = matches.get_one::<String>("on") {
+        return set_power(&unalias(output), Some(Power::On));
+    }
+    if let Some(output) = matches.get_one::<String>("off") {
+        return set_power(&unalias(output), Some(Power::Off));
+    }
+    if let Some(output) = matches.get_one::<String>("toggle-power") {
+        return set_power(&unalias(output), None);
+    }
+
+    // Layouts are computed from live output geometry and applied over IPC
+    if let Some(layout_matches) = matches.subcommand_matches("layout") {
+        return run_layout(layout_matches);
+    }
+
+    // Pinning only changes the state file
+    if let Some(pin_matches) = matches.subcommand_matches("pin") {
+        return run_pin(pin_matches);
+    }
+    if let Some(current_matches) = matches.subcommand_matches("current") {
+        let output = unalias(current_matches.get_one::<String>("output").unwrap());
+        return print_current_scale(&output, current_matches.get_flag("dpi"));
+    }
+    if let Some(unpin_matches) = matches.subcommand_matches("unpin") {
+        return run_unpin(unpin_matches);
+    }
+
+    if matches.subcommand_matches("announce").is_some() {
+        return announce_pending();
+    }
+
+    // Export and import work on the raw files, so a broken settings file can still be replaced
+    if let Some(export_matches) = matches.subcommand_matches("export") {
+        return run_export(export_matches);
+    }
+    if let Some(("sync", sync_matches)) = matches
+        .subcommand_matches("profiles")
+        .and_then(|profiles| profiles.subcommand())
+    {
+        return profile_sync::sync(
+            sync_matches.get_one::<String>("remote").map(String::as_str),
+            sync_matches.get_one::<String>("branch").map(String::as_str),
+        );
+    }
+    if let Some(import_matches) = matches.subcommand_matches("import") {
+        return run_import(import_matches);
+    }
+
+    // Migrating works on the raw files, since a newer format is what it is there to fix
+    if matches.subcommand_matches("migrate").is_some() {
+        return migrate::run(&config_path()?);
+    }
+
+    // A change cut short by a crash is undone or finished before anything else is written
+    if let Some(recover_matches) = matches.subcommand_matches("recover") {
+        return run_recover(recover_matches);
+    }
+    match transaction::check_interrupted() {
+        Ok(Some(journal)) => warning!("{}", journal.describe()),
+        Ok(None) => {}
+        Err(e) => warning!("Failed to check for an interrupted change: {}", e),
+    }
+
+    // Load the tool's own settings (profiles, managed mode), letting flags override them
+    let settings = load_settings(&matches);
+
+    // The doctor reports a broken settings file instead of stopping at it
+    if matches.subcommand_matches("doctor").is_some() {
+        return doctor::run(settings);
+    }
+    let settings = settings?;
+    state::init(settings.record_scales);
+
+    if let Some(daemon_matches) = matches.subcommand_matches("daemon") {
+        return run_daemon(settings, &matches, daemon_matches);
+    }
+
+    if matches.subcommand_matches("subscribe").is_some() {
+        return events::subscribe(&settings.precision);
+    }
+
+    if matches.subcommand_matches("a11y-zoom").is_some() {
+        return toggle_zoom(&settings);
+    }
+
+    if let Some(candidates_matches) = matches.subcommand_matches("candidates") {
+        return print_candidates(
+            &settings,
+            settings.unalias(candidates_matches.get_one::<String>("output").unwrap()),
+            *candidates_matches.get_one::<f32>("max").unwrap(),
+            candidates_matches.get_flag("all-modes"),
+        );
+    }
+
+    // The arrangement editor works on live output geometry, and saves to the settings file
+    if matches.subcommand_matches("arrange").is_some() {
+        return arrange_outputs(&settings);
+    }
+
+    // Profiles are applied over IPC as well
+    if let Some(profile_matches) = matches.subcommand_matches("profile") {
+        return run_profile(&settings, profile_matches);
+    }
+
+    // At login, pick the profile for this machine and apply it without touching the config
+    if matches.subcommand_matches("apply-default").is_some() {
+        return apply_default_profile(&settings);
+    }
+
+    // Expand the user's home directory and locate the Sway config file
+    let config_path = config_path()?;
+
+    // Read all lines from the config file into a vector
+    let lines = read_config(&config_path)?;
+
+    if let Some(status_matches) = matches.subcommand_matches("status") {
+        return print_status(&settings, &lines, status_matches.get_flag("json"));
+    }
+    if let Some(bench_matches) = matches.subcommand_matches("bench") {
+        return run_bench(
+            &settings,
+            &config_path,
+            *bench_matches.get_one::<u32>("iterations").unwrap(),
+            bench_matches.get_flag("reload"),
+            bench_matches.get_flag("json"),
+        );
+    }
+    if let Some(lint_matches) = matches.subcommand_matches("lint") {
+        return lint_config(
+            &settings,
+            &config_path,
+            &lines,
+            lint_matches.get_flag("fix"),
+        );
+    }
+    if let Some(run_matches) = matches.subcommand_matches("run") {
+        let script = run_matches.get_one::<std::path::PathBuf>("file").unwrap();
+        return run_script(&settings, &config_path, &lines, script);
+    }
+    if let Some(sync_matches) = matches.subcommand_matches("sync") {
+        return sync_scales(
+            &settings,
+            &config_path,
+            &lines,
+            sync_matches.get_flag("from-live"),
+            sync_matches.get_flag("from-config"),
+        );
+    }
+
+    // Handle the history subcommand before touching the scale options
+    if let Some(history_matches) = matches.subcommand_matches("history") {
+        return run_history(&settings, &config_path, &lines, history_matches);
+    }
+
+    // Bit depth and HDR are switched over IPC and leave the config alone
+    let cycle_matches = matches.subcommand_matches("cycle");
+    let bit_depth = matches.get_flag("cycle-bitdepth")
+        || cycle_matches.is_some_and(|cycle_matches| cycle_matches.get_flag("bit-depth"));
+    let hdr = cycle_matches.is_some_and(|cycle_matches| cycle_matches.get_flag("hdr"));
+    if bit_depth || hdr {
+        let focused = cycle_matches.is_some_and(|cycle_matches| cycle_matches.get_flag("focused"));
+        return cycle_render(&settings, &lines, focused, hdr);
+    }
+
+    // Holding a key bound to a change fires it many times over; refuse the ones that come too fast
+    let one_shot = matches.subcommand_name() == Some("cycle")
+        || ["swap", "yes"].iter().any(|flag| matches.get_flag(flag))
+        || set_applies;
+    if one_shot {
+        check_change_interval(&settings)?;
+    }
+
+    // Cycling needs no interaction, so it shares its implementation with the daemon
+    if let Some(cycle_matches) = matches.subcommand_matches("cycle") {
+        cycle_scale(
+            &settings,
+            cycle_matches.get_flag("reverse"),
+            cycle_matches.get_flag("focused"),
+        )?;
+        return Ok(());
+    }
+    if matches.get_flag("swap") {
+        cycle_scale(&settings, false, matches.get_flag("focused"))?;
+        return Ok(());
+    }
+
+    change_scales(&settings, &matches, &config_path, &lines)
+}
+
+/// Function to build the command-line interface: the global flags and every subcommand
+fn build_cli() -> Command {
+    Command::new("Sway Scale Swapper")
+        .version(env!("CARGO_PKG_VERSION"))
+        .disable_version_flag(true)
+        .author("Your Name <youremail@example.com>")
+        .about("Manage scale settings in Sway configuration")
+        .long_about(
+            "Manage scale settings in Sway configuration. Without a subcommand, asks which \
+             displays to change and which scale to apply, like 'set' without an option.",
+        )
+        .after_help(
+            "Examples:\n  \
+             sway-scale-switcher                  Pick displays and a scale interactively\n  \
+             sway-scale-switcher cycle            Move every target display to the next option\n  \
+             sway-scale-switcher set 1.5          Apply scale 1.5 to every target display\n  \
+             sway-scale-switcher status           Show the current scale of each display\n  \
+             sway-scale-switcher doctor           Look for problems in the setup\n\n\
+             The older flags still work: --swap is 'cycle', --cycle-bitdepth is \
+             'cycle --bit-depth', --yes is 'set --default', and --on, --off and \
+             --toggle-power are 'power on', 'power off' and 'power toggle'.\n\n\
+             Environment:\n  \
+             SWAY_SCALE_SWITCHER_CONFIG     Sway config to edit instead of ~/.config/sway/config\n  \
+             SWAY_SCALE_SWITCHER_SETTINGS   Settings file to read instead of the usual one\n  \
+             SWAY_SCALE_SWITCHER_OUTPUTS    Target displays, comma-separated\n  \
+             SWAY_SCALE_SWITCHER_<SETTING>  Any setting, as a TOML value, e.g. \
+             SWAY_SCALE_SWITCHER_RELOAD_STRATEGY=ipc\n\
+             --backend, --socket, --host and --mock-outputs read SWAY_SCALE_SWITCHER_BACKEND and \
+             so on. Flags win over the environment, which wins over the settings file.",
+        )
+        .args(global_args())
+        .subcommand(cycle_command())
+        .subcommand(set_command())
+        .subcommand(power_command())
+        .subcommand(a11y_zoom_command())
+        .subcommand(candidates_command())
+        .subcommand(doctor_command())
+        .subcommand(schedule_command())
+        .subcommand(profile_command())
+        .subcommand(profiles_command())
+        .subcommand(apply_default_command())
+        .subcommand(layout_command())
+        .subcommand(arrange_command())
+        .subcommand(daemon_command())
+        .subcommand(subscribe_command())
+        .subcommand(status_command())
+        .subcommand(bench_command())
+        .subcommand(lint_command())
+        .subcommand(run_command())
+        .subcommand(sync_command())
+        .subcommand(pin_command())
+        .subcommand(current_command())
+        .subcommand(unpin_command())
+        .subcommand(announce_command())
+        .subcommand(export_command())
+        .subcommand(import_command())
+        .subcommand(migrate_command())
+        .subcommand(recover_command())
+        .subcommand(history_command())
+}
+
+/// Function to build the top-level flags, most of which every subcommand accepts as well
+fn global_args() -> Vec<Arg> {
+    vec![
+        Arg::new("version")
+            .short('V')
+            .long("version")
+            .help("Print version")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("json")
+            .long("json")
+            .help("With --version, print the version, git commit, backends and features as JSON")
+            .requires("version")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("swap")
+            .short('s')
+            .long("swap")
+            .help("Same as the cycle subcommand")
+            .hide(true)
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("cycle-bitdepth")
+            .long("cycle-bitdepth")
+            .help("Same as 'cycle --bit-depth'")
+            .hide(true)
+            .conflicts_with("swap")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("focused")
+            .long("focused")
+            .help("Same as 'cycle --focused'")
+            .requires("swap")
+            .hide(true)
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("context")
+            .long("context")
+            .value_name("NAME")
+            .help("Cycle through the named option group from the settings file")
+            .global(true),
+        Arg::new("yes")
+            .short('y')
+            .long("yes")
+            .help("Same as 'set --default'")
+            .hide(true)
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("no-preview")
+            .long("no-preview")
+            .help("When picking a scale interactively, write it without trying it out live first")
+            .global(true)
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("no-color")
+            .long("no-color")
+            .help("Print plain text without colors (also set by NO_COLOR)")
+            .global(true)
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("socket")
+            .long("socket")
+            .value_name("PATH")
+            .help("Sway IPC socket to talk to, e.g. for a nested sway or another seat")
+            .long_help(
+                "Sway IPC socket to talk to, e.g. for a nested sway or another seat. \
+                 Without it, SWAYSOCK is used; if that is unset, the sockets under \
+                 $XDG_RUNTIME_DIR are searched and you are asked to pick one if there \
+                 are several.",
+            )
+            .env("SWAY_SCALE_SWITCHER_SOCKET")
+            .global(true)
+            .value_parser(clap::value_parser!(std::path::PathBuf)),
+        Arg::new("host")
+            .long("host")
+            .value_name("[USER@]HOST")
+            .help("Apply changes to the sway on another machine, over SSH")
+            .long_help(
+                "Apply changes to the sway on another machine, e.g. a media PC, by running \
+                 swaymsg there over SSH. The config is still read and written here, e.g. a \
+                 copy of the remote one, and changes reach the remote session over IPC \
+                 instead of a reload. SSH must log in without asking for a password. \
+                 --socket names the socket on the remote machine; without it, the newest \
+                 one in its runtime directory is used.",
+            )
+            .env("SWAY_SCALE_SWITCHER_HOST")
+            .global(true)
+            .conflicts_with("backend"),
+        Arg::new("simulate-time")
+            .long("simulate-time")
+            .value_name("UNIX_TIME")
+            .help("Start the clock at this Unix time and fast-forward every timer, for tests")
+            .hide(true)
+            .global(true)
+            .value_parser(clap::value_parser!(u64)),
+        Arg::new("trigger")
+            .long("trigger")
+            .value_name("TEXT")
+            .help("Name what set off this change in the history, e.g. the rule of a script")
+            .global(true),
+        Arg::new("backend")
+            .long("backend")
+            .value_name("BACKEND")
+            .help("Compositor to apply changes to; 'mock' prints the commands instead")
+            .long_help(
+                "Compositor to apply changes to. 'mock' stands in for sway with an \
+                 in-memory model of the outputs and prints every command it receives, so \
+                 cycling, profiles and the daemon can be tried without a session. The config \
+                 file is still written. 'wlr-randr' changes outputs with wlr-randr on any \
+                 wlroots compositor, such as labwc or wayfire; the sway config is still \
+                 edited, but only output changes reach the running session. 'sway' uses \
+                 the sway IPC socket and nothing else.\n\n\
+                 Without --backend, the backends setting lists what to try in order, by \
+                 default [\"ipc\", \"wlr-randr\", \"config\"]: sway while it runs, then \
+                 wlr-randr, then only editing the config. The one used is reported after \
+                 each change and in 'status --json'.",
+            )
+            .env("SWAY_SCALE_SWITCHER_BACKEND")
+            .global(true)
+            .value_parser(clap::value_parser!(ipc::Backend)),
+        Arg::new("mock-outputs")
+            .long("mock-outputs")
+            .value_name("FILE")
+            .help("JSON description of the mock's outputs, e.g. saved from swaymsg -t get_outputs -r")
+            .env("SWAY_SCALE_SWITCHER_MOCK_OUTPUTS")
+            .global(true)
+            .value_parser(clap::value_parser!(std::path::PathBuf)),
+        Arg::new("no-reload")
+            .long("no-reload")
+            .help("Write the config without reloading sway, e.g. to batch several changes")
+            .global(true)
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("reload-strategy")
+            .long("reload-strategy")
+            .value_name("STRATEGY")
+            .help("How to apply a rewritten config to the running session")
+            .global(true)
+            .value_parser(clap::value_parser!(ReloadStrategy))
+            .conflicts_with("no-reload"),
+        Arg::new("validate")
+            .long("validate")
+            .value_name("MODE")
+            .help("Check a rewritten config with sway before writing it")
+            .global(true)
+            .value_parser(clap::value_parser!(validate::Validation)),
+        Arg::new("force")
+            .long("force")
+            .help("Reload even while a window is fullscreen or the screen is shared, or below min_logical_size; with import, overwrite files that differ")
+            .global(true)
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("reload-command")
+            .long("reload-command")
+            .value_name("COMMAND")
+            .help("Shell command run by the 'exec' reload strategy")
+            .global(true),
+        Arg::new("in")
+            .long("in")
+            .value_name("DURATION")
+            .help("Make the change later, after a delay such as 30m or 1h30m")
+            .global(true)
+            .conflicts_with("at"),
+        Arg::new("at")
+            .long("at")
+            .value_name("TIME")
+            .help("Make the change at the next 24-hour time of day, e.g. 22:00")
+            .global(true),
+        Arg::new("on")
+            .long("on")
+            .value_name("OUTPUT")
+            .help("Same as 'power on OUTPUT'")
+            .hide(true)
+            .conflicts_with_all(["off", "toggle-power"]),
+        Arg::new("off")
+            .long("off")
+            .value_name("OUTPUT")
+            .help("Same as 'power off OUTPUT'")
+            .hide(true)
+            .conflicts_with("toggle-power"),
+        Arg::new("toggle-power")
+            .long("toggle-power")
+            .value_name("OUTPUT")
+            .help("Same as 'power toggle OUTPUT'")
+            .hide(true),
+    ]
+}
+
+/// Function to build the `cycle` subcommand
+fn cycle_command() -> Command {
+    Command::new("cycle")
+        .about("Move the target displays to the next scale option")
+        .long_about(
+            "Move the target displays to the next scale option, wrapping around after \
+             the largest one. Pinned displays are left alone.",
+        )
+        .after_help(
+            "Examples:\n  \
+             sway-scale-switcher cycle\n  \
+             sway-scale-switcher cycle --reverse\n  \
+             sway-scale-switcher cycle --bit-depth\n  \
+             bindsym $mod+equal exec sway-scale-switcher cycle --focused",
+        )
+        .arg(
+            Arg::new("reverse")
+                .short('r')
+                .long("reverse")
+                .visible_alias("prev")
+                .help("Move to the previous option instead")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("bit-depth")
+                .long("bit-depth")
+                .help("Switch between 8 and 10 bit rendering instead of changing the scale")
+                .long_help(
+                    "Switch between 8 and 10 bit rendering instead of changing the scale. \
+                     Applied over IPC; put render_bit_depth in a profile to keep it.",
+                )
+                .conflicts_with("reverse")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("hdr")
+                .long("hdr")
+                .help("Turn HDR on or off instead of changing the scale")
+                .conflicts_with_all(["reverse", "bit-depth"])
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("focused")
+                .long("focused")
+                .help("Cycle only the output showing the focused workspace")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+/// Function to build the `set` subcommand
+fn set_command() -> Command {
+    Command::new("set")
+        .about("Apply a scale to the target displays")
+        .long_about(
+            "Apply a scale to the target displays. The scale is written like an entry in \
+             the Scale Options list, so '2@3840x2160', 'auto' and 'off' work too, and it \
+             need not be one of the options. Without a scale, asks which one to apply.",
+        )
+        .after_help(
+            "Examples:\n  \
+             sway-scale-switcher set 1.5\n  \
+             sway-scale-switcher set 2@3840x2160 --output eDP-1\n  \
+             sway-scale-switcher set --default",
+        )
+        .arg(
+            Arg::new("option")
+                .value_name("SCALE")
+                .help("Scale option to apply")
+                .value_parser(clap::value_parser!(ScaleOption)),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .value_name("OUTPUT")
+                .help("Change only this display; may be repeated")
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("default")
+                .long("default")
+                .help("Apply the default option from the settings, or else the first option")
+                .conflicts_with("option")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+/// Function to build the `power` subcommand
+fn power_command() -> Command {
+    Command::new("power")
+        .about("Turn an output on or off without changing the config")
+        .after_help(
+            "Examples:\n  \
+             sway-scale-switcher power off HDMI-A-1\n  \
+             bindswitch lid:toggle exec sway-scale-switcher power toggle eDP-1",
+        )
+        .arg(
+            Arg::new("state")
+                .required(true)
+                .value_parser(["on", "off", "toggle"]),
+        )
+        .arg(
+            Arg::new("output")
+                .help("Output to switch, by connector name")
+                .required(true),
+        )
+}
+
+/// Function to build the `a11y-zoom` subcommand
+fn a11y_zoom_command() -> Command {
+    Command::new("a11y-zoom")
+        .about("Magnify the focused output, or restore it if it is magnified")
+        .long_about(
+            "Magnify the focused output to zoom_scale from the settings file (2.5 by \
+             default) and move the pointer to the focused window. Running it again \
+             restores the scale the output had before. The config is not changed, so a \
+             reload also ends the magnification.",
+        )
+        .after_help("Example:\n  bindsym $mod+z exec sway-scale-switcher a11y-zoom")
+}
+
+/// Function to build the `candidates` subcommand
+fn candidates_command() -> Command {
+    Command::new("candidates")
+        .about("List scales that suit an output's modes, to build the scale options from")
+        .long_about(
+            "List the scales at which the output's current mode divides into a whole \
+             number of logical pixels, so nothing is blurred at the edges, with the \
+             logical size and, if the physical size is known, the effective DPI. Ends \
+             with a suggested list of options up to the scale that shows 96 logical \
+             pixels per inch.",
+        )
+        .after_help(
+            "Examples:\n  \
+             sway-scale-switcher candidates eDP-1\n  \
+             sway-scale-switcher candidates DP-3 --all-modes --max 4",
+        )
+        .arg(
+            Arg::new("output")
+                .help("Output by connector or description")
+                .required(true),
+        )
+        .arg(
+            Arg::new("max")
+                .long("max")
+                .value_name("SCALE")
+                .help("Largest scale to list")
+                .value_parser(clap::value_parser!(f32))
+                .default_value("3"),
+        )
+        .arg(
+            Arg::new("all-modes")
+                .long("all-modes")
+                .help("List scales for every resolution the output supports")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+/// Function to build the `doctor` subcommand
+fn doctor_command() -> Command {
+    Command::new("doctor")
+        .about("Check the settings, config and session for common problems")
+        .long_about(
+            "Check the settings, config and session for common problems: a missing Scale \
+             Options section, target displays without an output line, a managed snippet \
+             that is not included, or programs the settings rely on that are not \
+             installed. Exits with an error if any check fails.",
+        )
+}
+
+/// Function to build the `schedule` subcommand
+fn schedule_command() -> Command {
+    Command::new("schedule")
+        .about("List or cancel changes deferred with --in or --at")
+        .after_help(
+            "Examples:\n  \
+             sway-scale-switcher set 1.25 --at 22:00\n  \
+             sway-scale-switcher cycle --in 30m\n  \
+             sway-scale-switcher schedule list\n  \
+             sway-scale-switcher schedule cancel 2",
+        )
+        .subcommand_required(true)
+        .subcommand(Command::new("list").about("List the scheduled changes"))
+        .subcommand(
+            Command::new("cancel")
+                .about("Cancel a scheduled change")
+                .arg(
+                    Arg::new("id")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                ),
+        )
+        .subcommand(
+            Command::new("wait")
+                .about("Wait for a scheduled change and run it")
+                .hide(true)
+                .arg(
+                    Arg::new("id")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                ),
+        )
+}
+
+/// Function to build the `profile` subcommand
+fn profile_command() -> Command {
+    Command::new("profile")
+        .about("Apply a profile from the settings file")
+        .after_help(
+            "Examples:\n  \
+             sway-scale-switcher profile --list\n  \
+             sway-scale-switcher profile docked",
+        )
+        .arg(
+            Arg::new("name")
+                .help("Name of the profile to apply")
+                .required_unless_present("list"),
+        )
+        .arg(
+            Arg::new("list")
+                .short('l')
+                .long("list")
+                .help("List the available profiles")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+/// Function to build the `profiles` subcommand
+fn profiles_command() -> Command {
+    Command::new("profiles")
+        .about("Share the profiles and settings with other machines")
+        .subcommand_required(true)
+        .subcommand(
+            Command::new("sync")
+                .about("Pull or push the settings file through a git repository")
+                .long_about(
+                    "Pull or push the settings file, which holds the profiles, through a \
+                     git repository, so per-monitor preferences follow you across \
+                     machines. Whichever side changed since the last sync wins; if both \
+                     did, the newer copy wins with a warning. The repository comes from \
+                     the [sync] section of the settings:\n\n    \
+                     [sync]\n    \
+                     remote = \"git@example.com:me/scales.git\"\n    \
+                     branch = \"main\"\n    \
+                     encrypt_to = \"me@example.com\"\n\n\
+                     With encrypt_to the repository only holds the file encrypted with \
+                     gpg. A new machine without settings passes --remote once.",
+                )
+                .after_help(
+                    "Examples:\n  \
+                     sway-scale-switcher profiles sync\n  \
+                     sway-scale-switcher profiles sync --remote git@example.com:me/scales.git",
+                )
+                .arg(
+                    Arg::new("remote")
+                        .long("remote")
+                        .value_name("URL")
+                        .help("Repository to sync with instead of the one in [sync]"),
+                )
+                .arg(
+                    Arg::new("branch")
+                        .long("branch")
+                        .value_name("BRANCH")
+                        .help("Branch to sync on instead of the one in [sync]"),
+                ),
+        )
+}
+
+/// Function to build the `apply-default` subcommand
+fn apply_default_command() -> Command {
+    Command::new("apply-default")
+        .about("Apply the profile for this machine and its outputs, for use at login")
+        .long_about(
+            "Apply the profile for this machine and its outputs over IPC, without \
+             rewriting the config. The profile is the first automatic one that fits the \
+             connected outputs, else the first one whose hostname is this machine's, \
+             else the one named by default_profile in the settings.",
+        )
+        .after_help(
+            "Examples:\n  \
+             sway-scale-switcher apply-default\n  \
+             exec sway-scale-switcher apply-default    (in the sway config)",
+        )
+}
+
+/// Function to build the `layout` subcommand
+fn layout_command() -> Command {
+    Command::new("layout")
+        .about("Arrange outputs side by side, stacked, or overlaid for mirroring")
+        .long_about(
+            "Arrange outputs side by side, stacked, or overlaid for mirroring.\n\n\
+             'mirror' places every output at the position of the first one and scales \
+             it so the first output's whole area is visible. Sway still shows a separate workspace on each \
+             output; use a tool such as wl-mirror to duplicate the picture itself.",
+        )
+        .after_help(
+            "Examples:\n  \
+             sway-scale-switcher layout side-by-side eDP-1 HDMI-A-1\n  \
+             sway-scale-switcher layout mirror",
+        )
+        .arg(
+            Arg::new("arrangement")
+                .required(true)
+                .value_parser(clap::value_parser!(layout::Arrangement)),
+        )
+        .arg(
+            Arg::new("outputs")
+                .value_name("OUTPUT")
+                .num_args(1..)
+                .help("Outputs in order (defaults to all active outputs)"),
+        )
+}
+
+/// Function to build the `arrange` subcommand
+fn arrange_command() -> Command {
+    Command::new("arrange")
+        .about("Arrange outputs and their scales interactively on a map drawn to scale")
+        .long_about(
+            "Arrange outputs and their scales interactively. The active outputs are \
+             drawn to scale as boxes, and commands move them or change their scale, \
+             redrawing the map after each change:\n\n  \
+             place OUTPUT left-of|right-of|above|below OTHER\n  \
+             move OUTPUT X Y\n  \
+             scale OUTPUT SCALE\n  \
+             apply           try the arrangement out over IPC\n  \
+             revert          go back to the arrangement the outputs had at the start\n  \
+             print           print the output lines for the sway config\n  \
+             save NAME       save the arrangement as a profile in the settings file\n  \
+             q               quit, keeping whatever was applied",
+        )
+}
+
+/// Function to build the `daemon` subcommand
+fn daemon_command() -> Command {
+    Command::new("daemon")
+        .about("Run in the background, following output changes")
+        .long_about(
+            "Run in the background, following output changes.\n\n\
+             SIGUSR1 cycles the target displays to the next scale option and SIGUSR2 to \
+             the previous one, e.g. bindsym $mod+equal exec pkill -USR1 -f 'sway-scale-switcher daemon'. \
+             Signals that come within debounce_ms (250 by default) of each other are \
+             applied as a single change.\n\n\
+             Scales listed under [[workspaces]] in the settings file are applied while \
+             their workspace is visible, and the previous scale is restored afterwards.\n\n\
+             With [idle] in the settings file, swayidle tells the daemon when the session \
+             has been idle for after_minutes; it then applies the named profile and puts \
+             the outputs back as they were on resume.\n\n\
+             The daemon reloads the settings file when it is saved, and reports the scale \
+             options when the sway config is saved; settings that fail to load are \
+             refused and the previous ones kept. Clients of --events-socket may also \
+             send the line 'reload-config' and get back a JSON line saying whether it \
+             took.",
+        )
+        .after_help(
+            "Examples:\n  \
+             exec sway-scale-switcher daemon\n  \
+             sway-scale-switcher daemon --dbus --metrics-addr 127.0.0.1:9977",
+        )
+        .arg(
+            Arg::new("metrics-addr")
+                .long("metrics-addr")
+                .value_name("ADDR")
+                .help("Serve Prometheus metrics on this address, e.g. 127.0.0.1:9977")
+                .value_parser(clap::value_parser!(std::net::SocketAddr)),
+        )
+        .arg(
+            Arg::new("dbus")
+                .long("dbus")
+                .help("Expose the daemon on the session bus as org.swayscale.Switcher1")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("events-socket")
+                .long("events-socket")
+                .value_name("PATH")
+                .help("Stream scale changes as JSON lines to clients of this Unix socket")
+                .value_parser(clap::value_parser!(std::path::PathBuf)),
+        )
+        .arg(
+            Arg::new("pidfile")
+                .long("pidfile")
+                .value_name("PATH")
+                .help("Where to write the daemon's PID [default: $XDG_RUNTIME_DIR/sway-scale-switcher.pid]")
+                .value_parser(clap::value_parser!(std::path::PathBuf)),
+        )
+}
+
+/// Function to build the `subscribe` subcommand
+fn subscribe_command() -> Command {
+    Command::new("subscribe")
+        .about("Print a line of JSON whenever an output's scale changes")
+        .long_about(
+            "Print a line of JSON whenever an output's scale changes, starting with the \
+             current scale of every output. Each line holds the output, its new scale, \
+             the previous scale (null for a newly seen output) and a Unix timestamp.\n\n\
+             The daemon offers the same stream on a Unix socket with --events-socket, and \
+             on D-Bus as the ScaleChanged signal with --dbus.",
+        )
+}
+
+/// Function to build the `status` subcommand
+fn status_command() -> Command {
+    Command::new("status")
+        .about("Show each target display with its scale and the available options")
+        .after_help(
+            "The option each display uses is shown in brackets, e.g. '1 [1.5] 2'. \
+             Pinned displays are marked '(pinned)'.\n\n\
+             With --json, each display also carries its live mode, refresh rate and \
+             adaptive sync state while sway runs, and on_battery tells whether the \
+             machine runs on battery (null without one), so a bar widget needs only \
+             this one call.",
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Print the status as JSON")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+/// Function to build the `bench` subcommand
+fn bench_command() -> Command {
+    Command::new("bench")
+        .about("Time each stage of applying a scale, to compare backends")
+        .long_about(
+            "Cycle the target displays through their options and time each stage of \
+             every change: parsing the config and its options, deciding on the next \
+             scale, applying it through the selected backend, and waiting until the \
+             outputs report it. With --reload, reloading sway is timed as well. The \
+             config is left untouched, and the displays get their scales back at the \
+             end.\n\n\
+             Prints the minimum, median, mean and maximum of each stage and of the \
+             whole change, in milliseconds.",
+        )
+        .after_help(
+            "Examples:\n  \
+             sway-scale-switcher bench\n  \
+             sway-scale-switcher bench -n 50 --json\n  \
+             sway-scale-switcher --backend wlr-randr bench",
+        )
+        .arg(
+            Arg::new("iterations")
+                .short('n')
+                .long("iterations")
+                .value_name("N")
+                .help("Number of changes to time")
+                .value_parser(clap::value_parser!(u32).range(1..))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("reload")
+                .long("reload")
+                .help("Also time reloading sway after each change")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Print the breakdown as JSON")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+/// Function to build the `lint` subcommand
+fn lint_command() -> Command {
+    Command::new("lint")
+        .about("Check the Scale Options section for mistakes, optionally fixing them")
+        .long_about(
+            "Check the Scale Options section of the sway config: scales listed twice, \
+             options out of the order cycling follows (cycle_order), scales outside \
+             min_scale and max_scale (0.5 to 4 by default), targets listed twice, target \
+             displays without an output line, and marker text inside other comments. \
+             Each problem is reported with its line number and a suggested fix. Fails if \
+             a problem is left.",
+        )
+        .after_help(
+            "Examples:\n  \
+             sway-scale-switcher lint\n  \
+             sway-scale-switcher lint --fix",
+        )
+        .arg(
+            Arg::new("fix")
+                .long("fix")
+                .help("Rewrite the section to fix the problems that can be fixed")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+/// Function to build the `run` subcommand
+fn run_command() -> Command {
+    Command::new("run")
+        .about("Run a script of scale, mode, workspace and hook steps as one change")
+        .long_about(
+            "Run a script of steps as one change, one step per line:\n    \
+             scale OUTPUT SCALE         e.g. scale eDP-1 1.5, or scale eDP-1 off\n    \
+             mode OUTPUT MODE           e.g. mode HDMI-A-1 3840x2160@60Hz\n    \
+             workspace NAME OUTPUT      move a workspace to an output\n    \
+             hook COMMAND               run a shell command\n\
+             Blank lines and lines starting with # are skipped; quote names with spaces. \
+             The scales and modes are written in one transaction with a single reload, \
+             then workspaces are moved and hooks run in the order given. If a step \
+             fails, the scales, modes and moved workspaces are put back as they were.",
+        )
+        .after_help("Examples:\n  sway-scale-switcher run ~/.config/sway/dock.batch")
+        .arg(
+            Arg::new("file")
+                .help("Script to run")
+                .required(true)
+                .value_parser(clap::value_parser!(std::path::PathBuf)),
+        )
+}
+
+/// Function to build the `sync` subcommand
+fn sync_command() -> Command {
+    Command::new("sync")
+        .about("Compare the scales in the config with the live ones and reconcile them")
+        .long_about(
+            "Compare the scale each output has in the config with the scale sway is \
+             using right now, and list the outputs where they differ. Scales changed \
+             over IPC, e.g. by a profile or another tool, make the two drift apart.\n\n\
+             With --from-live the live scales are written back to the config; with \
+             --from-config the config's scales are applied to sway. Only outputs the \
+             config sets a scale for are compared.",
+        )
+        .arg(
+            Arg::new("from-live")
+                .long("from-live")
+                .help("Write the live scales to the config")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("from-config")
+                .long("from-config")
+                .help("Apply the config's scales to sway")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("from-live"),
+        )
+        .after_help(
+            "Examples:\n  sway-scale-switcher sync\n  sway-scale-switcher sync --from-live\n  \
+             sway-scale-switcher sync --from-config",
+        )
+}
+
+/// Function to build the `pin` subcommand
+fn pin_command() -> Command {
+    Command::new("pin")
+        .about("Exclude a display from cycling and profiles until it is unpinned")
+        .long_about(
+            "Exclude a display from cycling and profiles until it is unpinned. \
+             Without an output, list the pinned displays.",
+        )
+        .arg(
+            Arg::new("output")
+                .help("Name or description of the display to pin")
+                .required(false),
+        )
+}
+
+/// Function to build the `current` subcommand
+fn current_command() -> Command {
+    Command::new("current")
+        .about("Print the current scale of an output, for scripts")
+        .long_about(
+            "Print the current scale of an output: the one written to \
+             $XDG_RUNTIME_DIR/sway-scale-switcher/scale-<output> after the last change \
+             when record_scales = true is set in the settings file, or else the live \
+             scale while sway runs. With --dpi, print the matching DPI (96 per unit of \
+             scale), e.g. for the metadata of screenshots and recordings.",
+        )
+        .after_help(
+            "Examples:\n  sway-scale-switcher current eDP-1\n  \
+             sway-scale-switcher current eDP-1 --dpi",
+        )
+        .arg(
+            Arg::new("output")
+                .help("Name or description of the output")
+                .required(true),
+        )
+        .arg(
+            Arg::new("dpi")
+                .long("dpi")
+                .help("Print the DPI matching the scale instead")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+/// Function to build the `unpin` subcommand
+fn unpin_command() -> Command {
+    Command::new("unpin")
+        .about("Let cycling and profiles change a pinned display again")
+        .arg(
+            Arg::new("output")
+                .help("Name or description of the display to unpin")
+                .required(true),
+        )
+}
+
+/// Function to build the `announce` subcommand
+fn announce_command() -> Command {
+    Command::new("announce")
+        .about("Report scale changes made while sway was not running")
+        .long_about(
+            "Report scale changes made while sway was not running, as a desktop \
+             notification if notify-send is available. Set announce_offline_changes = true \
+             in the settings file and add this to the sway config:\n    \
+             exec sway-scale-switcher announce",
+        )
+}
+
+/// Function to build the `export` subcommand
+fn export_command() -> Command {
+    Command::new("export")
+        .about("Bundle the settings, profiles, state and managed snippet into one file")
+        .arg(
+            Arg::new("file")
+                .help("File to write the archive to; standard output if omitted")
+                .value_parser(clap::value_parser!(std::path::PathBuf)),
+        )
+}
+
+/// Function to build the `import` subcommand
+fn import_command() -> Command {
+    Command::new("import")
+        .about("Restore the settings, profiles, state and managed snippet from an archive")
+        .arg(
+            Arg::new("file")
+                .help("Archive written by the export subcommand")
+                .required(true)
+                .value_parser(clap::value_parser!(std::path::PathBuf)),
+        )
+}
+
+/// Function to build the `migrate` subcommand
+fn migrate_command() -> Command {
+    Command::new("migrate")
+        .about("Upgrade the settings and state files and move the scale options section into the settings")
+        .long_about(
+            "Upgrade the settings and state files to the format this version writes, \
+             and move the scale options section (the '# Target Display' and \
+             '# Scale Options' comments) out of the sway config into the settings file \
+             as 'targets' and 'scales'. The files are replaced together or not at all.\n\n\
+             Older files keep working without this, but a newer version may drop support \
+             for them.",
+        )
+}
+
+/// Function to build the `recover` subcommand
+fn recover_command() -> Command {
+    Command::new("recover")
+        .about("Undo or finish a change that was interrupted, e.g. by a crash")
+        .long_about(
+            "Undo or finish a change that was interrupted, e.g. by a crash or a kill \
+             while the config was being replaced. The files are checked against the \
+             versions from before and after the change; one edited since is left as \
+             it is, with its old contents kept beside it. Sway is reloaded afterwards \
+             if it is running.",
+        )
+        .arg(
+            Arg::new("keep")
+                .long("keep")
+                .help("Finish the change instead of putting the previous files back")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+/// Function to build the `history` subcommand
+fn history_command() -> Command {
+    Command::new("history")
+        .about("List recent scale changes or revert to an earlier one")
+        .long_about(
+            "List recent scale changes or revert to an earlier one. Each change names what \
+             triggered it in brackets: the command line and the process that ran it, a \
+             --trigger label, or for the daemon the signal and its sender, the socket \
+             client, the hotplug, workspace or idle rule, or the D-Bus caller. The daemon \
+             logs the same as 'Triggered by ...' lines.",
+        )
+        .after_help(
+            "Examples:\n  \
+             sway-scale-switcher history -n 20\n  \
+             sway-scale-switcher history --revert 12",
+        )
+        .arg(
+            Arg::new("limit")
+                .short('n')
+                .long("limit")
+                .help("Number of recent changes to show")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("revert")
+                .long("revert")
+                .value_name("ID")
+                .help("Reapply the scales recorded in the given history entry")
+                .value_parser(clap::value_parser!(u64)),
+        )
+}
+
+/// Function to pick what changes are sent to: the backend named with `--backend`, the sway
+/// behind `--host`, or else the local sway socket and the first usable entry of the backend chain
+fn select_session(matches: &clap::ArgMatches) -> io::Result<()> {
+    let backend = matches.get_one::<ipc::Backend>("backend").copied();
+    if backend == Some(ipc::Backend::Mock) {
+        let seed = matches.get_one::<std::path::PathBuf>("mock-outputs");
+        ipc::use_mock(mock::Compositor::load(seed.map(|seed| seed.as_path()))?);
+    } else if backend == Some(ipc::Backend::WlrRandr) {
+        ipc::use_wlr_randr();
+    } else if let Some(host) = matches.get_one::<String>("host") {
+        if let Some(socket) = matches.get_one::<std::path::PathBuf>("socket") {
+            ipc::set_socket(socket.clone());
+        }
+        ipc::set_host(host.clone());
+    } else {
+        select_socket(matches.get_one::<std::path::PathBuf>("socket"))?;
+        // `--backend sway` talks to sway over IPC without falling back along the chain
+        if backend.is_none() {
+            select_backend();
+        }
+        check_session(matches)?;
+    }
+    Ok(())
+}
+
+/// Function to list the scheduled jobs, cancel one, or wait for one to run
+fn run_schedule(schedule_matches: &clap::ArgMatches) -> io::Result<()> {
+    match schedule_matches.subcommand() {
+        Some(("cancel", cancel_matches)) => {
+            let id = *cancel_matches.get_one::<u64>("id").unwrap();
+            if schedule::cancel(id)? {
+                println!("Cancelled job {}", id);
+                Ok(())
+            } else {
+                Err(io::Error::other(format!(
+                    "No scheduled job with id {}.",
+                    id
+                )))
+            }
+        }
+        Some(("wait", wait_matches)) => schedule::wait(*wait_matches.get_one::<u64>("id").unwrap()),
+        _ => {
+            print_schedule(&schedule::load_jobs()?);
+            Ok(())
+        }
+    }
+}
+
+/// Function to save a change for the timer to run at `due` instead of applying it now
+fn defer_change(matches: &clap::ArgMatches, due: u64, set_applies: bool) -> io::Result<()> {
+    let deferrable = ["swap", "cycle-bitdepth", "yes"]
+        .iter()
+        .any(|flag| matches.get_flag(flag))
+        || ["on", "off", "toggle-power"]
+            .iter()
+            .any(|flag| matches.contains_id(flag))
+        || matches!(
+            matches.subcommand_name(),
+            Some("cycle" | "power" | "profile")
+        )
+        || set_applies;
+    if !deferrable {
+        return Err(io::Error::other(
+            "--in and --at defer cycle, set with a scale or --default, profile and power.",
+        ));
+    }
+    let job = schedule::add(due, without_schedule_args(std::env::args().skip(1)))?;
+    println!(
+        "Scheduled job {} for {}; cancel it with 'sway-scale-switcher schedule cancel {}'.",
+        job.id,
+        schedule::format_time(job.due, schedule::local_offset()),
+        job.id
+    );
+    Ok(())
+}
+
+/// Function to arrange the named outputs, or every active one, and apply the positions over IPC
+fn run_layout(layout_matches: &clap::ArgMatches) -> io::Result<()> {
+    let arrangement = *layout_matches
+        .get_one::<layout::Arrangement>("arrangement")
+        .unwrap();
+    let outputs = ipc::get_outputs()?;
+    let selected: Vec<&ipc::Output> = match layout_matches.get_many::<String>("outputs") {
+        Some(names) => names
+            .map(|name| {
+                let name = unalias(name);
+                outputs
+                    .iter()
+                    .find(|o| o.name == name)
+                    .ok_or_else(|| io::Error::other(format!("Output '{}' not found.", name)))
+            })
+            .collect::<io::Result<_>>()?,
+        None => outputs.iter().filter(|o| o.active).collect(),
+    };
+    apply_output_settings(&layout::arrange(arrangement, &selected))
+}
+
+/// Function to pin a display so changes leave its scale alone, or to list the pinned ones
+fn run_pin(pin_matches: &clap::ArgMatches) -> io::Result<()> {
+    let mut state = state::load_state()?;
+    let Some(output) = pin_matches
+        .get_one::<String>("output")
+        .map(|name| unalias(name))
+    else {
+        if state.pinned.is_empty() {
+            println!("{}", tr!(NoPinned));
+        }
+        for display in &state.pinned {
+            println!("{}", display);
+        }
+        return Ok(());
+    };
+    if !state.pinned.insert(output.clone()) {
+        println!("{}", tr!(AlreadyPinned, output));
+        return Ok(());
+    }
+    state::save_state(&state)?;
+    println!("{}", tr!(Pinned, output));
+    Ok(())
+}
+
+/// Function to let changes reach a pinned display again
+fn run_unpin(unpin_matches: &clap::ArgMatches) -> io::Result<()> {
+    let output = unalias(unpin_matches.get_one::<String>("output").unwrap());
+    let mut state = state::load_state()?;
+    if !state.pinned.remove(&output) {
+        warning!("{} is not pinned.", output);
+        return Ok(());
+    }
+    state::save_state(&state)?;
+    println!("{}", tr!(Unpinned, output));
+    Ok(())
+}
+
+/// Function to announce the changes made while sway was not running, as a notification if
+/// notify-send works and on stdout otherwise
+fn announce_pending() -> io::Result<()> {
+    let Some(changes) = state::take_pending()? else {
+        return Ok(());
+    };
+    let title = tr!(OfflineChanges);
+    let notified = process::Command::new("notify-send")
+        .arg(&title)
+        .arg(&changes)
+        .status()
+        .is_ok_and(|status| status.success());
+    if !notified {
+        println!("{}\n{}", title, changes);
+    }
+    Ok(())
+}
+
+/// Function to bundle the settings file, the state file and the managed snippet into one file, or
+/// print the bundle
+fn run_export(export_matches: &clap::ArgMatches) -> io::Result<()> {
+    let snippet_path =
+        expanduser(snippet::SNIPPET_PATH).expect("Failed to expand managed snippet path");
+    let archive = archive::collect(Path::new(&snippet_path))?;
+    let json = serde_json::to_string_pretty(&archive).map_err(io::Error::other)? + "\n";
+    match export_matches.get_one::<std::path::PathBuf>("file") {
+        Some(file) => {
+            fs::write(file, json)?;
+            println!("Exported to {}", file.display());
+        }
+        None => io::stdout().write_all(json.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Function to restore the files saved in an archive made by `export`
+fn run_import(import_matches: &clap::ArgMatches) -> io::Result<()> {
+    let file = import_matches
+        .get_one::<std::path::PathBuf>("file")
+        .unwrap();
+    let archive = archive::parse(&fs::read_to_string(file)?)?;
+    let snippet_path =
+        expanduser(snippet::SNIPPET_PATH).expect("Failed to expand managed snippet path");
+    let written = archive::restore(
+        &archive,
+        Path::new(&snippet_path),
+        import_matches.get_flag("force"),
+    )?;
+    for path in written {
+        println!("Restored {}", path.display());
+    }
+    Ok(())
+}
+
+/// Function to undo or finish a change cut short by a crash, reloading sway afterwards
+fn run_recover(recover_matches: &clap::ArgMatches) -> io::Result<()> {
+    if !transaction::recover(recover_matches.get_flag("keep"))? {
+        println!("There is no interrupted change to recover.");
+        return Ok(());
+    }
+    if ipc::is_running() {
+        ipc::reload()?;
+        println!("{}", tr!(Reloaded));
+    }
+    Ok(())
+}
+
+/// Function to start the daemon, which reloads the settings with the same flags when they change
+fn run_daemon(
+    settings: settings::Settings,
+    matches: &clap::ArgMatches,
+    daemon_matches: &clap::ArgMatches,
+) -> io::Result<()> {
+    let metrics_addr = daemon_matches
+        .get_one::<std::net::SocketAddr>("metrics-addr")
+        .copied();
+    let pidfile = daemon_matches
+        .get_one::<std::path::PathBuf>("pidfile")
+        .cloned()
+        .unwrap_or_else(pidfile::default_path);
+    daemon::run(
+        settings,
+        &|| load_settings(matches),
+        metrics_addr,
+        daemon_matches.get_flag("dbus"),
+        &pidfile,
+        daemon_matches
+            .get_one::<std::path::PathBuf>("events-socket")
+            .map(|path| path.as_path()),
+    )
+}
+
+/// Function to list the profiles, or to apply the named one to every display that is not pinned
+fn run_profile(
+    settings: &settings::Settings,
+    profile_matches: &clap::ArgMatches,
+) -> io::Result<()> {
+    if profile_matches.get_flag("list") {
+        if settings.profiles.is_empty() {
+            println!("No profiles defined.");
+        }
+        for (name, profile) in &settings.profiles {
+            if profile.auto {
+                println!("{} (auto)", name);
+            } else {
+                println!("{}", name);
+            }
+        }
+        return Ok(());
+    }
+    let name = profile_matches.get_one::<String>("name").unwrap();
+    let profile = settings.profiles.get(name).ok_or_else(|| {
+        io::Error::other(format!("No profile named '{}' in the settings file.", name))
+    })?;
+    let outputs = state::load_state()?.skip_pinned(profile.outputs.clone());
+    screencast::check(settings.screencast_guard)?;
+    apply_output_settings(&outputs)
+}
+
+/// Function to list the recent changes, or to reapply the scales recorded in one of them
+fn run_history(
+    settings: &settings::Settings,
+    config_path: &str,
+    lines: &[String],
+    history_matches: &clap::ArgMatches,
+) -> io::Result<()> {
+    let entries = history::read_history()?;
+    let Some(&id) = history_matches.get_one::<u64>("revert") else {
+        let limit = *history_matches.get_one::<usize>("limit").unwrap();
+        history::print_history(&entries, limit);
+        return Ok(());
+    };
+    let entry = entries
+        .iter()
+        .find(|entry| entry.id == id)
+        .ok_or_else(|| io::Error::other(format!("No history entry with id {}.", id)))?;
+    println!("Reverting to history entry {}", id);
+    let displays: Vec<String> = entry.outputs.keys().cloned().collect();
+    let edit_files = editable_config(settings, config_path, lines, &displays)?;
+    apply_scales(settings, &edit_files, &entry.outputs)
+}
+
+/// Function to apply a scale to the target displays: the one given to `set`, the default
+/// option, or the one the user picks at the prompt
+fn change_scales(
+    settings: &settings::Settings,
+    matches: &clap::ArgMatches,
+    config_path: &str,
+    lines: &[String],
+) -> io::Result<()> {
+    // Parse the scale options section to get target displays and scale values
+    let scale_options = find_scale_options(settings, lines)?;
+
+    // `set --output` narrows the change to the named displays
+    let set_matches = matches.subcommand_matches("set");
+    let chosen: Option<Vec<String>> = set_matches
+        .and_then(|set_matches| set_matches.get_many::<String>("output"))
+        .map(|outputs| {
+            outputs
+                .map(|name| resolve_display(settings, lines, name))
+                .collect()
+        });
+    if let Some(chosen) = &chosen {
+        validate_targets(settings, lines, chosen)?;
+    }
+    let targets = chosen
+        .clone()
+        .unwrap_or_else(|| scale_options.target_displays.clone());
+
+    // Pick the file that holds the output lines: the main config, or the managed snippet
+    let edit_files = editable_config(settings, config_path, lines, &targets)?;
+    let edit_lines = joined(&edit_files);
+
+    // A scale given on the command line is applied as is, whether or not it is an option
+    if let Some(option) =
+        set_matches.and_then(|set_matches| set_matches.get_one::<ScaleOption>("option"))
+    {
+        let values = [option.clone()];
+        let outputs = outputs_for_expressions(settings, &values)?;
+        let scales = scales_for_option(settings, &values, &targets, 0, &outputs)?;
+        return apply_scales(settings, &edit_files, &scales);
+    }
+
+    // Without a prompt, apply the default option (or the first one) to every target display
+    if matches.get_flag("yes")
+        || set_matches.is_some_and(|set_matches| set_matches.get_flag("default"))
+    {
+        let index = default_option_index(settings, &scale_options.scale_values)?;
+        let outputs = outputs_for_expressions(settings, &scale_options.scale_values)?;
+        let scales = scales_for_option(
+            settings,
+            &scale_options.scale_values,
+            &targets,
+            index,
+            &outputs,
+        )?;
+        println!(
+            "{}",
+            tr!(
+                ApplyingDefault,
+                scale_options.scale_values[index],
+                targets.join(", ")
+            )
+        );
+        return apply_scales(settings, &edit_files, &scales);
+    }
+
+    // With several target displays, ask which of them to change first
+    let displays = if let Some(chosen) = chosen {
+        chosen
+    } else if scale_options.target_displays.len() > 1 {
+        let current = config::get_output_scales(&edit_lines, &scale_options.target_displays);
+        match prompt_user_for_displays(settings, &scale_options.target_displays, &current)? {
+            Some(displays) => displays,
+            None => {
+                println!("{}", tr!(NoChanges));
+                return Ok(());
+            }
+        }
+    } else {
+        scale_options.target_displays.clone()
+    };
+
+    // Determine the current scale by inspecting the output lines
+    let current_scale = current_scale(settings, &edit_lines, &displays);
+
+    // Resolve options like `auto` for the first selected display, which the prompt lists
+    let outputs = outputs_for_expressions(settings, &scale_options.scale_values)?;
+    let mut scale_values = scale_options.scale_values.clone();
+    let mut resolved = resolve_options(settings, &scale_values, &displays[0], &outputs)?;
+
+    // Ask the user which scale to apply, showing the logical size each option gives the first
+    // selected display while sway runs
+    let live = if ipc::is_running() {
+        ipc::get_outputs().unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let mut choice = prompt_user_for_scale(
+        settings,
+        &mut scale_values,
+        &mut resolved,
+        &current_scale,
+        live.iter().find(|output| output.name == displays[0]),
+    )?;
+
+    // While sway runs, try the choice out live and only write it once the user keeps it
+    if !matches.get_flag("no-preview") && ipc::is_running() {
+        choice = preview_scale(settings, &scale_values, &displays, &outputs, choice)?;
+    }
+
+    // If choice is None, the user chose to quit; exit without making changes
+    if let Some(index) = choice {
+        let scales = scales_for_option(settings, &scale_values, &displays, index, &outputs)?;
+        apply_scales(settings, &edit_files, &scales)?;
+        if index >= scale_options.scale_values.len() {
+            offer_custom_scale(
+                settings,
+                config_path,
+                &scale_options.scale_values,
+                &scale_values[index],
+            )?;
+        }
+    } else {
+        println!("{}", tr!(NoChanges));
+    }
+
+    Ok(())
+}
+
+/// Function to print the version, or as JSON the build details an installer can check before
+/// relying on a backend, feature or subcommand
+fn print_version(cli: &Command, json: bool) -> io::Result<()> {
+    let version = env!("CARGO_PKG_VERSION");
+    if !json {
+        println!("{} {}", cli.get_name(), version);
+        return Ok(());
+    }
+    let backends: Vec<String> = ipc::Backend::value_variants()
+        .iter()
+        .filter_map(|backend| backend.to_possible_value())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    let subcommands: Vec<&str> = cli
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name())
+        .collect();
+    let info = serde_json::json!({
+        "version": version,
+        "git_hash": option_env!("GIT_HASH").filter(|hash| !hash.is_empty()),
+        "backends": backends,
+        "features": { "dbus": cfg!(feature = "dbus") },
+        "subcommands": subcommands,
+    });
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&info).map_err(io::Error::other)?
+    );
+    Ok(())
+}
+
+/// Function to locate the Sway config file: SWAY_SCALE_SWITCHER_CONFIG if set, e.g. for a nested
+/// sway, else ~/.config/sway/config
+fn config_path() -> io::Result<String> {
+    let path = std::env::var(format!("{}CONFIG", settings::ENV_PREFIX))
+        .ok()
+        .filter(|path| !path.is_empty())
+        .unwrap_or_else(|| "~/.config/sway/config".to_string());
+    expanduser(&path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Failed to expand config path"))
+}
+
+/// Function to read the Sway config file into a vector of lines
+fn read_config(config_path: &str) -> io::Result<Vec<String>> {
+    let contents = fs::read(config_path)
+        .map_err(|e| io::Error::new(e.kind(), format!("Failed to open {}: {}", config_path, e)))?;
+    Ok(text::decode_lines(&contents))
+}
+
+/// Function to parse the scale options from the config lines and check their target displays
+fn find_scale_options(settings: &settings::Settings, lines: &[String]) -> io::Result<ScaleOptions> {
+    let mut scale_options = scale_options_section(settings, lines).map_err(io::Error::other)?;
+    validate_targets(settings, lines, &scale_options.target_displays)?;
+
+    // An option group from the settings replaces the options listed in the config
+    if let Some(group) = contexts::select(settings)? {
+        if group.scales.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "The selected option group has no scales.",
+            ));
+        }
+        scale_options.scale_values = group.scales.clone();
+    }
+    Ok(scale_options)
+}
+
+/// Function to read the target displays and scale options from the settings, or else from the
+/// marker section of the config
+fn scale_options_section(
+    settings: &settings::Settings,
+    lines: &[String],
+) -> Result<ScaleOptions, String> {
+    let mut scale_options = match (settings.targets.is_empty(), settings.scales.is_empty()) {
+        (true, true) => config::find_scale_options(lines).map(|mut scale_options| {
+            // Target lines may name a display by its alias too
+            for target in &mut scale_options.target_displays {
+                *target = settings.unalias(target).to_string();
+            }
+            scale_options
+        }),
+        (false, false) => Ok(ScaleOptions {
+            target_displays: settings.targets.clone(),
+            scale_values: settings.scales.clone(),
+        }),
+        (false, true) => Err("The settings set targets but no scales.".to_string()),
+        (true, false) => Err("The settings set scales but no targets.".to_string()),
+    }?;
+    if let Some(targets) = &settings.target_override {
+        scale_options.target_displays = targets.clone();
+    }
+    Ok(scale_options)
+}
+
+/// Function to check that every target display has an output line in the config (or the managed
+/// snippet) or is currently connected, so a typo fails loudly instead of writing nothing useful
+fn validate_targets(
+    settings: &settings::Settings,
+    lines: &[String],
+    target_displays: &[String],
+) -> io::Result<()> {
+    let mut known = config::find_output_names(&definitions(settings, lines));
+    if target_displays.iter().all(|target| known.contains(target)) {
+        return Ok(());
+    }
+
+    // Only ask sway when the config alone does not account for every target
+    let outputs = ipc::get_outputs();
+    let connected = |target: &String| {
+        outputs.as_ref().is_ok_and(|outputs| {
+            outputs
+                .iter()
+                .any(|o| &o.name == target || &o.description() == target)
+        })
+    };
+    let unknown: Vec<&str> = target_displays
+        .iter()
+        .filter(|target| !known.contains(target) && !connected(target))
+        .map(String::as_str)
+        .collect();
+    if unknown.is_empty() {
+        return Ok(());
+    }
+
+    if let Ok(outputs) = &outputs {
+        for output in outputs {
+            if !known.contains(&output.name) {
+                known.push(output.name.clone());
+            }
+        }
+    }
+    let mut message = format!(
+        "{} {} {} no output line in the config and no connected output.\nDetected outputs: {}",
+        if unknown.len() == 1 {
+            "Target display"
+        } else {
+            "Target displays"
+        },
+        unknown
+            .iter()
+            .map(|name| format!("'{}'", name))
+            .collect::<Vec<_>>()
+            .join(", "),
+        if unknown.len() == 1 {
+            "matches"
+        } else {
+            "match"
+        },
+        if known.is_empty() {
+            "none".to_string()
+        } else {
+            known.join(", ")
+        }
+    );
+    if outputs.is_err() {
+        message.push_str(" (sway is not reachable, so only the config was checked)");
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidInput, message))
+}
+
+/// Function to determine the current scale of the target displays, warning if it is ambiguous
+fn current_scale(
+    settings: &settings::Settings,
+    lines: &[String],
+    target_displays: &[String],
+) -> ScaleOption {
+    match config::get_current_scale(lines, target_displays, settings.precision.epsilon) {
+        CurrentScale::Uniform(scale) => scale,
+        CurrentScale::Mixed(scale) => {
+            warning!(
+                "Multiple scales found for target displays. Using the first scale: {}",
+                scale
+            );
+            scale
+        }
+        CurrentScale::Missing => {
+            warning!(
+                "No current scale found for target displays. Defaulting to first scale option."
+            );
+            ScaleOption::scale(1.0)
+        }
+    }
+}
+
+/// Function to cycle all target displays to the next scale option, or the previous one if `reverse` is set.
+/// With `focused`, only the output showing the focused workspace is cycled instead.
+fn cycle_scale(
+    settings: &settings::Settings,
+    reverse: bool,
+    focused: bool,
+) -> io::Result<ScaleOption> {
+    cycle_scale_by(settings, if reverse { -1 } else { 1 }, focused)
+}
+
+/// Function to move all target displays `steps` options along, backwards if negative, in one
+/// change. The daemon uses it to apply several cycle requests that arrive close together.
+fn cycle_scale_by(
+    settings: &settings::Settings,
+    steps: i64,
+    focused: bool,
+) -> io::Result<ScaleOption> {
+    // The daemon takes requests on several threads; each change reads the config it rewrites
+    // only once the one before is done
+    let _lock = transaction::write_lock();
+    let config_path = config_path()?;
+    let lines = read_config(&config_path)?;
+    let scale_options = find_scale_options(settings, &lines)?;
+    let displays = if focused {
+        vec![focused_display(settings, &lines)?]
+    } else {
+        scale_options.target_displays.clone()
+    };
+
+    // Pinned displays keep their scale until they are unpinned
+    let state = state::load_state()?;
+    for display in &displays {
+        if state.is_pinned(display) {
+            println!("{}", tr!(SkippingPinned, display));
+        }
+    }
+    let targets = state.unpinned(&displays);
+    if targets.is_empty() {
+        return Err(io::Error::other(
+            "All target displays are pinned; unpin one with 'sway-scale-switcher unpin <output>'.",
+        ));
+    }
+
+    let edit_files = editable_config(settings, &config_path, &lines, &targets)?;
+    let edit_lines = joined(&edit_files);
+
+    // A disabled display sits at the `off` entry, whatever scale its output line still has;
+    // matching physical sizes leaves the scales differing, so the first display leads
+    let leading = if settings.match_physical_size {
+        &targets[..1]
+    } else {
+        &targets[..]
+    };
+    let mut current_scale = current_scale(settings, &edit_lines, leading);
+    if scale_options.scale_values.iter().any(|option| option.off)
+        && !disabled_displays(&targets[..1]).is_empty()
+    {
+        current_scale = ScaleOption::off();
+    }
+
+    // Cycle through the options as resolved for the first target display; every display
+    // then gets the same entry, resolved for its own panel
+    let outputs = outputs_for_expressions(settings, &scale_options.scale_values)?;
+    let resolved = resolve_options(settings, &scale_options.scale_values, &targets[0], &outputs)?;
+    let epsilon = settings.precision.epsilon;
+    let step = |scale: &ScaleOption| {
+        if steps < 0 {
+            config::get_previous_index(&resolved, scale, epsilon, settings.cycle_order)
+        } else {
+            config::get_next_index(&resolved, scale, epsilon, settings.cycle_order)
+        }
+    };
+    let mut index = step(&current_scale);
+    for _ in 1..steps.unsigned_abs() {
+        index = step(&resolved[index]);
+    }
+    let next_scale = resolved[index].clone();
+    if config::contains_scale(&resolved, &current_scale, epsilon) {
+        println!("{}", tr!(Swapping, ui::change(&current_scale, &next_scale)));
+    } else {
+        println!("{}", tr!(ScaleNotFound, current_scale, next_scale));
+    }
+    let scales = scales_for_option(
+        settings,
+        &scale_options.scale_values,
+        &targets,
+        index,
+        &outputs,
+    )?;
+    apply_scales(settings, &edit_files, &scales)?;
+    Ok(next_scale)
+}
+
+/// Function to name the focused output the way the config does, by connector or by description
+fn focused_display(settings: &settings::Settings, lines: &[String]) -> io::Result<String> {
+    let output = ipc::focused_output()?;
+    let known = config::find_output_names(&definitions(settings, lines));
+
+    let description = output.description();
+    if !known.contains(&output.name) && known.contains(&description) {
+        return Ok(description);
+    }
+    // The managed snippet gains a line for any display it does not mention yet
+    if !settings.managed && !known.contains(&output.name) {
+        return Err(io::Error::other(format!(
+            "The focused output '{}' has no output line in the config.",
+            output.name
+        )));
+    }
+    Ok(output.name)
+}
+
+/// Function to list the displays, by connector or description, that sway has disabled.
+/// Without a running sway nothing is known to be disabled.
+fn disabled_displays(displays: &[String]) -> Vec<String> {
+    if !ipc::is_running() {
+        return Vec::new();
+    }
+    let Ok(outputs) = ipc::get_outputs() else {
+        return Vec::new();
+    };
+    displays
+        .iter()
+        .filter(|display| {
+            outputs.iter().any(|output| {
+                !output.active && (&&output.name == display || &&output.description() == display)
+            })
+        })
+        .cloned()
+        .collect()
+}
+
+/// Function to find the option `set --default` applies: the configured default, or else the first option
+fn default_option_index(
+    settings: &settings::Settings,
+    scale_values: &[ScaleOption],
+) -> io::Result<usize> {
+    let Some(default) = &settings.default_option else {
+        return Ok(0);
+    };
+    scale_values
+        .iter()
+        .position(|option| option.to_string() == default.to_string())
+        .ok_or_else(|| {
+            io::Error::other(format!(
+                "The default option {} is not one of the scale options.",
+                default
+            ))
+        })
+}
+
+/// Function to query the outputs over IPC if any option is an expression that needs them, or
+/// every option does because they stand for physical sizes
+fn outputs_for_expressions(
+    settings: &settings::Settings,
+    scale_values: &[ScaleOption],
+) -> io::Result<Vec<ipc::Output>> {
+    if !settings.match_physical_size && scale_values.iter().all(|option| option.expr.is_none()) {
+        return Ok(Vec::new());
+    }
+    ipc::get_outputs().map_err(|e| {
+        io::Error::other(format!(
+            "Scale options like 'auto' need a running sway to resolve: {}",
+            e
+        ))
+    })
+}
+
+/// Function to resolve expression options such as `auto+0.25` for one display, and with
+/// `match_physical_size` the plain numbers as well
+fn resolve_options(
+    settings: &settings::Settings,
+    scale_values: &[ScaleOption],
+    display: &str,
+    outputs: &[ipc::Output],
+) -> io::Result<Vec<ScaleOption>> {
+    if !settings.match_physical_size && scale_values.iter().all(|option| option.expr.is_none()) {
+        return Ok(scale_values.to_vec());
+    }
+
+    let vars = expr::Vars::from_dpi(output_dpi(settings, display, outputs)?);
+    scale_values
+        .iter()
+        .map(|option| {
+            let resolved = if settings.match_physical_size {
+                option.resolve_apparent(&vars)
+            } else {
+                option.resolve(&vars)
+            };
+            resolved.map_err(|e| {
+                io::Error::other(format!(
+                    "Cannot resolve '{}' for {}: {}",
+                    option, display, e
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Function to work out a display's DPI from its mode and EDID size, unless the settings override it
+fn output_dpi(
+    settings: &settings::Settings,
+    display: &str,
+    outputs: &[ipc::Output],
+) -> io::Result<f32> {
+    if let Some(&dpi) = settings.dpi.get(display) {
+        return Ok(dpi);
+    }
+
+    let output = outputs
+        .iter()
+        .find(|o| o.name == display || o.description() == display)
+        .ok_or_else(|| io::Error::other(format!("Output '{}' is not connected.", display)))?;
+    let mode = output
+        .current_mode
+        .as_ref()
+        .ok_or_else(|| io::Error::other(format!("Output '{}' has no current mode.", display)))?;
+    // The EDID is found by connector, so targets named by description look up their connector
+    let (width_mm, _) = edid::physical_size(&output.name).ok_or_else(|| {
+        io::Error::other(format!(
+            "Physical size of '{}' is unknown; set its DPI under [dpi] in the settings file.",
+            display
+        ))
+    })?;
+    Ok(mode.width as f32 * 25.4 / width_mm as f32)
+}
+
+/// Function to print the scales that divide an output's modes evenly, and a suggested list of
+/// options for its current mode
+fn print_candidates(
+    settings: &settings::Settings,
+    name: &str,
+    max_scale: f32,
+    all_modes: bool,
+) -> io::Result<()> {
+    let outputs = ipc::get_outputs()?;
+    let output = outputs
+        .iter()
+        .find(|output| output.name == name || output.description() == name)
+        .ok_or_else(|| {
+            let names: Vec<&str> = outputs.iter().map(|output| output.name.as_str()).collect();
+            io::Error::other(format!(
+                "Output '{}' not found. Connected outputs: {}",
+                name,
+                names.join(", ")
+            ))
+        })?;
+    let current = output
+        .current_mode
+        .as_ref()
+        .ok_or_else(|| io::Error::other(format!("Output '{}' has no current mode.", name)))?;
+
+    // The current mode first, then every other resolution once
+    let mut resolutions = vec![(current.width, current.height)];
+    if all_modes {
+        for mode in &output.modes {
+            if !resolutions.contains(&(mode.width, mode.height)) {
+                resolutions.push((mode.width, mode.height));
+            }
+        }
+    }
+
+    let physical_width = edid::physical_size(&output.name).map(|(width, _)| width);
+    let dpi = |width: i32| {
+        settings
+            .dpi
+            .get(name)
+            .copied()
+            .or_else(|| physical_width.map(|mm| width as f32 * 25.4 / mm as f32))
+    };
+    let mut suggested = Vec::new();
+    for (i, &(width, height)) in resolutions.iter().enumerate() {
+        if all_modes {
+            println!("{}{}x{}", if i > 0 { "\n" } else { "" }, width, height);
+        }
+        let options = candidates::candidates(width as u32, height as u32, max_scale);
+        let dpi = dpi(width);
+        let rows: Vec<Vec<(String, ui::Style)>> = options
+            .iter()
+            .map(|candidate| {
+                vec![
+                    (
+                        ScaleOption::scale(candidate.scale).to_string(),
+                        ui::Style::Green,
+                    ),
+                    (
+                        format!("{}x{}", candidate.logical_width, candidate.logical_height),
+                        ui::Style::Plain,
+                    ),
+                    match dpi {
+                        Some(dpi) => (format!("{:.0}", dpi / candidate.scale), ui::Style::Plain),
+                        None => ("unknown".to_string(), ui::Style::Dim),
+                    },
+                ]
+            })
+            .collect();
+        ui::print_table(&["SCALE", "LOGICAL SIZE", "DPI"], &rows);
+        if i == 0 {
+            suggested = candidates::suggest(&options, dpi.map(|dpi| dpi / 96.0));
+        }
+    }
+
+    let suggested: Vec<String> = suggested
+        .into_iter()
+        .map(|scale| ScaleOption::scale(scale).to_string())
+        .collect();
+    println!(
+        "\nSuggested options for {}x{}:",
+        current.width, current.height
+    );
+    println!("  # Scale Options = {}", suggested.join(", "));
+    Ok(())
+}
+
+/// Function to time `iterations` changes of the target displays, stage by stage, without
+/// editing the config, then give the displays their scales back
+fn run_bench(
+    settings: &settings::Settings,
+    config_path: &str,
+    iterations: u32,
+    reload: bool,
+    json: bool,
+) -> io::Result<()> {
+    if !ipc::is_running() {
+        return Err(io::Error::other(
+            "Benchmarking applies scales, so it needs a running sway; pass --backend mock to time \
+             the tool alone.",
+        ));
+    }
+    let mut stages = vec![
+        bench::Stage::new("parse"),
+        bench::Stage::new("decide"),
+        bench::Stage::new("apply"),
+        bench::Stage::new("confirm"),
+    ];
+    if reload {
+        stages.push(bench::Stage::new("reload"));
+    }
+
+    // Remember the live scales, and the modes too if any option changes them
+    let lines = read_config(config_path)?;
+    let scale_options = find_scale_options(settings, &lines)?;
+    let with_modes = scale_options
+        .scale_values
+        .iter()
+        .any(|option| option.mode.is_some());
+    let original = live_settings(
+        &ipc::get_outputs()?,
+        &scale_options.target_displays,
+        with_modes,
+    );
+
+    // Whatever goes wrong partway, the displays are put back before reporting it
+    let mut current = None;
+    let mut result = Ok(());
+    for _ in 0..iterations {
+        result = bench_iteration(settings, config_path, &mut stages, &mut current);
+        if result.is_err() {
+            break;
+        }
+    }
+    for output in &original {
+        if let Err(e) = ipc::run_command(&output.to_command()) {
+            warning!("Failed to restore {}: {}", output.name, e);
+        }
+    }
+    result?;
+    bench::print_report(ipc::backend_name(), &stages, json)
+}
+
+/// Function to time one change for `run_bench`, adding a sample to each stage. `current` is
+/// the option the previous change applied, None before the first.
+fn bench_iteration(
+    settings: &settings::Settings,
+    config_path: &str,
+    stages: &mut [bench::Stage],
+    current: &mut Option<ScaleOption>,
+) -> io::Result<()> {
+    let epsilon = settings.precision.epsilon;
+
+    // Parse: read the config and find its options, as every keypress does
+    let start = Instant::now();
+    let lines = read_config(config_path)?;
+    let scale_options = find_scale_options(settings, &lines)?;
+    stages[0].samples.push(start.elapsed());
+
+    // Decide: resolve the options and step to the next one that leaves the displays on
+    let start = Instant::now();
+    let targets = &scale_options.target_displays;
+    let current_option = current
+        .take()
+        .unwrap_or_else(|| current_scale(settings, &lines, &targets[..1]));
+    let outputs = outputs_for_expressions(settings, &scale_options.scale_values)?;
+    let resolved = resolve_options(settings, &scale_options.scale_values, &targets[0], &outputs)?;
+    let mut index =
+        config::get_next_index(&resolved, &current_option, epsilon, settings.cycle_order);
+    for _ in 1..resolved.len() {
+        if !resolved[index].off {
+            break;
+        }
+        index = config::get_next_index(&resolved, &resolved[index], epsilon, settings.cycle_order);
+    }
+    if resolved[index].off {
+        return Err(io::Error::other(
+            "The scale options only turn the displays off; there is no scale to time.",
+        ));
+    }
+    let scales = scales_for_option(
+        settings,
+        &scale_options.scale_values,
+        targets,
+        index,
+        &outputs,
+    )?;
+    *current = Some(resolved[index].clone());
+    stages[1].samples.push(start.elapsed());
+
+    // Apply: send the change through the backend
+    let start = Instant::now();
+    for output in scale_settings(settings, &scales) {
+        ipc::run_command(&output.to_command())?;
+    }
+    stages[2].samples.push(start.elapsed());
+
+    // Confirm: wait until the outputs report the new scales
+    let start = Instant::now();
+    confirm_scales(&scales, epsilon)?;
+    stages[3].samples.push(start.elapsed());
+
+    if let Some(stage) = stages.get_mut(4) {
+        let start = Instant::now();
+        ipc::reload()?;
+        stage.samples.push(start.elapsed());
+    }
+    Ok(())
+}
+
+/// Function to record the live scale of each given display, matched by name or description,
+/// and its mode with the refresh rate when `with_modes` is set, for putting the displays back
+/// after trying scales on them
+fn live_settings(
+    live: &[ipc::Output],
+    displays: &[String],
+    with_modes: bool,
+) -> Vec<OutputSettings> {
+    live.iter()
+        .filter(|output| {
+            output.active
+                && (displays.contains(&output.name) || displays.contains(&output.description()))
+        })
+        .map(|output| OutputSettings {
+            name: output.name.clone(),
+            scale: output.scale,
+            mode: output
+                .current_mode
+                .as_ref()
+                .filter(|_| with_modes)
+                .map(ipc::Mode::to_arg),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Function to poll the outputs until each display reports its new scale, giving up after a
+/// few seconds. Displays that are not connected are not waited for.
+fn confirm_scales(scales: &BTreeMap<String, ScaleOption>, epsilon: f32) -> io::Result<()> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        let outputs = ipc::get_outputs()?;
+        let pending = scales.iter().find(|(display, option)| {
+            outputs
+                .iter()
+                .find(|output| &output.name == *display || output.description() == **display)
+                .is_some_and(|output| {
+                    output
+                        .scale
+                        .is_none_or(|scale| (scale - option.scale).abs() > epsilon)
+                })
+        });
+        let Some((display, option)) = pending else {
+            return Ok(());
+        };
+        if Instant::now() >= deadline {
+            return Err(io::Error::other(format!(
+                "{} did not report scale {} within 5 seconds",
+                display, option.scale
+            )));
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Function to pick the option at `index` for every given display, resolved for each one
+fn scales_for_option(
+    settings: &settings::Settings,
+    scale_values: &[ScaleOption],
+    displays: &[String],
+    index: usize,
+    outputs: &[ipc::Output],
+) -> io::Result<BTreeMap<String, ScaleOption>> {
+    displays
+        .iter()
+        .map(|display| {
+            let resolved = resolve_options(settings, scale_values, display, outputs)?;
+            Ok((display.clone(), resolved[index].clone()))
+        })
+        .collect()
+}
+
+/// Function to set the scale of a single display, named by connector, description or alias.
+/// Pinned displays keep their scale until they are unpinned.
+#[cfg_attr(not(feature = "dbus"), allow(dead_code))]
+fn set_output_scale(settings: &settings::Settings, display: &str, scale: f64) -> io::Result<()> {
+    let scale = ScaleOption::try_scale(scale as f32)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let _lock = transaction::write_lock();
+    let config_path = config_path()?;
+    let lines = read_config(&config_path)?;
+    let display = resolve_display(settings, &lines, display);
+    if state::load_state()?.is_pinned(&display) {
+        return Err(io::Error::other(format!(
+            "{} is pinned; unpin it with 'sway-scale-switcher unpin {}' first.",
+            display, display
+        )));
+    }
+    let displays = [display];
+    validate_targets(settings, &lines, &displays)?;
+    let edit_files = editable_config(settings, &config_path, &lines, &displays)?;
+    let [display] = displays;
+    let scales = BTreeMap::from([(display, scale)]);
+    apply_scales(settings, &edit_files, &scales)
+}
+
+/// Function to print a table of the target displays, their scale in the config and the options,
+/// highlighting the option each display currently uses
+fn print_status(settings: &settings::Settings, lines: &[String], json: bool) -> io::Result<()> {
+    let scale_options = find_scale_options(settings, lines)?;
+    let targets = &scale_options.target_displays;
+    let current = if settings.managed {
+        let snippet_path =
+            expanduser(snippet::SNIPPET_PATH).expect("Failed to expand managed snippet path");
+        config::get_output_scales(&snippet::read_snippet(&snippet_path)?, targets)
+    } else {
+        config::get_output_scales(&definitions(settings, lines), targets)
+    };
+    let pinned = state::load_state()?;
+    let epsilon = settings.precision.epsilon;
+    if json {
+        return print_status_json(settings, &scale_options, &current, &pinned);
+    }
+
+    let rows: Vec<Vec<(String, ui::Style)>> = targets
+        .iter()
+        .map(|display| {
+            let scale = current.get(display);
+            let options: Vec<String> = scale_options
+                .scale_values
+                .iter()
+                .map(|option| {
+                    let selected = scale.is_some_and(|scale| {
+                        option.matches(scale.scale, scale.mode.as_deref(), epsilon)
+                    });
+                    if selected {
+                        format!("[{}]", option)
+                    } else {
+                        option.to_string()
+                    }
+                })
+                .collect();
+            let name = if pinned.is_pinned(display) {
+                format!("{} (pinned)", settings.label(display))
+            } else {
+                settings.label(display)
+            };
+            vec![
+                (name, ui::Style::Plain),
+                match scale {
+                    Some(scale) => (scale.to_string(), ui::Style::Green),
+                    None => ("unset".to_string(), ui::Style::Dim),
+                },
+                (options.join(" "), ui::Style::Plain),
+            ]
+        })
+        .collect();
+    ui::print_table(&["OUTPUT", "SCALE", "OPTIONS"], &rows);
+    Ok(())
+}
+
+/// Function to print the status as JSON, with the live state of each display while sway runs
+fn print_status_json(
+    settings: &settings::Settings,
+    scale_options: &ScaleOptions,
+    current: &BTreeMap<String, ScaleOption>,
+    pinned: &state::State,
+) -> io::Result<()> {
+    let live = if ipc::is_running() {
+        ipc::get_outputs().unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let displays: Vec<serde_json::Value> = scale_options
+        .target_displays
+        .iter()
+        .map(|display| {
+            let scale = current.get(display);
+            let option = scale.and_then(|scale| {
+                scale_options.scale_values.iter().position(|option| {
+                    option.matches(
+                        scale.scale,
+                        scale.mode.as_deref(),
+                        settings.precision.epsilon,
+                    )
+                })
+            });
+            let output = live
+                .iter()
+                .find(|output| &output.name == display || &output.description() == display);
+            let mode = output.and_then(|output| output.current_mode.as_ref());
+            serde_json::json!({
+                "name": display,
+                "scale": scale.map(|scale| scale.scale),
+                "option": option,
+                "options": scale_options
+                    .scale_values
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+                "alias": settings.alias_of(display),
+                "pinned": pinned.is_pinned(display),
+                "live": output.map(|output| serde_json::json!({
+                    "active": output.active,
+                    "scale": output.scale,
+                    "mode": mode.map(|mode| format!("{}x{}", mode.width, mode.height)),
+                    "refresh_hz": mode
+                        .filter(|mode| mode.refresh > 0)
+                        .map(|mode| mode.refresh as f64 / 1000.0),
+                    "adaptive_sync": output
+                        .adaptive_sync_status
+                        .as_deref()
+                        .map(|status| status == "enabled"),
+                })),
+            })
+        })
+        .collect();
+    let last_backend = history::read_history()
+        .ok()
+        .and_then(|entries| entries.last().and_then(|entry| entry.backend.clone()));
+    let status = serde_json::json!({
+        "displays": displays,
+        "on_battery": power::on_battery(),
+        "backend": {
+            "chain": settings.backend_chain().iter().map(|backend| backend.name()).collect::<Vec<_>>(),
+            "selected": ipc::backend_name(),
+            "last_change": last_backend,
+        },
+    });
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&status).map_err(io::Error::other)?
+    );
+    Ok(())
+}
+
+/// Function to report the problems `lint` finds in the Scale Options section, fixing the ones it
+/// can when asked. Fails if any problem is left.
+fn lint_config(
+    settings: &settings::Settings,
+    config_path: &str,
+    lines: &[String],
+    fix: bool,
+) -> io::Result<()> {
+    let outputs = config::find_output_names(&definitions(settings, lines));
+    let findings = lint::lint(
+        lines,
+        &outputs,
+        scale_range(settings),
+        settings.precision.epsilon,
+        settings.cycle_order,
+    );
+    let (fixed, left): (Vec<&lint::Finding>, Vec<&lint::Finding>) = findings
+        .iter()
+        .partition(|finding| fix && finding.is_fixable());
+
+    if !fixed.is_empty() {
+        let original = fs::read(config_path)?;
+        let updated = lint::fix(lines, fixed.iter().copied());
+        let mut transaction = transaction::Transaction::new();
+        transaction.stage(
+            Path::new(config_path),
+            &text::encode_lines(&original, &updated),
+        )?;
+        transaction.commit()?;
+        for finding in &fixed {
+            println!(
+                "{}:{}: fixed: {}",
+                config_path,
+                finding.line + 1,
+                finding.message
+            );
+        }
+    }
+    for finding in &left {
+        println!(
+            "{}:{}: {}\n    {} {}",
+            config_path,
+            finding.line + 1,
+            finding.message,
+            if finding.is_fixable() {
+                "fix (lint --fix):"
+            } else {
+                "fix:"
+            },
+            finding.suggestion
+        );
+    }
+
+    match left.len() {
+        0 if findings.is_empty() => {
+            println!("No problems found.");
+            Ok(())
+        }
+        0 => Ok(()),
+        1 => Err(io::Error::other("1 problem found.")),
+        n => Err(io::Error::other(format!("{} problems found.", n))),
+    }
+}
+
+/// Function to list the outputs whose scale in the config differs from the live one, then
+/// optionally write the live scales to the config or apply the config's scales to sway
+fn sync_scales(
+    settings: &settings::Settings,
+    config_path: &str,
+    lines: &[String],
+    from_live: bool,
+    from_config: bool,
+) -> io::Result<()> {
+    if !ipc::is_running() {
+        return Err(io::Error::other(
+            "Comparing with the live scales needs a running sway.",
+        ));
+    }
+    // Output lines may name a display by connector or by description, so both are compared
+    let live: BTreeMap<String, f32> = ipc::get_outputs()?
+        .into_iter()
+        .filter(|output| output.active)
+        .filter_map(|output| {
+            let scale = output.scale?;
+            Some([(output.description(), scale), (output.name, scale)])
+        })
+        .flatten()
+        .collect();
+    let names: Vec<String> = live.keys().cloned().collect();
+    let configured = config::get_output_scales(&definitions(settings, lines), &names);
+
+    let drifted: BTreeMap<String, ScaleOption> = configured
+        .into_iter()
+        .filter(|(display, scale)| (live[display] - scale.scale).abs() > settings.precision.epsilon)
+        .collect();
+    if drifted.is_empty() {
+        println!("The config matches the live scales.");
+        return Ok(());
+    }
+    let rows: Vec<Vec<(String, ui::Style)>> = drifted
+        .iter()
+        .map(|(display, scale)| {
+            vec![
+                (display.clone(), ui::Style::Plain),
+                (scale.to_string(), ui::Style::Red),
+                (
+                    ScaleOption::scale(live[display]).to_string(),
+                    ui::Style::Green,
+                ),
+            ]
+        })
+        .collect();
+    ui::print_table(&["OUTPUT", "CONFIG", "LIVE"], &rows);
+
+    if from_config {
+        return apply_output_settings(&scale_settings(settings, &drifted));
+    }
+    if !from_live {
+        println!(
+            "Run 'sync --from-live' to update the config, or 'sync --from-config' to update sway."
+        );
+        return Ok(());
+    }
+
+    // Sway already shows these scales, so the rewritten config needs no reload
+    let settings = settings::Settings {
+        reload_strategy: ReloadStrategy::None,
+        ..settings.clone()
+    };
+    let displays: Vec<String> = drifted.keys().cloned().collect();
+    let scales: BTreeMap<String, ScaleOption> = displays
+        .iter()
+        .map(|display| (display.clone(), ScaleOption::scale(live[display])))
+        .collect();
+    let edit_files = editable_config(&settings, config_path, lines, &displays)?;
+    apply_scales(&settings, &edit_files, &scales)
+}
+
+/// Function to run a script of steps as one change: every scale and mode is written in a single
+/// transaction and reload, then workspaces are moved and hooks run in order. When a later step
+/// fails, the moved workspaces and the previous scales are put back.
+fn run_script(
+    settings: &settings::Settings,
+    config_path: &str,
+    lines: &[String],
+    script: &Path,
+) -> io::Result<()> {
+    let text = fs::read_to_string(script).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to read {}: {}", script.display(), e),
+        )
+    })?;
+    let mut steps = batch::parse(&text).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: {}", script.display(), e),
+        )
+    })?;
+    for step in &mut steps {
+        match step {
+            batch::Step::Scale { output, .. } | batch::Step::Mode { output, .. } => {
+                *output = resolve_display(settings, lines, output);
+            }
+            batch::Step::Workspace { output, .. } => *output = settings.unalias(output).to_string(),
+            batch::Step::Hook(_) => {}
+        }
+    }
+
+    // Moving workspaces needs sway, so find that out before anything changes
+    let moves_workspaces = steps
+        .iter()
+        .any(|step| matches!(step, batch::Step::Workspace { .. }));
+    let workspaces = if moves_workspaces {
+        if !ipc::is_running() {
+            return Err(io::Error::other("Moving workspaces needs a running sway."));
+        }
+        ipc::get_workspaces()?
+    } else {
+        Vec::new()
+    };
+
+    // Gather the scale and mode steps into one change, starting from what the config sets
+    let mut displays: Vec<String> = Vec::new();
+    for step in &steps {
+        if let batch::Step::Scale { output, .. } | batch::Step::Mode { output, .. } = step {
+            if !displays.contains(output) {
+                displays.push(output.clone());
+            }
+        }
+    }
+    let edit_files = editable_config(settings, config_path, lines, &displays)?;
+    let previous = config::get_output_scales(&joined(&edit_files), &displays);
+
+    // Keep the files as they are and the outputs as sway shows them, so a failed step puts
+    // both back exactly, modes added to output lines included
+    let originals = edit_files
+        .iter()
+        .map(|(path, _)| match fs::read(path) {
+            Ok(contents) => Ok((path.clone(), Some(contents))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok((path.clone(), None)),
+            Err(e) => Err(e),
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    let with_modes = steps.iter().any(|step| match step {
+        batch::Step::Mode { .. } => true,
+        batch::Step::Scale { option, .. } => option.mode.is_some(),
+        _ => false,
+    });
+    let live = if !displays.is_empty() && ipc::is_running() {
+        live_settings(&ipc::get_outputs()?, &displays, with_modes)
+    } else {
+        Vec::new()
+    };
+
+    let mut scales: BTreeMap<String, ScaleOption> = BTreeMap::new();
+    for step in &steps {
+        match step {
+            batch::Step::Scale { output, option } => {
+                // A mode given earlier stays unless the scale brings its own
+                let mut option = option.clone();
+                if option.mode.is_none() {
+                    option.mode = scales.get(output).and_then(|scale| scale.mode.clone());
+                }
+                scales.insert(output.clone(), option);
+            }
+            batch::Step::Mode { output, mode } => {
+                let current = scales
+                    .get(output)
+                    .or_else(|| previous.get(output))
+                    .cloned()
+                    .ok_or_else(|| {
+                        io::Error::other(format!(
+                            "{} has no scale in the config; give it one with a scale step first",
+                            output
+                        ))
+                    })?;
+                scales.insert(
+                    output.clone(),
+                    ScaleOption {
+                        mode: Some(mode.clone()),
+                        ..current
+                    },
+                );
+            }
+            _ => {}
+        }
+    }
+    if !scales.is_empty() {
+        apply_scales(settings, &edit_files, &scales)?;
+    }
+
+    // The remaining steps run in order; remember where moved workspaces came from
+    let mut moved: Vec<(String, String)> = Vec::new();
+    let result = steps.iter().try_for_each(|step| match step {
+        batch::Step::Workspace { workspace, output } => {
+            move_workspace(workspace, output)?;
+            if let Some(from) = workspaces.iter().find(|w| &w.name == workspace) {
+                moved.push((workspace.clone(), from.output.clone()));
+            }
+            Ok(())
+        }
+        batch::Step::Hook(command) => {
+            let status = process::Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .status()?;
+            if status.success() {
+                Ok(())
+            } else {
+                Err(io::Error::other(format!(
+                    "Hook '{}' exited with {}",
+                    command, status
+                )))
+            }
+        }
+        _ => Ok(()),
+    });
+    let Err(e) = result else {
+        return Ok(());
+    };
+
+    warning!("{}; rolling back the script", e);
+    for (workspace, output) in moved.iter().rev() {
+        if let Err(e) = move_workspace(workspace, output) {
+            warning!("Failed to move workspace {} back: {}", workspace, e);
+        }
+    }
+    if !scales.is_empty() {
+        let mut transaction = transaction::Transaction::new();
+        for (path, original) in &originals {
+            if let Some(contents) = original {
+                transaction.stage(Path::new(path), contents)?;
+            }
+        }
+        drop(transaction.commit()?);
+        for (path, original) in &originals {
+            if original.is_none() {
+                let _ = fs::remove_file(path);
+            }
+        }
+        if !live.is_empty() {
+            apply_output_settings(&live)?;
+        }
+        let restored = previous
+            .into_iter()
+            .filter(|(display, _)| scales.contains_key(display))
+            .collect();
+        if let Err(e) = history::record_change(scales, restored, ipc::backend_name()) {
+            warning!("Failed to record change in history: {}", e);
+        }
+    }
+    Err(e)
+}
+
+/// Function to move a workspace to an output
+fn move_workspace(workspace: &str, output: &str) -> io::Result<()> {
+    ipc::run_command(&format!(
+        "workspace {}; move workspace to output {}",
+        ipc::quote(workspace),
+        ipc::quote(output)
+    ))
+}
+
+/// Function to gather the lines output definitions may live in: the config and the files it
+/// includes, plus the managed snippet in managed mode
+fn definitions(settings: &settings::Settings, lines: &[String]) -> Vec<String> {
+    let mut all = lines.to_vec();
+    if let Ok(config_path) = config_path() {
+        for (_, included) in includes::included_files(Path::new(&config_path), lines) {
+            all.extend(included);
+        }
+    }
+    if settings.managed {
+        let snippet_path =
+            expanduser(snippet::SNIPPET_PATH).expect("Failed to expand managed snippet path");
+        if let Ok(snippet_lines) = snippet::read_snippet(&snippet_path) {
+            all.extend(snippet_lines);
+        }
+    }
+    all
+}
+
+/// Function to put the lines of several files one after another, e.g. to look up scales
+/// wherever they are defined
+fn joined(files: &[(String, Vec<String>)]) -> Vec<String> {
+    files
+        .iter()
+        .flat_map(|(_, lines)| lines.iter().cloned())
+        .collect()
+}
+
+/// Function to choose the files the output lines are edited in. In managed mode this is the
+/// snippet owned by the tool, seeded with any target displays it does not mention yet.
+/// Otherwise it is the config, followed by every included file that defines one of the displays.
+fn editable_config(
+    settings: &settings::Settings,
+    config_path: &str,
+    lines: &[String],
+    displays: &[String],
+) -> io::Result<Vec<(String, Vec<String>)>> {
+    if !settings.managed {
+        let mut files = vec![(config_path.to_string(), lines.to_vec())];
+        for (path, included) in includes::included_files(Path::new(config_path), lines) {
+            let defines_display = config::find_output_entries(&included)
+                .iter()
+                .any(|entry| displays.contains(&entry.name));
+            if defines_display {
+                files.push((path.to_string_lossy().to_string(), included));
+            }
+        }
+        return Ok(files);
+    }
+
+    let snippet_path =
+        expanduser(snippet::SNIPPET_PATH).expect("Failed to expand managed snippet path");
+    if !snippet::is_included(lines, &snippet_path) {
+        warning!(
+            "The config does not include the managed snippet. Add this line to it:\n    include {}",
+            snippet::SNIPPET_PATH
+        );
+    }
+
+    let mut snippet_lines = snippet::read_snippet(&snippet_path)?;
+    let existing = config::get_output_scales(&snippet_lines, displays);
+    let current = config::get_output_scales(lines, displays);
+    snippet::seed_outputs(&mut snippet_lines, &existing, displays, &current);
+    Ok(vec![(snippet_path, snippet_lines)])
+}
+
+/// Function to refuse scales that leave an output with a logical resolution below
+/// `min_logical_size`. The mode comes from the option, the output line or the live output, and
+/// displays whose mode none of them gives are let through.
+fn check_logical_size(
+    settings: &settings::Settings,
+    scales: &BTreeMap<String, ScaleOption>,
+    previous: &BTreeMap<String, ScaleOption>,
+) -> io::Result<()> {
+    let Some(minimum) = settings.min_logical_size else {
+        return Ok(());
+    };
+    let live = if ipc::is_running() {
+        ipc::get_outputs().unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    let mut too_small = Vec::new();
+    for (display, scale) in scales {
+        let configured = previous
+            .get(display)
+            .and_then(|scale| scale.mode.as_deref())
+            .and_then(options::mode_resolution);
+        let current = configured.or_else(|| {
+            live.iter()
+                .find(|output| &output.name == display || &output.description() == display)
+                .and_then(|output| output.current_mode.as_ref())
+                .map(|mode| (mode.width, mode.height))
+        });
+        let size = match current {
+            Some(current) => scale.logical_size(current),
+            None if scale.mode.is_some() => scale.logical_size((0, 0)),
+            None => None,
+        };
+        if let Some((width, height)) = size {
+            if !minimum.fits((width, height)) {
+                too_small.push(format!("{} at {} ({}x{})", display, scale, width, height));
+            }
+        }
+    }
+    if too_small.is_empty() {
+        return Ok(());
+    }
+    Err(io::Error::other(format!(
+        "{} would fall below the minimum logical size of {}, where some bars and dialogs no \
+         longer fit. Nothing was changed; pass --force to apply anyway, or lower \
+         min_logical_size in the settings.",
+        too_small.join(", "),
+        minimum
+    )))
+}
+
+/// Function to write new scales for each display into the files holding their output lines,
+/// reload Sway and record the change. The first file is the one validation checks.
+fn apply_scales(
+    settings: &settings::Settings,
+    files: &[(String, Vec<String>)],
+    scales: &BTreeMap<String, ScaleOption>,
+) -> io::Result<()> {
+    // A backend chain with nothing usable must not leave a config edit behind
+    if ipc::chain_is_empty() {
+        return Err(ipc::no_backend());
+    }
+    let _lock = transaction::write_lock();
+    let displays: Vec<String> = scales.keys().cloned().collect();
+    let previous = config::get_output_scales(&joined(files), &displays);
+
+    // Round every scale once, so the config, sway and the history all see the same value
+    let scales: BTreeMap<String, ScaleOption> = scales
+        .iter()
+        .map(|(display, scale)| (display.clone(), scale.rounded(&settings.precision)))
+        .collect();
+    let scales = &scales;
+
+    // Show what changes on each display, aligned by display name
+    let width = scales
+        .keys()
+        .map(|display| display.len())
+        .max()
+        .unwrap_or(0);
+    for (display, scale) in scales {
+        let before = previous
+            .get(display)
+            .map_or_else(|| "unset".to_string(), |scale| scale.to_string());
+        println!(
+            "  {:<width$}  {}",
+            display,
+            ui::change(before, scale),
+            width = width
+        );
+    }
+
+    // The `off` entry disables displays over IPC; their output lines keep the last real scale
+    let (off, scales): (BTreeMap<String, ScaleOption>, BTreeMap<String, ScaleOption>) =
+        scales.clone().into_iter().partition(|(_, scale)| scale.off);
+    let scales = &scales;
+
+    // Scales that squeeze an output below the minimum logical size need --force
+    check_logical_size(settings, scales, &previous)?;
+
+    // Changing the scale in the middle of a screen capture breaks it
+    if (settings.reload_strategy != ReloadStrategy::None || !off.is_empty()) && ipc::is_running() {
+        screencast::check(settings.screencast_guard)?;
+    }
+
+    // A full reload can drop games and video calls out of fullscreen, so ask for --force first
+    if !scales.is_empty()
+        && ipc::is_running()
+        && settings.reload_strategy == ReloadStrategy::Full
+        && !settings.reload_over_fullscreen
+    {
+        let windows = ipc::get_tree()
+            .map(|tree| ipc::fullscreen_windows(&tree))
+            .unwrap_or_default();
+        if !windows.is_empty() {
+            return Err(io::Error::other(format!(
+                "{} {} fullscreen, and reloading sway can take {} out of fullscreen or interrupt \
+                 screen sharing. Nothing was changed; pass --force to reload anyway, or use \
+                 --reload-strategy ipc.",
+                windows.join(", "),
+                if windows.len() == 1 { "is" } else { "are" },
+                if windows.len() == 1 { "it" } else { "them" },
+            )));
+        }
+    }
+
+    // Displays are only turned off once the rest of the change has been written, so a rejected
+    // or failed change leaves them on
+    if !off.is_empty() && !ipc::is_running() {
+        return Err(io::Error::other(
+            "Turning a display off needs a running sway.",
+        ));
+    }
+    let disable = || {
+        off.keys().try_for_each(|display| {
+            ipc::run_command(&format!("output {} disable", ipc::quote(display)))
+        })
+    };
+    if scales.is_empty() {
+        return disable();
+    }
+
+    // Where several lines set a display's scale, say which one changes
+    let displays: Vec<String> = scales.keys().cloned().collect();
+    for (path, lines) in files {
+        for conflict in config::find_conflicts(lines, &displays) {
+            warning!("{}", conflict.describe(path));
+        }
+    }
+
+    // Update the scale in the output lines of every file, one display at a time
+    let updated: Vec<(String, Vec<String>)> = files
+        .iter()
+        .map(|(path, lines)| {
+            let mut updated_lines = lines.clone();
+            for (display, scale) in scales {
+                updated_lines = config::update_scale_in_outputs(
+                    &updated_lines,
+                    std::slice::from_ref(display),
+                    scale,
+                );
+                if let Some(filter) = settings.scale_filter_for(display) {
+                    updated_lines = config::update_scale_filter_in_outputs(
+                        &updated_lines,
+                        std::slice::from_ref(display),
+                        filter.for_scale(scale.scale),
+                    );
+                }
+            }
+            (path.clone(), updated_lines)
+        })
+        .collect();
+
+    // Stage the new files beside the old ones, then let sway check the result first if asked
+    // to; a rejected config is never applied
+    let mut transaction = transaction::Transaction::new();
+    let staged = stage_files(&mut transaction, files, &updated);
+    validate::check(
+        settings.validate,
+        Path::new(&files[0].0),
+        &joined(&updated),
+        scales,
+        settings.precision.epsilon,
+    )?;
+
+    // Write the files; if that is impossible (read-only or network filesystem), fall back to IPC
+    let running = ipc::is_running();
+    // The journal stays until the end of this function, so a run killed before sway has picked
+    // up the change is noticed by the next one
+    let committed = match staged.and_then(|()| transaction.commit()) {
+        Ok(committed) => Some(committed),
+        Err(e) if !running => return Err(e),
+        Err(e) => {
+            warning!(
+                "Could not update the config file: {}. Applying the change through {} only; it \
+                 will be lost on the next reload.",
+                e,
+                ipc::backend_name()
+            );
+            apply_output_settings(&scale_settings(settings, scales))?;
+            None
+        }
+    };
+    disable()?;
+
+    // Displays that were turned off come back on with their new scale
+    let displays: Vec<String> = scales.keys().cloned().collect();
+    for display in disabled_displays(&displays) {
+        if let Err(e) = ipc::run_command(&format!("output {} enable", ipc::quote(&display))) {
+            warning!("Failed to enable {}: {}", display, e);
+        }
+    }
+
+    // Without a session to reload, optionally leave a note to announce when sway next starts
+    let offline = !running
+        && matches!(
+            settings.reload_strategy,
+            ReloadStrategy::Full | ReloadStrategy::Ipc
+        );
+    if offline && settings.announce_offline_changes {
+        for (display, scale) in scales {
+            let text = match previous.get(display) {
+                Some(before) => format!("{}: {} -> {}", display, before, scale),
+                None => format!("{}: {}", display, scale),
+            };
+            if let Err(e) = state::add_pending(&text) {
+                warning!(
+                    "Failed to remember the change for the next sway start: {}",
+                    e
+                );
+            }
+        }
+    }
+
+    // Remember when the change happened, for min_change_interval_ms
+    if settings.min_change_interval_ms.is_some() {
+        if let Err(e) = state::load_state().and_then(|mut state| {
+            state.last_change_ms = Some(clock::now_ms());
+            state::save_state(&state)
+        }) {
+            warning!("Failed to record the time of the change: {}", e);
+        }
+    }
+
+    // Say which backend the change went out through: the config alone when it was written but
+    // the session is not reloaded, otherwise the backend that reached the session
+    let backend =
+        if committed.is_some() && (offline || settings.reload_strategy == ReloadStrategy::None) {
+            "config"
+        } else {
+            ipc::backend_name()
+        };
 
-/// Struct to hold scale options and target displays
-#[derive(Debug, Clone)]
-struct ScaleOptions {
-    target_displays: Vec<String>,
-    scale_values: Vec<f32>,
+    // Record the change so it can be listed or reverted later
+    if let Err(e) = history::record_change(previous, scales.clone(), backend) {
+        warning!("Failed to record change in history: {}", e);
+    }
+
+    // Keep scale-dependent files such as bar styles in step with the new scale
+    templates::render_all(&settings.templates, scales);
+    toolkits::apply(&settings.toolkits, scales, settings.precision.epsilon);
+    if let Err(e) = state::record_scales(
+        scales
+            .iter()
+            .map(|(display, scale)| (display.as_str(), scale.scale)),
+    ) {
+        warning!("Failed to record the new scales: {}", e);
+    }
+
+    // Reload Sway configuration to apply changes
+    println!("Applied with: {}", backend);
+    if committed.is_none() {
+        return Ok(());
+    }
+    if offline {
+        println!("{}", tr!(SwayNotRunning));
+        println!("({})", platform::diagnose(&platform::Environment::detect()));
+        return Ok(());
+    }
+    reload(settings, scales)
 }
 
-fn main() -> io::Result<()> {
-    // Parse command-line arguments using Clap
-    let matches = Command::new("Sway Scale Swapper")
-        .version("1.0")
-        .author("Your Name <youremail@example.com>")
-        .about("Manage scale settings in Sway configuration")
-        .arg(
-            Arg::new("swap")
-                .short('s')
-                .long("swap")
-                .help("Cycle to the next scale option in ascending order")
-                .action(clap::ArgAction::SetTrue),
-        )
-        .get_matches();
+/// Function to apply a rewritten config to the running session using the configured strategy
+fn reload(settings: &settings::Settings, scales: &BTreeMap<String, ScaleOption>) -> io::Result<()> {
+    match settings.reload_strategy {
+        // Other wlroots compositors and remote machines have no copy of this config to reload,
+        // so the changes go out directly
+        ReloadStrategy::Full if ipc::is_wlr_randr() || ipc::is_remote() => {
+            apply_output_settings(&scale_settings(settings, scales))?;
+        }
+        ReloadStrategy::Full => {
+            if ipc::reload().is_ok() {
+                println!("{}", tr!(Reloaded));
+            } else {
+                warning!("Failed to reload Sway configuration.");
+            }
+        }
+        ReloadStrategy::Ipc => {
+            apply_output_settings(&scale_settings(settings, scales))?;
+        }
+        ReloadStrategy::Exec => {
+            let command = settings.reload_command.as_deref().ok_or_else(|| {
+                io::Error::other("The 'exec' reload strategy needs a reload command")
+            })?;
+            let status = process::Command::new("sh")
+                .arg("-c")
+                .arg(command)
+                .status()?;
+            if !status.success() {
+                warning!("Reload command '{}' exited with {}", command, status);
+            }
+        }
+        ReloadStrategy::None => println!("{}", tr!(ReloadSkipped)),
+    }
+    Ok(())
+}
 
-    // Determine if the swap flag is present
-    let swap = matches.get_flag("swap");
+/// Function to stage the first file and every other file whose lines changed. Line endings, a
+/// byte order mark and bytes that are not UTF-8 are kept on every line that was not edited.
+fn stage_files(
+    transaction: &mut transaction::Transaction,
+    files: &[(String, Vec<String>)],
+    updated: &[(String, Vec<String>)],
+) -> io::Result<()> {
+    for (i, ((path, before), (_, after))) in files.iter().zip(updated).enumerate() {
+        if i > 0 && before == after {
+            continue;
+        }
+        let original = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        transaction.stage(Path::new(path), &text::encode_lines(&original, after))?;
+    }
+    Ok(())
+}
 
-    // Expand the user's home directory and locate the Sway config file
-    let config_path = expanduser("~/.config/sway/config").expect("Failed to expand config path");
+/// Function to work out when a change deferred with `--in` or `--at` is due, if it is deferred
+fn scheduled_time(matches: &clap::ArgMatches) -> io::Result<Option<u64>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+    if let Some(delay) = matches.get_one::<String>("in") {
+        let seconds = schedule::parse_duration(delay).map_err(invalid)?;
+        return Ok(Some(clock::now() + seconds));
+    }
+    if let Some(time) = matches.get_one::<String>("at") {
+        let (hours, minutes) = schedule::parse_time(time).map_err(invalid)?;
+        return Ok(Some(schedule::next_at(
+            clock::now(),
+            schedule::local_offset(),
+            hours,
+            minutes,
+        )));
+    }
+    Ok(None)
+}
 
-    // Read all lines from the config file into a vector
-    let file = File::open(&config_path).expect("Failed to open config file");
-    let reader = BufReader::new(file);
-    let lines: Vec<String> = reader.lines().filter_map(Result::ok).collect();
+/// Function to refuse a change that follows the previous one more closely than
+/// `min_change_interval_ms` allows
+fn check_change_interval(settings: &settings::Settings) -> io::Result<()> {
+    let Some(interval) = settings.min_change_interval_ms else {
+        return Ok(());
+    };
+    let Some(last) = state::load_state()?.last_change_ms else {
+        return Ok(());
+    };
+    let elapsed = clock::now_ms().saturating_sub(last);
+    if elapsed < interval {
+        return Err(io::Error::other(format!(
+            "Ignoring this change: the previous one was {} ms ago, and min_change_interval_ms is {}.",
+            elapsed, interval
+        )));
+    }
+    Ok(())
+}
 
-    // Identify the 'Scale Options Start' and 'Scale Options End' indices
-    let scale_start = lines
-        .iter()
-        .position(|line| line.contains("Scale Options Start"))
-        .unwrap_or_else(|| {
-            eprintln!("Error: 'Scale Options Start' marker not found in the config file.");
-            process::exit(1);
-        });
-    let scale_end = lines
+/// Function to drop `--in` and `--at` from the command line, leaving the change to run later
+fn without_schedule_args(args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut skip_value = false;
+    for arg in args {
+        if skip_value {
+            skip_value = false;
+        } else if arg == "--in" || arg == "--at" {
+            skip_value = true;
+        } else if !arg.starts_with("--in=") && !arg.starts_with("--at=") {
+            kept.push(arg);
+        }
+    }
+    kept
+}
+
+/// Function to print the scheduled changes with their local due time
+fn print_schedule(jobs: &[schedule::Job]) {
+    if jobs.is_empty() {
+        println!("No changes are scheduled.");
+        return;
+    }
+    let offset = schedule::local_offset();
+    let rows: Vec<Vec<(String, ui::Style)>> = jobs
         .iter()
-        .position(|line| line.contains("Scale Options End"))
-        .unwrap_or_else(|| {
-            eprintln!("Error: 'Scale Options End' marker not found in the config file.");
-            process::exit(1);
-        });
+        .map(|job| {
+            vec![
+                (job.id.to_string(), ui::Style::Plain),
+                (schedule::format_time(job.due, offset), ui::Style::Green),
+                (job.args.join(" "), ui::Style::Plain),
+            ]
+        })
+        .collect();
+    ui::print_table(&["ID", "AT", "COMMAND"], &rows);
+}
+
+/// Function to switch an output's power state over IPC, toggling it if no state is given
+fn set_power(output: &str, power: Option<Power>) -> io::Result<()> {
+    let power = match power {
+        Some(power) => power,
+        None => {
+            let outputs = ipc::get_outputs()?;
+            let current = outputs.iter().find(|o| o.name == output).ok_or_else(|| {
+                let names: Vec<&str> = outputs.iter().map(|o| o.name.as_str()).collect();
+                io::Error::other(format!(
+                    "Output '{}' not found. Connected outputs: {}",
+                    output,
+                    names.join(", ")
+                ))
+            })?;
+            if current.is_powered() {
+                Power::Off
+            } else {
+                Power::On
+            }
+        }
+    };
 
-    // Extract the scale options section
-    let scale_section = &lines[scale_start..=scale_end];
+    let settings = OutputSettings {
+        name: output.to_string(),
+        power: Some(power),
+        ..Default::default()
+    };
+    apply_output_settings(&[settings])
+}
 
-    // Parse the scale options to get target displays and scale values
-    let scale_options = parse_scale_options(scale_section);
+/// Function to magnify the focused output over IPC, or restore the scale it had before, then
+/// put the pointer on the focused window so it is easy to find at the new size
+fn toggle_zoom(settings: &settings::Settings) -> io::Result<()> {
+    let output = ipc::focused_output()?;
+    let mut state = state::load_state()?;
+    let scale = match state.zoomed.remove(&output.name) {
+        Some(previous) => previous,
+        None => {
+            state
+                .zoomed
+                .insert(output.name.clone(), output.scale.unwrap_or(1.0));
+            settings.zoom_scale.unwrap_or(2.5)
+        }
+    };
+    screencast::check(settings.screencast_guard)?;
+    apply_output_settings(&[OutputSettings {
+        name: output.name.clone(),
+        scale: Some(scale),
+        ..Default::default()
+    }])?;
+    state::save_state(&state)?;
 
-    // Determine the current scale by inspecting the output lines
-    let current_scale = get_current_scale(&lines, &scale_options.target_displays);
+    // The window moved and resized with the new scale, so look it up afterwards
+    let focused = ipc::get_tree().map(|tree| ipc::focused_node(&tree).map(|node| node.rect));
+    if let Ok(Some(rect)) = focused {
+        let command = format!(
+            "seat - cursor set {} {}",
+            rect.x + rect.width / 2,
+            rect.y + rect.height / 2
+        );
+        if let Err(e) = ipc::run_command(&command) {
+            warning!("Failed to move the pointer: {}", e);
+        }
+    }
+    Ok(())
+}
+
+/// Function to turn new scales into per-output settings that can be applied over IPC
+fn scale_settings(
+    settings: &settings::Settings,
+    scales: &BTreeMap<String, ScaleOption>,
+) -> Vec<OutputSettings> {
+    scales
+        .iter()
+        .map(|(display, scale)| OutputSettings {
+            name: display.clone(),
+            scale: Some(scale.scale),
+            mode: scale.mode.clone(),
+            scale_filter: settings.scale_filter_for(display),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Function to switch the target displays, or the focused one, between 8 and 10 bit rendering,
+/// or turn their HDR on or off. The first display decides the direction, so all of them end up
+/// the same.
+fn cycle_render(
+    settings: &settings::Settings,
+    lines: &[String],
+    focused: bool,
+    hdr: bool,
+) -> io::Result<()> {
+    let displays = if focused {
+        vec![focused_display(settings, lines)?]
+    } else {
+        find_scale_options(settings, lines)?.target_displays
+    };
+    let state = state::load_state()?;
+    for display in &displays {
+        if state.is_pinned(display) {
+            println!("{}", tr!(SkippingPinned, display));
+        }
+    }
+    let targets = state.unpinned(&displays);
+    if targets.is_empty() {
+        return Err(io::Error::other(
+            "All target displays are pinned; unpin one with 'sway-scale-switcher unpin <output>'.",
+        ));
+    }
 
-    // Decide on the new scale based on the presence of the swap flag
-    let new_scale = if swap {
-        Some(get_next_scale(&scale_options.scale_values, current_scale))
+    // Sway releases that do not report these settings fall back to what was last applied
+    let outputs = ipc::get_outputs()?;
+    let live = outputs
+        .iter()
+        .find(|output| output.name == targets[0] || output.description() == targets[0]);
+    let change = if hdr {
+        let current = live
+            .and_then(|output| output.hdr)
+            .or(state.hdr.get(&targets[0]).copied())
+            .unwrap_or(false);
+        OutputSettings {
+            hdr: Some(!current),
+            ..Default::default()
+        }
     } else {
-        prompt_user_for_scale(&scale_options.scale_values, current_scale)?
+        let current = live
+            .and_then(|output| output.render_bit_depth)
+            .or(state.bit_depths.get(&targets[0]).copied())
+            .unwrap_or(8);
+        OutputSettings {
+            render_bit_depth: Some(if current == 10 { 8 } else { 10 }),
+            ..Default::default()
+        }
     };
+    let changes: Vec<OutputSettings> = targets
+        .iter()
+        .map(|display| OutputSettings {
+            name: display.clone(),
+            ..change.clone()
+        })
+        .collect();
+    apply_output_settings(&changes)
+}
 
-    // If new_scale is None, the user chose to quit; exit without making changes
-    if let Some(scale) = new_scale {
-        // Update the scale in the output lines for all target displays
-        let updated_lines = update_scale_in_outputs(&lines, &scale_options.target_displays, scale);
+/// Function to apply the profile `matcher::default_profile` picks for this machine and its
+/// connected outputs
+fn apply_default_profile(settings: &settings::Settings) -> io::Result<()> {
+    if let Some(name) = settings
+        .default_profile
+        .as_ref()
+        .filter(|name| !settings.profiles.contains_key(*name))
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("default_profile names '{}', which is not a profile.", name),
+        ));
+    }
+    let outputs = ipc::get_outputs()?;
+    let Some((name, profile, reason)) = matcher::default_profile(
+        &settings.profiles,
+        settings.default_profile.as_deref(),
+        contexts::hostname().as_deref(),
+        &outputs,
+    ) else {
+        println!("No profile fits this machine or its outputs; nothing to apply.");
+        return Ok(());
+    };
+    println!("Applying profile '{}', since {}", name, reason);
+    apply_output_settings(&state::load_state()?.skip_pinned(profile))
+}
 
-        // Write the updated config to a temporary file to ensure atomicity
-        let temp_path = Path::new("/home/fribbit/.config/sway/config_temp");
-        let temp_file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(temp_path)
-            .expect("Failed to create temporary config file");
-        let mut writer = BufWriter::new(temp_file);
+/// Function to apply per-output settings over IPC, one `output` command per display
+fn apply_output_settings(outputs: &[OutputSettings]) -> io::Result<()> {
+    for output in outputs {
+        let command = output.to_command();
+        ipc::run_command(&command)?;
+        println!("Applied: {}", command);
+    }
+    if let Err(e) = state::record_scales(
+        outputs
+            .iter()
+            .filter_map(|output| Some((output.name.as_str(), output.scale?))),
+    ) {
+        warning!("Failed to record the new scales: {}", e);
+    }
 
-        for line in updated_lines {
-            writeln!(writer, "{}", line)?;
+    // Remember bit depth and HDR, which not every sway release reports back
+    if outputs
+        .iter()
+        .any(|output| output.render_bit_depth.is_some() || output.hdr.is_some())
+    {
+        let mut state = state::load_state()?;
+        for output in outputs {
+            if let Some(depth) = output.render_bit_depth {
+                state.bit_depths.insert(output.name.clone(), depth);
+            }
+            if let Some(hdr) = output.hdr {
+                state.hdr.insert(output.name.clone(), hdr);
+            }
         }
+        state::save_state(&state)?;
+    }
+    Ok(())
+}
+
+/// Function to apply output settings over IPC and record the scales they set in the history,
+/// for changes the daemon makes on its own
+fn apply_recorded_settings(outputs: &[OutputSettings]) -> io::Result<()> {
+    // What the scales were before, for reverting
+    let live = ipc::get_outputs().unwrap_or_default();
+    let previous: BTreeMap<String, ScaleOption> = outputs
+        .iter()
+        .filter(|output| output.scale.is_some())
+        .filter_map(|output| {
+            let live = live.iter().find(|live| live.name == output.name)?;
+            let mode = output
+                .mode
+                .as_ref()
+                .and(live.current_mode.as_ref())
+                .map(|mode| mode.to_arg());
+            Some((output.name.clone(), ScaleOption::new(live.scale?, mode)))
+        })
+        .collect();
 
-        // Rename the temporary file to replace the old configuration
-        fs::rename(temp_path, &config_path).expect("Failed to replace the original config file");
+    apply_output_settings(outputs)?;
 
-        // Reload Sway configuration to apply changes
-        if process::Command::new("swaymsg")
-            .arg("reload")
-            .spawn()
-            .is_ok()
-        {
-            println!("Successfully reloaded Sway configuration.");
-        } else {
-            eprintln!("Failed to reload Sway configuration.");
+    let scales: BTreeMap<String, ScaleOption> = outputs
+        .iter()
+        .filter_map(|output| {
+            let scale = ScaleOption::new(output.scale?, output.mode.clone());
+            Some((output.name.clone(), scale))
+        })
+        .collect();
+    if !scales.is_empty() {
+        if let Err(e) = history::record_change(previous, scales, ipc::backend_name()) {
+            warning!("Failed to record change in history: {}", e);
         }
-    } else {
-        println!("No changes made. Exiting.");
     }
+    Ok(())
+}
+
+/// Function to resolve an output named before the settings are loaded, e.g. to `pin`; a broken
+/// settings file leaves the name as it is
+fn unalias(name: &str) -> String {
+    settings::load_settings().map_or_else(
+        |_| name.to_string(),
+        |settings| settings.unalias(name).to_string(),
+    )
+}
+
+/// Function to turn an output named on the command line into the display the config knows. An
+/// alias becomes the output it stands for, and while sway runs, a connector or description
+/// becomes the other one when only that has an output line.
+fn resolve_display(settings: &settings::Settings, lines: &[String], name: &str) -> String {
+    let name = settings.unalias(name);
+    let known = config::find_output_names(&definitions(settings, lines));
+    if known.iter().any(|known| known == name) || !ipc::is_running() {
+        return name.to_string();
+    }
+    ipc::get_outputs()
+        .unwrap_or_default()
+        .into_iter()
+        .find(|output| output.name == name || output.description() == name)
+        .and_then(|output| {
+            [output.name.clone(), output.description()]
+                .into_iter()
+                .find(|id| known.contains(id))
+        })
+        .unwrap_or_else(|| name.to_string())
+}
 
+/// Function to print an output's scale for scripts: the one recorded after the last change, or
+/// the live one while sway runs
+fn print_current_scale(output: &str, dpi: bool) -> io::Result<()> {
+    let scale = match state::recorded_scale(output)? {
+        Some(scale) => Some(scale),
+        None if ipc::is_running() => ipc::get_outputs()?
+            .into_iter()
+            .find(|live| live.name == output || live.description() == output)
+            .and_then(|live| live.scale),
+        None => None,
+    };
+    let scale =
+        scale.ok_or_else(|| io::Error::other(format!("No scale is known for {}", output)))?;
+    if dpi {
+        // Wayland's logical pixel is taken as 96 DPI, as X11 and most image tools do
+        println!("{}", (scale * 96.0).round());
+    } else {
+        println!("{}", scale);
+    }
     Ok(())
 }
 
+/// Function to load the tool's settings, letting the command line flags override them. The
+/// daemon calls it again whenever it reloads the settings.
+fn load_settings(matches: &clap::ArgMatches) -> io::Result<settings::Settings> {
+    settings::load_settings().map(|mut settings| {
+        if matches.get_flag("no-reload") {
+            settings.reload_strategy = ReloadStrategy::None;
+        }
+        if let Some(&strategy) = matches.get_one::<ReloadStrategy>("reload-strategy") {
+            settings.reload_strategy = strategy;
+        }
+        if let Some(command) = matches.get_one::<String>("reload-command") {
+            settings.reload_command = Some(command.clone());
+        }
+        if matches.get_flag("force") {
+            settings.reload_over_fullscreen = true;
+            settings.min_logical_size = None;
+            if settings.screencast_guard == screencast::Guard::Refuse {
+                settings.screencast_guard = screencast::Guard::Warn;
+            }
+        }
+        if let Some(context) = matches.get_one::<String>("context") {
+            settings.context = Some(context.clone());
+        }
+        if let Some(&validation) = matches.get_one::<validate::Validation>("validate") {
+            settings.validate = validation;
+        }
+        settings
+    })
+}
+
 /// Function to expand the user's home directory
 fn expanduser(path: &str) -> Option<String> {
-    if path.starts_with('~') {
+    if let Some(rest) = path.strip_prefix('~') {
         if let Some(home_dir) = dirs::home_dir() {
             let mut expanded = home_dir.to_string_lossy().to_string();
-            expanded.push_str(&path[1..]);
+            expanded.push_str(rest);
             Some(expanded)
         } else {
             None
@@ -125,185 +3621,538 @@ fn expanduser(path: &str) -> Option<String> {
     }
 }
 
-/// Function to parse the Scale Options section
-fn parse_scale_options(lines: &[String]) -> ScaleOptions {
-    let mut target_displays = Vec::new();
-    let mut scale_values = Vec::new();
+/// Function to pick the first usable entry of the backend chain in the settings: sway IPC while
+/// sway runs, wlr-randr on other compositors, and editing the config alone otherwise. The
+/// settings are read leniently here; a broken file is reported once they are loaded properly.
+fn select_backend() {
+    let settings = settings::load_settings().unwrap_or_default();
+    let chain = settings.backend_chain();
+    let selected = chain.iter().copied().find(|backend| match backend {
+        settings::ApplyBackend::Ipc => ipc::is_running(),
+        settings::ApplyBackend::WlrRandr => platform::on_path("wlr-randr") && wlr::is_running(),
+        settings::ApplyBackend::Config => true,
+    });
+    ipc::follow_chain(chain, selected);
+}
+
+/// Function to say up front why sway is out of reach when the command only works in a session,
+/// instead of leaving it to a failing swaymsg later
+fn check_session(matches: &clap::ArgMatches) -> io::Result<()> {
+    let session_only = matches!(
+        matches.subcommand_name(),
+        Some(
+            "power"
+                | "a11y-zoom"
+                | "layout"
+                | "arrange"
+                | "daemon"
+                | "subscribe"
+                | "apply-default"
+                | "sync"
+                | "bench"
+        )
+    ) || ["on", "off", "toggle-power"]
+        .iter()
+        .any(|flag| matches.contains_id(flag));
+    if !session_only || ipc::is_running() {
+        return Ok(());
+    }
+    warning!(
+        "This command needs a running sway, but {}.",
+        platform::diagnose(&platform::Environment::detect())
+    );
+    eprintln!("{}", platform::offline_hint(Path::new(&config_path()?)));
+    Ok(())
+}
 
-    // Regular expressions to extract target displays and scale options
-    let target_regex = Regex::new(r"# Target Display = (.+)").unwrap();
-    let scale_regex = Regex::new(r"# Scale Options = (.+)").unwrap();
+/// Function to pick the sway instance to talk to: the `--socket` flag, then `SWAYSOCK`, then
+/// the live sockets in the runtime directory, asking which one to use if there are several
+fn select_socket(socket: Option<&std::path::PathBuf>) -> io::Result<()> {
+    if let Some(socket) = socket {
+        ipc::set_socket(socket.clone());
+        return Ok(());
+    }
+    if std::env::var_os("SWAYSOCK").is_some() {
+        return Ok(());
+    }
 
-    for line in lines {
-        if let Some(captures) = target_regex.captures(line) {
-            let display = captures.get(1).unwrap().as_str().trim().to_string();
-            target_displays.push(display);
-        } else if let Some(captures) = scale_regex.captures(line) {
-            let scales_str = captures.get(1).unwrap().as_str();
-            scale_values = scales_str
-                .split(',')
-                .filter_map(|s| s.trim().parse::<f32>().ok())
-                .collect();
+    let Some(runtime_dir) = dirs::runtime_dir() else {
+        return Ok(());
+    };
+    let mut sockets = ipc::discover_sockets(&runtime_dir);
+    if sockets.len() <= 1 {
+        if let Some(socket) = sockets.pop() {
+            ipc::set_socket(socket);
         }
+        return Ok(());
     }
 
-    // Error handling if no target displays or scale options are found
-    if target_displays.is_empty() {
-        eprintln!("Error: No target displays found in Scale Options section.");
-        process::exit(1);
+    let listing: Vec<String> = sockets
+        .iter()
+        .enumerate()
+        .map(|(i, socket)| format!("{}. {}", i + 1, socket.display()))
+        .collect();
+    if !io::stdin().is_terminal() {
+        return Err(io::Error::other(format!(
+            "SWAYSOCK is not set and several sway instances are running; pick one with --socket:\n{}",
+            listing.join("\n")
+        )));
     }
 
-    if scale_values.is_empty() {
-        eprintln!("Error: No scale options found in Scale Options section.");
-        process::exit(1);
+    println!("Several sway instances are running:");
+    for line in &listing {
+        println!("{}", line);
+    }
+    println!("Enter the number of the one to use:");
+    loop {
+        let Some(input) = read_line()? else {
+            return Err(io::Error::other("No sway instance selected."));
+        };
+        if let Ok(choice) = input.parse::<usize>() {
+            if choice > 0 && choice <= sockets.len() {
+                ipc::set_socket(sockets.swap_remove(choice - 1));
+                return Ok(());
+            }
+        }
+        println!(
+            "Invalid selection. Please enter a number between 1 and {}.",
+            sockets.len()
+        );
     }
+}
 
-    ScaleOptions {
-        target_displays,
-        scale_values,
+/// Function to read one line of input, returning None once standard input is closed
+fn read_line() -> io::Result<Option<String>> {
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        return Ok(None);
     }
+    Ok(Some(input.trim().to_string()))
 }
 
-/// Function to determine the current scale by inspecting the output lines for target displays.
-fn get_current_scale(lines: &[String], target_displays: &[String]) -> f32 {
-    // Regular expression to match uncommented output lines and extract display name and scale
-    let output_regex = Regex::new(r#"^output\s+"([^"]+)"\s+scale\s+([0-9.]+)"#).unwrap();
+/// Function to prompt the user to pick which target displays to change, defaulting to all of them.
+/// Returns None if the user chose to quit.
+fn prompt_user_for_displays(
+    settings: &settings::Settings,
+    target_displays: &[String],
+    current: &BTreeMap<String, ScaleOption>,
+) -> io::Result<Option<Vec<String>>> {
+    println!("{}", tr!(TargetDisplays));
+    for (i, display) in target_displays.iter().enumerate() {
+        match current.get(display) {
+            Some(scale) => println!(
+                "{}",
+                tr!(
+                    DisplayWithScale,
+                    i + 1,
+                    settings.label(display),
+                    ui::paint(scale, ui::Style::Bold)
+                )
+            ),
+            None => println!(
+                "{}",
+                tr!(DisplayWithoutScale, i + 1, settings.label(display))
+            ),
+        }
+    }
+    println!("{}", tr!(AllDisplays));
+    println!("{}", tr!(QuitOption));
+    println!("{}", tr!(EnterDisplays));
 
-    let mut scales = Vec::new();
+    loop {
+        let Some(input) = read_line()? else {
+            return Ok(None);
+        };
 
-    for line in lines {
-        if let Some(captures) = output_regex.captures(line) {
-            let display = captures.get(1).unwrap().as_str().trim().to_string();
-            let scale: f32 = captures
-                .get(2)
-                .unwrap()
-                .as_str()
-                .trim()
-                .parse()
-                .unwrap_or(1.0);
+        if input.is_empty() || input.eq_ignore_ascii_case("a") {
+            return Ok(Some(target_displays.to_vec()));
+        }
+        if input.eq_ignore_ascii_case("q") {
+            return Ok(None);
+        }
 
-            if target_displays.contains(&display) {
-                scales.push(scale);
-            }
+        // Parse every number, keeping the order of the targets and ignoring repeats
+        let choices: Option<Vec<usize>> = input
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                part.parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0 && n <= target_displays.len())
+            })
+            .collect();
+        if let Some(choices) = choices.filter(|choices| !choices.is_empty()) {
+            let selected: Vec<String> = target_displays
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| choices.contains(&(i + 1)))
+                .map(|(_, display)| display.clone())
+                .collect();
+            println!("{}", tr!(SelectedDisplays, selected.join(", ")));
+            return Ok(Some(selected));
         }
+        println!("{}", tr!(InvalidDisplays, target_displays.len()));
     }
+}
 
-    if scales.is_empty() {
-        eprintln!("Warning: No current scale found for target displays. Defaulting to first scale option.");
-        // Default to the first scale option
-        1.0
-    } else {
-        // Ensure all scales are the same; if not, notify the user
-        let first_scale = scales[0];
-        if scales.iter().all(|&s| (s - first_scale).abs() < 1e-6) {
-            first_scale
-        } else {
-            eprintln!(
-                "Warning: Multiple scales found for target displays. Using the first scale: {}",
-                first_scale
-            );
-            first_scale
+/// Function to apply a chosen scale over IPC without touching the config, then let the user keep
+/// it, try another option or go back to the scales the displays had. Returns the option to
+/// write, or None if the user went back.
+fn preview_scale(
+    settings: &settings::Settings,
+    scale_values: &[ScaleOption],
+    displays: &[String],
+    outputs: &[ipc::Output],
+    choice: Option<usize>,
+) -> io::Result<Option<usize>> {
+    let Some(index) = choice else {
+        return Ok(None);
+    };
+
+    // Remember the live scales, and the modes too if any option changes them
+    let with_modes = scale_values.iter().any(|option| option.mode.is_some());
+    let original = live_settings(&ipc::get_outputs()?, displays, with_modes);
+    screencast::check(settings.screencast_guard)?;
+
+    // Quitting, running out of input and failing partway all put the displays back
+    match try_scales(settings, scale_values, displays, outputs, index) {
+        Ok(Some(index)) => Ok(Some(index)),
+        Ok(None) => {
+            apply_output_settings(&original)?;
+            println!("{}", tr!(PreviewReverted));
+            Ok(None)
+        }
+        Err(e) => {
+            if let Err(revert) = apply_output_settings(&original) {
+                warning!("Failed to revert the preview: {}", revert);
+            }
+            Err(e)
         }
     }
 }
 
-/// Function to get the next scale in ascending order, cycling back to the first if at the end.
-fn get_next_scale(scale_values: &[f32], current_scale: f32) -> f32 {
-    let mut sorted_scales = scale_values.to_vec();
-    sorted_scales.sort_by(|a, b| a.partial_cmp(b).unwrap());
+/// Function to apply the option at `index` for `preview_scale` and let the user try others,
+/// returning the one they keep, or None if they quit
+fn try_scales(
+    settings: &settings::Settings,
+    scale_values: &[ScaleOption],
+    displays: &[String],
+    outputs: &[ipc::Output],
+    mut index: usize,
+) -> io::Result<Option<usize>> {
+    loop {
+        // Turning a display off cannot be undone from a prompt on it, so it is not previewed
+        let scales = scales_for_option(settings, scale_values, displays, index, outputs)?;
+        if scales.values().any(|scale| scale.off) {
+            return Ok(Some(index));
+        }
+        apply_output_settings(&scale_settings(settings, &scales))?;
+        println!("{}", tr!(PreviewScale, scale_values[index]));
 
-    // Define a small epsilon for floating-point comparison
-    let epsilon = 1e-6;
+        loop {
+            let input = read_line()?;
+            match input.as_deref() {
+                Some("") => return Ok(Some(index)),
+                None | Some("q") | Some("Q") => return Ok(None),
+                Some(input) => match input.parse::<usize>() {
+                    Ok(choice) if choice > 0 && choice <= scale_values.len() => {
+                        index = choice - 1;
+                        break;
+                    }
+                    _ => println!("{}", tr!(InvalidScale, scale_values.len())),
+                },
+            }
+        }
+    }
+}
 
-    // Find the index of current_scale in sorted_scales
-    let mut index = None;
-    for (i, &scale) in sorted_scales.iter().enumerate() {
-        if (scale - current_scale).abs() < epsilon {
-            index = Some(i);
-            break;
+/// Function to let the user arrange the active outputs and change their scales on a map drawn
+/// to scale, trying the result out over IPC and saving it as a profile
+fn arrange_outputs(settings: &settings::Settings) -> io::Result<()> {
+    let outputs = ipc::get_outputs()?;
+    let original: Vec<layout::Placement> = outputs
+        .iter()
+        .filter_map(layout::Placement::from_output)
+        .collect();
+    if original.is_empty() {
+        return Err(io::Error::other(
+            "No active output with a known mode to arrange.",
+        ));
+    }
+    let mut placements = original.clone();
+    let (min, max) = scale_range(settings);
+    let usage = "Commands: place OUTPUT left-of|right-of|above|below OTHER, move OUTPUT X Y, \
+                 scale OUTPUT SCALE, apply, revert, print, save NAME, q";
+    let show = |placements: &[layout::Placement]| {
+        for line in layout::render(placements, 60) {
+            println!("{}", line);
+        }
+    };
+    show(&placements);
+    println!("{}", usage);
+
+    while let Some(input) = read_line()? {
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let output = |name: &str| {
+            placements
+                .iter()
+                .position(|placement| placement.name == name)
+                .ok_or_else(|| format!("'{}' is not on the map", name))
+        };
+        let changed: Result<bool, String> = match words.as_slice() {
+            [] => continue,
+            ["q" | "Q" | "quit"] => break,
+            ["place", name, side, other] => side
+                .parse()
+                .and_then(|side| layout::place(&mut placements, name, side, other))
+                .map(|()| true),
+            ["move", name, x, y] => match (output(name), x.parse(), y.parse()) {
+                (Ok(i), Ok(x), Ok(y)) => {
+                    placements[i].x = x;
+                    placements[i].y = y;
+                    Ok(true)
+                }
+                (Err(e), _, _) => Err(e),
+                _ => Err(format!("'{} {}' is not a position", x, y)),
+            },
+            ["scale", name, scale] => match (output(name), scale.parse::<f32>()) {
+                (Ok(i), Ok(scale)) if (min..=max).contains(&scale) => {
+                    placements[i].scale = settings.precision.round(scale);
+                    Ok(true)
+                }
+                (Err(e), _) => Err(e),
+                _ => Err(format!(
+                    "The scale must be a number from {} to {}",
+                    min, max
+                )),
+            },
+            // A failed change is reported and the editing goes on
+            ["apply"] => {
+                layout::normalize(&mut placements);
+                screencast::check(settings.screencast_guard)
+                    .and_then(|()| apply_output_settings(&placements_settings(&placements)))
+                    .map(|()| false)
+                    .map_err(|e| e.to_string())
+            }
+            ["revert"] => {
+                placements = original.clone();
+                apply_output_settings(&placements_settings(&original))
+                    .map(|()| true)
+                    .map_err(|e| e.to_string())
+            }
+            ["print"] => {
+                layout::normalize(&mut placements);
+                for placement in &placements {
+                    println!("{}", placement.to_settings().to_command());
+                }
+                Ok(false)
+            }
+            ["save", name] => {
+                layout::normalize(&mut placements);
+                save_profile(settings, name, &placements)
+                    .map(|()| false)
+                    .map_err(|e| e.to_string())
+            }
+            _ => Err(usage.to_string()),
+        };
+        match changed {
+            Ok(true) => show(&placements),
+            Ok(false) => {}
+            Err(message) => println!("{}", message),
         }
     }
+    Ok(())
+}
 
-    if let Some(i) = index {
-        // Move to the next index, wrapping around if necessary
-        let next_index = (i + 1) % sorted_scales.len();
-        let next_scale = sorted_scales[next_index];
-        println!("Swapping scale from {} to {}", current_scale, next_scale);
-        next_scale
-    } else {
-        // If current_scale is not found, default to the first scale
-        let first_scale = sorted_scales[0];
-        println!(
-            "Current scale {} not found in scale options. Using first scale {}",
-            current_scale, first_scale
-        );
-        first_scale
+/// Function to turn placements into the settings that apply them
+fn placements_settings(placements: &[layout::Placement]) -> Vec<OutputSettings> {
+    placements
+        .iter()
+        .map(layout::Placement::to_settings)
+        .collect()
+}
+
+/// Function to append an arrangement to the settings file as a new profile
+fn save_profile(
+    settings: &settings::Settings,
+    name: &str,
+    placements: &[layout::Placement],
+) -> io::Result<()> {
+    if settings.profiles.contains_key(name) {
+        return Err(io::Error::other(format!(
+            "A profile named '{}' already exists in the settings file.",
+            name
+        )));
+    }
+    let path = settings::settings_path()
+        .ok_or_else(|| io::Error::other("Failed to locate the settings file"))?;
+    let mut contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    let quote = |value: &str| toml::Value::String(value.to_string()).to_string();
+    contents.push_str(&format!("\n[profiles.{}]\noutputs = [\n", quote(name)));
+    for placement in placements {
+        contents.push_str(&format!(
+            "    {{ name = {}, scale = {}, position = [{}, {}] }},\n",
+            quote(&placement.name),
+            placement.scale,
+            placement.x,
+            placement.y
+        ));
     }
+    contents.push_str("]\n");
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut transaction = transaction::Transaction::new();
+    transaction.stage(&path, contents.as_bytes())?;
+    transaction.commit()?;
+    println!("Saved profile '{}' to {}", name, path.display());
+    Ok(())
+}
+
+/// Function to get the smallest and largest scale a user may type in
+fn scale_range(settings: &settings::Settings) -> (f32, f32) {
+    (
+        settings.min_scale.unwrap_or(0.5),
+        settings.max_scale.unwrap_or(4.0),
+    )
+}
+
+/// Function to label an option in the prompt: the option as written, its value if it is an
+/// expression, and the logical size it gives the output if the output's mode is known
+fn option_label(
+    option: &ScaleOption,
+    resolved: &ScaleOption,
+    output: Option<&ipc::Output>,
+) -> String {
+    let mut label = option.to_string();
+    if option.expr.is_some() {
+        label.push_str(&format!(" (= {})", resolved));
+    }
+    let logical = output
+        .and_then(|output| output.current_mode.as_ref())
+        .and_then(|mode| resolved.logical_size((mode.width, mode.height)));
+    if let Some((width, height)) = logical {
+        label.push_str(&format!(" -> {}x{} logical", width, height));
+    }
+    label
 }
 
 /// Function to prompt the user to select a scale from available options, with an option to quit.
-fn prompt_user_for_scale(scale_values: &[f32], current_scale: f32) -> io::Result<Option<f32>> {
-    println!("Current active scale: {}", current_scale);
-    println!("Available scale options:");
-    for (i, scale) in scale_values.iter().enumerate() {
-        println!("{}. {}", i + 1, scale);
+/// A scale typed with a decimal point, e.g. `1.37`, is checked against the allowed range, rounded
+/// and added to the end of the options. Returns the index of the chosen option.
+fn prompt_user_for_scale(
+    settings: &settings::Settings,
+    scale_values: &mut Vec<ScaleOption>,
+    resolved: &mut Vec<ScaleOption>,
+    current_scale: &ScaleOption,
+    output: Option<&ipc::Output>,
+) -> io::Result<Option<usize>> {
+    let epsilon = settings.precision.epsilon;
+    println!(
+        "{}",
+        tr!(CurrentScale, ui::paint(current_scale, ui::Style::Bold))
+    );
+    println!("{}", tr!(AvailableOptions));
+    for (i, (scale, value)) in scale_values.iter().zip(resolved.iter()).enumerate() {
+        let label = option_label(scale, value, output);
+        if value.matches(current_scale.scale, current_scale.mode.as_deref(), epsilon) {
+            println!(
+                "{}",
+                tr!(CurrentOption, i + 1, ui::paint(label, ui::Style::Green))
+            );
+        } else {
+            println!("{}. {}", i + 1, label);
+        }
     }
-    println!("Q. Quit without making changes");
-    println!("Enter the number of the scale you want to apply or 'Q' to quit:");
+    println!("{}", tr!(QuitOption));
+    println!("{}", tr!(EnterScale));
 
     loop {
-        let input: String = read!();
-        let trimmed = input.trim();
+        let Some(input) = read_line()? else {
+            return Ok(None);
+        };
 
-        if trimmed.eq_ignore_ascii_case("q") {
-            println!("Quitting without making changes.");
+        if input.eq_ignore_ascii_case("q") {
+            println!("{}", tr!(Quitting));
             return Ok(None);
         }
 
-        if let Ok(choice) = trimmed.parse::<usize>() {
+        if let Ok(choice) = input.parse::<usize>() {
             if choice > 0 && choice <= scale_values.len() {
-                let selected_scale = scale_values[choice - 1];
-                println!("Selected scale: {}", selected_scale);
-                return Ok(Some(selected_scale));
+                println!("{}", tr!(SelectedScale, resolved[choice - 1]));
+                return Ok(Some(choice - 1));
             }
         }
-        println!(
-            "Invalid selection. Please enter a number between 1 and {}, or 'Q' to quit.",
-            scale_values.len()
-        );
+
+        // A number with a decimal point is a scale of its own rather than the number of an option
+        if let Some(scale) = input
+            .contains('.')
+            .then(|| input.parse::<f32>().ok())
+            .flatten()
+        {
+            let (min, max) = scale_range(settings);
+            if !(min..=max).contains(&scale) {
+                println!("{}", tr!(ScaleOutOfRange, scale, min, max));
+                continue;
+            }
+            let rounded = settings.precision.round(scale);
+            if rounded != scale {
+                println!("{}", tr!(ScaleRounded, scale, rounded));
+            }
+
+            // A scale that is already an option picks that option
+            let existing = resolved.iter().position(|value| {
+                !value.off && value.mode.is_none() && value.matches(rounded, None, epsilon)
+            });
+            let index = existing.unwrap_or_else(|| {
+                scale_values.push(ScaleOption::scale(rounded));
+                resolved.push(ScaleOption::scale(rounded));
+                scale_values.len() - 1
+            });
+            println!("{}", tr!(SelectedScale, resolved[index]));
+            return Ok(Some(index));
+        }
+        println!("{}", tr!(InvalidScale, scale_values.len()));
     }
 }
 
-/// Function to update the scale in the output lines for all target displays
-fn update_scale_in_outputs(
-    lines: &[String],
-    target_displays: &[String],
-    new_scale: f32,
-) -> Vec<String> {
-    // Regular expression to match uncommented output lines and capture parts
-    let output_regex = Regex::new(r#"^output\s+"([^"]+)"\s+scale\s+([0-9.]+)"#).unwrap();
-
-    lines
-        .iter()
-        .map(|line| {
-            if let Some(captures) = output_regex.captures(line) {
-                let display_name = captures.get(1).unwrap().as_str().trim().to_string();
-                // let _current_scale: f32 = captures.get(2).unwrap().as_str().trim().parse().unwrap_or(1.0);
-
-                if target_displays.contains(&display_name) {
-                    // Update the scale
-                    // Preserve any additional parameters after the scale
-                    let rest_start = captures.get(2).unwrap().end();
-                    let rest = &line[rest_start..];
-                    format!("output \"{}\" scale {}{}", display_name, new_scale, rest)
-                } else {
-                    // Not a target display; leave the line unchanged
-                    line.clone()
-                }
-            } else {
-                // Not an output line; leave it unchanged
-                line.clone()
-            }
-        })
-        .collect()
+/// Function to offer to add a scale typed at the prompt to the `# Scale Options` line, so cycling
+/// reaches it too. Options from the settings or an option group are left alone.
+fn offer_custom_scale(
+    settings: &settings::Settings,
+    config_path: &str,
+    shown: &[ScaleOption],
+    option: &ScaleOption,
+) -> io::Result<()> {
+    let original = fs::read(config_path)?;
+    let lines = text::decode_lines(&original);
+    let from_config = settings.scales.is_empty()
+        && config::find_scale_options(&lines).is_ok_and(|options| options.scale_values == shown);
+    let updated = match config::add_scale_option(&lines, option) {
+        Some(updated) if from_config => updated,
+        _ => return Ok(()),
+    };
+
+    println!("{}", tr!(SaveCustomScale, option));
+    let answer = read_line()?.unwrap_or_default().to_lowercase();
+    // Yes in the languages with a catalog: yes, sí, ja
+    if !answer.starts_with(['y', 's', 'j']) {
+        return Ok(());
+    }
+    let mut transaction = transaction::Transaction::new();
+    transaction.stage(
+        Path::new(config_path),
+        &text::encode_lines(&original, &updated),
+    )?;
+    transaction.commit()?;
+    println!("{}", tr!(SavedCustomScale, option));
+    Ok(())
 }
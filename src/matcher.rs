@@ -0,0 +1,235 @@
+use crate::ipc::Output;
+use crate::settings::{OutputSettings, Profile};
+use std::collections::BTreeMap;
+
+/// Function to check whether a profile's output entry refers to a connected output, by connector
+/// name (`eDP-1`), by description (`Make Model Serial`), or by the wildcard `*`
+fn output_matches(criteria: &str, output: &Output) -> bool {
+    criteria == "*" || criteria == output.name || criteria == output.description()
+}
+
+/// Function to pair each profile entry with a distinct connected output, covering every output.
+/// Returns the profile's settings addressed to the matched connectors, or None if it does not fit.
+pub fn match_profile(profile: &Profile, outputs: &[Output]) -> Option<Vec<OutputSettings>> {
+    if profile.outputs.len() != outputs.len() {
+        return None;
+    }
+
+    let mut assigned = vec![None; profile.outputs.len()];
+    if !assign(profile, outputs, 0, &mut assigned) {
+        return None;
+    }
+
+    Some(
+        profile
+            .outputs
+            .iter()
+            .zip(assigned)
+            .map(|(settings, index)| OutputSettings {
+                name: outputs[index.unwrap()].name.clone(),
+                ..settings.clone()
+            })
+            .collect(),
+    )
+}
+
+/// Function to find an assignment for the entries from `entry` onwards by backtracking, so a
+/// wildcard never takes an output that a more specific entry needs
+fn assign(
+    profile: &Profile,
+    outputs: &[Output],
+    entry: usize,
+    assigned: &mut Vec<Option<usize>>,
+) -> bool {
+    if entry == profile.outputs.len() {
+        return true;
+    }
+
+    for (index, output) in outputs.iter().enumerate() {
+        if assigned.contains(&Some(index)) || !output_matches(&profile.outputs[entry].name, output)
+        {
+            continue;
+        }
+        assigned[entry] = Some(index);
+        if assign(profile, outputs, entry + 1, assigned) {
+            return true;
+        }
+        assigned[entry] = None;
+    }
+    false
+}
+
+/// Function to check that a profile is meant for this machine; profiles without a hostname are
+/// meant for every machine
+fn host_matches(profile: &Profile, hostname: Option<&str>) -> bool {
+    profile
+        .hostname
+        .as_ref()
+        .is_none_or(|expected| hostname == Some(expected.as_str()))
+}
+
+/// Function to pick the first automatic profile, by name, that matches the connected outputs
+pub fn find_profile<'a>(
+    profiles: &'a BTreeMap<String, Profile>,
+    hostname: Option<&str>,
+    outputs: &[Output],
+) -> Option<(&'a str, Vec<OutputSettings>)> {
+    profiles
+        .iter()
+        .filter(|(_, profile)| profile.auto && host_matches(profile, hostname))
+        .find_map(|(name, profile)| Some((name.as_str(), match_profile(profile, outputs)?)))
+}
+
+/// Function to address a profile's entries to the connected outputs they name, leaving out the
+/// ones that are not connected. `*` stands for every output no other entry names.
+pub fn connected_entries(profile: &Profile, outputs: &[Output]) -> Vec<OutputSettings> {
+    let named = |output: &Output| {
+        profile
+            .outputs
+            .iter()
+            .any(|entry| entry.name != "*" && output_matches(&entry.name, output))
+    };
+    profile
+        .outputs
+        .iter()
+        .flat_map(|entry| {
+            outputs
+                .iter()
+                .filter(|output| {
+                    if entry.name == "*" {
+                        !named(output)
+                    } else {
+                        output_matches(&entry.name, output)
+                    }
+                })
+                .map(|output| OutputSettings {
+                    name: output.name.clone(),
+                    ..entry.clone()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Function to choose the profile to apply at login: an automatic profile that fits the
+/// connected outputs, else the first profile for this hostname, else the named default.
+/// Returns the profile's name, its settings and why it was chosen.
+pub fn default_profile<'a>(
+    profiles: &'a BTreeMap<String, Profile>,
+    default: Option<&'a str>,
+    hostname: Option<&str>,
+    outputs: &[Output],
+) -> Option<(&'a str, Vec<OutputSettings>, &'static str)> {
+    if let Some((name, settings)) = find_profile(profiles, hostname, outputs) {
+        return Some((name, settings, "it matches the connected outputs"));
+    }
+    if let Some((name, profile)) = profiles
+        .iter()
+        .find(|(_, profile)| profile.hostname.is_some() && host_matches(profile, hostname))
+    {
+        return Some((
+            name,
+            connected_entries(profile, outputs),
+            "it is for this hostname",
+        ));
+    }
+    let (name, profile) = profiles.get_key_value(default?)?;
+    Some((
+        name,
+        connected_entries(profile, outputs),
+        "it is the default profile",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(name: &str, make: &str, model: &str, serial: &str) -> Output {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "make": make,
+            "model": model,
+            "serial": serial,
+        }))
+        .unwrap()
+    }
+
+    fn profile(entries: &[&str]) -> Profile {
+        Profile {
+            auto: true,
+            hostname: None,
+            outputs: entries
+                .iter()
+                .map(|name| OutputSettings {
+                    name: name.to_string(),
+                    scale: Some(1.5),
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn matches_by_name_and_description() {
+        let outputs = [
+            output("eDP-1", "BOE", "0x095F", "Unknown"),
+            output("DP-3", "Dell Inc.", "DELL U2720Q", "ABC123"),
+        ];
+        let settings = match_profile(
+            &profile(&["Dell Inc. DELL U2720Q ABC123", "eDP-1"]),
+            &outputs,
+        )
+        .unwrap();
+        let names: Vec<&str> = settings.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["DP-3", "eDP-1"]);
+        assert_eq!(settings[0].scale, Some(1.5));
+    }
+
+    #[test]
+    fn requires_exactly_the_connected_outputs() {
+        let outputs = [output("eDP-1", "", "", ""), output("HDMI-A-1", "", "", "")];
+        assert!(match_profile(&profile(&["eDP-1"]), &outputs).is_none());
+        assert!(match_profile(&profile(&["eDP-1", "DP-1"]), &outputs).is_none());
+        assert!(match_profile(&profile(&["*", "eDP-1"]), &outputs).is_some());
+    }
+
+    #[test]
+    fn only_automatic_profiles_are_considered() {
+        let outputs = [output("eDP-1", "", "", "")];
+        let mut profiles = BTreeMap::from([
+            ("a-manual".to_string(), profile(&["eDP-1"])),
+            ("b-laptop".to_string(), profile(&["eDP-1"])),
+        ]);
+        profiles.get_mut("a-manual").unwrap().auto = false;
+        let (name, _) = find_profile(&profiles, None, &outputs).unwrap();
+        assert_eq!(name, "b-laptop");
+    }
+
+    #[test]
+    fn picks_the_login_profile_by_outputs_then_hostname_then_default() {
+        let outputs = [output("eDP-1", "", "", ""), output("DP-3", "", "", "")];
+        let mut profiles = BTreeMap::from([
+            ("desk".to_string(), profile(&["eDP-1", "DP-3"])),
+            ("laptop".to_string(), profile(&["eDP-1", "HDMI-A-1"])),
+            ("work".to_string(), profile(&["DP-3", "*"])),
+        ]);
+        profiles.get_mut("work").unwrap().hostname = Some("office".to_string());
+
+        let (name, _, _) = default_profile(&profiles, None, None, &outputs).unwrap();
+        assert_eq!(name, "desk");
+
+        profiles.get_mut("desk").unwrap().auto = false;
+        let (name, settings, _) =
+            default_profile(&profiles, Some("laptop"), Some("office"), &outputs).unwrap();
+        assert_eq!(name, "work");
+        let names: Vec<&str> = settings.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["DP-3", "eDP-1"]);
+
+        let (name, settings, _) =
+            default_profile(&profiles, Some("laptop"), Some("home"), &outputs).unwrap();
+        assert_eq!(name, "laptop");
+        assert_eq!(settings.len(), 1);
+        assert!(default_profile(&profiles, Some("missing"), None, &outputs).is_none());
+    }
+}
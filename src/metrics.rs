@@ -0,0 +1,84 @@
+use crate::daemon::DaemonState;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Function to serve the Prometheus text format on `/metrics` from a background thread
+pub fn serve(addr: SocketAddr, state: Arc<Mutex<DaemonState>>) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    thread::spawn(move || {
+        for stream in listener.incoming().map_while(Result::ok) {
+            if let Err(e) = handle_request(stream, &state) {
+                warning!("Failed to answer metrics request: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Function to answer a single HTTP request
+fn handle_request(mut stream: TcpStream, state: &Mutex<DaemonState>) -> io::Result<()> {
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+
+    let (status, body) = if path == "/metrics" {
+        ("200 OK", render(&state.lock().unwrap()))
+    } else {
+        ("404 Not Found", "Not found\n".to_string())
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+/// Function to render the daemon state in the Prometheus text exposition format
+fn render(state: &DaemonState) -> String {
+    let mut body = String::new();
+
+    body.push_str("# HELP sway_scale_switcher_output_scale Current scale of each output.\n");
+    body.push_str("# TYPE sway_scale_switcher_output_scale gauge\n");
+    for (output, scale) in &state.scales {
+        let _ = writeln!(
+            body,
+            "sway_scale_switcher_output_scale{{output=\"{}\"}} {}",
+            escape_label(output),
+            scale
+        );
+    }
+
+    body.push_str(
+        "# HELP sway_scale_switcher_changes_total Scale changes observed by the daemon.\n",
+    );
+    body.push_str("# TYPE sway_scale_switcher_changes_total counter\n");
+    let _ = writeln!(body, "sway_scale_switcher_changes_total {}", state.changes);
+
+    if let Some(timestamp) = state.last_change {
+        body.push_str(
+            "# HELP sway_scale_switcher_last_change_timestamp_seconds Time of the last observed scale change.\n",
+        );
+        body.push_str("# TYPE sway_scale_switcher_last_change_timestamp_seconds gauge\n");
+        let _ = writeln!(
+            body,
+            "sway_scale_switcher_last_change_timestamp_seconds {}",
+            timestamp
+        );
+    }
+
+    body
+}
+
+/// Function to escape a label value as required by the exposition format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
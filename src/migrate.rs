@@ -0,0 +1,272 @@
+use crate::{config, settings, state, text, transaction};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Version of the settings format this build writes; files without a `version` are version 1
+pub const SETTINGS_VERSION: u32 = 2;
+/// Version of the state file this build writes; files without a `version` are version 1
+pub const STATE_VERSION: u32 = 2;
+
+/// Changes to the settings text between versions, in order: entry `n` upgrades version `n + 1`.
+/// They work on the text so comments and formatting survive a migration.
+const SETTINGS_STEPS: [fn(&str) -> String; 1] = [
+    // Version 2 added `version`, `targets` and `scales`; older files set none of them
+    |contents| contents.to_string(),
+];
+
+/// Changes to the state between versions, in order: entry `n` upgrades version `n + 1`
+const STATE_STEPS: [fn(&mut serde_json::Value); 1] = [
+    // Version 2 added `version` and fields that all default to empty
+    |_| {},
+];
+
+/// Function to read the version a settings file declares
+pub fn settings_version(contents: &str) -> Result<u32, String> {
+    let table: toml::Table = toml::from_str(contents).map_err(|e| e.to_string())?;
+    match table.get("version") {
+        None => Ok(1),
+        Some(toml::Value::Integer(version)) if *version >= 1 => Ok(*version as u32),
+        Some(version) => Err(format!("Invalid version {}", version)),
+    }
+}
+
+/// Function to set the top-level `version` key, replacing the one already there
+fn set_version(contents: &str, version: u32) -> String {
+    let line = format!("version = {}", version);
+    let mut lines: Vec<String> = contents.lines().map(str::to_string).collect();
+    let top_level = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .unwrap_or(lines.len());
+    match lines[..top_level]
+        .iter()
+        .position(|existing| existing.split('=').next().map(str::trim) == Some("version"))
+    {
+        Some(index) => lines[index] = line,
+        None => lines.insert(0, line),
+    }
+    lines.join("\n") + "\n"
+}
+
+/// Function to bring a settings file up to the current version, refusing files written by a
+/// newer build. Files already current are returned unchanged.
+pub fn migrate_settings(contents: &str) -> Result<String, String> {
+    let version = settings_version(contents)?;
+    if version > SETTINGS_VERSION {
+        return Err(format!(
+            "The settings have version {}, but this build only reads up to version {}; \
+             upgrade sway-scale-switcher.",
+            version, SETTINGS_VERSION
+        ));
+    }
+    let mut contents = contents.to_string();
+    for step in &SETTINGS_STEPS[version as usize - 1..] {
+        contents = step(&contents);
+    }
+    if version < SETTINGS_VERSION {
+        contents = set_version(&contents, SETTINGS_VERSION);
+    }
+    Ok(contents)
+}
+
+/// Function to bring a parsed state file up to the current version, refusing files written by
+/// a newer build
+pub fn migrate_state(value: &mut serde_json::Value) -> Result<(), String> {
+    let version = match value.get("version") {
+        None => 1,
+        Some(version) => version
+            .as_u64()
+            .filter(|&version| version >= 1)
+            .ok_or_else(|| format!("Invalid version {}", version))? as u32,
+    };
+    if version > STATE_VERSION {
+        return Err(format!(
+            "The state has version {}, but this build only reads up to version {}; \
+             upgrade sway-scale-switcher.",
+            version, STATE_VERSION
+        ));
+    }
+    for step in &STATE_STEPS[version as usize - 1..] {
+        step(value);
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.insert("version".to_string(), STATE_VERSION.into());
+    }
+    Ok(())
+}
+
+/// Function to move the marker section from the sway config lines into the settings text as
+/// `targets` and `scales`. Returns None if the config has no marker section.
+fn move_markers(lines: &[String], settings: &str) -> Result<Option<(Vec<String>, String)>, String> {
+    let start = lines
+        .iter()
+        .position(|line| line.contains("Scale Options Start"));
+    let end = lines
+        .iter()
+        .position(|line| line.contains("Scale Options End"));
+    let (Some(start), Some(end)) = (start, end) else {
+        return Ok(None);
+    };
+    let options = config::find_scale_options(lines)?;
+
+    let quoted = |items: Vec<String>| {
+        items
+            .into_iter()
+            .map(|item| toml::Value::String(item).to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let keys = format!(
+        "targets = [{}]\nscales = [{}]\n",
+        quoted(options.target_displays),
+        quoted(
+            options
+                .scale_values
+                .iter()
+                .map(|option| option.to_string())
+                .collect()
+        )
+    );
+
+    // The keys go right after `version`, ahead of any table
+    let settings = match settings.split_once('\n') {
+        Some((version, rest)) => format!("{}\n{}{}", version, keys, rest),
+        None => format!("{}\n{}", settings, keys),
+    };
+    let mut remaining = lines[..start].to_vec();
+    remaining.extend_from_slice(&lines[end + 1..]);
+    Ok(Some((remaining, settings)))
+}
+
+/// Function to upgrade the settings and state files to the current versions and move the
+/// scale options section out of the sway config into the settings, all or nothing
+pub fn run(config_path: &str) -> io::Result<()> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let settings_path = settings::settings_path()
+        .ok_or_else(|| io::Error::other("Could not determine config directory"))?;
+    let original = match fs::read_to_string(&settings_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let version = settings_version(&original)
+        .map_err(|e| invalid(format!("{}: {}", settings_path.display(), e)))?;
+    let mut migrated = migrate_settings(&original).map_err(invalid)?;
+    let mut changes = Vec::new();
+    if version < SETTINGS_VERSION && !original.trim().is_empty() {
+        changes.push(format!(
+            "Upgraded {} from version {} to {}",
+            settings_path.display(),
+            version,
+            SETTINGS_VERSION
+        ));
+    }
+
+    // Settings that already list targets keep them; the markers are then left for the user
+    let config_bytes = fs::read(config_path)?;
+    let lines = text::decode_lines(&config_bytes);
+    let parsed = settings::parse_settings(&migrated).map_err(invalid)?;
+    let mut new_config = None;
+    if parsed.targets.is_empty() {
+        if let Some((remaining, settings)) = move_markers(&lines, &migrated).map_err(invalid)? {
+            changes.push(format!(
+                "Moved the scale options section from {} to {}",
+                config_path,
+                settings_path.display()
+            ));
+            migrated = settings;
+            new_config = Some(remaining);
+        }
+    } else if config::find_scale_options(&lines).is_ok() {
+        warning!(
+            "The settings already set targets, so the scale options section in {} is unused; remove it.",
+            config_path
+        );
+    }
+
+    // Without a settings file there is nothing to upgrade unless the markers move into one
+    let mut transaction = transaction::Transaction::new();
+    if migrated != original && (!original.trim().is_empty() || new_config.is_some()) {
+        if let Some(parent) = settings_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        transaction.stage(&settings_path, migrated.as_bytes())?;
+    }
+    if let Some(remaining) = &new_config {
+        transaction.stage(
+            Path::new(config_path),
+            &text::encode_lines(&config_bytes, remaining),
+        )?;
+    }
+    transaction.commit()?;
+
+    // Loading the state migrates it; saving writes it back in the current version
+    if let Some(path) = state::state_path().filter(|path| path.exists()) {
+        let value: serde_json::Value =
+            serde_json::from_slice(&fs::read(&path)?).map_err(|e| invalid(e.to_string()))?;
+        if value.get("version").and_then(|version| version.as_u64()) != Some(STATE_VERSION.into()) {
+            state::save_state(&state::load_state()?)?;
+            changes.push(format!(
+                "Upgraded {} to version {}",
+                path.display(),
+                STATE_VERSION
+            ));
+        }
+    }
+
+    if changes.is_empty() {
+        println!("Everything is already in the current format.");
+    }
+    for change in changes {
+        println!("{}", change);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_settings_and_refuses_newer_ones() {
+        let old = "# Laptop\nmanaged = true\n\n[profiles.desk]\noutputs = []\n";
+        let migrated = migrate_settings(old).unwrap();
+        assert_eq!(migrated, format!("version = {}\n{}", SETTINGS_VERSION, old));
+        assert_eq!(migrate_settings(&migrated).unwrap(), migrated);
+        assert!(migrate_settings("version = 99\n")
+            .unwrap_err()
+            .contains("upgrade"));
+
+        let mut state = serde_json::json!({ "pinned": ["eDP-1"] });
+        migrate_state(&mut state).unwrap();
+        assert_eq!(state["version"], STATE_VERSION);
+        assert!(migrate_state(&mut serde_json::json!({ "version": 99 })).is_err());
+    }
+
+    #[test]
+    fn moves_the_marker_section_into_the_settings() {
+        let lines: Vec<String> = [
+            "set $mod Mod4",
+            "### Scale Options Start",
+            "# Target Display = eDP-1",
+            "# Scale Options = 1.0, 1.5@2560x1440",
+            "### Scale Options End",
+            "output eDP-1 scale 1.5",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+        let (remaining, settings) = move_markers(&lines, "version = 2\n[profiles]\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(remaining, ["set $mod Mod4", "output eDP-1 scale 1.5"]);
+        assert_eq!(
+            settings,
+            "version = 2\ntargets = [\"eDP-1\"]\nscales = [\"1\", \"1.5@2560x1440\"]\n[profiles]\n"
+        );
+        let parsed = settings::parse_settings(&settings).unwrap();
+        assert_eq!(parsed.targets, ["eDP-1"]);
+        assert_eq!(parsed.scales.len(), 2);
+    }
+}
@@ -0,0 +1,313 @@
+use crate::ipc::{Mode, Output, Workspace};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// A description of the mock's outputs: either what `swaymsg -t get_outputs -r` prints, or an
+/// object that also lists workspaces
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Seed {
+    Outputs(Vec<Output>),
+    Session {
+        outputs: Vec<Output>,
+        #[serde(default)]
+        workspaces: Vec<Workspace>,
+    },
+}
+
+/// An in-memory stand-in for sway that keeps track of the outputs it is told to change
+#[derive(Debug, Clone)]
+pub struct Compositor {
+    outputs: Vec<Output>,
+    workspaces: Vec<Workspace>,
+}
+
+/// Function to describe the output the mock has when no seed file is given
+fn default_output() -> Output {
+    Output {
+        name: "eDP-1".to_string(),
+        make: "Mock".to_string(),
+        model: "Display".to_string(),
+        serial: "0".to_string(),
+        active: true,
+        power: Some(true),
+        dpms: None,
+        scale: Some(1.0),
+        current_mode: Some(Mode {
+            width: 1920,
+            height: 1080,
+            refresh: 60000,
+        }),
+        modes: vec![
+            Mode {
+                width: 1920,
+                height: 1080,
+                refresh: 60000,
+            },
+            Mode {
+                width: 1280,
+                height: 720,
+                refresh: 60000,
+            },
+        ],
+        render_bit_depth: Some(8),
+        hdr: Some(false),
+        adaptive_sync_status: Some("disabled".to_string()),
+        scale_filter: Some("nearest".to_string()),
+        subpixel_hinting: Some("rgb".to_string()),
+        max_render_time: Some(0),
+        rect: Default::default(),
+    }
+}
+
+/// Function to split a sway command into words, honouring double quotes and backslashes
+pub fn words(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quoted => word.extend(chars.next()),
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+impl Compositor {
+    /// Function to build the mock from a JSON description of its outputs, or with a single
+    /// 1920x1080 eDP-1 at scale 1 if there is none
+    pub fn load(seed: Option<&Path>) -> io::Result<Compositor> {
+        let (outputs, workspaces) = match seed {
+            Some(path) => {
+                let seed = serde_json::from_slice(&fs::read(path)?).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Failed to parse {}: {}", path.display(), e),
+                    )
+                })?;
+                match seed {
+                    Seed::Outputs(outputs) => (outputs, Vec::new()),
+                    Seed::Session {
+                        outputs,
+                        workspaces,
+                    } => (outputs, workspaces),
+                }
+            }
+            None => (vec![default_output()], Vec::new()),
+        };
+
+        // Without workspaces, each active output shows one and the first has focus
+        let workspaces = if workspaces.is_empty() {
+            outputs
+                .iter()
+                .filter(|output| output.active)
+                .enumerate()
+                .map(|(i, output)| Workspace {
+                    name: (i + 1).to_string(),
+                    output: output.name.clone(),
+                    visible: true,
+                    focused: i == 0,
+                })
+                .collect()
+        } else {
+            workspaces
+        };
+        Ok(Compositor {
+            outputs,
+            workspaces,
+        })
+    }
+
+    pub fn outputs(&self) -> Vec<Output> {
+        self.outputs.clone()
+    }
+
+    pub fn workspaces(&self) -> Vec<Workspace> {
+        self.workspaces.clone()
+    }
+
+    /// Function to print a command and apply the `output` commands among them to the model.
+    /// Other commands are accepted without effect.
+    pub fn run_command(&mut self, command: &str) -> io::Result<()> {
+        println!("[mock] {}", command);
+        let words = words(command);
+        if words.first().map(String::as_str) != Some("output") || words.len() < 2 {
+            return Ok(());
+        }
+        let output = self
+            .outputs
+            .iter_mut()
+            .find(|output| output.name == words[1] || output.description() == words[1])
+            .ok_or_else(|| io::Error::other(format!("Unknown output {}", words[1])))?;
+
+        let mut rest = words[2..].iter().map(String::as_str);
+        while let Some(word) = rest.next() {
+            match word {
+                "scale" => {
+                    let scale = rest.next().and_then(|scale| scale.parse().ok());
+                    output.scale = Some(scale.ok_or_else(|| {
+                        io::Error::other(format!("Invalid scale in '{}'", command))
+                    })?);
+                }
+                "mode" | "resolution" | "res" => {
+                    let mode = rest.next().unwrap_or_default();
+                    let (size, rate) = mode.split_once('@').unwrap_or((mode, ""));
+                    let size = size.split_once('x').and_then(|(width, height)| {
+                        Some((width.parse().ok()?, height.parse().ok()?))
+                    });
+                    let (width, height) = size.ok_or_else(|| {
+                        io::Error::other(format!("Invalid mode in '{}'", command))
+                    })?;
+                    let refresh = rate
+                        .trim_end_matches("Hz")
+                        .parse::<f32>()
+                        .map_or(0, |rate| (rate * 1000.0).round() as i32);
+                    output.current_mode = Some(Mode {
+                        width,
+                        height,
+                        refresh,
+                    });
+                }
+                "render_bit_depth" => {
+                    let depth = rest.next().and_then(|depth| depth.parse().ok());
+                    output.render_bit_depth = Some(depth.ok_or_else(|| {
+                        io::Error::other(format!("Invalid render_bit_depth in '{}'", command))
+                    })?);
+                }
+                "hdr" => match rest.next() {
+                    Some("on") => output.hdr = Some(true),
+                    Some("off") => output.hdr = Some(false),
+                    _ => return Err(io::Error::other(format!("Invalid hdr in '{}'", command))),
+                },
+                "adaptive_sync" => match rest.next() {
+                    Some("on" | "enable") => output.adaptive_sync_status = Some("enabled".into()),
+                    Some("off" | "disable") => {
+                        output.adaptive_sync_status = Some("disabled".into())
+                    }
+                    _ => {
+                        return Err(io::Error::other(format!(
+                            "Invalid adaptive_sync in '{}'",
+                            command
+                        )))
+                    }
+                },
+                "scale_filter" => match rest.next() {
+                    Some(filter @ ("linear" | "nearest" | "smart")) => {
+                        output.scale_filter = Some(filter.into())
+                    }
+                    _ => {
+                        return Err(io::Error::other(format!(
+                            "Invalid scale_filter in '{}'",
+                            command
+                        )))
+                    }
+                },
+                "subpixel" => match rest.next() {
+                    Some(layout @ ("rgb" | "bgr" | "vrgb" | "vbgr" | "none")) => {
+                        output.subpixel_hinting = Some(layout.into())
+                    }
+                    _ => {
+                        return Err(io::Error::other(format!(
+                            "Invalid subpixel in '{}'",
+                            command
+                        )))
+                    }
+                },
+                "max_render_time" => match rest.next() {
+                    Some("off") => output.max_render_time = Some(0),
+                    Some(ms) => {
+                        output.max_render_time = Some(ms.parse().map_err(|_| {
+                            io::Error::other(format!("Invalid max_render_time in '{}'", command))
+                        })?)
+                    }
+                    None => {
+                        return Err(io::Error::other(format!(
+                            "Invalid max_render_time in '{}'",
+                            command
+                        )))
+                    }
+                },
+                "enable" => output.active = true,
+                "disable" => output.active = false,
+                "power" | "dpms" => match rest.next() {
+                    Some("on") => output.power = Some(true),
+                    Some("off") => output.power = Some(false),
+                    Some("toggle") => output.power = Some(!output.is_powered()),
+                    _ => return Err(io::Error::other(format!("Invalid power in '{}'", command))),
+                },
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Function to reload like sway does: every known output takes the scale its `output` line
+    /// in the config gives it
+    pub fn reload(&mut self) {
+        println!("[mock] reload");
+        let Ok(config_path) = crate::config_path() else {
+            return;
+        };
+        let Ok(lines) = crate::read_config(&config_path) else {
+            return;
+        };
+        let mut all = lines.clone();
+        for (_, included) in crate::includes::included_files(Path::new(&config_path), &lines) {
+            all.extend(included);
+        }
+        let names: Vec<String> = self
+            .outputs
+            .iter()
+            .map(|output| output.name.clone())
+            .collect();
+        let scales = crate::config::get_output_scales(&all, &names);
+        for output in &mut self.outputs {
+            if let Some(scale) = scales.get(&output.name) {
+                output.scale = Some(scale.scale);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_output_commands_to_the_model() {
+        let mut compositor = Compositor::load(None).unwrap();
+        compositor
+            .run_command("output \"eDP-1\" mode 2560x1440@60Hz scale 1.5 power off")
+            .unwrap();
+        let output = &compositor.outputs()[0];
+        assert_eq!(output.scale, Some(1.5));
+        assert_eq!(
+            output.current_mode.as_ref().map(|mode| mode.width),
+            Some(2560)
+        );
+        assert_eq!(
+            output.current_mode.as_ref().map(|mode| mode.to_arg()),
+            Some("2560x1440@60.000Hz".to_string())
+        );
+        assert!(!output.is_powered());
+        assert!(compositor.run_command("output HDMI-A-1 scale 2").is_err());
+        assert_eq!(compositor.workspaces()[0].output, "eDP-1");
+        assert_eq!(
+            words(r#"output "a \"b\"" enable"#),
+            ["output", "a \"b\"", "enable"]
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use crate::expr::{ScaleExpr, Vars};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// How scales are rounded when they are written, and how close two scales must be to count as equal
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Precision {
+    /// Decimal places kept when a scale is written to the config or sent to sway
+    pub decimals: u32,
+    /// Largest difference between two scales that are still considered the same
+    pub epsilon: f32,
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Precision {
+            decimals: 3,
+            epsilon: 0.0005,
+        }
+    }
+}
+
+/// The order cycling steps through the scale options in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CycleOrder {
+    /// From the smallest scale to the largest
+    #[default]
+    Ascending,
+    /// From the largest scale to the smallest
+    Descending,
+    /// In the order the options are written
+    AsWritten,
+}
+
+impl Precision {
+    /// Function to round a scale to the configured number of decimals, dropping float
+    /// artifacts such as `1.2000001`
+    pub fn round(&self, scale: f32) -> f32 {
+        let factor = 10f64.powi(self.decimals as i32);
+        ((scale as f64 * factor).round() / factor) as f32
+    }
+
+    /// Function to check whether two scales are equal within the configured epsilon
+    pub fn same(&self, a: f32, b: f32) -> bool {
+        (a - b).abs() <= self.epsilon
+    }
+}
+
+/// A single entry in the Scale Options list: a scale, optionally paired with an output mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScaleOption {
+    pub scale: f32,
+    pub mode: Option<String>,
+    /// Set for entries like `auto+0.25` whose scale depends on the output; `scale` is
+    /// meaningless until the option has been resolved
+    pub expr: Option<ScaleExpr>,
+    /// Set for the `off` entry, which disables the display instead of scaling it
+    pub off: bool,
+}
+
+impl ScaleOption {
+    /// Function to create an option that only changes the scale
+    pub fn scale(scale: f32) -> Self {
+        ScaleOption {
+            scale,
+            mode: None,
+            expr: None,
+            off: false,
+        }
+    }
+
+    /// Function to create an option that only changes the scale, refusing scales that are not
+    /// positive finite numbers
+    pub fn try_scale(scale: f32) -> Result<Self, String> {
+        if !scale.is_finite() || scale <= 0.0 {
+            return Err(format!(
+                "invalid scale '{}': must be a positive number",
+                scale
+            ));
+        }
+        Ok(ScaleOption::scale(scale))
+    }
+
+    /// Function to create an option with a fixed scale and an optional mode
+    pub fn new(scale: f32, mode: Option<String>) -> Self {
+        ScaleOption {
+            scale,
+            mode,
+            expr: None,
+            off: false,
+        }
+    }
+
+    /// Function to create the `off` entry; its scale of 0 sorts it before every real scale
+    pub fn off() -> Self {
+        ScaleOption {
+            scale: 0.0,
+            mode: None,
+            expr: None,
+            off: true,
+        }
+    }
+
+    /// Function to turn an expression option into a fixed one using an output's variables
+    pub fn resolve(&self, vars: &Vars) -> Result<ScaleOption, String> {
+        match &self.expr {
+            Some(expr) => Ok(ScaleOption::new(expr.eval(vars)?, self.mode.clone())),
+            None => Ok(self.clone()),
+        }
+    }
+
+    /// Function to resolve the option with a plain number read as an apparent size, the way
+    /// `match_physical_size` reads it: `1.25` gives the scale at which the output shows elements
+    /// 25% larger than at 96 DPI, so they look the same size on every output
+    pub fn resolve_apparent(&self, vars: &Vars) -> Result<ScaleOption, String> {
+        if self.expr.is_some() || self.off {
+            return self.resolve(vars);
+        }
+        let scale = ((self.scale * vars.native) * 1000.0).round() / 1000.0;
+        Ok(ScaleOption::new(scale, self.mode.clone()))
+    }
+
+    /// Function to round the scale to the configured number of decimals
+    pub fn rounded(&self, precision: &Precision) -> Self {
+        ScaleOption {
+            scale: precision.round(self.scale),
+            ..self.clone()
+        }
+    }
+
+    /// Function to work out the logical size the option gives an output, from the option's own
+    /// mode or else the output's current one. None for the `off` entry.
+    pub fn logical_size(&self, current: (i32, i32)) -> Option<(i32, i32)> {
+        if self.off || self.scale <= 0.0 {
+            return None;
+        }
+        let (width, height) = match &self.mode {
+            Some(mode) => mode_resolution(mode)?,
+            None => current,
+        };
+        Some((
+            (width as f32 / self.scale).round() as i32,
+            (height as f32 / self.scale).round() as i32,
+        ))
+    }
+
+    /// Function to check whether this option describes the given scale and mode, treating
+    /// scales within `epsilon` of each other as equal. Options without a mode match any mode,
+    /// and the `off` entry matches no scale at all.
+    pub fn matches(&self, scale: f32, mode: Option<&str>, epsilon: f32) -> bool {
+        if self.off || (self.scale - scale).abs() > epsilon {
+            return false;
+        }
+        match (&self.mode, mode) {
+            // A mode without a refresh rate matches the same resolution at any rate
+            (Some(own), Some(mode)) => {
+                mode == own || (!own.contains('@') && mode.split('@').next() == Some(own))
+            }
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+}
+
+impl FromStr for ScaleOption {
+    type Err = String;
+
+    /// Parses entries like `1.5` or `1.0@3840x2160` (a refresh rate may follow, e.g. `@60Hz`).
+    /// The scale may also be an expression such as `auto+0.25` or `native/2`, and `off`
+    /// disables the display.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("off") {
+            return Ok(ScaleOption::off());
+        }
+        let (scale_str, mode) = match s.split_once('@') {
+            Some((scale, mode)) => (scale.trim(), Some(mode.trim())),
+            None => (s, None),
+        };
+
+        // Anything that is not a plain number is parsed as an expression
+        let (scale, expr) = match scale_str.parse::<f32>() {
+            Ok(scale) => (ScaleOption::try_scale(scale)?.scale, None),
+            Err(_) => {
+                let expr = ScaleExpr::parse(scale_str)
+                    .map_err(|e| format!("invalid scale '{}': {}", scale_str, e))?;
+                if expr.has_variables() {
+                    (0.0, Some(expr))
+                } else {
+                    // Constant expressions like `3/2` can be evaluated right away
+                    (expr.eval(&Vars::from_dpi(96.0))?, None)
+                }
+            }
+        };
+
+        let mode = match mode {
+            Some(mode) if is_valid_mode(mode) => Some(mode.to_string()),
+            Some(mode) => return Err(format!("invalid mode '{}'", mode)),
+            None => None,
+        };
+
+        Ok(ScaleOption {
+            scale,
+            mode,
+            expr,
+            off: false,
+        })
+    }
+}
+
+impl fmt::Display for ScaleOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.off {
+            return write!(f, "off");
+        }
+        match &self.expr {
+            Some(expr) => write!(f, "{}", expr)?,
+            None => write!(f, "{}", self.scale)?,
+        }
+        match &self.mode {
+            Some(mode) => write!(f, "@{}", mode),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Scale-only options serialize as plain numbers so existing history entries stay readable
+impl Serialize for ScaleOption {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match (&self.mode, &self.expr) {
+            (None, None) if !self.off => serializer.serialize_f32(self.scale),
+            _ => serializer.serialize_str(&self.to_string()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ScaleOption {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(f32),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Number(scale) => ScaleOption::try_scale(scale).map_err(serde::de::Error::custom),
+            Raw::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// Function to read the width and height out of a mode such as `2880x1800@120Hz`
+pub fn mode_resolution(mode: &str) -> Option<(i32, i32)> {
+    let resolution = mode.split('@').next()?;
+    let (width, height) = resolution.split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+/// Function to check that a mode looks like `WIDTHxHEIGHT`, optionally followed by `@RATE` or `@RATEHz`
+fn is_valid_mode(mode: &str) -> bool {
+    let (resolution, rate) = match mode.split_once('@') {
+        Some((resolution, rate)) => (resolution, Some(rate)),
+        None => (mode, None),
+    };
+
+    let valid_resolution = match resolution.split_once('x') {
+        Some((w, h)) => w.parse::<u32>().is_ok() && h.parse::<u32>().is_ok(),
+        None => false,
+    };
+    let valid_rate = match rate {
+        Some(rate) => rate.trim_end_matches("Hz").parse::<f32>().is_ok(),
+        None => true,
+    };
+
+    valid_resolution && valid_rate
+}
@@ -0,0 +1,95 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// A file holding the daemon's PID, so scripts can signal it; removed again when dropped
+pub struct PidFile {
+    path: PathBuf,
+}
+
+/// Function to pick the default PID file location, preferring the per-user runtime directory
+pub fn default_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("sway-scale-switcher.pid")
+}
+
+impl PidFile {
+    /// Function to write the current PID to `path`, refusing if another daemon still owns it
+    pub fn create(path: &Path) -> io::Result<PidFile> {
+        if let Some(pid) = read_pid(path) {
+            if pid != process::id() && Path::new(&format!("/proc/{}", pid)).exists() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("The daemon is already running with PID {}", pid),
+                ));
+            }
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, format!("{}\n", process::id()))?;
+        Ok(PidFile {
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Function to remove the file, leaving it alone if another process has taken it over
+    pub fn remove(&self) {
+        if read_pid(&self.path) == Some(process::id()) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// Function to read the PID stored in a PID file, if there is a valid one
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "sway-scale-switcher-pidfile-{}-{}",
+            process::id(),
+            name
+        ))
+    }
+
+    #[test]
+    fn writes_and_removes_the_pid() {
+        let path = temp_path("own");
+        let pidfile = PidFile::create(&path).unwrap();
+        assert_eq!(read_pid(&path), Some(process::id()));
+        drop(pidfile);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn replaces_stale_files_but_not_live_ones() {
+        let path = temp_path("stale");
+        // PIDs are capped well below u32::MAX, so this process cannot exist
+        fs::write(&path, format!("{}\n", u32::MAX)).unwrap();
+        drop(PidFile::create(&path).unwrap());
+
+        // PID 1 is always running
+        fs::write(&path, "1\n").unwrap();
+        assert!(PidFile::create(&path).is_err());
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,111 @@
+use std::path::Path;
+
+/// What the environment says about the session this runs in, for telling why sway is out of
+/// reach
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    pub wayland_display: Option<String>,
+    pub swaysock: Option<String>,
+    /// `DISPLAY`, set in X11 sessions
+    pub x11_display: Option<String>,
+    /// `XDG_SESSION_TYPE`, e.g. `wayland`, `x11` or `tty`
+    pub session_type: Option<String>,
+    /// Running under the Windows Subsystem for Linux
+    pub wsl: bool,
+    pub swaymsg_on_path: bool,
+}
+
+impl Environment {
+    /// Function to read the environment of this process
+    pub fn detect() -> Self {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+        Environment {
+            wayland_display: var("WAYLAND_DISPLAY"),
+            swaysock: var("SWAYSOCK"),
+            x11_display: var("DISPLAY"),
+            session_type: var("XDG_SESSION_TYPE"),
+            wsl: var("WSL_DISTRO_NAME").is_some() || var("WSL_INTEROP").is_some(),
+            swaymsg_on_path: on_path("swaymsg"),
+        }
+    }
+}
+
+/// Function to check whether a program can be found on PATH
+pub fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+/// Function to explain, as well as the environment allows, why no sway answers
+pub fn diagnose(env: &Environment) -> String {
+    if env.wsl {
+        return "this is WSL, whose graphical apps run under WSLg's own compositor rather than \
+                sway"
+            .to_string();
+    }
+    if !env.swaymsg_on_path {
+        return "swaymsg is not on PATH, so sway cannot be reached".to_string();
+    }
+    if let Some(socket) = &env.swaysock {
+        return format!(
+            "SWAYSOCK is {}, but no sway listens there; it may be left over from an earlier \
+             session",
+            socket
+        );
+    }
+    let x11 = env.session_type.as_deref() == Some("x11")
+        || (env.x11_display.is_some() && env.wayland_display.is_none());
+    if x11 {
+        return "this is an X11 session, and sway is a Wayland compositor".to_string();
+    }
+    match &env.wayland_display {
+        Some(display) => format!(
+            "WAYLAND_DISPLAY is {} but SWAYSOCK is not set and no sway socket was found; if the \
+             compositor is not sway, try --backend wlr-randr",
+            display
+        ),
+        None => "neither WAYLAND_DISPLAY nor SWAYSOCK is set, as on a TTY, over SSH or from cron"
+            .to_string(),
+    }
+}
+
+/// Function to suggest what still works without a session
+pub fn offline_hint(config: &Path) -> String {
+    format!(
+        "Editing {} with 'set' or 'cycle' still works, and the change applies when sway next \
+         starts; --host reaches sway on another machine, and --backend mock tries commands out.",
+        config.display()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_the_likeliest_reason_sway_is_out_of_reach() {
+        let desktop = Environment {
+            swaymsg_on_path: true,
+            ..Default::default()
+        };
+        assert!(diagnose(&desktop).starts_with("neither WAYLAND_DISPLAY nor SWAYSOCK"));
+        let x11 = Environment {
+            x11_display: Some(":0".to_string()),
+            ..desktop.clone()
+        };
+        assert!(diagnose(&x11).contains("X11 session"));
+        let other = Environment {
+            wayland_display: Some("wayland-1".to_string()),
+            ..desktop.clone()
+        };
+        assert!(diagnose(&other).contains("--backend wlr-randr"));
+        let stale = Environment {
+            swaysock: Some("/run/user/1000/sway-ipc.1000.42.sock".to_string()),
+            ..other.clone()
+        };
+        assert!(diagnose(&stale).contains("left over"));
+        let wsl = Environment { wsl: true, ..other };
+        assert!(diagnose(&wsl).contains("WSLg"));
+        assert!(diagnose(&Environment::default()).contains("swaymsg is not on PATH"));
+    }
+}
@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::Path;
+
+/// Where the kernel lists batteries and power adapters
+const POWER_SUPPLY: &str = "/sys/class/power_supply";
+
+/// Function to tell whether the machine runs on battery, or None if it has no battery
+pub fn on_battery() -> Option<bool> {
+    on_battery_in(Path::new(POWER_SUPPLY))
+}
+
+/// Function to tell from a power_supply directory whether the machine runs on battery: it has a
+/// system battery and no adapter is online. Peripheral batteries, e.g. a mouse's, do not count.
+fn on_battery_in(dir: &Path) -> Option<bool> {
+    let read = |path: &Path, name: &str| {
+        fs::read_to_string(path.join(name))
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+    let mut battery = false;
+    let mut plugged_in = false;
+    for entry in fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        match read(&path, "type").as_str() {
+            "Battery" if read(&path, "scope") != "Device" => battery = true,
+            "Mains" | "USB" if read(&path, "online") == "1" => plugged_in = true,
+            _ => {}
+        }
+    }
+    battery.then_some(!plugged_in)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_power_source() {
+        let dir = std::env::temp_dir().join(format!("sss-power-{}", std::process::id()));
+        let supply = |name: &str, files: &[(&str, &str)]| {
+            fs::create_dir_all(dir.join(name)).unwrap();
+            for (file, value) in files {
+                fs::write(dir.join(name).join(file), format!("{}\n", value)).unwrap();
+            }
+        };
+        supply(
+            "hid-mouse-battery",
+            &[("type", "Battery"), ("scope", "Device")],
+        );
+        assert_eq!(on_battery_in(&dir), None);
+
+        supply("BAT0", &[("type", "Battery")]);
+        supply("AC", &[("type", "Mains"), ("online", "0")]);
+        assert_eq!(on_battery_in(&dir), Some(true));
+        supply("AC", &[("online", "1")]);
+        assert_eq!(on_battery_in(&dir), Some(false));
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(on_battery_in(&dir), None);
+    }
+}
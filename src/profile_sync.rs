@@ -0,0 +1,329 @@
+use crate::settings;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::UNIX_EPOCH;
+
+/// Name of the settings file in the repository, with `.gpg` appended when it is encrypted
+const FILE: &str = "config.toml";
+/// Copy of the settings as of the last sync, kept inside the clone's .git directory, which tells
+/// which side changed since
+const LAST_SYNC: &str = "sway-scale-switcher-last-sync";
+
+/// What a sync does with the settings file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    UpToDate,
+    /// Take the remote's copy; `conflict` when this machine changed its copy as well
+    Pull {
+        conflict: bool,
+    },
+    /// Send this machine's copy; `conflict` when the remote changed its copy as well
+    Push {
+        conflict: bool,
+    },
+}
+
+/// Function to decide how to bring the local and remote copies together. Whichever side
+/// changed since the last sync wins; when both did, the newer one wins.
+pub fn decide(
+    local: Option<&str>,
+    remote: Option<&str>,
+    last: Option<&str>,
+    local_is_newer: bool,
+) -> Action {
+    match (local, remote) {
+        (local, remote) if local == remote => Action::UpToDate,
+        (None, _) => Action::Pull { conflict: false },
+        (_, None) => Action::Push { conflict: false },
+        (local, _) if local == last => Action::Pull { conflict: false },
+        (_, remote) if remote == last => Action::Push { conflict: false },
+        _ if local_is_newer => Action::Push { conflict: true },
+        _ => Action::Pull { conflict: true },
+    }
+}
+
+/// Function to locate the tool's clone of the sync repository
+fn clone_path() -> Option<PathBuf> {
+    let base = dirs::state_dir().or_else(dirs::data_local_dir)?;
+    Some(base.join("sway-scale-switcher").join("sync"))
+}
+
+/// Function to run git in the clone and return its standard output, or its error message
+fn git(dir: &Path, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| io::Error::other(format!("Failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Function to run gpg over `input` and return what it writes
+fn gpg(args: &[&str], input: &[u8]) -> io::Result<Vec<u8>> {
+    use std::io::Write;
+
+    let mut child = Command::new("gpg")
+        .args(["--batch", "--quiet", "--yes"])
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::other(format!("Failed to run gpg: {}", e)))?;
+    child
+        .stdin
+        .take()
+        .expect("gpg stdin is piped")
+        .write_all(input)?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "gpg failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+/// Function to read the remote's copy of the settings from the clone, decrypting it if it is
+/// encrypted. A new machine has no settings to say so yet, so the file itself decides.
+fn read_remote(dir: &Path) -> io::Result<Option<String>> {
+    let encrypted = dir.join(format!("{}.gpg", FILE));
+    let contents = if encrypted.exists() {
+        gpg(&["--decrypt"], &fs::read(&encrypted)?)?
+    } else {
+        match fs::read(dir.join(FILE)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    };
+    String::from_utf8(contents)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Function to write this machine's copy into the clone, encrypted to `encrypt_to` if given.
+/// Returns the file name to commit.
+fn write_remote(dir: &Path, contents: &str, encrypt_to: Option<&str>) -> io::Result<String> {
+    match encrypt_to {
+        Some(recipient) => {
+            let name = format!("{}.gpg", FILE);
+            let encrypted = gpg(
+                &["--encrypt", "--recipient", recipient],
+                contents.as_bytes(),
+            )?;
+            fs::write(dir.join(&name), encrypted)?;
+            // A plain copy left from before encryption was turned on must not linger
+            if dir.join(FILE).exists() {
+                git(dir, &["rm", "--quiet", FILE])?;
+            }
+            Ok(name)
+        }
+        None => {
+            let encrypted = format!("{}.gpg", FILE);
+            if dir.join(&encrypted).exists() {
+                git(dir, &["rm", "--quiet", &encrypted])?;
+            }
+            fs::write(dir.join(FILE), contents)?;
+            Ok(FILE.to_string())
+        }
+    }
+}
+
+/// Function to bring the settings file in step with the git repository at `remote`: pull the
+/// remote's copy if only it changed, push this machine's if only that did, and keep the newer
+/// one with a warning if both did. `remote` and `branch` come from the [sync] settings unless
+/// given here.
+pub fn sync(remote: Option<&str>, branch: Option<&str>) -> io::Result<()> {
+    let settings_path = settings::settings_path()
+        .ok_or_else(|| io::Error::other("Could not determine config directory"))?;
+    let local = match fs::read_to_string(&settings_path) {
+        Ok(contents) => Some(contents),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    // The [sync] section names the repository; a first sync on a new machine passes --remote
+    let configured = match &local {
+        Some(contents) => settings::parse_settings(contents)
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", settings_path.display(), e),
+                )
+            })?
+            .sync
+            .unwrap_or_default(),
+        None => settings::ProfileSync::default(),
+    };
+    let remote = match remote.or(configured.remote.as_deref()) {
+        Some(remote) => remote.to_string(),
+        None => {
+            return Err(io::Error::other(
+                "No repository to sync with; set remote in the [sync] section of the settings \
+                 or pass --remote.",
+            ))
+        }
+    };
+    let branch = branch.unwrap_or(&configured.branch).to_string();
+    let encrypt_to = configured.encrypt_to.as_deref();
+
+    // The clone belongs to the tool, so it simply follows the remote
+    let dir =
+        clone_path().ok_or_else(|| io::Error::other("Could not determine state directory"))?;
+    if !dir.join(".git").is_dir() {
+        fs::create_dir_all(&dir)?;
+        git(&dir, &["init", "--quiet"])?;
+    }
+    if git(&dir, &["remote", "get-url", "origin"]).is_ok() {
+        git(&dir, &["remote", "set-url", "origin", &remote])?;
+    } else {
+        git(&dir, &["remote", "add", "origin", &remote])?;
+    }
+    git(&dir, &["fetch", "--quiet", "origin"])?;
+    let tracking = format!("origin/{}", branch);
+    let remote_time = match git(&dir, &["log", "-1", "--format=%ct", &tracking]) {
+        Ok(time) => {
+            git(
+                &dir,
+                &["checkout", "--quiet", "--force", "-B", &branch, &tracking],
+            )?;
+            time.trim().parse::<u64>().unwrap_or(0)
+        }
+        Err(_) => 0,
+    };
+
+    let remote_contents = read_remote(&dir)?;
+    let last_path = dir.join(".git").join(LAST_SYNC);
+    let last = fs::read_to_string(&last_path).ok();
+    let local_time = fs::metadata(&settings_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_secs());
+
+    let action = decide(
+        local.as_deref(),
+        remote_contents.as_deref(),
+        last.as_deref(),
+        local_time >= remote_time,
+    );
+    match action {
+        Action::UpToDate => {
+            println!("The settings are already in step with {}.", remote);
+        }
+        Action::Pull { conflict } => {
+            if conflict {
+                warning!(
+                    "Both this machine and {} changed the settings since the last sync; keeping \
+                     the newer copy from {}.",
+                    remote,
+                    remote
+                );
+            }
+            let contents = remote_contents.as_deref().unwrap_or_default();
+            settings::parse_settings(contents).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("The settings in {} are invalid: {}", remote, e),
+                )
+            })?;
+            if let Some(parent) = settings_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&settings_path, contents)?;
+            println!("Pulled the settings from {}", remote);
+        }
+        Action::Push { conflict } => {
+            if conflict {
+                warning!(
+                    "Both this machine and {} changed the settings since the last sync; keeping \
+                     the newer copy from this machine.",
+                    remote
+                );
+            }
+            let contents = local.as_deref().unwrap_or_default();
+            let name = write_remote(&dir, contents, encrypt_to)?;
+            let host = crate::contexts::hostname().unwrap_or_else(|| "unknown host".to_string());
+            git(&dir, &["add", &name])?;
+            git(
+                &dir,
+                &[
+                    "-c",
+                    "user.name=sway-scale-switcher",
+                    "-c",
+                    "user.email=sway-scale-switcher@localhost",
+                    "commit",
+                    "--quiet",
+                    "-m",
+                    &format!("Update the settings from {}", host),
+                ],
+            )?;
+            git(
+                &dir,
+                &[
+                    "push",
+                    "--quiet",
+                    "origin",
+                    &format!("HEAD:refs/heads/{}", branch),
+                ],
+            )?;
+            println!("Pushed the settings to {}", remote);
+        }
+    }
+
+    let synced = match action {
+        Action::Push { .. } => local,
+        _ => remote_contents,
+    };
+    fs::write(&last_path, synced.unwrap_or_default())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_side_that_changed_wins_and_the_newer_one_on_conflict() {
+        let old = Some("a");
+        assert_eq!(decide(old, old, old, true), Action::UpToDate);
+        assert_eq!(
+            decide(Some("b"), old, old, false),
+            Action::Push { conflict: false }
+        );
+        assert_eq!(
+            decide(old, Some("b"), old, true),
+            Action::Pull { conflict: false }
+        );
+        assert_eq!(
+            decide(Some("b"), Some("c"), old, true),
+            Action::Push { conflict: true }
+        );
+        assert_eq!(
+            decide(Some("b"), Some("c"), old, false),
+            Action::Pull { conflict: true }
+        );
+
+        // A new machine takes the remote's copy, and an empty remote gets this machine's
+        assert_eq!(
+            decide(None, Some("b"), None, false),
+            Action::Pull { conflict: false }
+        );
+        assert_eq!(
+            decide(Some("b"), None, None, false),
+            Action::Push { conflict: false }
+        );
+    }
+}
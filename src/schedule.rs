@@ -0,0 +1,228 @@
+use crate::clock;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// A change deferred with `--in` or `--at`, run by a detached copy of the tool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    /// Unix timestamp the job is due at
+    pub due: u64,
+    /// Command-line arguments to run the tool with, without `--in` and `--at`
+    pub args: Vec<String>,
+}
+
+/// Function to locate the list of scheduled jobs, next to the state file
+fn schedule_path() -> Option<PathBuf> {
+    Some(crate::state::state_path()?.with_file_name("schedule.json"))
+}
+
+/// Function to parse a delay such as `30m`, `1h30m` or `45s`; a bare number counts minutes
+pub fn parse_duration(text: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid duration '{}'; use e.g. 30m, 1h30m or 45s.", text);
+    if let Ok(minutes) = text.parse::<u64>() {
+        return Ok(minutes * 60);
+    }
+
+    let mut total = 0;
+    let mut number = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+        total += value * unit;
+        number.clear();
+    }
+    if !number.is_empty() || text.is_empty() {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+/// Function to parse a time of day such as `22:00` into hours and minutes
+pub fn parse_time(text: &str) -> Result<(u64, u64), String> {
+    let invalid = || format!("Invalid time '{}'; use 24-hour HH:MM, e.g. 22:00.", text);
+    let (hours, minutes) = text.split_once(':').ok_or_else(invalid)?;
+    let hours: u64 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u64 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 23 || minutes > 59 {
+        return Err(invalid());
+    }
+    Ok((hours, minutes))
+}
+
+/// Function to find the next time the clock shows `hours:minutes`, given the local offset from
+/// UTC in seconds; a time that has already passed today means tomorrow
+pub fn next_at(now: u64, offset: i64, hours: u64, minutes: u64) -> u64 {
+    let local = now as i64 + offset;
+    let midnight = local - local.rem_euclid(86400);
+    let mut target = midnight + (hours * 3600 + minutes * 60) as i64;
+    if target <= local {
+        target += 86400;
+    }
+    (target - offset) as u64
+}
+
+/// Function to ask `date` for the local offset from UTC in seconds, since the standard library
+/// knows nothing about time zones. Falls back to UTC.
+pub fn local_offset() -> i64 {
+    let Ok(output) = Command::new("date").arg("+%z").output() else {
+        return 0;
+    };
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let (sign, digits) = match text.split_at_checked(1) {
+        Some(("-", digits)) => (-1, digits),
+        Some(("+", digits)) => (1, digits),
+        _ => return 0,
+    };
+    let (Some(hours), Some(minutes)) = (
+        digits.get(..2).and_then(|hours| hours.parse::<i64>().ok()),
+        digits
+            .get(2..4)
+            .and_then(|minutes| minutes.parse::<i64>().ok()),
+    ) else {
+        return 0;
+    };
+    sign * (hours * 3600 + minutes * 60)
+}
+
+/// Function to show a Unix timestamp as a local `HH:MM`
+pub fn format_time(timestamp: u64, offset: i64) -> String {
+    let seconds = (timestamp as i64 + offset).rem_euclid(86400);
+    format!("{:02}:{:02}", seconds / 3600, seconds % 3600 / 60)
+}
+
+/// Function to read the scheduled jobs, of which there are none if the file does not exist
+pub fn load_jobs() -> io::Result<Vec<Job>> {
+    let Some(path) = schedule_path() else {
+        return Ok(Vec::new());
+    };
+    match fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse {}: {}", path.display(), e),
+            )
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Function to write the scheduled jobs, replacing the file atomically
+fn save_jobs(jobs: &[Job]) -> io::Result<()> {
+    let path =
+        schedule_path().ok_or_else(|| io::Error::other("Could not determine state directory"))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(jobs).map_err(io::Error::other)?;
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, json + "\n")?;
+    fs::rename(&temp_path, &path)
+}
+
+/// Function to record a job and start the detached process that runs it when it is due
+pub fn add(due: u64, args: Vec<String>) -> io::Result<Job> {
+    let mut jobs = load_jobs()?;
+    let id = jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+    let job = Job { id, due, args };
+    jobs.push(job.clone());
+    save_jobs(&jobs)?;
+
+    // A process group of its own keeps the timer alive when the terminal closes. A simulated
+    // clock is handed on, so the timer fast-forwards as well.
+    let mut timer = Command::new(std::env::current_exe()?);
+    if clock::clock().is_simulated() {
+        timer.args(["--simulate-time", &clock::now().to_string()]);
+    }
+    timer
+        .args(["schedule", "wait", &id.to_string()])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()?;
+    Ok(job)
+}
+
+/// Function to remove a job before it runs, returning whether there was one with this id
+pub fn cancel(id: u64) -> io::Result<bool> {
+    let mut jobs = load_jobs()?;
+    let count = jobs.len();
+    jobs.retain(|job| job.id != id);
+    if jobs.len() == count {
+        return Ok(false);
+    }
+    save_jobs(&jobs)?;
+    Ok(true)
+}
+
+/// Function to sleep until a job is due, then run it. The job is looked up again after every
+/// nap so cancelling it takes effect, and naps are short so a suspend does not delay the job
+/// much past its time.
+pub fn wait(id: u64) -> io::Result<()> {
+    loop {
+        let mut jobs = load_jobs()?;
+        let Some(position) = jobs.iter().position(|job| job.id == id) else {
+            return Ok(());
+        };
+        let remaining = jobs[position].due.saturating_sub(clock::now());
+        if remaining > 0 {
+            clock::sleep(Duration::from_secs(remaining.min(60)));
+            continue;
+        }
+
+        let job = jobs.remove(position);
+        save_jobs(&jobs)?;
+
+        // The history names the job, unless the scheduled command names its trigger itself
+        let mut command = Command::new(std::env::current_exe()?);
+        if !job.args.iter().any(|arg| arg.starts_with("--trigger")) {
+            command.args(["--trigger", &format!("schedule job {}", job.id)]);
+        }
+        command.args(&job.args).status()?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations_and_times() {
+        assert_eq!(parse_duration("30m"), Ok(1800));
+        assert_eq!(parse_duration("1h30m"), Ok(5400));
+        assert_eq!(parse_duration("45s"), Ok(45));
+        assert_eq!(parse_duration("10"), Ok(600));
+        assert!(parse_duration("1x").is_err());
+        assert!(parse_duration("1h30").is_err());
+        assert_eq!(parse_time("22:05"), Ok((22, 5)));
+        assert!(parse_time("24:00").is_err());
+    }
+
+    #[test]
+    fn next_at_rolls_over_to_tomorrow() {
+        // 2024-01-01 20:00 UTC, which is 22:00 at UTC+2
+        let now = 1_704_139_200;
+        let offset = 2 * 3600;
+        assert_eq!(format_time(now, offset), "22:00");
+        assert_eq!(next_at(now, offset, 23, 30), now + 5400);
+        assert_eq!(next_at(now, offset, 22, 0), now + 86400);
+        assert_eq!(next_at(now, offset, 7, 0), now + 9 * 3600);
+    }
+}
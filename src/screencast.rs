@@ -0,0 +1,150 @@
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Recorders that capture outputs directly instead of through the desktop portal
+const RECORDERS: [&str; 3] = ["wf-recorder", "wl-screenrec", "gpu-screen-recorder"];
+
+/// What to do about a scale change while the screen is being recorded or shared
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Guard {
+    /// Refuse the change unless `--force` is given
+    #[default]
+    Refuse,
+    /// Make the change, but warn that the capture may break
+    Warn,
+    /// Do not look for screencasts
+    Off,
+}
+
+/// Function to list the recorder processes running under a procfs root
+fn recorders(proc: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(proc) else {
+        return Vec::new();
+    };
+    let mut found: Vec<(u32, String)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            let comm = fs::read_to_string(entry.path().join("comm")).ok()?;
+            let comm = comm.trim();
+            RECORDERS.contains(&comm).then(|| (pid, comm.to_string()))
+        })
+        .collect();
+    found.sort();
+    found
+        .into_iter()
+        .map(|(pid, comm)| format!("{} (PID {})", comm, pid))
+        .collect()
+}
+
+/// Function to count the sessions xdg-desktop-portal holds open, which screen casts and remote
+/// desktop connections use. A portal that is not running is not started just to ask.
+#[cfg(feature = "dbus")]
+fn portal_sessions() -> zbus::Result<usize> {
+    use zbus::blocking::fdo::{DBusProxy, IntrospectableProxy};
+    use zbus::proxy::CacheProperties;
+
+    const PORTAL: &str = "org.freedesktop.portal.Desktop";
+    const SESSIONS: &str = "/org/freedesktop/portal/desktop/session";
+
+    let connection = zbus::blocking::Connection::session()?;
+    if !DBusProxy::new(&connection)?.name_has_owner(PORTAL.try_into()?)? {
+        return Ok(0);
+    }
+    let children = |path: &str| -> zbus::Result<Vec<String>> {
+        let xml = IntrospectableProxy::builder(&connection)
+            .destination(PORTAL)?
+            .path(path)?
+            .cache_properties(CacheProperties::No)
+            .build()?
+            .introspect()?;
+        Ok(child_nodes(&xml))
+    };
+
+    // Sessions live one level below a node per client
+    let mut count = 0;
+    for client in children(SESSIONS)? {
+        count += children(&format!("{}/{}", SESSIONS, client))?.len();
+    }
+    Ok(count)
+}
+
+/// Function to report no portal sessions; without D-Bus support only recorders are found
+#[cfg(not(feature = "dbus"))]
+fn portal_sessions() -> io::Result<usize> {
+    Ok(0)
+}
+
+/// Function to list the child node names in D-Bus introspection XML
+#[cfg(feature = "dbus")]
+fn child_nodes(xml: &str) -> Vec<String> {
+    let node = regex::Regex::new(r#"<node\s+name="([^"/]+)""#).unwrap();
+    node.captures_iter(xml)
+        .map(|captures| captures[1].to_string())
+        .collect()
+}
+
+/// Function to describe every screen recording or sharing session that looks active
+pub fn active() -> Vec<String> {
+    let mut found = recorders(Path::new("/proc"));
+    match portal_sessions() {
+        Ok(0) | Err(_) => {}
+        Ok(1) => found.push("a screen sharing session (xdg-desktop-portal)".to_string()),
+        Ok(count) => found.push(format!(
+            "{} screen sharing sessions (xdg-desktop-portal)",
+            count
+        )),
+    }
+    found
+}
+
+/// Function to stop a scale change while something captures the screen, since a change of
+/// scale or resolution in the middle of a capture breaks it
+pub fn check(guard: Guard) -> io::Result<()> {
+    if guard == Guard::Off {
+        return Ok(());
+    }
+    let found = active();
+    if found.is_empty() {
+        return Ok(());
+    }
+    if guard == Guard::Warn {
+        warning!(
+            "The screen is being captured by {}; the capture may break.",
+            found.join(", ")
+        );
+        return Ok(());
+    }
+    Err(io::Error::other(format!(
+        "The screen is being captured by {}, and changing the scale would break the capture. \
+         Nothing was changed; pass --force to change it anyway.",
+        found.join(", ")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_recorder_processes() {
+        let proc = std::env::temp_dir().join(format!("sss-proc-{}", std::process::id()));
+        for (pid, comm) in [("42", "wf-recorder\n"), ("7", "bash\n"), ("self", "x\n")] {
+            fs::create_dir_all(proc.join(pid)).unwrap();
+            fs::write(proc.join(pid).join("comm"), comm).unwrap();
+        }
+        assert_eq!(recorders(&proc), ["wf-recorder (PID 42)"]);
+        fs::remove_dir_all(&proc).unwrap();
+        assert!(recorders(&proc).is_empty());
+    }
+
+    #[cfg(feature = "dbus")]
+    #[test]
+    fn reads_child_nodes_from_introspection() {
+        let xml = r#"<node><interface name="a"/><node name=":1_42"/><node name="t1"/></node>"#;
+        assert_eq!(child_nodes(xml), [":1_42", "t1"]);
+    }
+}
@@ -0,0 +1,703 @@
+use crate::options::{CycleOrder, Precision, ScaleOption};
+use crate::screencast::Guard;
+use crate::templates::Template;
+use crate::toolkits::Toolkits;
+use crate::validate::Validation;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Settings for the tool itself, read from `~/.config/sway-scale-switcher/config.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// Version of the settings format, see `migrate`
+    pub version: u32,
+    /// Short names for outputs, e.g. `laptop = "eDP-1"`, accepted wherever an output name is
+    pub aliases: BTreeMap<String, String>,
+    /// Target displays, replacing the `# Target Display` lines in the sway config
+    pub targets: Vec<String>,
+    /// Scale options, replacing the `# Scale Options` line in the sway config
+    pub scales: Vec<ScaleOption>,
+    /// Write output lines to a snippet owned by the tool instead of editing the main config
+    pub managed: bool,
+    pub profiles: BTreeMap<String, Profile>,
+    /// Profile `apply-default` falls back to when none matches the outputs or hostname
+    pub default_profile: Option<String>,
+    /// Files re-rendered whenever the scale changes
+    pub templates: Vec<Template>,
+    /// GTK and Qt scaling settings adjusted along with the scale
+    pub toolkits: Toolkits,
+    /// How sway picks up a rewritten config
+    pub reload_strategy: ReloadStrategy,
+    /// Command run by the `exec` reload strategy
+    pub reload_command: Option<String>,
+    /// Do a full reload even while a window is fullscreen, as `--force` does
+    pub reload_over_fullscreen: bool,
+    /// Whether to refuse, or only warn about, scale changes while the screen is recorded or shared
+    pub screencast_guard: Guard,
+    /// Check a rewritten config with sway before it replaces the old one
+    pub validate: Validation,
+    /// Remember changes made while sway is not running, for `sway-scale-switcher announce`
+    pub announce_offline_changes: bool,
+    /// Write each output's scale to `$XDG_RUNTIME_DIR/sway-scale-switcher/scale-<output>` after
+    /// a change, for screenshot scripts and `sway-scale-switcher current`
+    pub record_scales: bool,
+    /// Experimental: set sway's scale_filter along with the scale, e.g. `auto` for nearest on
+    /// whole scales and linear on fractional ones; unset leaves the filter alone
+    pub scale_filter: Option<ScaleFilter>,
+    /// Experimental: scale_filter for single outputs, by connector or description, overriding
+    /// `scale_filter`
+    pub output_scale_filters: BTreeMap<String, ScaleFilter>,
+    /// Named scale ladders used instead of the config's options, e.g. `[options.work]`
+    pub options: BTreeMap<String, OptionGroup>,
+    /// Option group to use regardless of hostname and monitors; `--context` overrides it
+    pub context: Option<String>,
+    /// DPI of outputs whose EDID reports no usable physical size, for resolving `auto` and `native`
+    pub dpi: BTreeMap<String, f32>,
+    /// Rounding of written scales and tolerance when comparing them
+    pub precision: Precision,
+    /// Scale option applied by `set --default`, e.g. `1.5` or `"2@3840x2160"`; defaults to the first option
+    pub default_option: Option<ScaleOption>,
+    /// Order cycling steps through the options in: `ascending` (the default), `descending` or
+    /// `as-written`
+    pub cycle_order: CycleOrder,
+    /// Read the plain numbers among the options as apparent sizes, e.g. `1.25` for elements 25%
+    /// larger than at 96 DPI, and give each target display the scale that shows them at that
+    /// physical size instead of the same number everywhere
+    pub match_physical_size: bool,
+    /// Smallest scale the prompt accepts when one is typed in; defaults to 0.5
+    pub min_scale: Option<f32>,
+    /// Largest scale the prompt accepts when one is typed in; defaults to 4
+    pub max_scale: Option<f32>,
+    /// Scale `a11y-zoom` magnifies the focused output to; defaults to 2.5
+    pub zoom_scale: Option<f32>,
+    /// Smallest logical resolution a scale may leave an output with, e.g. `"1024x600"`, as some
+    /// bars and dialogs stop fitting below it; `--force` skips the check
+    pub min_logical_size: Option<LogicalSize>,
+    /// Scales the daemon applies while a workspace is visible, restoring the previous scale after
+    pub workspaces: Vec<WorkspaceOverride>,
+    /// Profile the daemon applies after a while without input, restoring the outputs on resume
+    pub idle: Option<Idle>,
+    /// Milliseconds the daemon waits for further cycle requests before applying them as one
+    /// change; defaults to 250, and 0 applies each request at once
+    pub debounce_ms: Option<u64>,
+    /// One-shot changes that follow the previous change more closely than this are refused
+    pub min_change_interval_ms: Option<u64>,
+    /// Show failures in the session, for keybindings whose output nobody sees
+    pub error_feedback: Option<ErrorFeedback>,
+    /// Ways of reaching the session, tried in order; leaving one out disables it. Defaults to
+    /// `["ipc", "wlr-randr", "config"]`
+    pub backends: Option<Vec<ApplyBackend>>,
+    /// Git repository `profiles sync` keeps this file in, so profiles follow you across machines
+    pub sync: Option<ProfileSync>,
+    /// Target displays from `SWAY_SCALE_SWITCHER_OUTPUTS`, replacing the ones the settings or
+    /// the config name while keeping their options
+    #[serde(skip)]
+    pub target_override: Option<Vec<String>>,
+}
+
+/// Where `profiles sync` sends the settings file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProfileSync {
+    /// URL or path of the repository, anything `git push` accepts
+    pub remote: Option<String>,
+    /// Branch to sync on; defaults to `main`
+    pub branch: String,
+    /// GPG key to encrypt the file to, so the repository only ever holds ciphertext; every
+    /// machine needs the secret key
+    pub encrypt_to: Option<String>,
+}
+
+impl Default for ProfileSync {
+    fn default() -> Self {
+        ProfileSync {
+            remote: None,
+            branch: "main".to_string(),
+            encrypt_to: None,
+        }
+    }
+}
+
+/// A way of getting a change to the session, see `backends`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApplyBackend {
+    /// sway's IPC socket
+    Ipc,
+    /// wlr-randr, on any compositor with the output management protocol
+    WlrRandr,
+    /// Only edit the config, which applies when sway next starts
+    Config,
+}
+
+impl ApplyBackend {
+    /// The chain used when the settings name none
+    pub const DEFAULT_CHAIN: [ApplyBackend; 3] = [
+        ApplyBackend::Ipc,
+        ApplyBackend::WlrRandr,
+        ApplyBackend::Config,
+    ];
+
+    /// Function to name the backend as the settings do
+    pub fn name(self) -> &'static str {
+        match self {
+            ApplyBackend::Ipc => "ipc",
+            ApplyBackend::WlrRandr => "wlr-randr",
+            ApplyBackend::Config => "config",
+        }
+    }
+}
+
+/// A logical resolution, written `WIDTHxHEIGHT` like a mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogicalSize {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl LogicalSize {
+    /// Function to check whether a size reaches this one in both directions
+    pub fn fits(&self, (width, height): (i32, i32)) -> bool {
+        width >= self.width && height >= self.height
+    }
+}
+
+impl fmt::Display for LogicalSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl<'de> Deserialize<'de> for LogicalSize {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        match crate::options::mode_resolution(&text) {
+            Some((width, height)) if !text.contains('@') => Ok(LogicalSize { width, height }),
+            _ => Err(serde::de::Error::custom(format!(
+                "invalid logical size '{}', expected WIDTHxHEIGHT such as 1024x600",
+                text
+            ))),
+        }
+    }
+}
+
+/// How a failed invocation shows itself in the session
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErrorFeedback {
+    /// Sway mode to switch to, shown by swaybar's mode indicator; the sway config must define it,
+    /// e.g. `mode "scale-error" { bindsym Escape mode default }`
+    pub mode: Option<String>,
+    /// Color to flash the focused window's border in, e.g. `#ff0000`
+    pub border_color: Option<String>,
+    /// How long the feedback lasts before sway is put back; defaults to 1
+    pub seconds: f32,
+}
+
+impl Default for ErrorFeedback {
+    fn default() -> Self {
+        ErrorFeedback {
+            mode: None,
+            border_color: None,
+            seconds: 1.0,
+        }
+    }
+}
+
+/// A scale for one output while a given workspace is visible on it
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkspaceOverride {
+    /// Workspace name as sway reports it, e.g. `9` or `9:media`
+    pub workspace: String,
+    /// Output by connector or description; unset applies to whichever output shows the workspace
+    pub output: Option<String>,
+    pub scale: f32,
+}
+
+/// A profile the daemon applies while the session is idle, as swayidle reports it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Idle {
+    /// Minutes without input before the profile applies
+    pub after_minutes: f32,
+    /// Profile to apply, e.g. one with lower scales or refresh rates, or outputs powered off
+    pub profile: String,
+}
+
+/// A named list of scale options, chosen with `--context` or picked automatically when all the
+/// criteria it sets hold
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OptionGroup {
+    pub scales: Vec<ScaleOption>,
+    /// Only pick this group automatically on the machine with this hostname
+    pub hostname: Option<String>,
+    /// Only pick this group automatically while all of these outputs, by connector or
+    /// description, are connected
+    #[serde(default)]
+    pub outputs: Vec<String>,
+}
+
+/// How a change written to the config is applied to the running session
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReloadStrategy {
+    /// Run `swaymsg reload`, which also restarts bars and other `exec_always` commands
+    #[default]
+    Full,
+    /// Send targeted `output` commands over IPC for the changed displays only
+    Ipc,
+    /// Run the configured reload command
+    Exec,
+    /// Leave the running session alone
+    None,
+}
+
+/// A named set of per-output settings applied together
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Profile {
+    /// Let the daemon apply this profile whenever exactly its outputs are connected
+    pub auto: bool,
+    /// Only use this profile on the machine with this hostname; `apply-default` also picks it
+    /// there when no automatic profile fits
+    pub hostname: Option<String>,
+    /// Outputs are named by connector (`eDP-1`), description (`Make Model Serial`) or `*`
+    pub outputs: Vec<OutputSettings>,
+}
+
+/// How sway filters a scaled output, see `output <name> scale_filter` in sway-output(5)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaleFilter {
+    /// Nearest for whole scales, which stay sharp, and linear for fractional ones
+    Auto,
+    Linear,
+    Nearest,
+    Smart,
+}
+
+impl ScaleFilter {
+    /// Function to read a filter as sway reports it
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(ScaleFilter::Linear),
+            "nearest" => Some(ScaleFilter::Nearest),
+            "smart" => Some(ScaleFilter::Smart),
+            _ => None,
+        }
+    }
+
+    /// Function to pick the filter sway should use at the given scale
+    pub fn for_scale(self, scale: f32) -> &'static str {
+        match self {
+            ScaleFilter::Auto if scale.fract() == 0.0 => "nearest",
+            ScaleFilter::Auto | ScaleFilter::Linear => "linear",
+            ScaleFilter::Nearest => "nearest",
+            ScaleFilter::Smart => "smart",
+        }
+    }
+}
+
+/// Subpixel layout of an output, see `output <name> subpixel` in sway-output(5)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Subpixel {
+    Rgb,
+    Bgr,
+    Vrgb,
+    Vbgr,
+    None,
+}
+
+impl Subpixel {
+    /// Function to read a subpixel layout as sway reports it in `subpixel_hinting`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rgb" => Some(Subpixel::Rgb),
+            "bgr" => Some(Subpixel::Bgr),
+            "vrgb" => Some(Subpixel::Vrgb),
+            "vbgr" => Some(Subpixel::Vbgr),
+            "none" => Some(Subpixel::None),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Subpixel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subpixel::Rgb => write!(f, "rgb"),
+            Subpixel::Bgr => write!(f, "bgr"),
+            Subpixel::Vrgb => write!(f, "vrgb"),
+            Subpixel::Vbgr => write!(f, "vbgr"),
+            Subpixel::None => write!(f, "none"),
+        }
+    }
+}
+
+impl Settings {
+    /// Function to get the backends to try, in order
+    pub fn backend_chain(&self) -> &[ApplyBackend] {
+        self.backends
+            .as_deref()
+            .unwrap_or(&ApplyBackend::DEFAULT_CHAIN)
+    }
+
+    /// Function to find the scale filter to set along with a display's scale, if any
+    pub fn scale_filter_for(&self, display: &str) -> Option<ScaleFilter> {
+        self.output_scale_filters
+            .get(display)
+            .copied()
+            .or(self.scale_filter)
+    }
+
+    /// Function to look up the output an alias stands for; other names are returned as they are
+    pub fn unalias<'a>(&'a self, name: &'a str) -> &'a str {
+        self.aliases.get(name).map_or(name, String::as_str)
+    }
+
+    /// Function to find the alias of a display, if it has one
+    pub fn alias_of(&self, display: &str) -> Option<&str> {
+        self.aliases
+            .iter()
+            .find(|(_, output)| *output == display)
+            .map(|(alias, _)| alias.as_str())
+    }
+
+    /// Function to name a display for people: with its alias, if it has one
+    pub fn label(&self, display: &str) -> String {
+        match self.alias_of(display) {
+            Some(alias) => format!("{} ({})", display, alias),
+            None => display.to_string(),
+        }
+    }
+
+    /// Function to replace aliases by the outputs they stand for wherever the settings name one
+    fn expand_aliases(&mut self) {
+        let aliases = self.aliases.clone();
+        let expand = |name: &mut String| {
+            if let Some(output) = aliases.get(name.as_str()) {
+                *name = output.clone();
+            }
+        };
+        fn expand_keys<T>(map: &mut BTreeMap<String, T>, aliases: &BTreeMap<String, String>) {
+            for (alias, output) in aliases {
+                if let Some(value) = map.remove(alias) {
+                    map.insert(output.clone(), value);
+                }
+            }
+        }
+        self.targets.iter_mut().for_each(expand);
+        for profile in self.profiles.values_mut() {
+            profile
+                .outputs
+                .iter_mut()
+                .for_each(|output| expand(&mut output.name));
+        }
+        for group in self.options.values_mut() {
+            group.outputs.iter_mut().for_each(expand);
+        }
+        for entry in &mut self.workspaces {
+            entry.output.iter_mut().for_each(expand);
+        }
+        expand_keys(&mut self.dpi, &aliases);
+        expand_keys(&mut self.output_scale_filters, &aliases);
+    }
+}
+
+/// Settings for a single output within a profile; unset fields are left as they are
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputSettings {
+    pub name: String,
+    pub scale: Option<f32>,
+    pub mode: Option<String>,
+    pub power: Option<Power>,
+    /// Position of the top-left corner in layout coordinates
+    pub position: Option<[i32; 2]>,
+    /// Bits per color channel, 8 or 10; 10 suits photo editing, 8 allows higher refresh rates
+    pub render_bit_depth: Option<u8>,
+    /// Turn HDR on or off, on sway releases that support it
+    pub hdr: Option<bool>,
+    /// Filter sway scales the output with; `auto` picks one to suit the scale
+    pub scale_filter: Option<ScaleFilter>,
+    /// Subpixel order for font hinting; `none` suits scaled or rotated text work
+    pub subpixel: Option<Subpixel>,
+    /// Milliseconds sway leaves itself to render a frame, lowering latency for games; 0 turns
+    /// it off
+    pub max_render_time: Option<u32>,
+}
+
+/// Power state of an output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Power {
+    On,
+    Off,
+}
+
+impl fmt::Display for Power {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Power::On => write!(f, "on"),
+            Power::Off => write!(f, "off"),
+        }
+    }
+}
+
+impl OutputSettings {
+    /// Function to build the sway `output` command for these settings
+    pub fn to_command(&self) -> String {
+        let mut command = format!("output {}", crate::ipc::quote(&self.name));
+        if let Some(mode) = &self.mode {
+            command.push_str(&format!(" mode {}", mode));
+        }
+        if let Some(scale) = self.scale {
+            command.push_str(&format!(" scale {}", scale));
+        }
+        if let Some(filter) = self.scale_filter {
+            let scale = self.scale.unwrap_or(1.0);
+            command.push_str(&format!(" scale_filter {}", filter.for_scale(scale)));
+        }
+        if let Some([x, y]) = self.position {
+            command.push_str(&format!(" position {} {}", x, y));
+        }
+        if let Some(power) = self.power {
+            command.push_str(&format!(" power {}", power));
+        }
+        if let Some(depth) = self.render_bit_depth {
+            command.push_str(&format!(" render_bit_depth {}", depth));
+        }
+        if let Some(hdr) = self.hdr {
+            command.push_str(if hdr { " hdr on" } else { " hdr off" });
+        }
+        if let Some(subpixel) = self.subpixel {
+            command.push_str(&format!(" subpixel {}", subpixel));
+        }
+        match self.max_render_time {
+            Some(0) => command.push_str(" max_render_time off"),
+            Some(ms) => command.push_str(&format!(" max_render_time {}", ms)),
+            None => {}
+        }
+        command
+    }
+}
+
+/// Prefix of the environment variables that override settings, e.g.
+/// `SWAY_SCALE_SWITCHER_RELOAD_STRATEGY=ipc`
+pub const ENV_PREFIX: &str = "SWAY_SCALE_SWITCHER_";
+/// Variables under the prefix that stand for something other than a setting: the two files,
+/// the target displays and the flags that pick the backend
+const ENV_RESERVED: [&str; 7] = [
+    "CONFIG",
+    "SETTINGS",
+    "OUTPUTS",
+    "BACKEND",
+    "SOCKET",
+    "HOST",
+    "MOCK_OUTPUTS",
+];
+
+/// Function to locate the settings file: `SWAY_SCALE_SWITCHER_SETTINGS` if set, else under the
+/// user's config directory
+pub fn settings_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var(format!("{}SETTINGS", ENV_PREFIX))
+        .ok()
+        .filter(|path| !path.is_empty())
+    {
+        return crate::expanduser(&path).map(PathBuf::from);
+    }
+    Some(
+        dirs::config_dir()?
+            .join("sway-scale-switcher")
+            .join("config.toml"),
+    )
+}
+
+/// Function to read settings overrides out of environment variables, by setting name. Values
+/// are TOML, and anything that does not parse as TOML is taken as a string, so
+/// `SWAY_SCALE_SWITCHER_RELOAD_STRATEGY=ipc` needs no quotes.
+pub fn env_overrides(vars: impl Iterator<Item = (String, String)>) -> Result<toml::Table, String> {
+    let mut overrides = toml::Table::new();
+    for (name, value) in vars {
+        let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        if ENV_RESERVED.contains(&key) {
+            continue;
+        }
+        let key = key.to_lowercase();
+        let value = format!("value = {}", value)
+            .parse::<toml::Table>()
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or(toml::Value::String(value));
+
+        // Each override is checked on its own, so a mistake names its variable
+        let single = toml::Table::from_iter([(key.clone(), value.clone())]);
+        toml::Value::Table(single)
+            .try_into::<Settings>()
+            .map_err(|e| format!("{}: {}", name, e.message()))?;
+        overrides.insert(key, value);
+    }
+    Ok(overrides)
+}
+
+/// Function to load the settings file, falling back to defaults if it does not exist, with
+/// any overrides from the environment on top
+pub fn load_settings() -> io::Result<Settings> {
+    let overrides = env_overrides(std::env::vars())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let path = settings_path();
+    let contents = match path.as_ref().map(fs::read_to_string) {
+        Some(Ok(contents)) => contents,
+        Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => String::new(),
+    };
+    let mut settings = parse_layered(&contents, overrides).map_err(|e| {
+        let path = path.map_or_else(String::new, |path| format!("{}: ", path.display()));
+        io::Error::new(io::ErrorKind::InvalidData, format!("{}{}", path, e))
+    })?;
+
+    if let Ok(outputs) = std::env::var(format!("{}OUTPUTS", ENV_PREFIX)) {
+        let outputs: Vec<String> = outputs
+            .split(',')
+            .map(str::trim)
+            .filter(|output| !output.is_empty())
+            .map(|output| settings.unalias(output).to_string())
+            .collect();
+        if !outputs.is_empty() {
+            settings.target_override = Some(outputs);
+        }
+    }
+    Ok(settings)
+}
+
+/// Function to parse settings of any version this build knows, migrating older ones in memory
+pub fn parse_settings(contents: &str) -> Result<Settings, String> {
+    parse_layered(contents, toml::Table::new())
+}
+
+/// Function to parse settings with `overrides` replacing whole top-level settings
+fn parse_layered(contents: &str, overrides: toml::Table) -> Result<Settings, String> {
+    let contents = crate::migrate::migrate_settings(contents)?;
+    let mut settings: Settings = if overrides.is_empty() {
+        toml::from_str(&contents).map_err(|e| e.to_string())?
+    } else {
+        let mut table: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        table.extend(overrides);
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| e.message().to_string())?
+    };
+    settings.expand_aliases();
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_aliases_wherever_an_output_is_named() {
+        let settings = parse_settings(
+            "targets = [\"laptop\", \"DP-2\"]\n\
+             scales = [\"1\", \"2\"]\n\
+             [aliases]\n\
+             laptop = \"eDP-1\"\n\
+             [dpi]\n\
+             laptop = 280\n\
+             [[workspaces]]\n\
+             workspace = \"9\"\n\
+             output = \"laptop\"\n\
+             scale = 1.5\n",
+        )
+        .unwrap();
+        assert_eq!(settings.targets, ["eDP-1", "DP-2"]);
+        assert_eq!(settings.dpi.get("eDP-1"), Some(&280.0));
+        assert_eq!(settings.workspaces[0].output.as_deref(), Some("eDP-1"));
+        assert_eq!(settings.unalias("laptop"), "eDP-1");
+        assert_eq!(settings.label("eDP-1"), "eDP-1 (laptop)");
+        assert_eq!(settings.label("DP-2"), "DP-2");
+    }
+
+    #[test]
+    fn profiles_set_subpixel_and_max_render_time() {
+        let settings = parse_settings(
+            "[profiles.gaming]\n\
+             outputs = [{ name = \"DP-1\", scale = 1, subpixel = \"none\", max_render_time = 2 }]\n\
+             [profiles.text]\n\
+             outputs = [{ name = \"DP-1\", scale = 1.5, subpixel = \"rgb\", max_render_time = 0 }]\n",
+        )
+        .unwrap();
+        assert_eq!(
+            settings.profiles["gaming"].outputs[0].to_command(),
+            "output \"DP-1\" scale 1 subpixel none max_render_time 2"
+        );
+        assert_eq!(
+            settings.profiles["text"].outputs[0].to_command(),
+            "output \"DP-1\" scale 1.5 subpixel rgb max_render_time off"
+        );
+        assert!(parse_settings(
+            "[profiles.bad]\noutputs = [{ name = \"DP-1\", subpixel = \"rgbw\" }]\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn numeric_scales_must_be_positive() {
+        assert!(parse_settings("scales = [1, 2]\n").is_ok());
+        for scales in ["[0, 1]", "[-1]", "[\"0\"]"] {
+            let error = parse_settings(&format!("scales = {}\n", scales)).unwrap_err();
+            assert!(error.contains("must be a positive number"), "{}", error);
+        }
+        assert!(parse_settings("default_option = 0\n").is_err());
+    }
+
+    #[test]
+    fn reads_the_minimum_logical_size() {
+        let settings = parse_settings("min_logical_size = \"1024x600\"\n").unwrap();
+        let minimum = settings.min_logical_size.unwrap();
+        assert_eq!(minimum.to_string(), "1024x600");
+        assert!(minimum.fits((1280, 720)));
+        assert!(!minimum.fits((960, 540)));
+        assert!(!minimum.fits((1366, 512)));
+        assert!(parse_settings("min_logical_size = \"1024\"\n").is_err());
+        assert!(parse_settings("min_logical_size = \"1024x600@60Hz\"\n").is_err());
+    }
+
+    #[test]
+    fn environment_variables_override_settings() {
+        let vars = [
+            ("SWAY_SCALE_SWITCHER_RELOAD_STRATEGY", "ipc"),
+            ("SWAY_SCALE_SWITCHER_MANAGED", "true"),
+            ("SWAY_SCALE_SWITCHER_SCALES", "[1, \"2@3840x2160\"]"),
+            ("SWAY_SCALE_SWITCHER_BACKEND", "mock"),
+            ("HOME", "/home/me"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+        let overrides = env_overrides(vars.into_iter()).unwrap();
+        let settings = parse_layered("managed = false\nzoom_scale = 3.0\n", overrides).unwrap();
+        assert_eq!(settings.reload_strategy, ReloadStrategy::Ipc);
+        assert!(settings.managed);
+        assert_eq!(settings.scales.len(), 2);
+        assert_eq!(settings.zoom_scale, Some(3.0));
+
+        let bad = [("SWAY_SCALE_SWITCHER_NO_SUCH".to_string(), "1".to_string())];
+        let error = env_overrides(bad.into_iter()).unwrap_err();
+        assert!(
+            error.starts_with("SWAY_SCALE_SWITCHER_NO_SUCH: unknown field"),
+            "{}",
+            error
+        );
+    }
+}
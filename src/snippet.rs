@@ -0,0 +1,58 @@
+use crate::options::ScaleOption;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Location of the file owned by the tool when running in managed mode
+pub const SNIPPET_PATH: &str = "~/.config/sway/sway-scale-switcher.conf";
+
+/// First line of a generated snippet
+const SNIPPET_HEADER: &str =
+    "# Managed by sway-scale-switcher. Changes to this file will be overwritten.";
+
+/// Function to read the managed snippet, starting a new one if it does not exist yet
+pub fn read_snippet(path: &str) -> io::Result<Vec<String>> {
+    match fs::read(path) {
+        Ok(contents) => Ok(crate::text::decode_lines(&contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(vec![SNIPPET_HEADER.to_string()]),
+        Err(e) => Err(e),
+    }
+}
+
+/// Function to add an output line for each display that the snippet does not mention yet,
+/// seeded with the scale currently set in the main config (or 1.0 if there is none)
+pub fn seed_outputs(
+    snippet_lines: &mut Vec<String>,
+    existing: &BTreeMap<String, ScaleOption>,
+    displays: &[String],
+    current: &BTreeMap<String, ScaleOption>,
+) {
+    for display in displays {
+        if existing.contains_key(display) {
+            continue;
+        }
+        let scale = current
+            .get(display)
+            .cloned()
+            .unwrap_or(ScaleOption::scale(1.0));
+        let mut line = format!("output \"{}\" scale {}", display, scale.scale);
+        if let Some(mode) = &scale.mode {
+            line.push_str(&format!(" mode {}", mode));
+        }
+        snippet_lines.push(line);
+    }
+}
+
+/// Function to check whether the main config includes the snippet file
+pub fn is_included(config_lines: &[String], snippet_path: &str) -> bool {
+    let file_name = Path::new(snippet_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    config_lines.iter().any(|line| {
+        let line = line.trim();
+        line.starts_with("include") && line.contains(&file_name)
+    })
+}
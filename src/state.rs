@@ -0,0 +1,250 @@
+use crate::settings::OutputSettings;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether changes are written to the per-output scale files, see `record_scales`
+static RECORD_SCALES: AtomicBool = AtomicBool::new(false);
+
+/// Runtime state that outlives a single invocation, kept next to the history file
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    /// Version of the state format, see `migrate`
+    pub version: u32,
+    /// Displays that cycling and profiles leave alone until they are unpinned
+    pub pinned: BTreeSet<String>,
+    /// Outputs magnified by `a11y-zoom`, with the scale to restore
+    pub zoomed: BTreeMap<String, f32>,
+    /// Unix time in milliseconds of the last change written, kept for `min_change_interval_ms`
+    pub last_change_ms: Option<u64>,
+    /// Render bit depth last applied to each output, for sway releases that do not report it
+    pub bit_depths: BTreeMap<String, u8>,
+    /// HDR state last applied to each output, for sway releases that do not report it
+    pub hdr: BTreeMap<String, bool>,
+}
+
+/// Function to locate the state file under the user's state directory
+pub fn state_path() -> Option<PathBuf> {
+    let base = dirs::state_dir().or_else(dirs::data_local_dir)?;
+    Some(base.join("sway-scale-switcher").join("state.json"))
+}
+
+/// Function to read the state file, falling back to an empty state if there is none yet
+pub fn load_state() -> io::Result<State> {
+    let Some(path) = state_path() else {
+        return Ok(State::default());
+    };
+    let invalid = |e: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse {}: {}", path.display(), e),
+        )
+    };
+    match fs::read(&path) {
+        Ok(bytes) => {
+            let mut value: serde_json::Value =
+                serde_json::from_slice(&bytes).map_err(|e| invalid(e.to_string()))?;
+            crate::migrate::migrate_state(&mut value).map_err(invalid)?;
+            serde_json::from_value(value).map_err(|e| invalid(e.to_string()))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(State::default()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Function to write the state file, replacing it atomically
+pub fn save_state(state: &State) -> io::Result<()> {
+    let path = state_path().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not determine state directory",
+        )
+    })?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let state = State {
+        version: crate::migrate::STATE_VERSION,
+        ..state.clone()
+    };
+    let json = serde_json::to_string_pretty(&state).map_err(io::Error::other)?;
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, json + "\n")?;
+    fs::rename(&temp_path, &path)
+}
+
+/// Function to locate the marker listing changes made while sway was not running
+fn pending_path() -> Option<PathBuf> {
+    Some(state_path()?.with_file_name("pending-announcement"))
+}
+
+/// Function to add a change to the marker, so it can be announced when sway next starts
+pub fn add_pending(text: &str) -> io::Result<()> {
+    let path = pending_path().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not determine state directory",
+        )
+    })?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?;
+    writeln!(file, "{}", text)
+}
+
+/// Function to read and remove the marker, returning the changes it listed
+pub fn take_pending() -> io::Result<Option<String>> {
+    let Some(path) = pending_path() else {
+        return Ok(None);
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    fs::remove_file(&path)?;
+    Ok(Some(text.trim_end().to_string()))
+}
+
+/// Function to turn the per-output scale files on or off for the rest of the run, following the
+/// `record_scales` setting
+pub fn init(record_scales: bool) {
+    RECORD_SCALES.store(record_scales, Ordering::Relaxed);
+}
+
+/// Function to locate the per-output scale files, which only need to last the session
+fn scales_dir() -> Option<PathBuf> {
+    Some(dirs::runtime_dir()?.join("sway-scale-switcher"))
+}
+
+/// Function to name an output's scale file; descriptions may contain slashes
+fn scale_file(dir: &Path, output: &str) -> PathBuf {
+    dir.join(format!("scale-{}", output.replace('/', "_")))
+}
+
+/// Function to write each output's new scale to `$XDG_RUNTIME_DIR/sway-scale-switcher/scale-<output>`
+/// if `record_scales` is set, for scripts that need the scale without asking sway
+pub fn record_scales<'a>(scales: impl IntoIterator<Item = (&'a str, f32)>) -> io::Result<()> {
+    if !RECORD_SCALES.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let dir = scales_dir().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not determine runtime directory",
+        )
+    })?;
+    write_scales_in(&dir, scales)
+}
+
+fn write_scales_in<'a>(
+    dir: &Path,
+    scales: impl IntoIterator<Item = (&'a str, f32)>,
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    for (output, scale) in scales {
+        // Replace the file atomically, so a script never reads half a number
+        let path = scale_file(dir, output);
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, format!("{}\n", scale))?;
+        fs::rename(&temp_path, &path)?;
+    }
+    Ok(())
+}
+
+/// Function to read the scale last recorded for an output, or None if there is none
+pub fn recorded_scale(output: &str) -> io::Result<Option<f32>> {
+    match scales_dir() {
+        Some(dir) => read_scale_in(&dir, output),
+        None => Ok(None),
+    }
+}
+
+fn read_scale_in(dir: &Path, output: &str) -> io::Result<Option<f32>> {
+    let path = scale_file(dir, output);
+    match fs::read_to_string(&path) {
+        Ok(text) => text.trim().parse().map(Some).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse {}: {}", path.display(), e),
+            )
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl State {
+    /// Function to check whether a display, given by connector name or description, is pinned
+    pub fn is_pinned(&self, display: &str) -> bool {
+        self.pinned.contains(display)
+    }
+
+    /// Function to drop the pinned displays from a list of targets
+    pub fn unpinned(&self, displays: &[String]) -> Vec<String> {
+        displays
+            .iter()
+            .filter(|display| !self.is_pinned(display))
+            .cloned()
+            .collect()
+    }
+
+    /// Function to drop the pinned displays from a profile's output settings, saying which
+    pub fn skip_pinned(&self, outputs: Vec<OutputSettings>) -> Vec<OutputSettings> {
+        outputs
+            .into_iter()
+            .filter(|output| {
+                let pinned = self.is_pinned(&output.name);
+                if pinned {
+                    println!("{}", tr!(SkippingPinned, output.name));
+                }
+                !pinned
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_pinned_displays_and_reads_older_files() {
+        let state: State = serde_json::from_str("{}").unwrap();
+        assert!(state.pinned.is_empty());
+
+        let state: State = serde_json::from_str(r#"{"pinned": ["eDP-1"]}"#).unwrap();
+        let displays = vec!["eDP-1".to_string(), "HDMI-A-1".to_string()];
+        assert_eq!(state.unpinned(&displays), vec!["HDMI-A-1".to_string()]);
+    }
+
+    #[test]
+    fn writes_and_reads_scale_files() {
+        let dir = std::env::temp_dir().join(format!("sss-scales-{}", std::process::id()));
+        assert_eq!(read_scale_in(&dir, "eDP-1").unwrap(), None);
+
+        write_scales_in(&dir, [("eDP-1", 1.5), ("Dell Inc. DELL U2720Q A/B", 2.0)]).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("scale-eDP-1")).unwrap(),
+            "1.5\n"
+        );
+        assert_eq!(read_scale_in(&dir, "eDP-1").unwrap(), Some(1.5));
+        assert_eq!(
+            read_scale_in(&dir, "Dell Inc. DELL U2720Q A/B").unwrap(),
+            Some(2.0)
+        );
+
+        fs::write(dir.join("scale-eDP-1"), "big\n").unwrap();
+        assert!(read_scale_in(&dir, "eDP-1").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,73 @@
+use crate::options::ScaleOption;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+
+/// A user-provided template rendered whenever the scale changes
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Template {
+    /// File containing `{{scale}}`, `{{scale_percent}}` and `{{output}}` placeholders
+    pub source: String,
+    /// Where the rendered file is written
+    pub destination: String,
+    /// Only render for changes to this output; defaults to the first changed output
+    pub output: Option<String>,
+    /// Shell command run after the file has been written, e.g. to reload a bar
+    pub reload: Option<String>,
+}
+
+/// Function to render every configured template for the outputs that just changed
+pub fn render_all(templates: &[Template], scales: &BTreeMap<String, ScaleOption>) {
+    for template in templates {
+        let change = match &template.output {
+            Some(output) => scales.get_key_value(output),
+            None => scales.iter().next(),
+        };
+        let Some((output, scale)) = change else {
+            continue;
+        };
+
+        if let Err(e) = render_template(template, output, scale.scale) {
+            warning!("Failed to render template {}: {}", template.source, e);
+        }
+    }
+}
+
+/// Function to render a single template, write it out and run its reload command
+fn render_template(template: &Template, output: &str, scale: f32) -> io::Result<()> {
+    let source = crate::expanduser(&template.source).unwrap_or(template.source.clone());
+    let destination =
+        crate::expanduser(&template.destination).unwrap_or(template.destination.clone());
+
+    let contents = fs::read_to_string(&source)?;
+    let rendered = render(&contents, output, scale);
+
+    if let Some(parent) = Path::new(&destination).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&destination, rendered)?;
+    println!("Rendered {}", destination);
+
+    if let Some(command) = &template.reload {
+        let status = process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .status()?;
+        if !status.success() {
+            warning!("Reload command '{}' exited with {}", command, status);
+        }
+    }
+    Ok(())
+}
+
+/// Function to substitute the template variables
+fn render(contents: &str, output: &str, scale: f32) -> String {
+    contents
+        .replace("{{scale}}", &scale.to_string())
+        .replace("{{scale_percent}}", &((scale * 100.0).round()).to_string())
+        .replace("{{output}}", output)
+}
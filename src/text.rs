@@ -0,0 +1,149 @@
+use std::borrow::Cow;
+
+/// UTF-8 byte order mark some editors put at the start of a file
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// A line of a file as it is stored on disk
+struct RawLine<'a> {
+    bytes: &'a [u8],
+    /// `\n`, `\r\n`, or empty for a last line without a newline
+    ending: &'a [u8],
+}
+
+/// Function to split a file into lines, keeping each line's own ending
+fn split(contents: &[u8]) -> (bool, Vec<RawLine<'_>>) {
+    let (bom, mut rest) = match contents.strip_prefix(BOM) {
+        Some(rest) => (true, rest),
+        None => (false, contents),
+    };
+
+    let mut lines = Vec::new();
+    while !rest.is_empty() {
+        let Some(newline) = rest.iter().position(|&b| b == b'\n') else {
+            lines.push(RawLine {
+                bytes: rest,
+                ending: b"",
+            });
+            break;
+        };
+        let start = if newline > 0 && rest[newline - 1] == b'\r' {
+            newline - 1
+        } else {
+            newline
+        };
+        lines.push(RawLine {
+            bytes: &rest[..start],
+            ending: &rest[start..=newline],
+        });
+        rest = &rest[newline + 1..];
+    }
+
+    (bom, lines)
+}
+
+/// Function to turn a line into text for the parser; bytes that are not UTF-8 become U+FFFD
+fn decode(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Function to split a file into lines of text, without their endings
+pub fn decode_lines(contents: &[u8]) -> Vec<String> {
+    // Valid UTF-8, the common case, can be split as text in one pass
+    if let Ok(text) = std::str::from_utf8(contents) {
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        return text.lines().map(String::from).collect();
+    }
+
+    split(contents)
+        .1
+        .iter()
+        .map(|line| decode(line.bytes))
+        .collect()
+}
+
+/// Function to turn edited lines back into file contents. Lines that still read the same as in
+/// `original` are copied byte for byte, edited lines keep the ending of the line they replace,
+/// and inserted lines use the file's first line ending, so only the edited lines change.
+pub fn encode_lines(original: &[u8], updated: &[String]) -> Vec<u8> {
+    let (bom, lines) = split(original);
+    let default_ending: &[u8] = lines
+        .iter()
+        .map(|line| line.ending)
+        .find(|ending| !ending.is_empty())
+        .unwrap_or(b"\n");
+    let unchanged =
+        |i: usize, text: &str| lines.get(i).is_some_and(|line| decode(line.bytes) == text);
+
+    let mut encoded: Vec<(Cow<[u8]>, &[u8])> = Vec::new();
+    let mut i = 0;
+    for (j, text) in updated.iter().enumerate() {
+        if unchanged(i, text) {
+            encoded.push((Cow::Borrowed(lines[i].bytes), lines[i].ending));
+            i += 1;
+        } else if i < lines.len() && updated.get(j + 1).is_some_and(|next| unchanged(i, next)) {
+            // The next line is the original one, so this line was inserted before it
+            encoded.push((Cow::Owned(text.clone().into_bytes()), default_ending));
+        } else if i < lines.len() {
+            encoded.push((Cow::Owned(text.clone().into_bytes()), lines[i].ending));
+            i += 1;
+        } else {
+            encoded.push((Cow::Owned(text.clone().into_bytes()), default_ending));
+        }
+    }
+
+    // Only the last line may lack a newline, and only if the original file's last line did
+    let missing_final_newline = lines.last().is_some_and(|line| line.ending.is_empty());
+    let count = encoded.len();
+    let mut contents = if bom { BOM.to_vec() } else { Vec::new() };
+    for (n, (bytes, ending)) in encoded.into_iter().enumerate() {
+        contents.extend_from_slice(&bytes);
+        if n + 1 < count || !missing_final_newline {
+            contents.extend_from_slice(if ending.is_empty() {
+                default_ending
+            } else {
+                ending
+            });
+        }
+    }
+    contents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(original: &[u8], f: impl FnOnce(&mut Vec<String>)) -> Vec<u8> {
+        let mut lines = decode_lines(original);
+        f(&mut lines);
+        encode_lines(original, &lines)
+    }
+
+    #[test]
+    fn unchanged_lines_round_trip() {
+        let original = b"\xef\xbb\xbfa\r\nb\n\xff\xfe c\r\nlast";
+        assert_eq!(edit(original, |_| {}), original);
+        let utf8 = "\u{feff}caf\u{e9}\r\n\r\nlast\n".as_bytes();
+        assert_eq!(decode_lines(utf8), vec!["caf\u{e9}", "", "last"]);
+        assert_eq!(edit(utf8, |_| {}), utf8);
+    }
+
+    #[test]
+    fn edited_lines_keep_their_ending() {
+        let original = b"# \xe9t\xe9\r\noutput A scale 1\r\n";
+        let rewritten = edit(original, |lines| lines[1] = "output A scale 2".to_string());
+        assert_eq!(rewritten, b"# \xe9t\xe9\r\noutput A scale 2\r\n");
+    }
+
+    #[test]
+    fn inserted_lines_use_the_file_line_ending() {
+        let original = b"output A {\r\n  scale 1\r\n}";
+        let rewritten = edit(original, |lines| {
+            lines.insert(2, "  mode 800x600".to_string())
+        });
+        assert_eq!(rewritten, b"output A {\r\n  scale 1\r\n  mode 800x600\r\n}");
+
+        let appended = edit(b"a\r\n", |lines| lines.push("b".to_string()));
+        assert_eq!(appended, b"a\r\nb\r\n");
+        assert_eq!(encode_lines(b"", &["a".to_string()]), b"a\n");
+    }
+}
@@ -0,0 +1,149 @@
+use crate::options::ScaleOption;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+
+/// Where the Qt scale factor is written unless `qt_env_file` says otherwise
+const QT_ENV_FILE: &str = "~/.config/environment.d/90-sway-scale-switcher-qt.conf";
+
+/// Toolkit settings kept in step with the compositor scale, for apps that read their own
+/// scaling settings on top of sway's
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Toolkits {
+    /// Output whose scale drives the toolkit settings; defaults to the first changed output
+    pub output: Option<String>,
+    /// GNOME text-scaling-factor for each sway scale, e.g. `"2" = 1.25`
+    pub text_scaling: BTreeMap<String, f32>,
+    /// QT_SCALE_FACTOR for each sway scale
+    pub qt_scale_factor: BTreeMap<String, f32>,
+    /// environment.d drop-in the Qt factor is written to
+    pub qt_env_file: Option<String>,
+}
+
+/// Function to look up the toolkit factor for a sway scale; scales that are not listed get the
+/// neutral factor 1
+fn factor_for(mapping: &BTreeMap<String, f32>, scale: f32, epsilon: f32) -> f32 {
+    mapping
+        .iter()
+        .find(|(key, _)| {
+            key.parse::<f32>()
+                .is_ok_and(|key| (key - scale).abs() <= epsilon)
+        })
+        .map_or(1.0, |(_, &factor)| factor)
+}
+
+/// Function to check that every key of a mapping is a scale, so a typo is reported once
+fn check_keys(name: &str, mapping: &BTreeMap<String, f32>) {
+    for key in mapping.keys() {
+        if key.parse::<f32>().is_err() {
+            warning!(
+                "Ignoring '{}' under [toolkits.{}]; it is not a scale.",
+                key,
+                name
+            );
+        }
+    }
+}
+
+/// Function to update the toolkit settings for the output that just changed
+pub fn apply(toolkits: &Toolkits, scales: &BTreeMap<String, ScaleOption>, epsilon: f32) {
+    if toolkits.text_scaling.is_empty() && toolkits.qt_scale_factor.is_empty() {
+        return;
+    }
+    let change = match &toolkits.output {
+        Some(output) => scales.get(output),
+        None => scales.values().next(),
+    };
+    let Some(scale) = change.filter(|scale| !scale.off) else {
+        return;
+    };
+
+    if !toolkits.text_scaling.is_empty() {
+        check_keys("text_scaling", &toolkits.text_scaling);
+        let factor = factor_for(&toolkits.text_scaling, scale.scale, epsilon);
+        if let Err(e) = set_text_scaling(factor) {
+            warning!("Failed to set the GNOME text scaling factor: {}", e);
+        }
+    }
+    if !toolkits.qt_scale_factor.is_empty() {
+        check_keys("qt_scale_factor", &toolkits.qt_scale_factor);
+        let factor = factor_for(&toolkits.qt_scale_factor, scale.scale, epsilon);
+        let path = toolkits.qt_env_file.as_deref().unwrap_or(QT_ENV_FILE);
+        if let Err(e) = set_qt_scale_factor(path, factor) {
+            warning!("Failed to set the Qt scale factor: {}", e);
+        }
+    }
+}
+
+/// Function to set GNOME's text scaling, which GTK apps pick up right away
+fn set_text_scaling(factor: f32) -> io::Result<()> {
+    let status = process::Command::new("gsettings")
+        .args([
+            "set",
+            "org.gnome.desktop.interface",
+            "text-scaling-factor",
+            &factor.to_string(),
+        ])
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!(
+            "gsettings exited with {}",
+            status
+        )));
+    }
+    println!("Set GNOME text scaling to {}", factor);
+    Ok(())
+}
+
+/// Function to write the Qt scale factor to an environment.d drop-in and hand it to the user's
+/// systemd manager, so Qt apps started from now on use it
+fn set_qt_scale_factor(path: &str, factor: f32) -> io::Result<()> {
+    let path = crate::expanduser(path).unwrap_or(path.to_string());
+    if let Some(parent) = Path::new(&path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, qt_env_contents(factor))?;
+    println!("Set QT_SCALE_FACTOR to {} in {}", factor, path);
+
+    // Without a systemd user manager the drop-in still applies from the next login
+    let _ = process::Command::new("systemctl")
+        .args(["--user", "set-environment"])
+        .arg(format!("QT_SCALE_FACTOR={}", factor))
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .status();
+    Ok(())
+}
+
+/// Function to build the environment.d drop-in for a Qt scale factor
+fn qt_env_contents(factor: f32) -> String {
+    format!(
+        "# Written by sway-scale-switcher whenever the scale changes\nQT_SCALE_FACTOR={}\n",
+        factor
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_scales_to_toolkit_factors() {
+        let mapping = BTreeMap::from([
+            ("1.5".to_string(), 1.1),
+            ("2".to_string(), 1.25),
+            ("large".to_string(), 3.0),
+        ]);
+        assert_eq!(factor_for(&mapping, 2.0, 0.0005), 1.25);
+        assert_eq!(factor_for(&mapping, 1.5004, 0.0005), 1.1);
+        assert_eq!(factor_for(&mapping, 1.0, 0.0005), 1.0);
+        assert_eq!(
+            qt_env_contents(1.25).lines().last(),
+            Some("QT_SCALE_FACTOR=1.25")
+        );
+    }
+}
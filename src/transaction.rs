@@ -0,0 +1,705 @@
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// Held while a change reads and rewrites the config, so the daemon's threads never apply two
+/// changes at once or overwrite each other's journal
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+thread_local! {
+    /// Whether this thread holds the write lock further up the call stack
+    static HOLDING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// The hash of what this process last committed to each file, so the daemon can tell its own
+/// writes apart from the user's
+static WRITTEN: Mutex<BTreeMap<PathBuf, Fingerprint>> = Mutex::new(BTreeMap::new());
+
+/// The write lock, released on drop. Taking it again on a thread that holds it is a no-op, so a
+/// change can take it before reading the config and the transaction that writes it again.
+pub struct WriteLock(Option<MutexGuard<'static, ()>>);
+
+impl Drop for WriteLock {
+    fn drop(&mut self) {
+        if self.0.is_some() {
+            HOLDING.with(|holding| holding.set(false));
+        }
+    }
+}
+
+/// Function to wait for the write lock
+pub fn write_lock() -> WriteLock {
+    if HOLDING.with(Cell::get) {
+        return WriteLock(None);
+    }
+    let guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    HOLDING.with(|holding| holding.set(true));
+    WriteLock(Some(guard))
+}
+
+/// Function to check whether a file still holds what this process last committed to it
+pub fn written_here(path: &Path) -> bool {
+    let Ok(path) = fs::canonicalize(path) else {
+        return false;
+    };
+    let written = WRITTEN
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&path)
+        .copied();
+    written.is_some() && hash_file(&path).ok().flatten() == written
+}
+
+/// A file written to a temporary sibling, waiting to replace the original
+struct Staged {
+    path: PathBuf,
+    temp: PathBuf,
+    /// Contents before the change, or None if the file did not exist
+    original: Option<Vec<u8>>,
+    /// Copy of the original on disk, so it survives the process being killed mid-change
+    backup: Option<PathBuf>,
+    new_hash: Fingerprint,
+}
+
+/// Identifies a version of a file by its length and FNV-1a hash. Both are fixed by their
+/// definitions, so a journal written by one build is read the same way by the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Fingerprint {
+    len: u64,
+    fnv1a: u64,
+}
+
+/// How far a transaction got, as recorded in its journal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Phase {
+    /// New files were being written beside the originals, which were not touched yet
+    Staging,
+    /// The new files were being renamed into place
+    Committing,
+    /// Every file was in place and the running session was yet to pick them up
+    Applying,
+}
+
+/// One file of a transaction as the journal records it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct JournalEntry {
+    path: PathBuf,
+    temp: PathBuf,
+    backup: Option<PathBuf>,
+    original_hash: Option<Fingerprint>,
+    new_hash: Fingerprint,
+}
+
+/// A record of a transaction in progress, so one cut short by a crash or a kill can be undone
+/// or finished by the next run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Journal {
+    pid: u32,
+    phase: Phase,
+    files: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Function to explain what was interrupted and how to recover from it
+    pub fn describe(&self) -> String {
+        let files: Vec<String> = self
+            .files
+            .iter()
+            .map(|file| file.path.display().to_string())
+            .collect();
+        let phase = match self.phase {
+            Phase::Staging => "writing the new files",
+            Phase::Committing => "replacing the files",
+            Phase::Applying => "applying the change to sway",
+        };
+        format!(
+            "A change to {} was interrupted while {}. Run 'sway-scale-switcher recover' to put \
+             the previous config back, or 'sway-scale-switcher recover --keep' to finish the change.",
+            files.join(", "),
+            phase
+        )
+    }
+}
+
+/// A set of file replacements that either all happen or, as far as the filesystem allows,
+/// none do. Every new file is written out in full before the first one is renamed into place,
+/// and a journal records the progress until the change is done.
+pub struct Transaction {
+    staged: Vec<Staged>,
+    /// Held from the start of the transaction until its journal is removed
+    lock: WriteLock,
+    /// Where the journal goes; None keeps no journal
+    journal_path: Option<PathBuf>,
+    /// Whether this transaction has written the journal, and so owns it
+    journaled: bool,
+}
+
+/// The files of a committed transaction are in place; dropping this, once sway has picked them
+/// up or failed to, removes the journal and the backups
+pub struct Committed {
+    journal: Option<PathBuf>,
+    backups: Vec<PathBuf>,
+    _lock: WriteLock,
+}
+
+impl Drop for Committed {
+    fn drop(&mut self) {
+        for backup in &self.backups {
+            let _ = fs::remove_file(backup);
+        }
+        if let Some(journal) = &self.journal {
+            let _ = fs::remove_file(journal);
+        }
+    }
+}
+
+/// Function to locate the journal, next to the state file
+pub fn journal_path() -> Option<PathBuf> {
+    crate::state::state_path().map(|path| path.with_file_name("journal.json"))
+}
+
+/// Function to name a hidden file next to `path`, so a rename never crosses filesystems
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "config".to_string());
+    path.with_file_name(format!(".{}.sway-scale-switcher.{}", file_name, suffix))
+}
+
+/// Function to follow a chain of symlinks to the file it ends at, which may not exist yet.
+/// Writing there instead of renaming over the link keeps e.g. a config linked from a dotfiles
+/// repository a link.
+fn resolve_symlinks(path: &Path) -> io::Result<PathBuf> {
+    let mut path = path.to_path_buf();
+    // The kernel gives up after 40 links as well
+    for _ in 0..40 {
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                let target = fs::read_link(&path)?;
+                path = match path.parent() {
+                    Some(parent) if target.is_relative() => parent.join(target),
+                    _ => target,
+                };
+            }
+            _ => return Ok(path),
+        }
+    }
+    Err(io::Error::other(format!(
+        "Too many levels of symbolic links at {}",
+        path.display()
+    )))
+}
+
+/// Function to write a file in full and flush it to disk
+fn write_synced(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(contents)?;
+    writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+}
+
+/// Function to fingerprint file contents, to tell which version of a file is on disk
+fn hash(contents: &[u8]) -> Fingerprint {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    let fnv1a = contents.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    });
+    Fingerprint {
+        len: contents.len() as u64,
+        fnv1a,
+    }
+}
+
+/// Function to fingerprint a file, None if it does not exist
+fn hash_file(path: &Path) -> io::Result<Option<Fingerprint>> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(hash(&contents))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Function to read a journal, None if there is none
+fn read_journal(path: &Path) -> io::Result<Option<Journal>> {
+    match fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes).map(Some).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse {}: {}", path.display(), e),
+            )
+        }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Function to check whether the process that wrote a journal is still at work on it
+fn still_running(journal: &Journal) -> bool {
+    journal.pid != std::process::id() && Path::new(&format!("/proc/{}", journal.pid)).exists()
+}
+
+/// Function to remove the temporary files and backups a journal lists, and the journal itself
+fn discard(path: &Path, journal: &Journal) {
+    for file in &journal.files {
+        let _ = fs::remove_file(&file.temp);
+        if let Some(backup) = &file.backup {
+            let _ = fs::remove_file(backup);
+        }
+    }
+    let _ = fs::remove_file(path);
+}
+
+/// Function to look for a change an earlier run left unfinished. One interrupted before any
+/// file was replaced is cleaned up on the spot; any other is returned for the user to recover.
+pub fn check_interrupted() -> io::Result<Option<Journal>> {
+    match journal_path() {
+        Some(path) => check_journal(&path),
+        None => Ok(None),
+    }
+}
+
+fn check_journal(path: &Path) -> io::Result<Option<Journal>> {
+    let Some(journal) = read_journal(path)? else {
+        return Ok(None);
+    };
+    if still_running(&journal) {
+        return Ok(None);
+    }
+    if journal.phase == Phase::Staging {
+        discard(path, &journal);
+        println!("Removed the temporary files of an interrupted change; no file was modified.");
+        return Ok(None);
+    }
+    Ok(Some(journal))
+}
+
+/// Function to finish an interrupted change, or with `keep` false undo it, after checking that
+/// each file still holds one of the two versions the journal knows. Files edited since are left
+/// alone. Returns whether there was anything to recover.
+pub fn recover(keep: bool) -> io::Result<bool> {
+    match journal_path() {
+        Some(path) => recover_journal(&path, keep),
+        None => Ok(false),
+    }
+}
+
+fn recover_journal(path: &Path, keep: bool) -> io::Result<bool> {
+    let Some(journal) = read_journal(path)? else {
+        return Ok(false);
+    };
+    if still_running(&journal) {
+        return Err(io::Error::other(format!(
+            "The change is still being made by PID {}.",
+            journal.pid
+        )));
+    }
+
+    let mut untouched = Vec::new();
+    for file in &journal.files {
+        let current = hash_file(&file.path)?;
+        let is_new = current == Some(file.new_hash);
+        let is_original = current == file.original_hash;
+        if keep && !is_new && is_original && hash_file(&file.temp)? == Some(file.new_hash) {
+            fs::rename(&file.temp, &file.path)?;
+            println!("Finished replacing {}", file.path.display());
+        } else if !keep && is_new && !is_original {
+            match &file.backup {
+                Some(backup) => fs::rename(backup, &file.path)?,
+                None => fs::remove_file(&file.path)?,
+            }
+            println!("Restored {}", file.path.display());
+        } else if !is_new && !is_original {
+            warning!(
+                "{} was modified after the interrupted change, so it is left as it is.",
+                file.path.display()
+            );
+            if let Some(backup) = &file.backup {
+                untouched.push(backup);
+                eprintln!("Its contents before the change are in {}", backup.display());
+            }
+        }
+    }
+
+    // Backups the user may still need are kept; everything else goes with the journal
+    for file in &journal.files {
+        let _ = fs::remove_file(&file.temp);
+        if let Some(backup) = file
+            .backup
+            .as_ref()
+            .filter(|backup| !untouched.contains(backup))
+        {
+            let _ = fs::remove_file(backup);
+        }
+    }
+    fs::remove_file(path)?;
+    Ok(true)
+}
+
+impl Default for Transaction {
+    fn default() -> Self {
+        Transaction::new()
+    }
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Transaction::with_journal(journal_path())
+    }
+
+    /// Function to start a transaction that keeps its journal at `journal_path`
+    pub fn with_journal(journal_path: Option<PathBuf>) -> Self {
+        Transaction {
+            staged: Vec::new(),
+            lock: write_lock(),
+            journal_path,
+            journaled: false,
+        }
+    }
+
+    /// Function to record the staged files and how far the change has got
+    fn save_journal(&mut self, phase: Phase) -> io::Result<()> {
+        let Some(path) = &self.journal_path else {
+            return Ok(());
+        };
+        let journal = Journal {
+            pid: std::process::id(),
+            phase,
+            files: self
+                .staged
+                .iter()
+                .map(|file| JournalEntry {
+                    path: file.path.clone(),
+                    temp: file.temp.clone(),
+                    backup: file.backup.clone(),
+                    original_hash: file.original.as_deref().map(hash),
+                    new_hash: file.new_hash,
+                })
+                .collect(),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_vec_pretty(&journal).map_err(io::Error::other)?;
+        let temp = sibling(path, "tmp");
+        write_synced(&temp, &json)?;
+        fs::rename(&temp, path)?;
+        self.journaled = true;
+        Ok(())
+    }
+
+    /// Function to write the new contents of `path` to a temporary file beside it, keeping a
+    /// copy of the old contents until the change is done. A symlink is followed, so the file it
+    /// points at is replaced and the link stays.
+    pub fn stage(&mut self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        // An unfinished change from an earlier run has to be dealt with first
+        if !self.journaled {
+            if let Some(journal_path) = &self.journal_path {
+                if let Some(journal) = check_journal(journal_path)? {
+                    return Err(io::Error::other(journal.describe()));
+                }
+            }
+        }
+
+        let path = &resolve_symlinks(path)?;
+        let original = match fs::read(path) {
+            Ok(original) => Some(original),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        let temp = sibling(path, "tmp");
+        let backup = original.as_ref().map(|_| sibling(path, "bak"));
+        let written = write_synced(&temp, contents).and_then(|()| match (&backup, &original) {
+            (Some(backup), Some(original)) => write_synced(backup, original),
+            _ => Ok(()),
+        });
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp);
+            if let Some(backup) = &backup {
+                let _ = fs::remove_file(backup);
+            }
+            return Err(io::Error::new(
+                e.kind(),
+                format!("Failed to write {}: {}", path.display(), e),
+            ));
+        }
+        self.staged.push(Staged {
+            path: path.to_path_buf(),
+            temp,
+            original,
+            backup,
+            new_hash: hash(contents),
+        });
+        self.save_journal(Phase::Staging)
+    }
+
+    /// Function to move every staged file into place in the order they were staged. If a
+    /// rename fails, the files already replaced get their old contents back.
+    pub fn commit(mut self) -> io::Result<Committed> {
+        self.save_journal(Phase::Committing)?;
+
+        // Noted before the renames, so a watcher woken by one already knows it
+        let mut written = WRITTEN.lock().unwrap_or_else(|e| e.into_inner());
+        for file in &self.staged {
+            let path = fs::canonicalize(file.path.parent().unwrap_or(Path::new(".")))
+                .map(|dir| dir.join(file.path.file_name().unwrap_or_default()))
+                .unwrap_or_else(|_| file.path.clone());
+            written.insert(path, file.new_hash);
+        }
+        drop(written);
+
+        for (i, file) in self.staged.iter().enumerate() {
+            let Err(e) = fs::rename(&file.temp, &file.path) else {
+                continue;
+            };
+            for done in self.staged[..i].iter().rev() {
+                if let Err(restore_error) = restore(done) {
+                    warning!(
+                        "Could not restore {}: {}",
+                        done.path.display(),
+                        restore_error
+                    );
+                }
+            }
+            // Dropping the transaction removes the remaining temporary files and the journal
+            return Err(io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to replace {}: {}; no file was changed",
+                    file.path.display(),
+                    e
+                ),
+            ));
+        }
+        self.save_journal(Phase::Applying)?;
+
+        let staged = std::mem::take(&mut self.staged);
+        let journal = if self.journaled {
+            self.journal_path.take()
+        } else {
+            None
+        };
+        Ok(Committed {
+            journal,
+            backups: staged.into_iter().filter_map(|file| file.backup).collect(),
+            _lock: std::mem::replace(&mut self.lock, WriteLock(None)),
+        })
+    }
+}
+
+/// Function to put a replaced file's old contents back, atomically like the change itself
+fn restore(file: &Staged) -> io::Result<()> {
+    let Some(original) = &file.original else {
+        return fs::remove_file(&file.path);
+    };
+    let backup = sibling(&file.path, "restore");
+    write_synced(&backup, original)?;
+    fs::rename(&backup, &file.path)
+}
+
+impl Drop for Transaction {
+    /// A transaction dropped without committing leaves no temporary files behind
+    fn drop(&mut self) {
+        for file in &self.staged {
+            let _ = fs::remove_file(&file.temp);
+            if let Some(backup) = &file.backup {
+                let _ = fs::remove_file(backup);
+            }
+        }
+        if self.journaled {
+            if let Some(path) = &self.journal_path {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "sway-scale-switcher-transaction-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn replaces_every_file_or_none() {
+        let dir = temp_dir("commit");
+        let first = dir.join("config");
+        let second = dir.join("outputs");
+        fs::write(&first, "old first").unwrap();
+        fs::write(&second, "old second").unwrap();
+
+        let journal = Some(dir.join("journal.json"));
+        let mut transaction = Transaction::with_journal(journal.clone());
+        transaction.stage(&first, b"new first").unwrap();
+        transaction.stage(&second, b"new second").unwrap();
+        drop(transaction.commit().unwrap());
+        assert_eq!(fs::read_to_string(&first).unwrap(), "new first");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "new second");
+
+        // The second rename fails because its temporary file has gone missing
+        let mut transaction = Transaction::with_journal(journal);
+        transaction.stage(&first, b"newer first").unwrap();
+        transaction.stage(&second, b"newer second").unwrap();
+        fs::remove_file(sibling(&second, "tmp")).unwrap();
+        assert!(transaction.commit().is_err());
+        assert_eq!(fs::read_to_string(&first).unwrap(), "new first");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "new second");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn writes_through_symlinks() {
+        let dir = temp_dir("symlink");
+        fs::create_dir_all(dir.join("dotfiles/sway")).unwrap();
+        let target = dir.join("dotfiles/sway/config");
+        fs::write(&target, "old").unwrap();
+        let link = dir.join("config");
+        let chained = dir.join("config.link");
+        std::os::unix::fs::symlink("dotfiles/sway/config", &link).unwrap();
+        std::os::unix::fs::symlink(&link, &chained).unwrap();
+
+        let mut transaction = Transaction::with_journal(None);
+        transaction.stage(&chained, b"new").unwrap();
+        drop(transaction.commit().unwrap());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(fs::symlink_metadata(&chained)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_dir(dir.join("dotfiles/sway")).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn changes_take_turns_and_know_their_own_writes() {
+        let dir = temp_dir("lock");
+        let config = dir.join("config");
+        fs::write(&config, "old").unwrap();
+
+        // A thread that holds the lock can take it again; another one waits for it
+        let outer = write_lock();
+        let mut transaction = Transaction::with_journal(None);
+        transaction.stage(&config, b"new").unwrap();
+        let waiting = std::thread::spawn(|| drop(write_lock()));
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!waiting.is_finished());
+        drop(transaction.commit().unwrap());
+        drop(outer);
+        waiting.join().unwrap();
+
+        assert!(written_here(&config));
+        fs::write(&config, "edited").unwrap();
+        assert!(!written_here(&config));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn fingerprints_do_not_depend_on_the_build() {
+        // Published FNV-1a test vectors, which no toolchain update can change
+        assert_eq!(
+            hash(b""),
+            Fingerprint {
+                len: 0,
+                fnv1a: 0xcbf2_9ce4_8422_2325
+            }
+        );
+        assert_eq!(
+            hash(b"foobar"),
+            Fingerprint {
+                len: 6,
+                fnv1a: 0x8594_4171_f739_67e8
+            }
+        );
+    }
+
+    #[test]
+    fn dropping_removes_the_staged_files() {
+        let dir = temp_dir("drop");
+        let config = dir.join("config");
+        fs::write(&config, "old").unwrap();
+        let mut transaction = Transaction::with_journal(Some(dir.join("journal.json")));
+        transaction.stage(&config, b"new").unwrap();
+        drop(transaction);
+        assert_eq!(fs::read_to_string(&config).unwrap(), "old");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Function to stage new contents for both files and stop as a killed process would,
+    /// halfway through the renames
+    fn interrupt(dir: &Path, journal: &Path) -> (PathBuf, PathBuf) {
+        let first = dir.join("config");
+        let second = dir.join("outputs");
+        fs::write(&first, "old first").unwrap();
+        fs::write(&second, "old second").unwrap();
+        let mut transaction = Transaction::with_journal(Some(journal.to_path_buf()));
+        transaction.stage(&first, b"new first").unwrap();
+        transaction.stage(&second, b"new second").unwrap();
+        transaction.save_journal(Phase::Committing).unwrap();
+        fs::rename(sibling(&first, "tmp"), &first).unwrap();
+        // A killed process leaves its files behind but no longer holds the lock
+        drop(std::mem::replace(&mut transaction.lock, WriteLock(None)));
+        std::mem::forget(transaction);
+        (first, second)
+    }
+
+    #[test]
+    fn recovers_an_interrupted_change() {
+        let dir = temp_dir("recover");
+        let journal = dir.join("journal.json");
+
+        // Undoing puts the replaced file back and cleans up
+        let (first, second) = interrupt(&dir, &journal);
+        let pending = check_journal(&journal).unwrap().unwrap();
+        assert!(pending.describe().contains("while replacing the files"));
+        let mut blocked = Transaction::with_journal(Some(journal.clone()));
+        assert!(blocked.stage(&first, b"other").is_err());
+        assert!(recover_journal(&journal, false).unwrap());
+        assert_eq!(fs::read_to_string(&first).unwrap(), "old first");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "old second");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // Keeping finishes the renames instead
+        interrupt(&dir, &journal);
+        assert!(recover_journal(&journal, true).unwrap());
+        assert_eq!(fs::read_to_string(&first).unwrap(), "new first");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "new second");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        assert!(!recover_journal(&journal, true).unwrap());
+
+        // A file edited since is left alone, with its backup kept
+        interrupt(&dir, &journal);
+        fs::write(&first, "edited").unwrap();
+        assert!(recover_journal(&journal, false).unwrap());
+        assert_eq!(fs::read_to_string(&first).unwrap(), "edited");
+        assert_eq!(
+            fs::read_to_string(sibling(&first, "bak")).unwrap(),
+            "old first"
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by `--no-color` or the `NO_COLOR` environment variable
+static COLOR_DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Print a warning to stderr, with a translated prefix that is yellow when stderr is a terminal
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        eprintln!("{} {}", $crate::ui::prefix(&tr!(Warning), $crate::ui::Style::Yellow), format_args!($($arg)*))
+    };
+}
+
+/// Print an error to stderr, with a translated prefix that is red when stderr is a terminal
+macro_rules! error {
+    ($($arg:tt)*) => {
+        eprintln!("{} {}", $crate::ui::prefix(&tr!(Error), $crate::ui::Style::Red), format_args!($($arg)*))
+    };
+}
+
+/// The few styles the output uses
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    Plain,
+    Bold,
+    Dim,
+    Red,
+    Green,
+    Yellow,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Plain => "",
+            Style::Bold => "1",
+            Style::Dim => "2",
+            Style::Red => "31",
+            Style::Green => "1;32",
+            Style::Yellow => "1;33",
+        }
+    }
+}
+
+/// Function to turn colors off for the rest of the run, following `--no-color` and the
+/// `NO_COLOR` convention (any non-empty value disables colors)
+pub fn init(no_color: bool) {
+    let from_env = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    COLOR_DISABLED.store(no_color || from_env, Ordering::Relaxed);
+}
+
+/// Function to wrap text in an escape sequence if `enabled`
+fn paint_if(enabled: bool, text: impl Display, style: Style) -> String {
+    if !enabled || style == Style::Plain {
+        return text.to_string();
+    }
+    format!("\x1b[{}m{}\x1b[0m", style.code(), text)
+}
+
+/// Function to style text printed to stdout, leaving it plain when piped or colors are off
+pub fn paint(text: impl Display, style: Style) -> String {
+    let enabled = !COLOR_DISABLED.load(Ordering::Relaxed) && std::io::stdout().is_terminal();
+    paint_if(enabled, text, style)
+}
+
+/// Function to style the prefix of a message printed to stderr
+pub fn prefix(text: &str, style: Style) -> String {
+    let enabled = !COLOR_DISABLED.load(Ordering::Relaxed) && std::io::stderr().is_terminal();
+    paint_if(enabled, text, style)
+}
+
+/// Function to show a change as `before -> after`, the old value dimmed and the new one highlighted
+pub fn change(before: impl Display, after: impl Display) -> String {
+    format!(
+        "{} -> {}",
+        paint(before, Style::Dim),
+        paint(after, Style::Green)
+    )
+}
+
+/// Function to print rows under a header with every column padded to its widest cell.
+/// Cells are padded before they are styled, so escape sequences never skew the alignment.
+pub fn print_table(headers: &[&str], rows: &[Vec<(String, Style)>]) {
+    let mut widths: Vec<usize> = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect();
+    for row in rows {
+        for (width, (text, _)) in widths.iter_mut().zip(row) {
+            *width = (*width).max(text.chars().count());
+        }
+    }
+
+    let render = |cells: Vec<(String, Style)>| {
+        let last = cells.len().saturating_sub(1);
+        let line: Vec<String> = cells
+            .into_iter()
+            .enumerate()
+            .map(|(i, (text, style))| {
+                // The last column is not padded, so lines carry no trailing spaces
+                let padded = if i == last {
+                    text
+                } else {
+                    format!("{:<width$}", text, width = widths[i])
+                };
+                paint(padded, style)
+            })
+            .collect();
+        println!("{}", line.join("  "));
+    };
+
+    render(
+        headers
+            .iter()
+            .map(|header| (header.to_string(), Style::Bold))
+            .collect(),
+    );
+    for row in rows {
+        render(row.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn styles_only_when_enabled() {
+        assert_eq!(paint_if(false, "1.5", Style::Green), "1.5");
+        assert_eq!(paint_if(true, "1.5", Style::Plain), "1.5");
+        assert_eq!(paint_if(true, "1.5", Style::Green), "\x1b[1;32m1.5\x1b[0m");
+    }
+}
@@ -0,0 +1,266 @@
+use crate::options::ScaleOption;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How thoroughly a rewritten config is checked before it replaces the old one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Validation {
+    /// Trust the rewrite
+    #[default]
+    None,
+    /// Run `sway --validate` on the new config
+    Parse,
+    /// Also load the new config in a headless sway and compare the scales it reports
+    Headless,
+}
+
+/// Function to check a rewritten config before it is written, using a candidate file beside it
+/// so relative `include` paths resolve the same way
+pub fn check(
+    validation: Validation,
+    config_path: &Path,
+    lines: &[String],
+    expected: &BTreeMap<String, ScaleOption>,
+    epsilon: f32,
+) -> io::Result<()> {
+    if validation == Validation::None {
+        return Ok(());
+    }
+
+    let original = match fs::read(config_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    let candidate = sibling(config_path, "candidate");
+    fs::write(&candidate, crate::text::encode_lines(&original, lines))?;
+    let result = check_parse(&candidate).and_then(|()| {
+        if validation != Validation::Headless {
+            return Ok(());
+        }
+        let headless = sibling(config_path, "headless");
+        fs::write(
+            &headless,
+            headless_config(lines, expected).join("\n") + "\n",
+        )?;
+        let result = check_headless(&headless, expected, epsilon);
+        let _ = fs::remove_file(&headless);
+        result
+    });
+    let _ = fs::remove_file(&candidate);
+    result
+}
+
+/// Function to name a hidden file next to the config
+fn sibling(config_path: &Path, suffix: &str) -> PathBuf {
+    let file_name = config_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "config".to_string());
+    config_path.with_file_name(format!(".{}.sway-scale-switcher.{}", file_name, suffix))
+}
+
+/// Function to start sway, explaining what to do if it is not installed
+fn spawn_sway(command: &mut Command) -> io::Result<Child> {
+    command.spawn().map_err(|e| {
+        if e.kind() == io::ErrorKind::NotFound {
+            io::Error::other(
+                "Validating the config needs sway on PATH; set validate = \"none\" to skip it.",
+            )
+        } else {
+            e
+        }
+    })
+}
+
+/// Function to let sway parse the candidate config
+fn check_parse(candidate: &Path) -> io::Result<()> {
+    let output = spawn_sway(
+        Command::new("sway")
+            .arg("--validate")
+            .arg("--config")
+            .arg(candidate)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+    )?
+    .wait_with_output()?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "sway rejected the new config; it was not written:\n{}",
+            message
+        ),
+    ))
+}
+
+/// Function to build a config for the headless check. Headless outputs are named HEADLESS-1
+/// and up, so each target display's output lines are renamed to one of them, and `exec`
+/// lines are dropped so the check never starts the user's programs.
+fn headless_config(lines: &[String], expected: &BTreeMap<String, ScaleOption>) -> Vec<String> {
+    let names: BTreeMap<&str, String> = expected
+        .keys()
+        .enumerate()
+        .map(|(i, display)| (display.as_str(), format!("HEADLESS-{}", i + 1)))
+        .collect();
+
+    lines
+        .iter()
+        .filter(|line| {
+            let command = line.split_whitespace().next().unwrap_or("");
+            command != "exec" && command != "exec_always"
+        })
+        .map(|line| {
+            let trimmed = line.trim_start();
+            let Some(rest) = trimmed.strip_prefix("output ") else {
+                return line.clone();
+            };
+            let rest = rest.trim_start();
+            let (name, tail) = match rest.strip_prefix('"') {
+                Some(quoted) => match quoted.split_once('"') {
+                    Some((name, tail)) => (name, tail),
+                    None => return line.clone(),
+                },
+                None => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+            };
+            match names.get(name) {
+                Some(headless) => {
+                    let indent = &line[..line.len() - trimmed.len()];
+                    format!("{}output {}{}", indent, headless, tail)
+                }
+                None => line.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Function to load the headless config in a throwaway sway and compare the scales it applied
+fn check_headless(
+    config: &Path,
+    expected: &BTreeMap<String, ScaleOption>,
+    epsilon: f32,
+) -> io::Result<()> {
+    let runtime_dir = std::env::temp_dir().join(format!(
+        "sway-scale-switcher-validate-{}",
+        std::process::id()
+    ));
+    fs::create_dir_all(&runtime_dir)?;
+
+    let mut sway = spawn_sway(
+        Command::new("sway")
+            .arg("--config")
+            .arg(config)
+            .env("XDG_RUNTIME_DIR", &runtime_dir)
+            .env("WLR_BACKENDS", "headless")
+            .env("WLR_HEADLESS_OUTPUTS", expected.len().to_string())
+            .env("WLR_LIBINPUT_NO_DEVICES", "1")
+            .env_remove("WAYLAND_DISPLAY")
+            .env_remove("SWAYSOCK")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null()),
+    )?;
+    let result = compare_headless_scales(&runtime_dir, expected, epsilon);
+    let _ = sway.kill();
+    let _ = sway.wait();
+    let _ = fs::remove_dir_all(&runtime_dir);
+    result
+}
+
+/// Function to wait for the headless sway's socket, then check every target's scale over IPC
+fn compare_headless_scales(
+    runtime_dir: &Path,
+    expected: &BTreeMap<String, ScaleOption>,
+    epsilon: f32,
+) -> io::Result<()> {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let socket = loop {
+        if let Some(socket) = crate::ipc::discover_sockets(runtime_dir).pop() {
+            break socket;
+        }
+        if Instant::now() > deadline {
+            return Err(io::Error::other(
+                "The headless sway did not start within 10 seconds; the config was not written.",
+            ));
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+
+    let output = Command::new("swaymsg")
+        .arg("-s")
+        .arg(&socket)
+        .args(["-t", "get_outputs", "-r"])
+        .output()?;
+    let outputs: Vec<crate::ipc::Output> = serde_json::from_slice(&output.stdout)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut mismatches = Vec::new();
+    for (i, (display, scale)) in expected.iter().enumerate() {
+        let headless = format!("HEADLESS-{}", i + 1);
+        let actual = outputs
+            .iter()
+            .find(|output| output.name == headless)
+            .and_then(|output| output.scale);
+        if actual.is_none_or(|actual| (actual - scale.scale).abs() > epsilon) {
+            mismatches.push(format!(
+                "{} should be {} but is {}",
+                display,
+                scale.scale,
+                actual.map_or_else(|| "unknown".to_string(), |actual| actual.to_string())
+            ));
+        }
+    }
+    if mismatches.is_empty() {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "A headless sway did not apply the new scales, so the config was not written: {}",
+            mismatches.join("; ")
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renames_targets_and_drops_exec_lines() {
+        let lines: Vec<String> = [
+            "exec swayidle",
+            "output \"eDP-1\" scale 2 pos 0 0",
+            "  output HDMI-A-1 {",
+            "    scale 1.5",
+            "  }",
+            "output DP-9 scale 1",
+        ]
+        .iter()
+        .map(|line| line.to_string())
+        .collect();
+        let expected = BTreeMap::from([
+            ("HDMI-A-1".to_string(), ScaleOption::scale(1.5)),
+            ("eDP-1".to_string(), ScaleOption::scale(2.0)),
+        ]);
+        assert_eq!(
+            headless_config(&lines, &expected),
+            vec![
+                "output HEADLESS-2 scale 2 pos 0 0",
+                "  output HEADLESS-1 {",
+                "    scale 1.5",
+                "  }",
+                "output DP-9 scale 1",
+            ]
+        );
+    }
+}
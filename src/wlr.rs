@@ -0,0 +1,270 @@
+use crate::ipc::{Mode, Output, Rect};
+use serde::Deserialize;
+use std::io;
+use std::process;
+
+/// An output as reported by `wlr-randr --json`
+#[derive(Debug, Deserialize)]
+struct WlrOutput {
+    name: String,
+    #[serde(default)]
+    make: Option<String>,
+    #[serde(default)]
+    model: Option<String>,
+    #[serde(default)]
+    serial: Option<String>,
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default)]
+    modes: Vec<WlrMode>,
+    #[serde(default)]
+    position: Option<Position>,
+    #[serde(default)]
+    transform: Option<String>,
+    #[serde(default)]
+    scale: Option<f32>,
+    #[serde(default)]
+    adaptive_sync: Option<bool>,
+}
+
+/// A mode as reported by `wlr-randr --json`, with the refresh rate in hertz
+#[derive(Debug, Deserialize)]
+struct WlrMode {
+    width: i32,
+    height: i32,
+    #[serde(default)]
+    refresh: f32,
+    #[serde(default)]
+    current: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Position {
+    x: i32,
+    y: i32,
+}
+
+impl WlrMode {
+    fn to_mode(&self) -> Mode {
+        Mode {
+            width: self.width,
+            height: self.height,
+            refresh: (self.refresh * 1000.0).round() as i32,
+        }
+    }
+}
+
+impl WlrOutput {
+    /// Function to describe the output the way sway's IPC does, working out the logical size
+    /// from the mode, the scale and the rotation
+    fn into_output(self) -> Output {
+        let current_mode = self
+            .modes
+            .iter()
+            .find(|mode| mode.current)
+            .map(WlrMode::to_mode);
+        let scale = self.scale.unwrap_or(1.0);
+        let rotated = self
+            .transform
+            .as_deref()
+            .is_some_and(|transform| transform.ends_with("90") || transform.ends_with("270"));
+        let position = self.position.unwrap_or_default();
+        let rect = match &current_mode {
+            Some(mode) if self.enabled && scale > 0.0 => {
+                let (width, height) = if rotated {
+                    (mode.height, mode.width)
+                } else {
+                    (mode.width, mode.height)
+                };
+                Rect {
+                    x: position.x,
+                    y: position.y,
+                    width: (width as f32 / scale).round() as i32,
+                    height: (height as f32 / scale).round() as i32,
+                }
+            }
+            _ => Rect::default(),
+        };
+        Output {
+            name: self.name,
+            make: self.make.unwrap_or_default(),
+            model: self.model.unwrap_or_default(),
+            serial: self.serial.unwrap_or_default(),
+            active: self.enabled,
+            // Power management is a separate protocol that wlr-randr does not speak
+            power: None,
+            dpms: None,
+            scale: self.scale,
+            current_mode,
+            modes: self.modes.iter().map(WlrMode::to_mode).collect(),
+            render_bit_depth: None,
+            hdr: None,
+            adaptive_sync_status: self
+                .adaptive_sync
+                .map(|on| if on { "enabled" } else { "disabled" }.to_string()),
+            // Filtering, hinting and frame timing are up to the compositor
+            scale_filter: None,
+            subpixel_hinting: None,
+            max_render_time: None,
+            rect,
+        }
+    }
+}
+
+/// Function to read the outputs from `wlr-randr --json` output
+fn parse_outputs(json: &[u8]) -> io::Result<Vec<Output>> {
+    let outputs: Vec<WlrOutput> =
+        serde_json::from_slice(json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(outputs.into_iter().map(WlrOutput::into_output).collect())
+}
+
+/// Function to turn an `output` command as sway takes it into wlr-randr arguments, failing on
+/// the parts the output management protocol has no equivalent for
+fn command_args(command: &str) -> io::Result<Vec<String>> {
+    let words = crate::mock::words(command);
+    let unsupported = |what: &str| {
+        io::Error::other(format!(
+            "The wlr-randr backend cannot apply '{}' in '{}'",
+            what, command
+        ))
+    };
+    if words.first().map(String::as_str) != Some("output") || words.len() < 2 {
+        return Err(unsupported(words.first().map_or("", String::as_str)));
+    }
+
+    let mut args = vec!["--output".to_string(), words[1].clone()];
+    let mut rest = words[2..].iter().map(String::as_str);
+    let value = |rest: &mut dyn Iterator<Item = &str>, what: &str| {
+        rest.next()
+            .map(str::to_string)
+            .ok_or_else(|| io::Error::other(format!("Missing {} in '{}'", what, command)))
+    };
+    while let Some(word) = rest.next() {
+        match word {
+            "scale" => {
+                args.push("--scale".to_string());
+                args.push(value(&mut rest, "scale")?);
+            }
+            "mode" | "resolution" | "res" => {
+                args.push("--mode".to_string());
+                args.push(value(&mut rest, "mode")?);
+            }
+            "position" | "pos" => {
+                let x = value(&mut rest, "position")?;
+                let y = value(&mut rest, "position")?;
+                args.push("--pos".to_string());
+                args.push(format!("{},{}", x, y));
+            }
+            "transform" => {
+                args.push("--transform".to_string());
+                args.push(value(&mut rest, "transform")?);
+            }
+            "adaptive_sync" => {
+                let enabled = match value(&mut rest, "adaptive_sync")?.as_str() {
+                    "on" | "enable" => "enabled",
+                    _ => "disabled",
+                };
+                args.push("--adaptive-sync".to_string());
+                args.push(enabled.to_string());
+            }
+            // Filtering, hinting and frame timing are up to the compositor, so they are left out
+            "scale_filter" | "subpixel" | "max_render_time" => {
+                value(&mut rest, word)?;
+            }
+            "enable" => args.push("--on".to_string()),
+            "disable" => args.push("--off".to_string()),
+            other => return Err(unsupported(other)),
+        }
+    }
+    Ok(args)
+}
+
+/// Function to check whether a compositor answers wlr-randr
+pub fn is_running() -> bool {
+    process::Command::new("wlr-randr")
+        .arg("--json")
+        .stdout(process::Stdio::null())
+        .stderr(process::Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Function to query the connected outputs with wlr-randr
+pub fn get_outputs() -> io::Result<Vec<Output>> {
+    let output = process::Command::new("wlr-randr").arg("--json").output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "wlr-randr --json failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    parse_outputs(&output.stdout)
+}
+
+/// Function to apply an `output` command with wlr-randr
+pub fn run_command(command: &str) -> io::Result<()> {
+    let args = command_args(command)?;
+    let output = process::Command::new("wlr-randr").args(&args).output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "wlr-randr {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_output_commands() {
+        assert_eq!(
+            command_args("output \"HDMI-A-1\" mode 2560x1440@59.951Hz pos 1920 0 scale 1.5")
+                .unwrap(),
+            [
+                "--output",
+                "HDMI-A-1",
+                "--mode",
+                "2560x1440@59.951Hz",
+                "--pos",
+                "1920,0",
+                "--scale",
+                "1.5"
+            ]
+        );
+        assert_eq!(
+            command_args("output eDP-1 disable").unwrap(),
+            ["--output", "eDP-1", "--off"]
+        );
+        assert_eq!(
+            command_args("output eDP-1 scale 2 subpixel none max_render_time off").unwrap(),
+            ["--output", "eDP-1", "--scale", "2"]
+        );
+        assert!(command_args("output eDP-1 power off").is_err());
+        assert!(command_args("workspace 1 output eDP-1").is_err());
+    }
+
+    #[test]
+    fn reads_wlr_randr_json() {
+        let json = br#"[{"name":"eDP-1","description":"Sharp 0x1234","make":"Sharp",
+            "model":"LQ134N1","serial":null,"enabled":true,
+            "modes":[{"width":2560,"height":1600,"refresh":60.002,"preferred":true,"current":true},
+                     {"width":1920,"height":1200,"refresh":60.0,"preferred":false,"current":false}],
+            "position":{"x":0,"y":0},"transform":"90","scale":2.0,"adaptive_sync":false}]"#;
+        let outputs = parse_outputs(json).unwrap();
+        let output = &outputs[0];
+        assert_eq!(output.description(), "Sharp LQ134N1 ");
+        assert_eq!(output.scale, Some(2.0));
+        assert_eq!(output.modes.len(), 2);
+        assert_eq!(
+            output.current_mode.as_ref().map(Mode::to_arg),
+            Some("2560x1600@60.002Hz".to_string())
+        );
+        assert_eq!((output.rect.width, output.rect.height), (800, 1280));
+        assert!(output.is_powered());
+    }
+}
@@ -0,0 +1,138 @@
+use crate::ipc::{Output, Workspace};
+use crate::settings::{OutputSettings, WorkspaceOverride};
+use std::collections::BTreeMap;
+
+/// The scales outputs had before a workspace override took effect, so they can be restored
+#[derive(Debug, Default)]
+pub struct Overrides {
+    saved: BTreeMap<String, f32>,
+}
+
+/// Function to find the override for the workspace visible on an output, if there is one
+fn find_override<'a>(
+    overrides: &'a [WorkspaceOverride],
+    workspaces: &[Workspace],
+    output: &Output,
+) -> Option<&'a WorkspaceOverride> {
+    let visible = workspaces
+        .iter()
+        .find(|workspace| workspace.visible && workspace.output == output.name)?;
+    overrides.iter().find(|entry| {
+        entry.workspace == visible.name
+            && entry.output.as_ref().is_none_or(|criteria| {
+                criteria == &output.name || criteria == &output.description()
+            })
+    })
+}
+
+impl Overrides {
+    /// Function to work out the scale changes for the visible workspaces: apply an override when
+    /// its workspace shows up, remembering the scale it replaced, and restore that scale once no
+    /// override applies to the output anymore
+    pub fn update(
+        &mut self,
+        overrides: &[WorkspaceOverride],
+        workspaces: &[Workspace],
+        outputs: &[Output],
+        epsilon: f32,
+    ) -> Vec<OutputSettings> {
+        let mut changes = Vec::new();
+        for output in outputs {
+            let Some(current) = output.scale else {
+                continue;
+            };
+            let target = match find_override(overrides, workspaces, output) {
+                Some(entry) => {
+                    // Only the scale from before the first override is worth restoring
+                    self.saved.entry(output.name.clone()).or_insert(current);
+                    entry.scale
+                }
+                None => match self.saved.remove(&output.name) {
+                    Some(previous) => previous,
+                    None => continue,
+                },
+            };
+            if (target - current).abs() > epsilon {
+                changes.push(OutputSettings {
+                    name: output.name.clone(),
+                    scale: Some(target),
+                    ..Default::default()
+                });
+            }
+        }
+
+        // Outputs that were unplugged while overridden have nothing left to restore
+        self.saved
+            .retain(|name, _| outputs.iter().any(|output| &output.name == name));
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 0.0005;
+
+    fn output(name: &str, scale: f32) -> Output {
+        serde_json::from_value(serde_json::json!({ "name": name, "scale": scale })).unwrap()
+    }
+
+    fn workspace(name: &str, output: &str) -> Workspace {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "output": output,
+            "visible": true,
+        }))
+        .unwrap()
+    }
+
+    fn media_on_tv() -> Vec<WorkspaceOverride> {
+        vec![WorkspaceOverride {
+            workspace: "9".to_string(),
+            output: Some("HDMI-A-1".to_string()),
+            scale: 1.0,
+        }]
+    }
+
+    fn scales(changes: &[OutputSettings]) -> Vec<(&str, f32)> {
+        changes
+            .iter()
+            .map(|change| (change.name.as_str(), change.scale.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn applies_and_restores_the_override() {
+        let overrides = media_on_tv();
+        let mut state = Overrides::default();
+        let workspaces = [workspace("1", "eDP-1"), workspace("9", "HDMI-A-1")];
+        let outputs = [output("eDP-1", 2.0), output("HDMI-A-1", 1.5)];
+        let changes = state.update(&overrides, &workspaces, &outputs, EPSILON);
+        assert_eq!(scales(&changes), vec![("HDMI-A-1", 1.0)]);
+
+        // Switching between overridden states must not forget the original scale
+        let outputs = [output("eDP-1", 2.0), output("HDMI-A-1", 1.0)];
+        assert!(state
+            .update(&overrides, &workspaces, &outputs, EPSILON)
+            .is_empty());
+
+        let workspaces = [workspace("1", "eDP-1"), workspace("2", "HDMI-A-1")];
+        let changes = state.update(&overrides, &workspaces, &outputs, EPSILON);
+        assert_eq!(scales(&changes), vec![("HDMI-A-1", 1.5)]);
+        assert!(state
+            .update(&overrides, &workspaces, &outputs, EPSILON)
+            .is_empty());
+    }
+
+    #[test]
+    fn ignores_the_workspace_on_other_outputs() {
+        let overrides = media_on_tv();
+        let mut state = Overrides::default();
+        let workspaces = [workspace("9", "eDP-1")];
+        let outputs = [output("eDP-1", 2.0), output("HDMI-A-1", 1.5)];
+        assert!(state
+            .update(&overrides, &workspaces, &outputs, EPSILON)
+            .is_empty());
+    }
+}
@@ -0,0 +1,2353 @@
+mod common;
+
+use common::{fixture, TestHome};
+use std::io::Write;
+use std::process::Stdio;
+
+#[test]
+fn swap_rewrites_the_config_and_records_history() {
+    let home = TestHome::new(&fixture("basic.conf"));
+
+    let output = home.run(&["--swap"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Swapping scale: 1.5 -> 2"), "{}", stdout);
+
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"eDP-1\" scale 2 pos 0 0"));
+    assert!(config.contains("output \"HDMI-A-1\" scale 2 pos 1920 0"));
+    assert!(config.contains("output * bg ~/wallpaper.png fill"));
+
+    let history = home.run(&["history"]);
+    let stdout = String::from_utf8_lossy(&history.stdout);
+    assert!(stdout.contains("eDP-1: 1.5 -> 2"), "{}", stdout);
+}
+
+#[test]
+fn history_revert_reapplies_an_earlier_state() {
+    let home = TestHome::new(&fixture("unquoted.conf"));
+
+    home.run(&["--swap"]);
+    home.run(&["--swap"]);
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output eDP-1 scale 2\n"));
+
+    let output = home.run(&["history", "--revert", "1"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output eDP-1 scale 1\n"));
+}
+
+#[test]
+fn unknown_names_fail_with_a_message() {
+    let home = TestHome::new(&fixture("basic.conf"));
+
+    for (args, message) in [
+        (
+            &["history", "--revert", "99"][..],
+            "No history entry with id 99.",
+        ),
+        (
+            &["profile", "away"],
+            "No profile named 'away' in the settings file.",
+        ),
+        (&["layout", "stacked", "DP-9"], "Output 'DP-9' not found."),
+        (&["power", "toggle", "DP-9"], "Output 'DP-9' not found."),
+    ] {
+        let output = home
+            .command()
+            .args(["--backend", "mock"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{:?}", output);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains(&format!("Error: {}", message)),
+            "{}",
+            stderr
+        );
+    }
+    assert_eq!(home.read(".config/sway/config"), fixture("basic.conf"));
+}
+
+#[test]
+fn missing_markers_fail_with_a_message() {
+    let home = TestHome::new("output eDP-1 scale 2\n");
+
+    let output = home.run(&["--swap"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("'Scale Options Start' marker not found"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn managed_mode_leaves_the_main_config_untouched() {
+    let config = fixture("includes.conf");
+    let home = TestHome::new(&config);
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "managed = true\n",
+    );
+
+    let output = home.run(&["--swap"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(home.read(".config/sway/config"), config);
+    assert!(home
+        .read(".config/sway/sway-scale-switcher.conf")
+        .contains("output \"eDP-1\" scale 1"));
+}
+
+#[test]
+fn no_reload_only_writes_the_config() {
+    let home = TestHome::new(&fixture("basic.conf"));
+
+    let output = home.run(&["--swap", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("skipping reload"));
+    assert!(home.read(".config/sway/config").contains("scale 2"));
+}
+
+#[test]
+fn exec_reload_strategy_runs_the_command() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    let marker = home.path("reloaded");
+
+    let output = home.run(&[
+        "--swap",
+        "--reload-strategy",
+        "exec",
+        "--reload-command",
+        &format!("touch '{}'", marker.display()),
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(marker.exists());
+}
+
+#[test]
+fn swap_edits_the_target_of_a_symlinked_config() {
+    let home = TestHome::new("");
+    home.write("dotfiles/sway/config", &fixture("basic.conf"));
+    let link = home.path(".config/sway/config");
+    std::fs::remove_file(&link).unwrap();
+    std::os::unix::fs::symlink("../../dotfiles/sway/config", &link).unwrap();
+
+    let output = home.run(&["--swap", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(std::fs::symlink_metadata(&link)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert!(home
+        .read("dotfiles/sway/config")
+        .contains("output \"eDP-1\" scale 2 pos 0 0"));
+}
+
+#[test]
+fn swap_keeps_line_endings_and_non_utf8_bytes() {
+    let config = fixture("unquoted.conf").replace('\n', "\r\n");
+    let home = TestHome::new(&config);
+    let path = home.path(".config/sway/config");
+    let mut contents = b"# Latin-1 comment: caf\xe9\r\n".to_vec();
+    contents.extend_from_slice(config.trim_end().as_bytes());
+    std::fs::write(&path, &contents).unwrap();
+
+    let output = home.run(&["--swap", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let rewritten = std::fs::read(&path).unwrap();
+    let expected = String::from_utf8_lossy(&contents)
+        .replace("output eDP-1 scale 2", "output eDP-1 scale 1")
+        .replace("scale 1.25", "scale 1");
+    assert!(rewritten.starts_with(b"# Latin-1 comment: caf\xe9\r\n"));
+    assert_eq!(
+        String::from_utf8_lossy(&rewritten),
+        expected,
+        "only the scale values should change"
+    );
+    assert!(!rewritten.ends_with(b"\n"));
+}
+
+#[test]
+fn unknown_target_display_lists_detected_outputs() {
+    let config =
+        fixture("basic.conf").replace("# Target Display = HDMI-A-1", "# Target Display = HDMI-A-2");
+    let home = TestHome::new(&config);
+
+    let output = home.run(&["--swap"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Target display 'HDMI-A-2' matches no output line"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("Detected outputs: eDP-1, HDMI-A-1"),
+        "{}",
+        stderr
+    );
+    assert_eq!(home.read(".config/sway/config"), config);
+}
+
+#[test]
+fn prompt_changes_only_the_selected_display() {
+    let home = TestHome::new(&fixture("basic.conf"));
+
+    let mut child = home
+        .command()
+        .arg("--no-reload")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"2\n1\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("2. HDMI-A-1 (scale 1.5)"));
+
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"eDP-1\" scale 1.5 pos 0 0"));
+    assert!(config.contains("output \"HDMI-A-1\" scale 1 pos 1920 0"));
+}
+
+#[test]
+fn prompt_takes_a_scale_of_its_own_and_can_keep_it() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    let prompt = |input: &[u8]| {
+        let mut child = home
+            .command()
+            .arg("--no-reload")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    // Out of range scales are refused, and the others are rounded to the configured precision
+    let stdout = prompt(b"1\n9.5\n1.3749\nn\n");
+    assert!(
+        stdout.contains("Scale 9.5 is outside the allowed range of 0.5 to 4."),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Scale 1.3749 is rounded to 1.375."),
+        "{}",
+        stdout
+    );
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"eDP-1\" scale 1.375 pos 0 0"));
+    assert!(config.contains("# Scale Options = 1.0, 1.5, 2.0\n"));
+
+    // Agreeing adds the scale to the options for cycling
+    let stdout = prompt(b"2\n1.25\ny\n");
+    assert!(
+        stdout.contains("Added 1.25 to the scale options."),
+        "{}",
+        stdout
+    );
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"HDMI-A-1\" scale 1.25 pos 1920 0"));
+    assert!(config.contains("# Scale Options = 1.0, 1.5, 2.0, 1.25\n"));
+}
+
+#[test]
+fn prompt_quits_when_input_ends() {
+    let home = TestHome::new(&fixture("basic.conf"));
+
+    let output = home.command().stdin(Stdio::null()).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("No changes made"));
+    assert_eq!(home.read(".config/sway/config"), fixture("basic.conf"));
+}
+
+#[test]
+fn prompt_previews_the_scale_before_writing_it() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1.5},
+            {"name":"HDMI-A-1","active":true,"scale":1.5,
+             "current_mode":{"width":3840,"height":2160,"refresh":60000}}]"#,
+    );
+    let prompt = |input: &[u8]| {
+        let mut child = home
+            .command()
+            .args([
+                "--backend",
+                "mock",
+                "--reload-strategy",
+                "ipc",
+                "--mock-outputs",
+            ])
+            .arg(home.path("outputs.json"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    // Trying two scales and going back restores the live scale and leaves the config alone
+    let stdout = prompt(b"2\n3\n1\nq\n");
+    assert!(stdout.contains("2. 1.5 -> 2560x1440 logical"), "{}", stdout);
+    assert!(
+        stdout.contains("[mock] output \"HDMI-A-1\" scale 2\n"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("[mock] output \"HDMI-A-1\" scale 1\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.ends_with("[mock] output \"HDMI-A-1\" scale 1.5\nApplied: output \"HDMI-A-1\" scale 1.5\nRestored the previous scale.\nNo changes made. Exiting.\n"), "{}", stdout);
+    assert_eq!(home.read(".config/sway/config"), fixture("basic.conf"));
+
+    // Enter keeps the previewed scale and writes it
+    prompt(b"2\n3\n\n");
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"HDMI-A-1\" scale 2 pos 1920 0"));
+
+    // A display named by its description comes back too, at its old mode and refresh rate
+    home.write(
+        ".config/sway/config",
+        &fixture("basic.conf")
+            .replace("# Target Display = eDP-1\n", "")
+            .replace("HDMI-A-1", "Dell U2720Q SN1")
+            .replace("1.0, 1.5, 2.0", "1.0, 2.0@1920x1080"),
+    );
+    home.write(
+        "outputs.json",
+        r#"[{"name":"HDMI-A-1","make":"Dell","model":"U2720Q","serial":"SN1","active":true,
+             "scale":1.5,"current_mode":{"width":3840,"height":2160,"refresh":144000}}]"#,
+    );
+    let stdout = prompt(b"2\nq\n");
+    assert!(
+        stdout.contains("Applied: output \"HDMI-A-1\" mode 3840x2160@144.000Hz scale 1.5\nRestored the previous scale."),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn yes_applies_the_default_option_without_prompting() {
+    let home = TestHome::new(&fixture("basic.conf"));
+
+    let output = home
+        .command()
+        .args(["--yes", "--no-reload"])
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 1 pos 0 0"));
+
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "default_option = 2.0\n",
+    );
+    let output = home.run(&["--yes", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"eDP-1\" scale 2 pos 0 0"));
+    assert!(config.contains("output \"HDMI-A-1\" scale 2 pos 1920 0"));
+}
+
+#[test]
+fn scale_filter_follows_the_scale() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "scale_filter = \"auto\"\n[output_scale_filters]\n\"HDMI-A-1\" = \"smart\"\n",
+    );
+
+    let output = home.run(&["--swap", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"eDP-1\" scale 2 scale_filter nearest pos 0 0"));
+    assert!(config.contains("output \"HDMI-A-1\" scale 2 scale_filter smart pos 1920 0"));
+
+    let output = home.run(&["--swap", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"eDP-1\" scale 1 scale_filter nearest pos 0 0"));
+
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1},
+            {"name":"HDMI-A-1","active":true,"scale":1}]"#,
+    );
+    let output = home
+        .command()
+        .args(["--backend", "mock", "--mock-outputs"])
+        .arg(home.path("outputs.json"))
+        .args(["--swap", "--reload-strategy", "ipc"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("output \"eDP-1\" scale 1.5 scale_filter linear"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn run_applies_a_script_as_one_change_and_rolls_back_on_error() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1.5,
+             "current_mode":{"width":1920,"height":1080,"refresh":60000}},
+            {"name":"HDMI-A-1","active":true,"scale":1.5}]"#,
+    );
+    let run = |script: &str| {
+        home.write("dock.batch", script);
+        home.command()
+            .args(["--backend", "mock", "--mock-outputs"])
+            .arg(home.path("outputs.json"))
+            .args(["run"])
+            .arg(home.path("dock.batch"))
+            .output()
+            .unwrap()
+    };
+
+    let output = run("# Docked\n\
+         scale eDP-1 2\n\
+         mode HDMI-A-1 3840x2160\n\
+         workspace 1 HDMI-A-1\n\
+         hook touch \"$HOME/docked\"\n");
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("[mock] reload").count(), 1, "{}", stdout);
+    assert!(
+        stdout.contains("[mock] workspace \"1\"; move workspace to output \"HDMI-A-1\""),
+        "{:?}",
+        output
+    );
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"eDP-1\" scale 2 pos 0 0"));
+    assert!(config.contains("output \"HDMI-A-1\" scale 1.5 mode 3840x2160 pos 1920 0"));
+    assert!(home.path("docked").exists());
+
+    // Rolling back puts the files back as they were, mode lines and all, and the live outputs
+    // as sway showed them
+    let before = home.read(".config/sway/config");
+    let output = run("scale eDP-1 1\nmode eDP-1 1280x720\nworkspace 2 eDP-1\nhook false\n");
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[mock] workspace \"2\"; move workspace to output \"HDMI-A-1\""));
+    assert!(
+        stdout.contains("[mock] output \"eDP-1\" mode 1920x1080@60.000Hz scale 1.5"),
+        "{}",
+        stdout
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("rolling back"));
+    assert_eq!(home.read(".config/sway/config"), before);
+
+    let output = run("scale eDP-1 1\nresize eDP-1\n");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("line 2: unknown step 'resize'"));
+}
+
+#[test]
+fn cycle_order_follows_the_written_options() {
+    let config = fixture("basic.conf").replace(
+        "# Scale Options = 1.0, 1.5, 2.0",
+        "# Scale Options = 1.0, 2.0, 1.5",
+    );
+    let home = TestHome::new(&config);
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "cycle_order = \"as-written\"\n",
+    );
+
+    let output = home.run(&["--swap", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 1 pos 0 0"));
+    home.run(&["--swap", "--no-reload"]);
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 2 pos 0 0"));
+
+    let output = home.run(&["cycle", "--prev", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 1 pos 0 0"));
+}
+
+#[test]
+fn aliases_stand_for_outputs_everywhere() {
+    let config =
+        fixture("basic.conf").replace("# Target Display = HDMI-A-1", "# Target Display = tv");
+    let home = TestHome::new(&config);
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "[aliases]\nlaptop = \"eDP-1\"\ntv = \"HDMI-A-1\"\n\n\
+         [profiles.desk]\noutputs = [{ name = \"laptop\", scale = 1.25 }]\n",
+    );
+
+    let output = home.run(&["set", "1", "--output", "laptop", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"eDP-1\" scale 1 pos 0 0"));
+
+    let output = home.run(&["pin", "tv"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&home.run(&["pin"]).stdout),
+        "HDMI-A-1\n"
+    );
+
+    let output = home.run(&["status", "--no-color"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("eDP-1 (laptop)"), "{}", stdout);
+    assert!(stdout.contains("HDMI-A-1 (tv) (pinned)"), "{}", stdout);
+
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1}]"#,
+    );
+    let output = home
+        .command()
+        .args(["--backend", "mock", "--mock-outputs"])
+        .arg(home.path("outputs.json"))
+        .args(["profile", "desk"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("output \"eDP-1\" scale 1.25"));
+
+    // The legacy power flags take aliases as well
+    for flag in ["--off", "--on", "--toggle-power"] {
+        let output = home
+            .command()
+            .args(["--backend", "mock", "--mock-outputs"])
+            .arg(home.path("outputs.json"))
+            .args([flag, "laptop"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{} {:?}", flag, output);
+        assert!(
+            String::from_utf8_lossy(&output.stdout).contains("output \"eDP-1\" power"),
+            "{} {:?}",
+            flag,
+            output
+        );
+    }
+}
+
+#[test]
+fn pinned_displays_are_skipped_by_swap() {
+    let home = TestHome::new(&fixture("basic.conf"));
+
+    let output = home.run(&["pin", "eDP-1"]);
+    assert!(output.status.success(), "{:?}", output);
+    let listed = home.run(&["pin"]);
+    assert_eq!(String::from_utf8_lossy(&listed.stdout), "eDP-1\n");
+
+    let output = home.run(&["--swap", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"eDP-1\" scale 1.5 pos 0 0"));
+    assert!(config.contains("output \"HDMI-A-1\" scale 2 pos 1920 0"));
+
+    let output = home.run(&["unpin", "eDP-1"]);
+    assert!(output.status.success(), "{:?}", output);
+    let output = home.run(&["--swap", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"eDP-1\" scale 1 pos 0 0"));
+    assert!(config.contains("output \"HDMI-A-1\" scale 1 pos 1920 0"));
+}
+
+#[test]
+fn status_prints_an_aligned_plain_table() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.run(&["pin", "HDMI-A-1"]);
+
+    // Colors are never used when stdout is not a terminal, whatever the flags say
+    let output = home.run(&["status"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(home.run(&["status", "--no-color"]).stdout, output.stdout);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "OUTPUT             SCALE  OPTIONS\n\
+         eDP-1              1.5    1 [1.5] 2\n\
+         HDMI-A-1 (pinned)  1.5    1 [1.5] 2\n"
+    );
+}
+
+#[test]
+fn bench_times_each_stage_and_restores_the_scales() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1.5},
+            {"name":"HDMI-A-1","active":true,"scale":1.5}]"#,
+    );
+
+    let output = home
+        .command()
+        .args(["bench", "-n", "3", "--backend", "mock", "--mock-outputs"])
+        .arg(home.path("outputs.json"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Backend: mock, 3 iterations"), "{}", stdout);
+    for stage in ["parse", "decide", "apply", "confirm", "total"] {
+        assert!(
+            stdout.lines().any(|line| line.starts_with(stage)),
+            "{}",
+            stdout
+        );
+    }
+    assert!(!stdout.lines().any(|line| line.starts_with("reload")));
+
+    // Three steps on from 1.5 through 2 and 1, then back to where the displays were
+    let commands: Vec<&str> = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("[mock] output \"eDP-1\" "))
+        .collect();
+    assert_eq!(
+        commands,
+        ["scale 2", "scale 1", "scale 1.5", "scale 1.5"],
+        "{}",
+        stdout
+    );
+
+    // The config is never edited
+    assert_eq!(home.read(".config/sway/config"), fixture("basic.conf"));
+
+    // Options that change the mode have the mode put back too, refresh rate and all
+    let home = TestHome::new(
+        &fixture("basic.conf")
+            .replace("# Target Display = HDMI-A-1\n", "")
+            .replace("1.0, 1.5, 2.0", "1.0, 2.0@1280x720"),
+    );
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1.5,
+             "current_mode":{"width":1920,"height":1080,"refresh":60000}}]"#,
+    );
+    let output = home
+        .command()
+        .args(["bench", "-n", "1", "--backend", "mock", "--mock-outputs"])
+        .arg(home.path("outputs.json"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let restored = stdout.lines().rfind(|line| line.starts_with("[mock] "));
+    assert_eq!(
+        restored,
+        Some("[mock] output \"eDP-1\" mode 1920x1080@60.000Hz scale 1.5"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn status_json_includes_the_live_mode_and_power_source() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1.5,"adaptive_sync_status":"enabled",
+             "current_mode":{"width":2880,"height":1800,"refresh":120000}}]"#,
+    );
+    home.run(&["pin", "HDMI-A-1"]);
+
+    let output = home
+        .command()
+        .args(["status", "--json", "--backend", "mock", "--mock-outputs"])
+        .arg(home.path("outputs.json"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let status: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let laptop = &status["displays"][0];
+    assert_eq!(laptop["name"], "eDP-1");
+    assert_eq!(laptop["scale"], 1.5);
+    assert_eq!(laptop["option"], 1);
+    assert_eq!(laptop["options"], serde_json::json!(["1", "1.5", "2"]));
+    assert_eq!(laptop["live"]["mode"], "2880x1800");
+    assert_eq!(laptop["live"]["refresh_hz"], 120.0);
+    assert_eq!(laptop["live"]["adaptive_sync"], true);
+    let tv = &status["displays"][1];
+    assert_eq!(tv["pinned"], true);
+    assert!(tv["live"].is_null());
+    assert!(status.get("on_battery").is_some());
+}
+
+#[test]
+fn records_scales_for_current() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1.5}]"#,
+    );
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "record_scales = true\n[profiles.desk]\noutputs = [{ name = \"eDP-1\", scale = 2.0 }]\n",
+    );
+    let run = |args: &[&str]| {
+        home.command()
+            .env("XDG_RUNTIME_DIR", home.path("run"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["current", "eDP-1"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No scale is known for eDP-1"));
+
+    let seed = home.path("outputs.json");
+    let output = run(&[
+        "--backend",
+        "mock",
+        "--mock-outputs",
+        seed.to_str().unwrap(),
+        "profile",
+        "desk",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(home.read("run/sway-scale-switcher/scale-eDP-1"), "2\n");
+
+    let output = run(&["current", "eDP-1"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n");
+    let output = run(&["current", "eDP-1", "--dpi"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "192\n");
+}
+
+#[test]
+fn failures_switch_sway_to_the_error_mode_and_flash_the_border() {
+    let config = format!(
+        "{}client.focused #4c7899 #285577 #ffffff\n",
+        fixture("basic.conf")
+    );
+    let home = TestHome::new(&config);
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "[error_feedback]\nmode = \"scale-error\"\nborder_color = \"#ff0000\"\nseconds = 0.1\n",
+    );
+
+    let output = home.run(&["--backend", "mock", "current", "DP-9"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "[mock] mode \"scale-error\"\n\
+         [mock] client.focused #ff0000 #285577 #ffffff #ff0000 #ff0000\n\
+         [mock] mode default\n\
+         [mock] client.focused #4c7899 #285577 #ffffff\n"
+    );
+
+    // Nothing is shown when the command succeeds
+    let output = home.run(&["--backend", "mock", "status"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("scale-error"));
+}
+
+#[test]
+fn lint_reports_problems_and_fixes_them() {
+    let config = fixture("basic.conf").replace(
+        "# Scale Options = 1.0, 1.5, 2.0",
+        "# Target Display = eDP-1\n# Scale Options = 2.0, 1.0, 1.5, 1.5",
+    );
+    let home = TestHome::new(&config);
+
+    let output = home.run(&["lint"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("config:7: target display eDP-1 is listed twice\n    fix (lint --fix): remove the repeated line"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("config:8: scale options are not in ascending order"),
+        "{}",
+        stdout
+    );
+    assert_eq!(home.read(".config/sway/config"), config);
+
+    let output = home.run(&["lint", "--fix"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(home.read(".config/sway/config"), fixture("basic.conf"));
+    let output = home.run(&["lint"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "No problems found.\n"
+    );
+}
+
+#[test]
+fn export_and_import_move_the_setup_to_another_home() {
+    let source = TestHome::new(&fixture("basic.conf"));
+    source.write(
+        ".config/sway-scale-switcher/config.toml",
+        "# laptop and TV\nmanaged = true\n",
+    );
+    source.run(&["pin", "HDMI-A-1"]);
+    let archive = source.path("setup.json");
+    let output = source.run(&["export", archive.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let target = TestHome::new(&fixture("basic.conf"));
+    target.write(
+        ".config/sway-scale-switcher/config.toml",
+        "managed = false\n",
+    );
+    let output = target.run(&["import", archive.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--force"));
+    assert_eq!(
+        target.read(".config/sway-scale-switcher/config.toml"),
+        "managed = false\n"
+    );
+
+    let output = target.run(&["import", "--force", archive.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        target.read(".config/sway-scale-switcher/config.toml"),
+        "# laptop and TV\nmanaged = true\n"
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&target.run(&["pin"]).stdout),
+        "HDMI-A-1\n"
+    );
+}
+
+#[test]
+fn rejected_configs_are_not_written() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    // A stand-in for sway that rejects every config it is asked to validate
+    home.install_bin(
+        "sway",
+        "#!/bin/sh\necho 'Error on line 10: unknown command' >&2\nexit 1\n",
+    );
+
+    let output = home.run(&["--swap", "--no-reload", "--validate", "parse"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Error on line 10"), "{}", stderr);
+    assert_eq!(home.read(".config/sway/config"), fixture("basic.conf"));
+    assert!(!home
+        .path(".config/sway/.config.sway-scale-switcher.candidate")
+        .exists());
+}
+
+#[test]
+fn rejected_changes_leave_displays_on() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.install_bin(
+        "sway",
+        "#!/bin/sh\necho 'Error on line 10: unknown command' >&2\nexit 1\n",
+    );
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1.5},
+            {"name":"HDMI-A-1","active":true,"scale":1.5}]"#,
+    );
+    home.write("off.batch", "scale eDP-1 off\nscale HDMI-A-1 2\n");
+
+    let output = home
+        .command()
+        .args(["--validate", "parse", "--backend", "mock", "--mock-outputs"])
+        .arg(home.path("outputs.json"))
+        .arg("run")
+        .arg(home.path("off.batch"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("disable"), "{}", stdout);
+    assert_eq!(home.read(".config/sway/config"), fixture("basic.conf"));
+}
+
+#[test]
+fn prompts_follow_the_locale() {
+    let home = TestHome::new(&fixture("basic.conf"));
+
+    let output = home
+        .command()
+        .env("LANG", "es_ES.UTF-8")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Pantallas de destino:"), "{}", stdout);
+    assert!(stdout.contains("2. HDMI-A-1 (escala 1.5)"), "{}", stdout);
+    assert!(
+        stdout.contains("No se hicieron cambios. Saliendo."),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn focused_swaps_only_the_focused_output() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.install_bin(
+        "swaymsg",
+        r#"#!/bin/sh
+case "$*" in
+*get_workspaces*) echo '[{"name":"1","output":"eDP-1","visible":true,"focused":false},{"name":"2","output":"HDMI-A-1","visible":true,"focused":true}]';;
+*get_outputs*) echo '[{"name":"eDP-1","scale":1.5},{"name":"HDMI-A-1","scale":1.5}]';;
+*) echo '[{"success":true}]';;
+esac
+"#,
+    );
+
+    let output = home.run(&["--swap", "--focused", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"eDP-1\" scale 1.5 pos 0 0"));
+    assert!(config.contains("output \"HDMI-A-1\" scale 2 pos 1920 0"));
+}
+
+#[test]
+fn changes_without_sway_are_written_and_announced_later() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "announce_offline_changes = true\n",
+    );
+
+    let output = home.run(&["--swap"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("sway is not running"));
+    assert!(output.stderr.is_empty(), "{:?}", output);
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 2 pos 0 0"));
+
+    home.install_bin(
+        "notify-send",
+        &format!(
+            "#!/bin/sh\nprintf '%s\\n' \"$@\" > {}\n",
+            home.path("notification").display()
+        ),
+    );
+    assert!(home.run(&["announce"]).status.success());
+    assert_eq!(
+        home.read("notification"),
+        "Display scales changed while sway was not running:\n\
+         HDMI-A-1: 1.5 -> 2\neDP-1: 1.5 -> 2\n"
+    );
+
+    // The marker is gone once announced
+    std::fs::remove_file(home.path("notification")).unwrap();
+    assert!(home.run(&["announce"]).status.success());
+    assert!(!home.path("notification").exists());
+}
+
+#[test]
+fn context_selects_an_option_group() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "[options.office]\nscales = [1.5, 1.75]\n",
+    );
+
+    let output = home.run(&["--swap", "--no-reload", "--context", "office"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 1.75 pos 0 0"));
+
+    // Without a context or matching criteria, the config's own options apply
+    let output = home.run(&["--swap", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 1 pos 0 0"));
+
+    let output = home.run(&["--swap", "--no-reload", "--context", "home"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Defined groups: office"));
+}
+
+#[test]
+fn off_entry_disables_and_reenables_the_display() {
+    let config = fixture("basic.conf")
+        .replace("# Target Display = HDMI-A-1\n", "")
+        .replace("1.0, 1.5, 2.0", "off, 1.0, 1.5");
+    let home = TestHome::new(&config);
+    // Anything listening on SWAYSOCK counts as a running sway; swaymsg itself is faked
+    let socket = home.path("sway.sock");
+    let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+    home.install_bin(
+        "swaymsg",
+        &format!(
+            "#!/bin/sh\necho \"$*\" >> {log}\ncase \"$*\" in\n*get_outputs*) cat {outputs};;\n*) echo '[{{\"success\":true}}]';;\nesac\n",
+            log = home.path("swaymsg.log").display(),
+            outputs = home.path("outputs.json").display()
+        ),
+    );
+    let run = |active: bool| {
+        home.write(
+            "outputs.json",
+            &format!(
+                "[{{\"name\":\"eDP-1\",\"active\":{},\"scale\":1.5}}]",
+                active
+            ),
+        );
+        let output = home
+            .command()
+            .env("SWAYSOCK", &socket)
+            .args(["--swap", "--reload-strategy", "ipc"])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+    };
+
+    // 1.5 is the largest option, so the cycle wraps around to off
+    run(true);
+    assert!(home
+        .read("swaymsg.log")
+        .contains("output \"eDP-1\" disable"));
+    assert_eq!(home.read(".config/sway/config"), config);
+
+    run(false);
+    assert!(home.read("swaymsg.log").contains("output \"eDP-1\" enable"));
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 1 pos 0 0"));
+}
+
+#[test]
+fn cycle_and_set_subcommands_change_the_scale() {
+    let home = TestHome::new(&fixture("basic.conf"));
+
+    let output = home.run(&["cycle", "--reverse", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Swapping scale: 1.5 -> 1"), "{}", stdout);
+
+    // A scale outside the options is fine, and --output leaves the other display alone
+    let output = home.run(&["set", "1.25", "--output", "eDP-1", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"eDP-1\" scale 1.25 pos 0 0"));
+    assert!(config.contains("output \"HDMI-A-1\" scale 1 pos 1920 0"));
+
+    let output = home.run(&["set", "2", "--output", "DP-9", "--no-reload"]);
+    assert!(!output.status.success());
+
+    // Scales that are not positive numbers never reach the config
+    let before = home.read(".config/sway/config");
+    for value in ["nan", "0"] {
+        let output = home.run(&["set", value, "--no-reload"]);
+        assert!(!output.status.success(), "{:?}", output);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("must be a positive number"));
+    }
+    assert_eq!(home.read(".config/sway/config"), before);
+}
+
+#[test]
+fn session_commands_say_why_sway_is_out_of_reach() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.install_bin(
+        "swaymsg",
+        "#!/bin/sh\necho 'Unable to retrieve socket path' >&2\nexit 1\n",
+    );
+
+    let output = home
+        .command()
+        .env_remove("SWAYSOCK")
+        .env_remove("WAYLAND_DISPLAY")
+        .env_remove("XDG_SESSION_TYPE")
+        .env_remove("WSL_DISTRO_NAME")
+        .env_remove("WSL_INTEROP")
+        .env("DISPLAY", ":0")
+        .args(["power", "off", "eDP-1"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("This command needs a running sway, but this is an X11 session"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("with 'set' or 'cycle' still works"),
+        "{}",
+        stderr
+    );
+
+    // Edits go ahead and say afterwards why nothing was reloaded
+    let output = home.run(&["set", "2"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("left over from an earlier session"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn doctor_reports_failed_checks() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.install_bin("swaymsg", "#!/bin/sh\nexit 1\n");
+
+    let output = home.run(&["doctor"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[ ok ] target displays"), "{}", stdout);
+    assert!(stdout.contains("[warn] sway: not running"), "{}", stdout);
+
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "managed = true\nreload_strategy = \"exec\"\n",
+    );
+    let output = home.run(&["doctor"]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("[FAIL] managed snippet"), "{}", stdout);
+    assert!(stdout.contains("[FAIL] reload"), "{}", stdout);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("2 problems found."));
+}
+
+#[test]
+fn fullscreen_windows_block_a_full_reload_without_force() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    let config = home.read(".config/sway/config");
+    let socket = home.path("sway.sock");
+    let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+    home.install_bin(
+        "swaymsg",
+        &format!(
+            "#!/bin/sh\necho \"$*\" >> {log}\ncase \"$*\" in\n*get_tree*) echo '{{\"nodes\":[{{\"app_id\":\"mpv\",\"fullscreen_mode\":1}}]}}';;\n*) echo '[{{\"success\":true}}]';;\nesac\n",
+            log = home.path("swaymsg.log").display()
+        ),
+    );
+    let run = |args: &[&str]| {
+        home.command()
+            .env("SWAYSOCK", &socket)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["cycle"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("mpv is fullscreen"));
+    assert_eq!(home.read(".config/sway/config"), config);
+
+    let output = run(&["cycle", "--force"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_ne!(home.read(".config/sway/config"), config);
+}
+
+#[test]
+fn scheduled_changes_run_later_and_can_be_cancelled() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    let config = home.read(".config/sway/config");
+
+    let output = home.run(&["set", "1.25", "--in", "1h", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Scheduled job 1"));
+    let list = home.run(&["schedule", "list"]);
+    let stdout = String::from_utf8_lossy(&list.stdout);
+    assert!(stdout.contains("set 1.25 --no-reload"), "{}", stdout);
+    assert!(home.run(&["schedule", "cancel", "1"]).status.success());
+    assert!(
+        String::from_utf8_lossy(&home.run(&["schedule", "list"]).stdout)
+            .contains("No changes are scheduled.")
+    );
+
+    // Only changes that need no prompt can be deferred
+    assert!(!home.run(&["status", "--in", "1m"]).status.success());
+
+    let output = home.run(&["cycle", "--in=1s", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(home.read(".config/sway/config"), config);
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while home.read(".config/sway/config") == config && std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 2 pos 0 0"));
+}
+
+#[test]
+fn simulated_time_fast_forwards_scheduled_changes() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    let config = home.read(".config/sway/config");
+
+    // 2024-01-01 20:00 UTC; the change is due three and a half hours later
+    let output = home
+        .command()
+        .env("TZ", "UTC")
+        .args(["--simulate-time", "1704139200", "set", "2", "--at", "23:30"])
+        .arg("--no-reload")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Scheduled job 1 for 23:30"));
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while home.read(".config/sway/config") == config && std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 2 pos 0 0"));
+
+    // The history puts the change down to the job
+    let history = || String::from_utf8_lossy(&home.run(&["history"]).stdout).to_string();
+    while !history().contains("[schedule job 1]") && std::time::Instant::now() < deadline {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    assert!(history().contains("[schedule job 1]"), "{}", history());
+}
+
+#[test]
+fn a11y_zoom_magnifies_and_restores_the_focused_output() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "zoom_scale = 3.0\n",
+    );
+    home.install_bin(
+        "swaymsg",
+        &format!(
+            "#!/bin/sh\necho \"$*\" >> {log}\ncase \"$*\" in\n\
+             *get_workspaces*) echo '[{{\"name\":\"1\",\"output\":\"eDP-1\",\"visible\":true,\"focused\":true}}]';;\n\
+             *get_outputs*) echo '[{{\"name\":\"eDP-1\",\"active\":true,\"scale\":1.5}}]';;\n\
+             *get_tree*) echo '{{\"nodes\":[{{\"focused\":true,\"rect\":{{\"x\":0,\"y\":0,\"width\":640,\"height\":360}}}}]}}';;\n\
+             *) echo '[{{\"success\":true}}]';;\nesac\n",
+            log = home.path("swaymsg.log").display()
+        ),
+    );
+
+    let output = home.run(&["a11y-zoom"]);
+    assert!(output.status.success(), "{:?}", output);
+    let log = home.read("swaymsg.log");
+    assert!(log.contains("output \"eDP-1\" scale 3"), "{}", log);
+    assert!(log.contains("seat - cursor set 320 180"), "{}", log);
+
+    let output = home.run(&["a11y-zoom"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(home
+        .read("swaymsg.log")
+        .contains("output \"eDP-1\" scale 1.5"));
+}
+
+#[test]
+fn outputs_in_included_files_are_rewritten_together() {
+    let home = TestHome::new(
+        "### Scale Options Start\n\
+         # Target Display = eDP-1\n\
+         # Target Display = HDMI-A-1\n\
+         # Scale Options = 1.0, 1.5, 2.0\n\
+         ### Scale Options End\n\
+         output eDP-1 scale 1.5\n\
+         include outputs/*\n",
+    );
+    home.write(
+        ".config/sway/outputs/desk.conf",
+        "# Desk monitor\r\noutput HDMI-A-1 scale 1.5 pos 1920 0\r\n",
+    );
+    home.write(".config/sway/outputs/input.conf", "input * xkb_layout us\n");
+
+    let output = home.run(&["cycle", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output eDP-1 scale 2\n"));
+    assert_eq!(
+        home.read(".config/sway/outputs/desk.conf"),
+        "# Desk monitor\r\noutput HDMI-A-1 scale 2 pos 1920 0\r\n"
+    );
+    assert_eq!(
+        home.read(".config/sway/outputs/input.conf"),
+        "input * xkb_layout us\n"
+    );
+
+    let status = home.run(&["status"]);
+    let stdout = String::from_utf8_lossy(&status.stdout);
+    assert!(stdout.contains("HDMI-A-1  2"), "{}", stdout);
+}
+
+#[test]
+fn toolkit_scaling_follows_the_new_scale() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.install_bin(
+        "gsettings",
+        &format!(
+            "#!/bin/sh\necho \"$@\" >> {}\n",
+            home.path("gsettings.log").display()
+        ),
+    );
+    home.install_bin("systemctl", "#!/bin/sh\nexit 0\n");
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "[toolkits]\n\
+         output = \"eDP-1\"\n\
+         qt_env_file = \"~/qt.conf\"\n\
+         [toolkits.text_scaling]\n\
+         \"2\" = 1.25\n\
+         [toolkits.qt_scale_factor]\n\
+         \"2\" = 2\n",
+    );
+
+    let output = home.run(&["--swap", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        home.read("gsettings.log"),
+        "set org.gnome.desktop.interface text-scaling-factor 1.25\n"
+    );
+    assert!(home.read("qt.conf").contains("\nQT_SCALE_FACTOR=2\n"));
+
+    // Scales without a mapping go back to the toolkits' own default
+    home.run(&["--swap", "--no-reload"]);
+    assert!(home
+        .read("gsettings.log")
+        .ends_with("text-scaling-factor 1\n"));
+    assert!(home.read("qt.conf").contains("\nQT_SCALE_FACTOR=1\n"));
+}
+
+#[test]
+fn sync_reconciles_the_config_with_live_scales() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    let socket = home.path("sway.sock");
+    let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+    home.install_bin(
+        "swaymsg",
+        &format!(
+            "#!/bin/sh\necho \"$*\" >> {log}\ncase \"$*\" in\n\
+             *get_outputs*) echo '[{{\"name\":\"eDP-1\",\"active\":true,\"scale\":2.0}},\
+             {{\"name\":\"HDMI-A-1\",\"active\":true,\"scale\":1.5}}]';;\n\
+             *) echo '[{{\"success\":true}}]';;\nesac\n",
+            log = home.path("swaymsg.log").display()
+        ),
+    );
+    let run = |args: &[&str]| {
+        home.command()
+            .env("SWAYSOCK", &socket)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["sync"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("eDP-1   1.5     2"), "{}", stdout);
+    assert!(!stdout.contains("HDMI-A-1"), "{}", stdout);
+
+    let output = run(&["sync", "--from-config"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(home
+        .read("swaymsg.log")
+        .contains("output \"eDP-1\" scale 1.5"));
+
+    let output = run(&["sync", "--from-live"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = home.read(".config/sway/config");
+    assert!(
+        config.contains("output \"eDP-1\" scale 2 pos 0 0"),
+        "{}",
+        config
+    );
+    assert!(config.contains("output \"HDMI-A-1\" scale 1.5 pos 1920 0"));
+    assert!(!home.read("swaymsg.log").contains("reload"));
+
+    // Output lines that name a display by its description are compared as well
+    home.write(
+        ".config/sway/config",
+        &fixture("basic.conf").replace("HDMI-A-1", "Dell U2720Q SN1"),
+    );
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1.5},
+            {"name":"HDMI-A-1","make":"Dell","model":"U2720Q","serial":"SN1","active":true,
+             "scale":2}]"#,
+    );
+    let output = home
+        .command()
+        .args(["--backend", "mock", "--mock-outputs"])
+        .arg(home.path("outputs.json"))
+        .args(["sync", "--from-live"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let config = home.read(".config/sway/config");
+    assert!(
+        config.contains("output \"Dell U2720Q SN1\" scale 2 pos 1920 0"),
+        "{}",
+        config
+    );
+}
+
+#[test]
+fn wlr_randr_backend_applies_scales_without_sway() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    let log = home.path("wlr-randr.log");
+    home.install_bin(
+        "wlr-randr",
+        &format!(
+            r#"#!/bin/sh
+if [ "$1" = --json ]; then
+    echo '[{{"name":"eDP-1","make":"Sharp","model":null,"serial":null,"enabled":true,"modes":[{{"width":1920,"height":1080,"refresh":60.0,"current":true}}],"position":{{"x":0,"y":0}},"transform":"normal","scale":1.5}}]'
+else
+    echo "$*" >> {}
+fi
+"#,
+            log.display()
+        ),
+    );
+
+    let output = home.run(&["--backend", "wlr-randr", "--swap"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 2 pos 0 0"));
+    let log = home.read("wlr-randr.log");
+    assert!(log.contains("--output eDP-1 --scale 2"), "{}", log);
+}
+
+#[test]
+fn backend_chain_falls_back_in_order_and_reports_the_backend() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    let config = home.read(".config/sway/config");
+
+    // Without sway, a chain of only ipc changes nothing, not even the config
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "backends = [\"ipc\"]\n",
+    );
+    let output = home.run(&["set", "2"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("None of the backends"));
+    assert_eq!(home.read(".config/sway/config"), config);
+
+    // The next entry that reaches a compositor applies the change
+    let log = home.path("wlr-randr.log");
+    home.install_bin(
+        "wlr-randr",
+        &format!(
+            r#"#!/bin/sh
+if [ "$1" = --json ]; then
+    echo '[{{"name":"eDP-1","make":"Sharp","model":null,"serial":null,"enabled":true,"modes":[{{"width":1920,"height":1080,"refresh":60.0,"current":true}}],"position":{{"x":0,"y":0}},"transform":"normal","scale":1.5}}]'
+else
+    echo "$*" >> {}
+fi
+"#,
+            log.display()
+        ),
+    );
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "backends = [\"ipc\", \"wlr-randr\", \"config\"]\n",
+    );
+    let output = home.run(&["set", "2"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Applied with: wlr-randr"));
+    assert!(home
+        .read("wlr-randr.log")
+        .contains("--output eDP-1 --scale 2"));
+
+    let output = home.run(&["status", "--json"]);
+    let status: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        status["backend"],
+        serde_json::json!({
+            "chain": ["ipc", "wlr-randr", "config"],
+            "selected": "wlr-randr",
+            "last_change": "wlr-randr",
+        })
+    );
+
+    // Leaving wlr-randr out edits the config alone
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "backends = [\"config\"]\n",
+    );
+    let output = home.run(&["set", "1.5"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Applied with: config"));
+    assert!(!home.read("wlr-randr.log").contains("--scale 1.5"));
+
+    // `--backend sway` asks for sway alone, without falling back to wlr-randr
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "backends = [\"wlr-randr\"]\n",
+    );
+    let output = home.run(&["--backend", "sway", "set", "1"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Applied with: config"));
+    assert!(!home.read("wlr-randr.log").contains("--scale 1"));
+}
+
+#[test]
+fn unwritable_configs_name_the_backend_that_applied_the_change() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    // A directory in the way of the staged copy makes the config impossible to write
+    std::fs::create_dir(home.path(".config/sway/.config.sway-scale-switcher.tmp")).unwrap();
+
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1.5},
+            {"name":"HDMI-A-1","active":true,"scale":1.5}]"#,
+    );
+
+    let output = home
+        .command()
+        .args(["--backend", "mock", "--mock-outputs"])
+        .arg(home.path("outputs.json"))
+        .args(["set", "2"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Applied with: mock"), "{}", stdout);
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("through mock only"),
+        "{:?}",
+        output
+    );
+    assert_eq!(home.read(".config/sway/config"), fixture("basic.conf"));
+}
+
+#[test]
+fn arrange_moves_outputs_on_a_map_and_saves_a_profile() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":2,
+             "current_mode":{"width":2880,"height":1800,"refresh":60000},
+             "rect":{"x":0,"y":0,"width":1440,"height":900}},
+            {"name":"HDMI-A-1","active":true,"scale":1,
+             "current_mode":{"width":1920,"height":1080,"refresh":60000},
+             "rect":{"x":1440,"y":0,"width":1920,"height":1080}}]"#,
+    );
+    let mut child = home
+        .command()
+        .args(["arrange", "--backend", "mock", "--mock-outputs"])
+        .arg(home.path("outputs.json"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(
+            b"place eDP-1 below HDMI-A-1\nscale eDP-1 9\nscale eDP-1 1.5\napply\nsave desk\nq\n",
+        )
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("|eDP-1"), "{}", stdout);
+    assert!(
+        stdout.contains("The scale must be a number from 0.5 to 4"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("[mock] output \"HDMI-A-1\" scale 1 position 0 0"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("[mock] output \"eDP-1\" scale 1.5 position 0 1080"),
+        "{}",
+        stdout
+    );
+
+    let profiles = home.run(&["profile", "--list"]);
+    assert_eq!(String::from_utf8_lossy(&profiles.stdout), "desk\n");
+    assert!(home
+        .read(".config/sway-scale-switcher/config.toml")
+        .contains("{ name = \"eDP-1\", scale = 1.5, position = [0, 1080] },"));
+}
+
+#[test]
+fn host_runs_swaymsg_over_ssh() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    let log = home.path("remote.log");
+    // A stand-in for ssh that runs the remote command with a local shell, as sshd would
+    home.install_bin(
+        "ssh",
+        &format!(
+            r#"#!/bin/sh
+while [ "$1" != -- ]; do shift; done
+shift
+echo "ssh $*" >> {log}
+exec sh -c "$*"
+"#,
+            log = log.display()
+        ),
+    );
+    home.install_bin(
+        "swaymsg",
+        &format!(
+            r#"#!/bin/sh
+printf '%s|' "$@" >> {log}
+echo >> {log}
+case "$*" in
+*get_outputs*) echo '[{{"name":"eDP-1","active":true,"scale":1.5}},{{"name":"HDMI-A-1","active":true,"scale":1.5}}]';;
+*) echo '[{{"success":true}}]';;
+esac
+"#,
+            log = log.display()
+        ),
+    );
+
+    let output = home.run(&["--host", "media@htpc", "--swap"]);
+    assert!(output.status.success(), "{:?}", output);
+    let log = home.read("remote.log");
+    // Whether sway runs is asked over SSH once per invocation, not at every step
+    assert_eq!(log.matches("-t|get_version|").count(), 1, "{}", log);
+    assert!(log.contains("output \"eDP-1\" scale 2|"), "{}", log);
+    assert!(!log.contains("|reload|"), "{}", log);
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 2 pos 0 0"));
+
+    // A failed connection is reported instead of being taken for sway not running
+    home.install_bin(
+        "ssh",
+        "#!/bin/sh\necho 'ssh: Could not resolve hostname htpc' >&2\nexit 255\n",
+    );
+    let output = home.run(&["--host", "htpc", "layout", "stacked"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Could not resolve hostname htpc"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn mock_backend_prints_the_commands_instead_of_running_sway() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1.5},
+            {"name":"HDMI-A-1","active":true,"scale":1.5}]"#,
+    );
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "[profiles.desk]\noutputs = [{ name = \"HDMI-A-1\", scale = 1.25 }]\n",
+    );
+    let mock = ["--backend", "mock", "--mock-outputs"];
+    let seed = home.path("outputs.json");
+    let run = |args: &[&str]| {
+        home.command()
+            .args(mock)
+            .arg(&seed)
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&["cycle", "--focused", "--reload-strategy", "ipc"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("[mock] output \"eDP-1\" scale 2"),
+        "{}",
+        stdout
+    );
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"HDMI-A-1\" scale 1.5 pos 1920 0"));
+
+    let output = run(&["profile", "desk"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("[mock] output \"HDMI-A-1\" scale 1.25"),
+        "{}",
+        stdout
+    );
+
+    let output = run(&["power", "off", "DP-3"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn apply_default_uses_the_default_profile_without_editing_the_config() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "default_profile = \"laptop\"\n\n\
+         [profiles.desk]\nauto = true\noutputs = [{ name = \"eDP-1\" }, { name = \"DP-3\" }]\n\n\
+         [profiles.laptop]\noutputs = [{ name = \"eDP-1\", scale = 1.25 }, { name = \"DP-3\", scale = 1 }]\n",
+    );
+
+    let output = home.run(&["--backend", "mock", "apply-default"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Applying profile 'laptop', since it is the default profile"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("[mock] output \"eDP-1\" scale 1.25"));
+    assert!(!stdout.contains("DP-3"));
+    assert_eq!(home.read(".config/sway/config"), fixture("basic.conf"));
+
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "default_profile = \"gone\"\n",
+    );
+    let output = home.run(&["--backend", "mock", "apply-default"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn quick_changes_are_refused_or_coalesced() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "min_change_interval_ms = 60000\ndebounce_ms = 500\n",
+    );
+
+    let output = home.run(&["cycle", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    let output = home.run(&["cycle", "--no-reload"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("min_change_interval_ms is 60000"));
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 2 pos 0 0"));
+
+    // The daemon applies two quick cycle requests as one change, from 2 past 1 to 1.5
+    let pidfile = home.path("daemon.pid");
+    let daemon = home
+        .command()
+        .args([
+            "--backend",
+            "mock",
+            "--reload-strategy",
+            "ipc",
+            "daemon",
+            "--pidfile",
+        ])
+        .arg(&pidfile)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    while !pidfile.exists() {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    let pid = daemon.id().to_string();
+    for _ in 0..2 {
+        let kill = std::process::Command::new("kill")
+            .args(["-USR1", &pid])
+            .status()
+            .unwrap();
+        assert!(kill.success());
+    }
+    std::thread::sleep(std::time::Duration::from_millis(1200));
+    std::process::Command::new("kill")
+        .args(["-TERM", &pid])
+        .status()
+        .unwrap();
+    let output = daemon.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("Swapping scale").count(), 1, "{}", stdout);
+    assert!(stdout.contains("Swapping scale: 2 -> 1.5"), "{}", stdout);
+}
+
+#[test]
+fn bit_depth_and_hdr_toggle_over_ipc() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    let config = home.read(".config/sway/config");
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1.5},
+            {"name":"HDMI-A-1","active":true,"scale":1.5}]"#,
+    );
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "[profiles.photo]\n\
+         outputs = [{ name = \"HDMI-A-1\", render_bit_depth = 10, hdr = true }]\n",
+    );
+    let seed = home.path("outputs.json");
+    let run = |args: &[&str]| {
+        let output = home
+            .command()
+            .args(["--backend", "mock", "--mock-outputs"])
+            .arg(&seed)
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{:?}", output);
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    // This sway reports no bit depth, so the second toggle relies on the one remembered
+    let stdout = run(&["cycle", "--bit-depth"]);
+    assert!(
+        stdout.contains("output \"eDP-1\" render_bit_depth 10"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("output \"HDMI-A-1\" render_bit_depth 10"),
+        "{}",
+        stdout
+    );
+    let stdout = run(&["--cycle-bitdepth"]);
+    assert!(
+        stdout.contains("output \"eDP-1\" render_bit_depth 8"),
+        "{}",
+        stdout
+    );
+
+    let stdout = run(&["cycle", "--hdr", "--focused"]);
+    assert!(stdout.contains("output \"eDP-1\" hdr on"), "{}", stdout);
+    assert!(!stdout.contains("HDMI-A-1"), "{}", stdout);
+
+    let stdout = run(&["profile", "photo"]);
+    assert!(
+        stdout.contains("output \"HDMI-A-1\" render_bit_depth 10 hdr on"),
+        "{}",
+        stdout
+    );
+    assert_eq!(home.read(".config/sway/config"), config);
+}
+
+#[test]
+fn migrate_moves_the_markers_into_versioned_settings() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "# Laptop setup\nannounce_offline_changes = true\n",
+    );
+    home.write(
+        ".local/state/sway-scale-switcher/state.json",
+        "{\"pinned\": [\"HDMI-A-1\"]}\n",
+    );
+
+    let output = home.run(&["migrate"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = home.read(".config/sway/config");
+    assert!(!config.contains("Scale Options"), "{}", config);
+    assert!(config.contains("output \"eDP-1\" scale 1.5 pos 0 0"));
+    assert_eq!(
+        home.read(".config/sway-scale-switcher/config.toml"),
+        "version = 2\n\
+         targets = [\"eDP-1\", \"HDMI-A-1\"]\n\
+         scales = [\"1\", \"1.5\", \"2\"]\n\
+         # Laptop setup\nannounce_offline_changes = true\n"
+    );
+    assert!(home
+        .read(".local/state/sway-scale-switcher/state.json")
+        .contains("\"version\": 2"));
+
+    // The moved options keep working, and pinned displays stay pinned
+    let output = home.run(&["cycle", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = home.read(".config/sway/config");
+    assert!(config.contains("output \"eDP-1\" scale 2 pos 0 0"));
+    assert!(config.contains("output \"HDMI-A-1\" scale 1.5 pos 1920 0"));
+
+    let output = home.run(&["migrate"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("already in the current format"),
+        "{}",
+        stdout
+    );
+
+    home.write(".config/sway-scale-switcher/config.toml", "version = 99\n");
+    let output = home.run(&["status"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("upgrade sway-scale-switcher"));
+}
+
+#[test]
+fn candidates_lists_scales_that_divide_the_mode_evenly() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "[dpi]\n\"eDP-1\" = 288\n",
+    );
+
+    let output = home.run(&["--backend", "mock", "candidates", "eDP-1"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("1.5    1280x720      192"), "{}", stdout);
+    assert!(!stdout.contains("1.75"), "{}", stdout);
+    assert!(
+        stdout.contains("# Scale Options = 1, 1.25, 1.5, 1.875, 2, 2.5, 3"),
+        "{}",
+        stdout
+    );
+
+    let output = home.run(&["--backend", "mock", "candidates", "DP-9"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn auto_options_find_displays_named_by_description() {
+    let home = TestHome::new(
+        &fixture("basic.conf")
+            .replace("# Target Display = eDP-1\n", "")
+            .replace("HDMI-A-1", "Dell U2720Q SN1")
+            .replace("1.0, 1.5, 2.0", "1.0, auto"),
+    );
+    home.write(
+        "outputs.json",
+        r#"[{"name":"HDMI-A-1","make":"Dell","model":"U2720Q","serial":"SN1","active":true,
+             "scale":1.5,"current_mode":{"width":3840,"height":2160,"refresh":60000}}]"#,
+    );
+
+    // The output is found by its description and its EDID by its connector; without a DRM
+    // device for it the size is unknown, but the display is never reported as disconnected
+    let output = home
+        .command()
+        .args(["--backend", "mock", "--mock-outputs"])
+        .arg(home.path("outputs.json"))
+        .args(["--swap", "--no-reload"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("is not connected"), "{}", stderr);
+    assert!(
+        output.status.success() || stderr.contains("Physical size of 'Dell U2720Q SN1' is unknown"),
+        "{:?}",
+        output
+    );
+}
+
+#[test]
+fn daemon_applies_the_idle_profile_until_input_resumes() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "[idle]\nafter_minutes = 5\nprofile = \"away\"\n\n\
+         [profiles.away]\noutputs = [{ name = \"eDP-1\", scale = 2, mode = \"1280x720@30Hz\" }]\n",
+    );
+    // Reports idle and resume once, as swayidle does through the commands it is given
+    home.install_bin(
+        "swayidle",
+        "#!/bin/sh\n[ \"$3\" = 300 ] || exit 1\nsleep 0.3\nsh -c \"$4\"\nsleep 0.3\nsh -c \"$6\"\nexec sleep 30\n",
+    );
+
+    let pidfile = home.path("daemon.pid");
+    let daemon = home
+        .command()
+        .args(["--backend", "mock", "daemon", "--pidfile"])
+        .arg(&pidfile)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1500));
+    std::process::Command::new("kill")
+        .args(["-TERM", &daemon.id().to_string()])
+        .status()
+        .unwrap();
+    let output = daemon.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let idle = stdout
+        .find("[mock] output \"eDP-1\" mode 1280x720@30Hz scale 2")
+        .expect(&stdout);
+    let restored = stdout
+        .find("[mock] output \"eDP-1\" mode 1920x1080@60.000Hz scale 1")
+        .expect(&stdout);
+    assert!(idle < restored, "{}", stdout);
+}
+
+#[test]
+fn daemon_reloads_the_settings_on_request_and_on_save() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "debounce_ms = 0\n",
+    );
+    let pidfile = home.path("daemon.pid");
+    let socket = home.path("events.sock");
+    let daemon = home
+        .command()
+        .args(["--backend", "mock", "daemon", "--pidfile"])
+        .arg(&pidfile)
+        .arg("--events-socket")
+        .arg(&socket)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+
+    let mut client = UnixStream::connect(&socket).unwrap();
+    let mut replies = BufReader::new(client.try_clone().unwrap()).lines();
+    client.write_all(b"reload-config\n").unwrap();
+    assert_eq!(
+        replies.next().unwrap().unwrap(),
+        r#"{"command":"reload-config","ok":true}"#
+    );
+
+    // A broken file is refused, and saving a good one is picked up without asking
+    home.write(".config/sway-scale-switcher/config.toml", "debounce = 0\n");
+    client.write_all(b"reload-config\n").unwrap();
+    let reply = replies.next().unwrap().unwrap();
+    assert!(reply.contains(r#""ok":false"#), "{}", reply);
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "debounce_ms = 100\n",
+    );
+    std::thread::sleep(std::time::Duration::from_millis(2500));
+
+    std::process::Command::new("kill")
+        .args(["-TERM", &daemon.id().to_string()])
+        .status()
+        .unwrap();
+    let output = daemon.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout
+            .matches("Reloaded the configuration: 2 target displays, 3 scale options")
+            .count(),
+        2,
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn version_json_lists_backends_and_features() {
+    let home = TestHome::new(&fixture("basic.conf"));
+
+    let output = home.run(&["--version", "--json"]);
+    assert!(output.status.success(), "{:?}", output);
+    let info: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(
+        info["backends"],
+        serde_json::json!(["sway", "mock", "wlr-randr"])
+    );
+    assert_eq!(info["features"]["dbus"], cfg!(feature = "dbus"));
+    assert!(info["subcommands"]
+        .as_array()
+        .unwrap()
+        .contains(&"daemon".into()));
+
+    let output = home.run(&["-V"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("Sway Scale Swapper {}\n", env!("CARGO_PKG_VERSION"))
+    );
+    assert!(!home.run(&["--json"]).status.success());
+}
+
+#[test]
+fn finished_changes_leave_nothing_to_recover() {
+    let home = TestHome::new(&fixture("basic.conf"));
+
+    let output = home.run(&["cycle", "--no-reload"]);
+    assert!(output.status.success(), "{:?}", output);
+    let state_dir = home.path(".local/state/sway-scale-switcher");
+    assert!(state_dir.join("history.jsonl").exists());
+    assert!(!state_dir.join("journal.json").exists());
+    assert_eq!(
+        std::fs::read_dir(home.path(".config/sway"))
+            .unwrap()
+            .count(),
+        1
+    );
+
+    let output = home.run(&["recover"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("no interrupted change"));
+}
+
+#[test]
+fn match_physical_size_gives_each_display_its_own_scale() {
+    let home =
+        TestHome::new(&fixture("basic.conf").replace("\"eDP-1\" scale 1.5", "\"eDP-1\" scale 3"));
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":3},
+            {"name":"HDMI-A-1","active":true,"scale":1.5}]"#,
+    );
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "match_physical_size = true\n\n[dpi]\n\"eDP-1\" = 192\n\"HDMI-A-1\" = 96\n",
+    );
+
+    // Both displays show elements at 150%, so both step on to 200%
+    let output = home
+        .command()
+        .args(["--backend", "mock", "--mock-outputs"])
+        .arg(home.path("outputs.json"))
+        .args(["cycle", "--reload-strategy", "ipc"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("[mock] output \"eDP-1\" scale 4"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("[mock] output \"HDMI-A-1\" scale 2"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn scales_below_the_minimum_logical_size_need_force() {
+    let home = TestHome::new(&fixture("basic.conf").replace(
+        "output \"eDP-1\" scale 1.5 pos 0 0",
+        "output \"eDP-1\" scale 1.5 mode 1920x1080 pos 0 0",
+    ));
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "min_logical_size = \"1024x600\"\n",
+    );
+    let config = home.read(".config/sway/config");
+
+    // 1920x1080 at 2 leaves 960x540, so nothing is written
+    let output = home.run(&["set", "2"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr
+            .contains("eDP-1 at 2 (960x540) would fall below the minimum logical size of 1024x600"),
+        "{}",
+        stderr
+    );
+    assert_eq!(home.read(".config/sway/config"), config);
+
+    let output = home.run(&["set", "2", "--force"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(home
+        .read(".config/sway/config")
+        .contains("output \"eDP-1\" scale 2 mode 1920x1080 pos 0 0"));
+
+    // A scale that keeps 1280x720 goes through
+    let output = home.run(&["set", "1.5"]);
+    assert!(output.status.success(), "{:?}", output);
+}
+
+#[test]
+fn history_names_what_triggered_each_change() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "debounce_ms = 0\n",
+    );
+
+    let output = home.run(&["--no-reload", "set", "2"]);
+    assert!(output.status.success(), "{:?}", output);
+    let output = home.run(&["--no-reload", "--trigger", "kanshi rule 'desk'", "set", "1"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    // The daemon puts a cycle down to the signal and the process that sent it
+    let pidfile = home.path("daemon.pid");
+    let daemon = home
+        .command()
+        .args([
+            "--backend",
+            "mock",
+            "--reload-strategy",
+            "ipc",
+            "daemon",
+            "--pidfile",
+        ])
+        .arg(&pidfile)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    while !pidfile.exists() {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    }
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    let pid = daemon.id().to_string();
+    std::process::Command::new("kill")
+        .args(["-USR1", &pid])
+        .status()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1500));
+    std::process::Command::new("kill")
+        .args(["-TERM", &pid])
+        .status()
+        .unwrap();
+    let output = daemon.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Triggered by signal SIGUSR1 from pid "),
+        "{}",
+        stdout
+    );
+
+    // The daemon's own write to the config is not taken for the user saving it
+    assert!(!stdout.contains("configuration saved"), "{}", stdout);
+
+    let output = home.run(&["history"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", stdout);
+    assert!(
+        lines[0].contains("[command line, run by pid "),
+        "{}",
+        stdout
+    );
+    assert!(lines[1].ends_with("[kanshi rule 'desk']"), "{}", stdout);
+    assert!(lines[2].contains("[signal SIGUSR1 from pid "), "{}", stdout);
+}
+
+#[test]
+fn profiles_sync_through_a_git_repository() {
+    let laptop = TestHome::new(&fixture("basic.conf"));
+    let desktop = TestHome::new(&fixture("basic.conf"));
+    let remote = laptop.path("remote.git");
+    let status = std::process::Command::new("git")
+        .args(["init", "--quiet", "--bare"])
+        .arg(&remote)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let settings = ".config/sway-scale-switcher/config.toml";
+    let sync = format!("[sync]\nremote = \"{}\"\n", remote.display());
+
+    // The first machine fills the empty repository, the second takes its copy
+    laptop.write(
+        settings,
+        &format!(
+            "{}\n[profiles.desk]\noutputs = [{{ name = \"DP-1\", scale = 1.5 }}]\n",
+            sync
+        ),
+    );
+    let output = laptop.run(&["profiles", "sync"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Pushed the settings"));
+
+    let output = desktop.run(&["profiles", "sync", "--remote", remote.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Pulled the settings"));
+    assert_eq!(desktop.read(settings), laptop.read(settings));
+
+    // A change on one machine reaches the other
+    let edited = desktop.read(settings)
+        + "\n[profiles.tv]\noutputs = [{ name = \"HDMI-A-1\", scale = 2 }]\n";
+    desktop.write(settings, &edited);
+    let output = desktop.run(&["profiles", "sync"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Pushed the settings"));
+    let output = laptop.run(&["profiles", "sync"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Pulled the settings"));
+    assert!(laptop.read(settings).contains("[profiles.tv]"));
+    let output = laptop.run(&["profiles", "sync"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("already in step"));
+
+    // When both changed, the newer copy wins with a warning
+    laptop.write(settings, &format!("zoom_scale = 3.0\n\n{}", sync));
+    std::fs::File::options()
+        .write(true)
+        .open(laptop.path(settings))
+        .unwrap()
+        .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(946684800))
+        .unwrap();
+    desktop.write(settings, &format!("zoom_scale = 4.0\n\n{}", sync));
+    let output = desktop.run(&["profiles", "sync"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Pushed the settings"));
+    let output = laptop.run(&["profiles", "sync"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Both this machine and"));
+    assert!(laptop.read(settings).contains("zoom_scale = 4.0"));
+}
+
+#[test]
+fn environment_variables_redirect_the_config_and_backend() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    let config = home.read(".config/sway/config");
+    home.write("nested/config", &config);
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1.5},
+            {"name":"HDMI-A-1","active":true,"scale":1.5}]"#,
+    );
+
+    let output = home
+        .command()
+        .env("SWAY_SCALE_SWITCHER_CONFIG", home.path("nested/config"))
+        .env("SWAY_SCALE_SWITCHER_BACKEND", "mock")
+        .env(
+            "SWAY_SCALE_SWITCHER_MOCK_OUTPUTS",
+            home.path("outputs.json"),
+        )
+        .env("SWAY_SCALE_SWITCHER_OUTPUTS", "HDMI-A-1")
+        .env("SWAY_SCALE_SWITCHER_RELOAD_STRATEGY", "ipc")
+        .args(["set", "2"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("[mock] output \"HDMI-A-1\" scale 2"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("eDP-1"), "{}", stdout);
+    assert_eq!(home.read(".config/sway/config"), config);
+    assert!(home
+        .read("nested/config")
+        .contains("output \"HDMI-A-1\" scale 2 pos 1920 0"));
+
+    // Flags win over the environment
+    let output = home
+        .command()
+        .env("SWAY_SCALE_SWITCHER_CONFIG", home.path("nested/config"))
+        .env("SWAY_SCALE_SWITCHER_BACKEND", "mock")
+        .env("SWAY_SCALE_SWITCHER_RELOAD_STRATEGY", "ipc")
+        .args(["--no-reload", "set", "1"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("[mock]"));
+
+    // A variable that names no setting is refused
+    let output = home
+        .command()
+        .env("SWAY_SCALE_SWITCHER_ZOOM", "2")
+        .args(["status"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("SWAY_SCALE_SWITCHER_ZOOM"));
+}
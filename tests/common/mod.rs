@@ -0,0 +1,98 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A throwaway home directory holding a sway config, removed again on drop
+pub struct TestHome {
+    pub root: PathBuf,
+}
+
+impl TestHome {
+    /// Function to create a fresh home directory with the given sway config
+    pub fn new(config: &str) -> TestHome {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let root = std::env::temp_dir().join(format!(
+            "sway-scale-switcher-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join(".config/sway")).unwrap();
+        fs::write(root.join(".config/sway/config"), config).unwrap();
+        TestHome { root }
+    }
+
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.root.join(relative)
+    }
+
+    pub fn read(&self, relative: &str) -> String {
+        fs::read_to_string(self.path(relative)).unwrap()
+    }
+
+    pub fn write(&self, relative: &str, contents: &str) {
+        let path = self.path(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    /// Function to put a stand-in for an external program, such as `sway` or `swaymsg`, in
+    /// front of the real one for every command built from this home
+    pub fn install_bin(&self, name: &str, script: &str) {
+        let relative = format!("bin/{}", name);
+        self.write(&relative, script);
+        fs::set_permissions(
+            self.path(&relative),
+            std::os::unix::fs::PermissionsExt::from_mode(0o755),
+        )
+        .unwrap();
+    }
+
+    /// Function to build a command for the binary that only sees this home directory.
+    /// `SWAYSOCK` points nowhere so a running sway session is never touched, and the locale is
+    /// cleared so messages are in English.
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_sway-scale-switcher"));
+        let path = format!(
+            "{}:{}",
+            self.path("bin").display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        command
+            .env("HOME", &self.root)
+            .env("PATH", path)
+            .env("SWAYSOCK", self.root.join("no-sway.sock"))
+            .env_remove("DBUS_SESSION_BUS_ADDRESS")
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("XDG_STATE_HOME")
+            .env_remove("XDG_DATA_HOME")
+            .env_remove("XDG_RUNTIME_DIR")
+            .env_remove("LC_ALL")
+            .env_remove("LC_MESSAGES")
+            .env_remove("LANG");
+        command
+    }
+
+    pub fn run(&self, args: &[&str]) -> Output {
+        self.command().args(args).output().unwrap()
+    }
+}
+
+impl Drop for TestHome {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Function to read a fixture from `tests/fixtures`
+pub fn fixture(name: &str) -> String {
+    fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name),
+    )
+    .unwrap()
+}
@@ -0,0 +1,196 @@
+//! Property tests for rewriting output lines. The binary has no library target, so the
+//! modules under test are included by path, as in the benchmarks.
+#![allow(dead_code)]
+
+#[path = "../src/config.rs"]
+mod config;
+#[path = "../src/expr.rs"]
+mod expr;
+#[path = "../src/options.rs"]
+mod options;
+#[path = "../src/text.rs"]
+mod text;
+
+use options::ScaleOption;
+use proptest::prelude::*;
+use std::collections::BTreeSet;
+
+/// Display names the generated configs pick from, quoted and unquoted alike
+const NAMES: [&str; 4] = ["eDP-1", "HDMI-A-1", "DP-3", "Dell Inc. U2720Q 1"];
+
+/// Function to generate a scale whose text survives a parse and print unchanged
+fn scale() -> impl Strategy<Value = f32> {
+    (1u32..=12).prop_map(|quarters| quarters as f32 * 0.25)
+}
+
+/// Function to generate a line sway would not read as an output definition
+fn other_line() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just(String::new()),
+        "[ \t]{0,4}# [ -~]{0,30}",
+        "(set|bindsym|exec|for_window|workspace) [ -~]{0,30}",
+        Just("output * bg ~/wallpaper.png fill".to_string()),
+        Just("# output eDP-1 scale 3".to_string()),
+        Just("client.focused #4c7899 #285577 #ffffff".to_string()),
+    ]
+}
+
+/// Function to generate the lines of one output definition for display `name`
+fn output_entry(name: usize, scales: [f32; 4]) -> impl Strategy<Value = Vec<String>> {
+    let display = NAMES[name];
+    let scale = scales[name];
+    (any::<bool>(), any::<bool>(), 0usize..3, "[ \t]{0,2}").prop_map(
+        move |(quoted, block, extra, indent)| {
+            let name = if quoted || display.contains(' ') {
+                format!("\"{}\"", display)
+            } else {
+                display.to_string()
+            };
+            let extra = ["", " pos 0 0", " transform 90 # rotated"][extra];
+            if block {
+                vec![
+                    format!("{}output {} {{", indent, name),
+                    format!("{}    scale {}{}", indent, scale, extra),
+                    format!("{}    # scale 9", indent),
+                    format!("{}}}", indent),
+                ]
+            } else {
+                vec![format!(
+                    "{}output {} scale {}{}",
+                    indent, name, scale, extra
+                )]
+            }
+        },
+    )
+}
+
+/// A generated config with the scale each display is defined with
+#[derive(Debug, Clone)]
+struct Config {
+    contents: Vec<u8>,
+    scales: [f32; 4],
+}
+
+/// Function to generate a config mixing output definitions with unrelated lines, using
+/// either line ending and with or without a final newline
+fn config() -> impl Strategy<Value = Config> {
+    prop::array::uniform4(scale()).prop_flat_map(|scales| {
+        let piece = prop_oneof![
+            other_line().prop_map(|line| vec![line]),
+            (0usize..NAMES.len()).prop_flat_map(move |name| output_entry(name, scales)),
+        ];
+        (
+            prop::collection::vec(piece, 0..24),
+            any::<bool>(),
+            any::<bool>(),
+        )
+            .prop_map(move |(pieces, crlf, final_newline)| {
+                let ending = if crlf { "\r\n" } else { "\n" };
+                let mut contents = pieces.concat().join(ending);
+                if final_newline {
+                    contents.push_str(ending);
+                }
+                Config {
+                    contents: contents.into_bytes(),
+                    scales,
+                }
+            })
+    })
+}
+
+/// Function to generate a subset of the display names to target
+fn targets() -> impl Strategy<Value = Vec<String>> {
+    prop::sample::subsequence(NAMES.to_vec(), 0..=NAMES.len())
+        .prop_map(|names| names.into_iter().map(String::from).collect())
+}
+
+/// Function to list the lines holding a targeted display's scale
+fn scale_lines(lines: &[String], targets: &[String]) -> BTreeSet<usize> {
+    config::find_output_entries(lines)
+        .into_iter()
+        .filter(|entry| targets.contains(&entry.name))
+        .map(|entry| entry.scale_line)
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn unrelated_lines_are_left_alone(config in config(), targets in targets(), new in scale()) {
+        let lines = text::decode_lines(&config.contents);
+        let updated = config::update_scale_in_outputs(&lines, &targets, &ScaleOption::scale(new));
+        prop_assert_eq!(updated.len(), lines.len());
+
+        let touched = scale_lines(&lines, &targets);
+        for (i, (before, after)) in lines.iter().zip(&updated).enumerate() {
+            if !touched.contains(&i) {
+                prop_assert_eq!(before, after, "line {}", i);
+            }
+        }
+
+        // Byte for byte, too: only the touched lines may differ on disk
+        let original = String::from_utf8(config.contents.clone()).unwrap();
+        let encoded = String::from_utf8(text::encode_lines(&config.contents, &updated)).unwrap();
+        let before: Vec<&str> = original.split_inclusive('\n').collect();
+        let after: Vec<&str> = encoded.split_inclusive('\n').collect();
+        prop_assert_eq!(before.len(), after.len());
+        for (i, (before, after)) in before.iter().zip(&after).enumerate() {
+            if !touched.contains(&i) {
+                prop_assert_eq!(before, after, "line {}", i);
+            }
+        }
+    }
+
+    #[test]
+    fn exactly_the_targeted_scales_change(config in config(), targets in targets(), new in scale()) {
+        let lines = text::decode_lines(&config.contents);
+        let updated = config::update_scale_in_outputs(&lines, &targets, &ScaleOption::scale(new));
+
+        let before = config::find_output_entries(&lines);
+        let after = config::find_output_entries(&updated);
+        prop_assert_eq!(before.len(), after.len());
+        for (i, (before_entry, after_entry)) in before.iter().zip(&after).enumerate() {
+            prop_assert_eq!(&before_entry.name, &after_entry.name);
+            // Only the last definition of a targeted display changes, as that is the one sway applies
+            let last = before[i + 1..].iter().all(|later| later.name != before_entry.name);
+            if targets.contains(&before_entry.name) && last {
+                prop_assert_eq!(after_entry.scale, new);
+            } else {
+                prop_assert_eq!(after_entry.scale, before_entry.scale);
+            }
+        }
+    }
+
+    #[test]
+    fn applying_the_same_scale_twice_changes_nothing(
+        config in config(),
+        targets in targets(),
+        new in scale(),
+    ) {
+        let lines = text::decode_lines(&config.contents);
+        let new = ScaleOption::scale(new);
+        let once = config::update_scale_in_outputs(&lines, &targets, &new);
+        let twice = config::update_scale_in_outputs(&once, &targets, &new);
+        prop_assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn changing_the_scales_back_restores_the_file(
+        config in config(),
+        targets in targets(),
+        new in scale(),
+    ) {
+        let lines = text::decode_lines(&config.contents);
+        prop_assert_eq!(text::encode_lines(&config.contents, &lines), config.contents.clone());
+
+        let mut updated = config::update_scale_in_outputs(&lines, &targets, &ScaleOption::scale(new));
+        for target in &targets {
+            let index = NAMES.iter().position(|name| name == target).unwrap();
+            updated = config::update_scale_in_outputs(
+                &updated,
+                std::slice::from_ref(target),
+                &ScaleOption::scale(config.scales[index]),
+            );
+        }
+        prop_assert_eq!(text::encode_lines(&config.contents, &updated), config.contents);
+    }
+}
@@ -0,0 +1,104 @@
+//! Runs the tool against a real, headless sway instance. Needs `sway` and `swaymsg` on the
+//! PATH, so it is ignored by default: `cargo test --test headless_sway -- --ignored`.
+
+mod common;
+
+use common::{fixture, TestHome};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A headless sway session, killed again on drop
+struct HeadlessSway {
+    child: Child,
+    socket: String,
+}
+
+impl HeadlessSway {
+    fn start(home: &TestHome) -> HeadlessSway {
+        let runtime_dir = home.path("runtime");
+        std::fs::create_dir_all(&runtime_dir).unwrap();
+        let mut sway = HeadlessSway {
+            child: Command::new("sway")
+                .arg("--config")
+                .arg(home.path(".config/sway/config"))
+                .env("HOME", &home.root)
+                .env("XDG_RUNTIME_DIR", &runtime_dir)
+                .env("WLR_BACKENDS", "headless")
+                .env("WLR_LIBINPUT_NO_DEVICES", "1")
+                .env_remove("WAYLAND_DISPLAY")
+                .env_remove("SWAYSOCK")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("failed to start sway"),
+            socket: String::new(),
+        };
+
+        // Wait for sway to create its IPC socket
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let socket = std::fs::read_dir(&runtime_dir)
+                .unwrap()
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .find(|path| {
+                    path.file_name()
+                        .is_some_and(|name| name.to_string_lossy().starts_with("sway-ipc."))
+                });
+            if let Some(socket) = socket {
+                sway.socket = socket.to_string_lossy().to_string();
+                return sway;
+            }
+            assert!(Instant::now() < deadline, "sway did not start");
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    fn scale_of(&self, output: &str) -> f64 {
+        let stdout = Command::new("swaymsg")
+            .args(["-s", &self.socket, "-t", "get_outputs", "-r"])
+            .output()
+            .unwrap()
+            .stdout;
+        let outputs: serde_json::Value = serde_json::from_slice(&stdout).unwrap();
+        outputs
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|o| o["name"] == output)
+            .and_then(|o| o["scale"].as_f64())
+            .unwrap()
+    }
+}
+
+impl Drop for HeadlessSway {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+#[ignore = "needs sway; run with --ignored"]
+fn swap_changes_the_live_scale() {
+    let config = fixture("basic.conf").replace("eDP-1", "HEADLESS-1");
+    let home = TestHome::new(&config);
+    let sway = HeadlessSway::start(&home);
+    assert_eq!(sway.scale_of("HEADLESS-1"), 1.5);
+
+    let output = home
+        .command()
+        .env("SWAYSOCK", &sway.socket)
+        .arg("--swap")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    // The reload is asynchronous; give sway a moment to apply the new config
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while sway.scale_of("HEADLESS-1") != 2.0 {
+        assert!(Instant::now() < deadline, "scale was not applied");
+        thread::sleep(Duration::from_millis(100));
+    }
+}
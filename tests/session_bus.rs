@@ -0,0 +1,132 @@
+//! Calls the daemon's D-Bus methods on a private session bus. Needs `dbus-daemon` and `gdbus`
+//! on the PATH, so it is ignored by default: `cargo test --test session_bus -- --ignored`.
+#![cfg(feature = "dbus")]
+
+mod common;
+
+use common::{fixture, TestHome};
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A private session bus, killed again on drop
+struct SessionBus {
+    child: Child,
+    address: String,
+}
+
+impl SessionBus {
+    fn start() -> SessionBus {
+        let mut child = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("failed to start dbus-daemon");
+        let mut address = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        SessionBus {
+            child,
+            address: address.trim().to_string(),
+        }
+    }
+
+    /// Function to call a method of the daemon's object with gdbus
+    fn call(&self, method: &str, args: &[&str]) -> Output {
+        Command::new("gdbus")
+            .args(["call", "--session", "--dest", "org.swayscale.Switcher1"])
+            .args(["--object-path", "/org/swayscale/Switcher1", "--method"])
+            .arg(format!("org.swayscale.Switcher1.{}", method))
+            .args(args)
+            .env("DBUS_SESSION_BUS_ADDRESS", &self.address)
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for SessionBus {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A daemon started for a test, killed again on drop so a failed assertion does not leave it
+/// holding the test's output open
+struct Daemon(Child);
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+#[test]
+#[ignore = "needs dbus-daemon and gdbus; run with --ignored"]
+fn set_scale_checks_the_scale_and_the_output() {
+    let home = TestHome::new(&fixture("basic.conf"));
+    home.write(
+        ".config/sway-scale-switcher/config.toml",
+        "[aliases]\nlaptop = \"eDP-1\"\n",
+    );
+    home.write(
+        "outputs.json",
+        r#"[{"name":"eDP-1","active":true,"scale":1.5},
+            {"name":"HDMI-A-1","active":true,"scale":1.5}]"#,
+    );
+    assert!(home.run(&["pin", "HDMI-A-1"]).status.success());
+
+    let bus = SessionBus::start();
+    let _daemon = Daemon(
+        home.command()
+            .env("DBUS_SESSION_BUS_ADDRESS", &bus.address)
+            .args(["--backend", "mock", "--mock-outputs"])
+            .arg(home.path("outputs.json"))
+            .args(["daemon", "--dbus", "--pidfile"])
+            .arg(home.path("daemon.pid"))
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !bus.call("GetStatus", &[]).status.success() {
+        assert!(
+            Instant::now() < deadline,
+            "the daemon did not claim its name"
+        );
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    // Scales that are not positive numbers are refused before anything is written
+    for args in [&["eDP-1", "0"][..], &["--", "eDP-1", "-1.5"]] {
+        let output = bus.call("SetScale", args);
+        assert!(!output.status.success(), "{:?}", output);
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("must be a positive number"),
+            "{:?}",
+            output
+        );
+    }
+    assert_eq!(home.read(".config/sway/config"), fixture("basic.conf"));
+
+    // A pinned display keeps its scale, and an alias names the output it stands for
+    let output = bus.call("SetScale", &["HDMI-A-1", "2"]);
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("HDMI-A-1 is pinned"),
+        "{:?}",
+        output
+    );
+    let output = bus.call("SetScale", &["laptop", "2"]);
+    assert!(output.status.success(), "{:?}", output);
+    let config = home.read(".config/sway/config");
+    assert!(
+        config.contains("output \"eDP-1\" scale 2 pos 0 0"),
+        "{}",
+        config
+    );
+    assert!(config.contains("output \"HDMI-A-1\" scale 1.5 pos 1920 0"));
+}